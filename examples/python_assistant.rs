@@ -154,6 +154,9 @@ async fn process_agent_messages(
                                 let _ = is_streaming;
                                 return Ok(());
                             }
+                            _ => {
+                                // Other events are not displayed by this example
+                            }
                         }
                     }
                     Err(e) => {
@@ -124,17 +124,28 @@ impl App {
                 environment: std::collections::HashMap::new(),
                 working_directory: None,
                 timeout: Some(60),
+                limits: Default::default(),
+                kill_policy: None,
+                max_output_bytes: None,
             })
             .tool(ToolConfig::FileWrite {
                 max_file_size: 10_000_000, // 10MB
                 allowed_extensions: vec![],
                 allow_overwrite: true,
                 create_directories: true,
+                working_directory: None,
+
+                allowed_paths: vec![],
+                denied_paths: vec![],
             })
             .tool(ToolConfig::FileRead {
                 max_file_size: 10_000_000, // 10MB
                 allowed_extensions: vec![],
                 allow_binary: false,
+                working_directory: None,
+
+                allowed_paths: vec![],
+                denied_paths: vec![],
             })
             .working_directory(PathBuf::from("/tmp"))
             .build()?;
@@ -373,6 +384,9 @@ impl App {
                         self.status = format!("❌ Error: {:?}", error);
                         // Don't change streaming state on error
                     }
+                    _ => {
+                        // Other events are not displayed by this example
+                    }
                 }
             }
         }
@@ -1,22 +1,74 @@
 //! Main agent implementation with execution capabilities.
 
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use async_channel::{Receiver, Sender};
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use tokio::sync::Mutex;
+use tokio::sync::{broadcast, oneshot, watch};
 use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 
 use codex_core::config::{Config as CodexConfig, ConfigOverrides};
-use codex_core::{CodexConversation, ConversationManager};
+use codex_core::config_types::{ShellEnvironmentPolicy, ShellEnvironmentPolicyInherit};
+use codex_core::{ConversationManager, ModelProviderInfo};
 use codex_login::{AuthManager, CodexAuth};
-use codex_protocol::protocol::{Event, EventMsg, InputItem, Op, Submission};
+use codex_protocol::config_types::{ReasoningEffort, ReasoningSummary};
+use codex_protocol::protocol::{AskForApproval, Event, InputItem, Op, ReviewDecision, Submission};
 use std::sync::Arc;
-
-use crate::config::AgentConfig;
-use crate::controller::AgentController;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::approval::{ApprovalDecision, ApprovalRequest};
+use crate::attachment;
+use crate::auth;
+use crate::autonomy::{Autonomy, CheckInPolicy, CheckInReason, NextStep};
+use crate::backend::{CodexBackend, ConversationBackend, LlmBackend};
+use crate::cache::cache_key;
+use crate::config::{AgentConfig, ConfigPatch};
+use crate::controller::{AgentController, ControlAck, ControlCommand};
+use crate::conversation::Conversation;
 use crate::error::{AgentError, OutputError, Result};
-use crate::messages::{InputMessage, OutputData, OutputMessage};
+use crate::event::{SequencedEvent, merge_events};
+use crate::explain::{TurnExplanation, explain_prompt, explanation_schema};
+use crate::lexicon::LexiconStream;
+use crate::messages::{InputMessage, OutputData, OutputMessage, QueryOptions};
+use crate::middleware::ToolCall;
+use crate::ops::{OpsAggregator, TokenUsage};
+use crate::patch::{self, PatchFile};
 use crate::plan::PlanMessage;
+use crate::preflight::{self, PreflightReport};
+use crate::protocol;
+use crate::provider::{ModelProvider, WireApi};
+use crate::queue::{InputQueue, PendingInput};
+use crate::rate_limit::{self, RateLimiter};
+use crate::replay::{self, Replay};
+use crate::retry::{self, RetryPolicy};
+use crate::sandbox::SandboxBackend;
+use crate::spans::{TurnSpans, TurnStatus};
+use crate::structured::{extract_json, retry_prompt, schema_errors, structured_prompt};
+use crate::suggestions::generate_suggestions;
+use crate::telemetry::{self, TelemetryEvent};
+use crate::tool_server::{self, DEFAULT_BRIDGE, ToolServer};
+use crate::tools::{ToolConfig, ToolRegistry};
+use crate::truncation::TruncatedStream;
+use crate::verify::{VerifyConfig, run_verification};
+use crate::web_search;
+use crate::workspace::{
+    FileIndex, QuotaStatus, TurnRecord, WorkspaceDelta, auto_commit_turn, checkpoint_turn,
+    commit_message_prompt, measure_disk_usage, scan_workspace,
+};
+
+/// Capacity of the unified event stream before events are dropped.
+const EVENT_BUFFER_SIZE: usize = 256;
+
+/// Maximum outputs recorded on a turn's event tape.
+const MAX_TAPE_EVENTS: usize = 500;
+
+/// Output messages buffered per broadcast subscriber before it lags.
+const OUTPUT_BROADCAST_CAPACITY: usize = 1024;
 
 /// Main agent structure for managing AI conversations.
 pub struct Agent {
@@ -24,36 +76,169 @@ pub struct Agent {
     config: AgentConfig,
 
     /// Internal Codex conversation handler
-    codex_conversation: Option<Arc<codex_core::CodexConversation>>,
+    codex_conversation: Option<Arc<dyn ConversationBackend>>,
+
+    /// Backend starting conversations, Codex unless replaced
+    backend: Option<Arc<dyn LlmBackend>>,
 
     /// Agent controller for state management
     controller: AgentController,
 
-    /// Control command receiver
-    control_rx: tokio::sync::mpsc::UnboundedReceiver<crate::controller::ControlCommand>,
+    /// Records of completed turns, shared with execution handles
+    turn_records: Arc<Mutex<Vec<TurnRecord>>>,
+
+    /// Changes of the patches applied in each turn, shared with execution handles
+    file_changes: Arc<Mutex<HashMap<u64, Vec<PatchFile>>>>,
+
+    /// Fan-out of output messages to subscribers
+    output_broadcast: broadcast::Sender<OutputMessage>,
+
+    /// Last sequence number assigned to an output message
+    output_seq: Arc<AtomicU64>,
+
+    /// Auth manager shared with other agents, e.g. in a pool
+    auth_manager: Option<Arc<AuthManager>>,
+
+    /// Tools available to the agent, shared with execution handles
+    tools: ToolRegistry,
+
+    /// Server of the host tools to the conversation's model
+    tool_server: ToolServer,
+
+    /// Reasoning settings of the Codex config, which turns fall back to
+    /// when the agent's config leaves them unset
+    codex_reasoning: (ReasoningEffort, ReasoningSummary),
 }
 
 impl Agent {
     /// Create a new agent with the given configuration.
     pub fn new(config: AgentConfig) -> Result<Self> {
-        let (controller, control_rx) = AgentController::new();
-
+        let mut tools =
+            ToolRegistry::new(config.tools().to_vec()).with_concurrency(config.tool_concurrency());
+        // Codex's own shell runs on the host, outside containers, and runs
+        // commands before the agent can check the paths they name
+        if matches!(config.sandbox_backend(), SandboxBackend::Container(_))
+            || config.checks_tool_paths()
+        {
+            tools = tools.with_shell_on_host();
+        }
+        let controller = AgentController::new();
         Ok(Agent {
+            tool_server: ToolServer::new(tools.clone(), controller.clone(), config.clone()),
+            tools,
             config,
             codex_conversation: None,
+            backend: None,
             controller,
-            control_rx,
+            turn_records: Arc::new(Mutex::new(Vec::new())),
+            file_changes: Arc::new(Mutex::new(HashMap::new())),
+            output_broadcast: broadcast::channel(OUTPUT_BROADCAST_CAPACITY).0,
+            output_seq: Arc::new(AtomicU64::new(0)),
+            auth_manager: None,
+            codex_reasoning: Default::default(),
         })
     }
 
+    /// Use a shared auth manager instead of loading one from the codex home.
+    pub(crate) fn with_auth_manager(mut self, auth_manager: Arc<AuthManager>) -> Self {
+        self.auth_manager = Some(auth_manager);
+        self
+    }
+
+    /// Run conversations on another backend, e.g. a test double.
+    pub(crate) fn with_backend(mut self, backend: Arc<dyn LlmBackend>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Drop the conversation and the records of its turns, so the next
+    /// execution starts a new conversation.
+    pub(crate) fn reset_conversation(&mut self) {
+        self.codex_conversation = None;
+        self.turn_records = Arc::default();
+        self.file_changes = Arc::default();
+    }
+
+    /// Run conversations on a mock backend replaying scripted turns.
+    #[cfg(feature = "testing")]
+    pub fn with_mock_backend(self, backend: crate::testing::MockBackend) -> Self {
+        self.with_backend(Arc::new(backend))
+    }
+
+    /// Play a recorded conversation back instead of asking the model.
+    pub fn with_replay(self, replay: Replay) -> Self {
+        self.with_backend(Arc::new(replay))
+    }
+
     /// Get a reference to the agent controller.
     pub fn controller(&self) -> &AgentController {
         &self.controller
     }
 
+    /// Get the agent's tool registry.
+    pub fn tool_registry(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    /// Check the setup before the first query: configuration, credentials,
+    /// model, MCP server commands, the tool bridge, and sandbox
+    /// prerequisites.
+    ///
+    /// Checks are local, so a key the provider rejects is only found by a
+    /// query. See [`PreflightReport::is_ok`].
+    pub async fn validate(&self) -> PreflightReport {
+        let tools = self.tools.tools();
+        preflight::run(&self.config, &tools, self.auth_manager.as_deref()).await
+    }
+
+    /// Get the records of all turns the agent completed.
+    pub async fn turn_records(&self) -> Vec<TurnRecord> {
+        self.turn_records.lock().await.clone()
+    }
+
+    /// Subscribe to every output message the agent emits.
+    ///
+    /// Each subscriber receives its own copy alongside the output channel
+    /// passed to `execute()`, so a UI and a logger can both listen. A
+    /// subscriber that falls behind by more than the buffer sees
+    /// `RecvError::Lagged`.
+    pub fn subscribe_output(&self) -> broadcast::Receiver<OutputMessage> {
+        self.output_broadcast.subscribe()
+    }
+
     /// Simple synchronous query method for basic use cases.
+    ///
+    /// With a response cache configured, a query starting the conversation
+    /// is answered from the cache when it was asked before.
     pub async fn query<S: Into<String>>(&mut self, message: S) -> Result<String> {
-        let input_message = InputMessage::new(message);
+        let message = message.into();
+        let cached = match self.config.response_cache() {
+            Some(cache) if self.codex_conversation.is_none() => {
+                Some((cache.clone(), cache_key(&message, &self.config)))
+            }
+            _ => None,
+        };
+        if let Some((cache, key)) = &cached
+            && let Some(response) = cache.get(key).await
+        {
+            debug!("Answered query from the response cache");
+            return Ok(response);
+        }
+
+        let response = self.query_with(message, QueryOptions::default()).await?;
+        if let Some((cache, key)) = &cached {
+            cache.put(key, &response).await;
+        }
+        Ok(response)
+    }
+
+    /// Query with per-turn overrides of the model, reasoning effort, or timeout.
+    pub async fn query_with<S: Into<String>>(
+        &mut self,
+        message: S,
+        options: QueryOptions,
+    ) -> Result<String> {
+        let input_message = InputMessage::new(message).with_options(options);
 
         // Create channels for this single query
         let (input_tx, input_rx) = async_channel::bounded(1);
@@ -68,37 +253,161 @@ impl Agent {
         let handle = self.execute(input_rx, plan_tx, output_tx).await?;
 
         // Collect output messages until completion
-        let mut result = String::new();
+        let result = collect_response(&output_rx).await?;
 
-        while let Ok(output) = output_rx.recv().await {
-            match output.data {
-                OutputData::Primary { content } => {
-                    result.push_str(&content);
-                }
-                OutputData::PrimaryDelta { content } => {
-                    result.push_str(&content);
-                }
-                OutputData::Completed => {
-                    break;
+        // Wait for execution to complete
+        handle.await?;
+
+        Ok(result)
+    }
+
+    /// Run many independent single-turn queries concurrently.
+    ///
+    /// Each prompt runs on a fresh agent with this agent's configuration and
+    /// backend, at most `concurrency` at a time. Responses are returned in
+    /// prompt order, along with the token usage summed over all queries.
+    pub async fn query_batch<I, S>(&self, prompts: I, concurrency: usize) -> BatchResult
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let results: Vec<(Result<String>, TokenUsage)> = futures::stream::iter(prompts)
+            .map(|prompt| {
+                let config = self.config.clone();
+                let backend = self.backend.clone();
+                async move {
+                    let mut agent = match Agent::new(config) {
+                        Ok(agent) => agent,
+                        Err(e) => return (Err(e), TokenUsage::default()),
+                    };
+                    agent.backend = backend;
+                    let response = agent.query(prompt).await;
+                    let mut usage = TokenUsage::default();
+                    for record in agent.turn_records.lock().await.iter() {
+                        usage += record.usage;
+                    }
+                    (response, usage)
                 }
-                OutputData::Error { error } => {
-                    return Err(AgentError::Execution {
-                        message: format!("Query failed: {:?}", error),
-                    });
+            })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await;
+
+        let mut batch = BatchResult {
+            responses: Vec::with_capacity(results.len()),
+            usage: TokenUsage::default(),
+        };
+        for (response, usage) in results {
+            batch.responses.push(response);
+            batch.usage += usage;
+        }
+        batch
+    }
+
+    /// Query the agent and stream the output of the turn as it is produced.
+    ///
+    /// The stream ends when the turn completes; errors are yielded as
+    /// [`OutputData::Error`] items.
+    pub async fn query_stream<S: Into<String>>(
+        &mut self,
+        message: S,
+    ) -> Result<BoxStream<'static, OutputData>> {
+        let (input_tx, input_rx) = async_channel::bounded(1);
+        let (plan_tx, plan_rx) = async_channel::unbounded();
+        let (output_tx, output_rx) = async_channel::bounded(100);
+
+        input_tx.send(InputMessage::new(message)).await?;
+        input_tx.close();
+
+        self.execute(input_rx, plan_tx, output_tx).await?;
+
+        // The plan receiver rides along so plan updates don't fail the turn
+        let stream =
+            futures::stream::unfold((output_rx, plan_rx), |(output_rx, plan_rx)| async move {
+                match output_rx.recv().await {
+                    Ok(OutputMessage {
+                        data: OutputData::Completed,
+                        ..
+                    })
+                    | Err(_) => None,
+                    Ok(output) => Some((output.data, (output_rx, plan_rx))),
                 }
-                _ => {
-                    // Ignore other message types for simple query
+            });
+
+        Ok(stream.boxed())
+    }
+
+    /// Query the agent for a structured response.
+    ///
+    /// The model is asked to answer with JSON conforming to `schema`, and the
+    /// response is parsed into `T`. Responses that fail to parse are sent back
+    /// to the model for correction up to [`AgentConfig::json_retries`] times.
+    pub async fn query_json<T, S>(&mut self, message: S, schema: serde_json::Value) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+        S: Into<String>,
+    {
+        let mut prompt = structured_prompt(&message.into(), &schema);
+        let mut attempt = 0;
+
+        loop {
+            let response = self.query(prompt).await?;
+            match serde_json::from_str(extract_json(&response)) {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.config.json_retries() => {
+                    attempt += 1;
+                    debug!("Invalid structured response, retry {}: {}", attempt, e);
+                    prompt = retry_prompt(&e, &schema);
                 }
+                Err(e) => return Err(e.into()),
             }
         }
+    }
 
-        // Wait for execution to complete
-        handle.await?;
+    /// Execute the agent with a single event stream.
+    ///
+    /// Output, plan updates, lifecycle state changes, and control
+    /// acknowledgements are merged into one channel of sequenced
+    /// [`AgentEvent`](crate::AgentEvent)s, so simple hosts don't need to
+    /// juggle separate receivers. A consumer that falls behind loses events
+    /// rather than stalling the agent, and is told so by
+    /// [`AgentEvent::GapDetected`](crate::AgentEvent::GapDetected).
+    pub async fn execute_unified(
+        &mut self,
+        input_rx: Receiver<InputMessage>,
+    ) -> Result<(AgentHandle, Receiver<SequencedEvent>)> {
+        let (plan_tx, plan_rx) = async_channel::bounded(100);
+        let (output_tx, output_rx) = async_channel::bounded(100);
+        let (event_tx, event_rx) = async_channel::bounded(EVENT_BUFFER_SIZE);
+
+        // Subscribe before starting so the transition to Running is seen
+        let state_rx = self.controller.subscribe();
+        let ack_rx = self.controller.subscribe_acks();
 
-        Ok(result.trim().to_string())
+        let handle = self.execute(input_rx, plan_tx, output_tx).await?;
+        tokio::spawn(merge_events(output_rx, plan_rx, state_rx, ack_rx, event_tx));
+
+        Ok((handle, event_rx))
+    }
+
+    /// Start a stateful multi-turn conversation.
+    ///
+    /// The returned handle keeps a single execution running, so successive
+    /// [`Conversation::chat`] calls share context without managing channels.
+    pub async fn conversation(&mut self) -> Result<Conversation> {
+        let (input_tx, input_rx) = async_channel::unbounded();
+        let (plan_tx, plan_rx) = async_channel::unbounded();
+        let (output_tx, output_rx) = async_channel::unbounded();
+
+        let handle = self.execute(input_rx, plan_tx, output_tx).await?;
+
+        Ok(Conversation::new(input_tx, plan_rx, output_rx, handle))
     }
 
     /// Execute the agent with full channel-based interface.
+    ///
+    /// The agent can be executed again once an execution completes or is
+    /// stopped; later executions continue the same conversation.
     pub async fn execute(
         &mut self,
         input_rx: Receiver<InputMessage>,
@@ -107,69 +416,205 @@ impl Agent {
     ) -> Result<AgentHandle> {
         // Initialize Codex conversation if not already done
         if self.codex_conversation.is_none() {
-            let codex_config = self._create_codex_config()?;
-
-            // Create conversation manager with appropriate auth
-            let conversation_manager = if let Some(api_key) = self.config.api_key() {
-                ConversationManager::with_auth(CodexAuth::from_api_key(api_key))
-            } else {
-                // Try to load from codex home directory or create with environment auth
-                let codex_home = codex_core::config::find_codex_home()
-                    .unwrap_or_else(|_| std::path::PathBuf::from("."));
-                let auth_manager = Arc::new(AuthManager::new(
-                    codex_home,
-                    codex_protocol::mcp_protocol::AuthMode::ApiKey,
-                ));
-                ConversationManager::new(auth_manager)
+            let backend = match &self.backend {
+                Some(backend) => backend.clone(),
+                None => {
+                    let backend = self._create_codex_backend().await?;
+                    self.codex_reasoning = backend.reasoning();
+                    Arc::new(backend)
+                }
             };
-
-            let new_conversation = conversation_manager
-                .new_conversation(codex_config)
-                .await
-                .map_err(|e| AgentError::Config {
-                    message: format!("Failed to create conversation: {:?}", e),
-                })?;
-
-            self.codex_conversation = Some(new_conversation.conversation);
+            let conversation = backend.start_conversation(self.tool_server.clone()).await?;
+            self.codex_conversation = Some(match self.config.recording() {
+                Some(path) => replay::record(conversation, path)?,
+                None => conversation,
+            });
         }
 
+        // Attach the controller to this execution
+        let control_rx = self.controller.connect().await;
+        let inputs = InputQueue::default();
+        let autonomy = Arc::new(Mutex::new(None));
+
         // Set initial state
         self.controller
             .set_execution_state(crate::controller::ExecutionState::Running)
             .await;
 
+        if let Some(sink) = self.config.telemetry() {
+            for event in telemetry::feature_events(&self.config) {
+                sink.record(&event);
+            }
+        }
+
         // Create the execution context
         let execution_context = ExecutionContext {
             config: self.config.clone(),
             controller: self.controller.clone(),
-            codex_conversation: self.codex_conversation.take().ok_or_else(|| {
+            codex_conversation: self.codex_conversation.clone().ok_or_else(|| {
                 AgentError::Generic {
                     message: "Failed to initialize Codex conversation".to_string(),
                 }
             })?,
-            input_rx,
+            backend: self.backend.clone(),
+            codex_reasoning: self.codex_reasoning,
+            inputs: inputs.clone(),
             plan_tx,
             output_tx,
-            control_rx: std::mem::replace(
-                &mut self.control_rx,
-                tokio::sync::mpsc::unbounded_channel().1,
-            ),
+            control_rx,
+            turn_records: self.turn_records.clone(),
+            file_changes: self.file_changes.clone(),
+            output_broadcast: self.output_broadcast.clone(),
+            output_seq: self.output_seq.clone(),
+            ops: self
+                .config
+                .ops_summary()
+                .map(|_| Mutex::new(OpsAggregator::default())),
+            ops_interval: self.config.ops_summary().map(periodic),
+            delta_interval: self
+                .config
+                .debounce_deltas()
+                .map(|max| periodic(Duration::from_secs(1) / max.max(1))),
+            pending_delta: Mutex::new(None),
+            input_id: None,
+            input_metadata: HashMap::new(),
+            input_text: String::new(),
+            checkpoint: None,
+            tape: Mutex::new(Vec::new()),
+            turn_usage: Mutex::new(TokenUsage::default()),
+            lexicon_stream: Mutex::new(LexiconStream::default()),
+            tool_outputs: Mutex::new(HashMap::new()),
+            patches: Mutex::new(HashMap::new()),
+            tool_calls: Mutex::new(HashMap::new()),
+            web_searches: Mutex::new(Vec::new()),
+            config_updates: Vec::new(),
+            turn_context_changed: false,
+            pending_instructions: None,
+            autonomy: autonomy.clone(),
+            tools: self.tools.clone(),
+            tool_server: self.tool_server.clone(),
+            rate_limiter: self
+                .config
+                .model_rate_limit()
+                .map(|(provider, credential, limit)| {
+                    rate_limit::limiter(&provider, &credential, limit)
+                }),
+            request_reserved: AtomicBool::new(false),
+            turn_spans: Mutex::new(None),
+            suggestions: Mutex::new(None),
         };
 
         // Spawn the execution task
-        let join_handle = tokio::spawn(async move { execution_loop(execution_context).await });
+        tokio::spawn(inputs.clone().fill_from(input_rx));
+        let (completion_tx, completion_rx) = watch::channel(None);
+        tokio::spawn(async move {
+            let result = execution_loop(execution_context).await;
+            completion_tx.send_replace(Some(result.map_err(|e| e.to_string())));
+        });
 
         Ok(AgentHandle {
+            config: Arc::new(self.config.clone()),
             controller: self.controller.clone(),
-            join_handle,
+            turn_records: self.turn_records.clone(),
+            file_changes: self.file_changes.clone(),
+            completion: completion_rx,
+            channels: None,
+            inputs,
+            output_broadcast: self.output_broadcast.clone(),
+            autonomy,
+            tools: self.tools.clone(),
+            backend: self.backend.clone(),
         })
     }
+
+    /// Start the agent with channels owned by the handle.
+    ///
+    /// Use [`AgentHandle::input_sink`], [`AgentHandle::output_stream`], and
+    /// [`AgentHandle::plan_stream`] to talk to the agent through the standard
+    /// `futures` traits instead of wiring channels by hand.
+    pub async fn start(&mut self) -> Result<AgentHandle> {
+        let (input_tx, input_rx) = async_channel::bounded(100);
+        let (plan_tx, plan_rx) = async_channel::unbounded();
+        let (output_tx, output_rx) = async_channel::bounded(100);
+
+        let mut handle = self.execute(input_rx, plan_tx, output_tx).await?;
+        handle.channels = Some(HandleChannels {
+            input_tx,
+            plan_rx,
+            output_rx,
+        });
+
+        Ok(handle)
+    }
+}
+
+/// Collect the response text of a turn from the output channel.
+///
+/// Reads until the turn completes, returning an error if the turn fails.
+pub(crate) async fn collect_response(output_rx: &Receiver<OutputMessage>) -> Result<String> {
+    let mut result = String::new();
+
+    while let Ok(output) = output_rx.recv().await {
+        match output.data {
+            OutputData::Primary { content } => {
+                result.push_str(&content);
+            }
+            OutputData::PrimaryDelta { content } => {
+                result.push_str(&content);
+            }
+            OutputData::Completed => {
+                break;
+            }
+            OutputData::Error { error } => {
+                return Err(AgentError::Execution {
+                    message: format!("Query failed: {:?}", error),
+                });
+            }
+            _ => {
+                // Ignore other message types for simple query
+            }
+        }
+    }
+
+    Ok(result.trim().to_string())
+}
+
+/// Results of [`Agent::query_batch`].
+#[derive(Debug)]
+pub struct BatchResult {
+    /// Response or error for each prompt, in prompt order
+    pub responses: Vec<Result<String>>,
+
+    /// Token usage summed over all queries
+    pub usage: TokenUsage,
 }
 
 /// Handle to a running agent execution.
+///
+/// Handles are cheap to clone; every clone controls the same execution and
+/// can subscribe to state changes or await completion independently.
+#[derive(Clone)]
 pub struct AgentHandle {
+    config: Arc<AgentConfig>,
     controller: AgentController,
-    join_handle: JoinHandle<Result<()>>,
+    turn_records: Arc<Mutex<Vec<TurnRecord>>>,
+    file_changes: Arc<Mutex<HashMap<u64, Vec<PatchFile>>>>,
+    completion: watch::Receiver<Option<std::result::Result<(), String>>>,
+    channels: Option<HandleChannels>,
+    inputs: InputQueue,
+    output_broadcast: broadcast::Sender<OutputMessage>,
+    autonomy: Arc<Mutex<Option<Autonomy>>>,
+    tools: ToolRegistry,
+    /// Backend the agent runs on, if not Codex, for requests made on its behalf
+    backend: Option<Arc<dyn LlmBackend>>,
+}
+
+/// Channel endpoints owned by handles created with [`Agent::start`].
+#[derive(Clone)]
+struct HandleChannels {
+    input_tx: Sender<InputMessage>,
+    plan_rx: Receiver<PlanMessage>,
+    output_rx: Receiver<OutputMessage>,
 }
 
 impl AgentHandle {
@@ -178,35 +623,247 @@ impl AgentHandle {
         &self.controller
     }
 
+    /// Get the configuration the agent is running with.
+    pub fn config(&self) -> &AgentConfig {
+        &self.config
+    }
+
+    /// Change settings of the running agent without restarting its
+    /// conversation.
+    ///
+    /// The update is applied once the running turn, if any, ends, and this
+    /// handle's [`config`](Self::config) reflects it on success. Fails
+    /// without changing anything if the updated configuration is invalid.
+    pub async fn update_config(&mut self, patch: ConfigPatch) -> Result<()> {
+        let config = self.controller.update_config(patch).await?;
+        self.config = Arc::new(config);
+        Ok(())
+    }
+
+    /// Get the records of all turns completed so far.
+    pub async fn turn_records(&self) -> Vec<TurnRecord> {
+        self.turn_records.lock().await.clone()
+    }
+
+    /// Undo the file changes the agent's patches made during a turn.
+    ///
+    /// Patches are reverted newest first: created files are removed, updated
+    /// files have their hunks reversed, and deleted files are restored from
+    /// the backups of an apply patch tool with `create_backup`. A file edited
+    /// after the turn may no longer match its hunks, which stops the rollback;
+    /// the changes not reverted yet stay recorded so it can be retried.
+    /// Changes made by shell commands are not tracked.
+    ///
+    /// Returns the paths restored.
+    pub async fn rollback_turn(&self, turn_id: u64) -> Result<Vec<PathBuf>> {
+        let mut file_changes = self.file_changes.lock().await;
+        let files = file_changes
+            .remove(&turn_id)
+            .ok_or_else(|| AgentError::Generic {
+                message: format!("No file changes recorded for turn {}", turn_id),
+            })?;
+
+        let working_directory = self.config.working_directory();
+        let mut restored = Vec::new();
+        for (index, file) in files.iter().enumerate().rev() {
+            let backup = patch::backup_path(working_directory, turn_id, &file.path);
+            let backup = tokio::fs::try_exists(&backup)
+                .await
+                .unwrap_or(false)
+                .then_some(backup.as_path());
+            if let Err(e) = patch::revert(file, working_directory, backup).await {
+                file_changes.insert(turn_id, files[..=index].to_vec());
+                return Err(AgentError::Tool {
+                    message: format!("Failed to roll back {}: {}", file.path.display(), e),
+                });
+            }
+            restored.push(file.path.clone());
+        }
+        Ok(restored)
+    }
+
+    /// Ask the model to explain the decisions it made during a turn.
+    ///
+    /// The turn's recorded event tape is given to a separate agent on the
+    /// same model and backend, so the running conversation is not disturbed.
+    pub async fn explain(&self, turn_id: u64) -> Result<TurnExplanation> {
+        let record = self
+            .turn_records()
+            .await
+            .into_iter()
+            .find(|record| record.turn_id == turn_id)
+            .ok_or_else(|| AgentError::Generic {
+                message: format!("No record of turn {}", turn_id),
+            })?;
+
+        let mut agent = Agent::new(self.config.for_side_query())?;
+        if let Some(backend) = &self.backend {
+            agent = agent.with_backend(backend.clone());
+        }
+        let mut explanation: TurnExplanation = agent
+            .query_json(explain_prompt(&record), explanation_schema())
+            .await?;
+        explanation.turn_id = turn_id;
+        Ok(explanation)
+    }
+
+    /// Subscribe to execution state changes.
+    pub fn subscribe(&self) -> watch::Receiver<crate::controller::AgentExecutionState> {
+        self.controller.subscribe()
+    }
+
+    /// Get a sink for sending input messages to the agent.
+    ///
+    /// Only available on handles created with [`Agent::start`]; closing the
+    /// sink ends the execution once queued input has been processed.
+    pub fn input_sink(
+        &self,
+    ) -> Option<impl futures::Sink<InputMessage, Error = AgentError> + Send + use<>> {
+        let input_tx = self.channels.as_ref()?.input_tx.clone();
+        Some(futures::sink::unfold(
+            input_tx,
+            |input_tx, message: InputMessage| async move {
+                input_tx.send(message).await?;
+                Ok::<_, AgentError>(input_tx)
+            },
+        ))
+    }
+
+    /// Get a stream of the agent's output messages.
+    ///
+    /// Only available on handles created with [`Agent::start`]. Streams from
+    /// clones of the handle share the same queue, so each message is
+    /// delivered to one of them.
+    pub fn output_stream(
+        &self,
+    ) -> Option<impl futures::Stream<Item = OutputMessage> + Send + use<>> {
+        Some(self.channels.as_ref()?.output_rx.clone())
+    }
+
+    /// Get a stream of plan updates.
+    ///
+    /// Only available on handles created with [`Agent::start`].
+    pub fn plan_stream(&self) -> Option<impl futures::Stream<Item = PlanMessage> + Send + use<>> {
+        Some(self.channels.as_ref()?.plan_rx.clone())
+    }
+
+    /// Subscribe to every output message the agent emits.
+    ///
+    /// Subscribers are independent of each other and of the output channel.
+    pub fn subscribe_output(&self) -> broadcast::Receiver<OutputMessage> {
+        self.output_broadcast.subscribe()
+    }
+
+    /// List the input messages queued behind the running turn.
+    pub async fn pending_inputs(&self) -> Vec<PendingInput> {
+        self.inputs.snapshot().await
+    }
+
+    /// Cancel a queued input before its turn starts.
+    ///
+    /// Returns whether the input was still pending.
+    pub async fn cancel_pending(&self, request_id: &str) -> bool {
+        self.inputs.cancel(request_id).await
+    }
+
+    /// Get the tool registry of the running agent.
+    pub fn tool_registry(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    /// Register a tool on the running agent, failing if the name is taken.
+    ///
+    /// Host tools, like custom tools, are served to the model right away:
+    /// the MCP server of the host tools announces the change, and lists the
+    /// tool from then on. Tools Codex implements itself are enabled when the
+    /// agent next creates a conversation.
+    pub fn register_tool(&self, tool: ToolConfig) -> Result<()> {
+        info!("Registering tool {}", tool.name());
+        self.tools.register(tool)
+    }
+
+    /// Unregister a tool from the running agent, returning it if found.
+    pub fn unregister_tool(&self, name: &str) -> Option<ToolConfig> {
+        info!("Unregistering tool {}", name);
+        self.tools.unregister(name)
+    }
+
+    /// Work toward `goal` without further user input.
+    ///
+    /// After every turn that leaves plan steps open, the agent is prompted to
+    /// continue. `OutputData::CheckIn` summaries are emitted as configured by
+    /// `check_in_every`; with `require_approval` the agent pauses at each one
+    /// until [`AgentController::resume`] is called. The run ends with a final
+    /// check-in once the plan is done, the time box or turn limit is reached,
+    /// or a turn fails.
+    pub async fn run_autonomously<S: Into<String>>(
+        &self,
+        goal: S,
+        check_in_every: CheckInPolicy,
+    ) -> Result<()> {
+        if self.is_finished() {
+            return Err(AgentError::Execution {
+                message: "Agent execution has finished".to_string(),
+            });
+        }
+
+        let mut autonomy = self.autonomy.lock().await;
+        if autonomy.is_some() {
+            return Err(AgentError::Execution {
+                message: "Agent is already running autonomously".to_string(),
+            });
+        }
+        let run = Autonomy::new(goal.into(), check_in_every);
+        self.inputs
+            .push(InputMessage::new(run.initial_prompt()))
+            .await;
+        *autonomy = Some(run);
+        Ok(())
+    }
+
+    /// End an autonomous run after the current turn.
+    ///
+    /// Returns whether a run was active.
+    pub async fn stop_autonomy(&self) -> bool {
+        self.autonomy.lock().await.take().is_some()
+    }
+
+    /// Check if the agent is running autonomously.
+    pub async fn is_autonomous(&self) -> bool {
+        self.autonomy.lock().await.is_some()
+    }
+
+    /// Check if the execution has finished.
+    pub fn is_finished(&self) -> bool {
+        self.completion.borrow().is_some()
+    }
+
     /// Wait for the agent execution to complete.
+    ///
+    /// Every clone of the handle observes the same outcome.
     pub async fn await_completion(self) -> Result<()> {
-        match self.join_handle.await {
-            Ok(result) => result,
-            Err(join_error) => Err(AgentError::Execution {
-                message: format!("Agent execution task failed: {}", join_error),
-            }),
+        let mut completion = self.completion;
+        let outcome = completion
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| AgentError::Execution {
+                message: "Agent execution task failed before completing".to_string(),
+            })?
+            .clone();
+
+        match outcome {
+            Some(Err(message)) => Err(AgentError::Execution { message }),
+            _ => Ok(()),
         }
     }
 }
 
-impl std::future::Future for AgentHandle {
+impl std::future::IntoFuture for AgentHandle {
     type Output = Result<()>;
+    type IntoFuture = futures::future::BoxFuture<'static, Result<()>>;
 
-    fn poll(
-        mut self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Self::Output> {
-        use std::pin::Pin;
-
-        match Pin::new(&mut self.join_handle).poll(cx) {
-            std::task::Poll::Ready(Ok(result)) => std::task::Poll::Ready(result),
-            std::task::Poll::Ready(Err(join_error)) => {
-                std::task::Poll::Ready(Err(AgentError::Execution {
-                    message: format!("Agent execution task failed: {}", join_error),
-                }))
-            }
-            std::task::Poll::Pending => std::task::Poll::Pending,
-        }
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.await_completion())
     }
 }
 
@@ -215,18 +872,211 @@ impl std::future::Future for AgentHandle {
 struct ExecutionContext {
     config: AgentConfig,
     controller: AgentController,
-    codex_conversation: Arc<CodexConversation>,
-    input_rx: Receiver<InputMessage>,
+    codex_conversation: Arc<dyn ConversationBackend>,
+    /// Backend the agent runs on, if not Codex, for requests made on its behalf
+    backend: Option<Arc<dyn LlmBackend>>,
+    /// Reasoning settings of the Codex config, for turns the config leaves
+    /// them unset
+    codex_reasoning: (ReasoningEffort, ReasoningSummary),
+    inputs: InputQueue,
     plan_tx: Sender<PlanMessage>,
     output_tx: Sender<OutputMessage>,
     control_rx: tokio::sync::mpsc::UnboundedReceiver<crate::controller::ControlCommand>,
+    turn_records: Arc<Mutex<Vec<TurnRecord>>>,
+    file_changes: Arc<Mutex<HashMap<u64, Vec<PatchFile>>>>,
+    output_broadcast: broadcast::Sender<OutputMessage>,
+    ops: Option<Mutex<OpsAggregator>>,
+    ops_interval: Option<tokio::time::Interval>,
+    /// Interval between flushes of batched deltas
+    delta_interval: Option<tokio::time::Interval>,
+    /// Delta batched until the next flush
+    pending_delta: Mutex<Option<OutputMessage>>,
+    output_seq: Arc<AtomicU64>,
+    /// Id of the input message whose turn is running
+    input_id: Option<String>,
+    /// Metadata of the input message whose turn is running
+    input_metadata: HashMap<String, serde_json::Value>,
+    /// Text of the input message whose turn is running
+    input_text: String,
+    /// Checkpoint taken before the running turn
+    checkpoint: Option<String>,
+    /// Outputs of the running turn, saved on its record
+    tape: Mutex<Vec<OutputMessage>>,
+    /// Tokens used by the running turn
+    turn_usage: Mutex<TokenUsage>,
+    /// Lexicon filtering state of the streamed response
+    lexicon_stream: Mutex<LexiconStream>,
+    /// Truncation state of command outputs over the bash output limit, by call id
+    tool_outputs: Mutex<HashMap<String, TruncatedStream>>,
+    /// Changes of the patches being applied, by call id
+    patches: Mutex<HashMap<String, Vec<PatchFile>>>,
+    /// Commands and patches passed by tool middleware, by call id
+    tool_calls: Mutex<HashMap<String, ToolCall>>,
+    /// Queries of web searches whose cited pages are not reported yet
+    web_searches: Mutex<Vec<String>>,
+    /// Config updates received during the running turn, applied once it ends
+    config_updates: Vec<(ConfigPatch, oneshot::Sender<Result<AgentConfig>>)>,
+    /// Whether a config update changed settings sent with each turn
+    turn_context_changed: bool,
+    /// Updated system prompt not yet given to the model
+    pending_instructions: Option<String>,
+    /// Autonomous run in progress, if any
+    autonomy: Arc<Mutex<Option<Autonomy>>>,
+    /// Tools of the agent, whose calls in flight are cancelled with the turn
+    tools: ToolRegistry,
+    /// Server of the tools to the model, told which turn its calls run in
+    tool_server: ToolServer,
+    /// Rate limiter of the model's provider, if it is limited
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// Whether the next model request was taken from the rate limiter already
+    request_reserved: AtomicBool,
+    /// Tracing spans of the running turn
+    turn_spans: Mutex<Option<TurnSpans>>,
+    /// Follow-up suggestions being generated for the last turn
+    suggestions: Mutex<Option<JoinHandle<Option<OutputMessage>>>>,
+}
+
+impl ExecutionContext {
+    /// Sequence an output message and send it to the output channel and
+    /// broadcast subscribers.
+    ///
+    /// Only the execution task emits, so messages go out in `seq` order.
+    async fn emit(&self, message: OutputMessage) -> Result<()> {
+        let is_delta = matches!(
+            message.data,
+            OutputData::PrimaryDelta { .. } | OutputData::ReasoningDelta { .. }
+        );
+        if is_delta && self.config.coalesce_deltas() {
+            return Ok(());
+        }
+        if self.delta_interval.is_none() {
+            return self.send(message).await;
+        }
+
+        // Batch deltas until the next flush; other outputs flush them first
+        let mut pending = self.pending_delta.lock().await;
+        match (
+            pending.as_mut().map(|pending| &mut pending.data),
+            &message.data,
+        ) {
+            (
+                Some(OutputData::PrimaryDelta { content }),
+                OutputData::PrimaryDelta { content: more },
+            )
+            | (
+                Some(OutputData::ReasoningDelta { content }),
+                OutputData::ReasoningDelta { content: more },
+            ) => {
+                content.push_str(more);
+                Ok(())
+            }
+            _ if is_delta => {
+                let flushed = pending.replace(message);
+                drop(pending);
+                match flushed {
+                    Some(flushed) => self.send(flushed).await,
+                    None => Ok(()),
+                }
+            }
+            _ => {
+                let flushed = pending.take();
+                drop(pending);
+                if let Some(flushed) = flushed {
+                    self.send(flushed).await?;
+                }
+                self.send(message).await
+            }
+        }
+    }
+
+    /// Wait for the provider's rate limit before submitting input to the model.
+    async fn throttle(&self) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+            self.request_reserved.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Emit the batched delta, if any.
+    async fn flush_delta(&self) -> Result<()> {
+        let flushed = self.pending_delta.lock().await.take();
+        match flushed {
+            Some(flushed) => self.send(flushed).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Send an output message without batching.
+    async fn send(&self, mut message: OutputMessage) -> Result<()> {
+        #[cfg(feature = "metrics")]
+        if let OutputData::Error { error } = &message.data {
+            crate::metrics::error(error.kind());
+        }
+        if let OutputData::Primary { content } = &mut message.data {
+            for processor in self.config.output_processors() {
+                *content = processor.process(std::mem::take(content));
+            }
+        }
+        if let Some(filter) = self.config.lexicon_filter() {
+            match &mut message.data {
+                OutputData::Primary { content } => {
+                    *content = filter.filter(content);
+                    self.lexicon_stream.lock().await.reset();
+                }
+                OutputData::PrimaryDelta { content } => {
+                    match self.lexicon_stream.lock().await.push(filter, content) {
+                        Some(filtered) => *content = filtered,
+                        // Held back until more of the response arrives
+                        None => return Ok(()),
+                    }
+                }
+                _ => {}
+            }
+        }
+        message.seq = self.output_seq.fetch_add(1, Ordering::Relaxed) + 1;
+        if message.input_id.is_none() {
+            message.input_id = self.input_id.clone();
+        }
+        if message.metadata.is_empty() {
+            message.metadata = self.input_metadata.clone();
+        }
+        if !matches!(
+            message.data,
+            OutputData::PrimaryDelta { .. }
+                | OutputData::ReasoningDelta { .. }
+                | OutputData::Heartbeat
+                | OutputData::OpsSummary { .. }
+        ) {
+            let mut tape = self.tape.lock().await;
+            if tape.len() < MAX_TAPE_EVENTS {
+                tape.push(message.clone());
+            }
+        }
+        if let Some(ops) = &self.ops {
+            ops.lock().await.record_output(&message.data);
+        }
+        if let Some(sink) = self.config.telemetry()
+            && let Some(event) = telemetry::output_event(&message.data)
+        {
+            sink.record(&event);
+        }
+        if self.output_broadcast.receiver_count() > 0 {
+            // Sending only fails when every subscriber has gone away
+            let _ = self.output_broadcast.send(message.clone());
+        }
+        self.output_tx.send(message).await?;
+        Ok(())
+    }
 }
 
 /// Main execution loop for the agent.
 async fn execution_loop(mut context: ExecutionContext) -> Result<()> {
     info!("Starting agent execution loop");
 
-    // Main execution loop
+    let mut heartbeat = context.config.heartbeat().map(periodic);
+
+    // Main execution loop, woken only by control commands, input messages,
+    // and the optional heartbeat and ops summary
     loop {
         // Check for control commands
         tokio::select! {
@@ -234,7 +1084,12 @@ async fn execution_loop(mut context: ExecutionContext) -> Result<()> {
             control_command = context.control_rx.recv() => {
                 if let Some(command) = control_command {
                     debug!("Received control command: {:?}", command);
-                    context.controller.handle_control_command(command).await;
+                    match command {
+                        ControlCommand::UpdateConfig { patch, response_tx } => {
+                            let _ = response_tx.send(update_config(&mut context, &patch));
+                        }
+                        command => context.controller.handle_control_command(command).await,
+                    }
 
                     // If stopped, break the loop
                     if context.controller.should_stop() {
@@ -246,23 +1101,32 @@ async fn execution_loop(mut context: ExecutionContext) -> Result<()> {
                 }
             }
 
-            // Handle input messages
-            input_message = context.input_rx.recv() => {
-                match input_message {
-                    Ok(message) => {
-                        // Wait if paused
-                        context.controller.wait_if_paused().await;
-
+            // Handle input messages; while paused inputs stay queued until resumed
+            pending = context.inputs.pop(), if !context.controller.is_paused() => {
+                match pending {
+                    Some(PendingInput { message, .. }) => {
                         // Check if we should stop
                         if context.controller.should_stop() {
                             break;
                         }
 
-                        // Process the input message
-                        if let Err(e) = process_input_message(
-                            &mut context,
-                            message,
-                        ).await {
+                        // Suggestions for the last turn are moot once the next starts
+                        if let Some(task) = context.suggestions.lock().await.take() {
+                            task.abort();
+                        }
+
+                        // Process the input message, tagging its outputs with its id
+                        context.input_id = message.id.clone();
+                        context.input_metadata = message.metadata.clone();
+                        context.input_text = message.message.clone();
+                        context.tape.lock().await.clear();
+                        *context.turn_usage.lock().await = TokenUsage::default();
+                        context.lexicon_stream.lock().await.reset();
+                        let result = process_input_message(&mut context, message).await;
+                        if let Some(spans) = context.turn_spans.lock().await.take() {
+                            spans.finish(TurnStatus::Failed);
+                        }
+                        if let Err(e) = &result {
                             error!("Error processing input message: {}", e);
 
                             // Send error output
@@ -275,14 +1139,23 @@ async fn execution_loop(mut context: ExecutionContext) -> Result<()> {
                                 },
                             );
 
-                            if let Err(send_err) = context.output_tx.send(error_output).await {
+                            if let Err(send_err) = context.emit(error_output).await {
                                 error!("Failed to send error output: {}", send_err);
                             }
 
                             context.controller.set_error(e.to_string()).await;
                         }
+                        for (patch, response_tx) in std::mem::take(&mut context.config_updates) {
+                            let _ = response_tx.send(update_config(&mut context, &patch));
+                        }
+                        if let Err(e) = continue_autonomy(&context, result.is_err()).await {
+                            warn!("Failed to continue autonomous run: {}", e);
+                        }
+                        context.input_id = None;
+                        context.input_metadata.clear();
+                        context.input_text.clear();
                     }
-                    Err(_) => {
+                    None => {
                         // Input channel closed, finish current processing and exit
                         debug!("Input channel closed");
                         break;
@@ -290,21 +1163,60 @@ async fn execution_loop(mut context: ExecutionContext) -> Result<()> {
                 }
             }
 
-            // Handle timeout or other conditions
-            _ = tokio::time::sleep(Duration::from_secs(1)) => {
-                // Periodic maintenance or heartbeat
-                continue;
+            // Emit follow-up suggestions once generated
+            message = next_suggestions(&context.suggestions) => {
+                if let Some(message) = message
+                    && let Err(e) = context.emit(message).await
+                {
+                    warn!("Failed to send suggestions: {}", e);
+                }
+            }
+
+            // Emit a heartbeat if configured
+            _ = next_tick(&mut heartbeat) => {
+                let heartbeat_message =
+                    OutputMessage::new(context.controller.turn_count(), OutputData::Heartbeat);
+                if let Err(e) = context.emit(heartbeat_message).await {
+                    warn!("Failed to send heartbeat: {}", e);
+                }
+            }
+
+            // Emit an operational summary if configured
+            _ = next_tick(&mut context.ops_interval) => {
+                if let Err(e) = emit_ops_summary(&context).await {
+                    warn!("Failed to send ops summary: {}", e);
+                }
+            }
+
+            // Emit deltas batched since the last flush
+            _ = next_tick(&mut context.delta_interval) => {
+                if let Err(e) = context.flush_delta().await {
+                    warn!("Failed to send batched delta: {}", e);
+                }
             }
         }
     }
 
     info!("Agent execution loop finished");
+    context.tools.cancel_calls();
+
+    // Suggestions still being generated go out before the completion marker,
+    // unless the agent was stopped
+    if let Some(task) = context.suggestions.lock().await.take() {
+        if context.controller.should_stop() {
+            task.abort();
+        } else if let Ok(Some(message)) = task.await
+            && let Err(e) = context.emit(message).await
+        {
+            warn!("Failed to send suggestions: {}", e);
+        }
+    }
 
     // Send final completion message
     let completion_message =
         OutputMessage::new(context.controller.turn_count(), OutputData::Completed);
 
-    if let Err(e) = context.output_tx.send(completion_message).await {
+    if let Err(e) = context.emit(completion_message).await {
         warn!("Failed to send completion message: {}", e);
     }
 
@@ -319,79 +1231,677 @@ async fn execution_loop(mut context: ExecutionContext) -> Result<()> {
     Ok(())
 }
 
-/// Process a single input message.
-async fn process_input_message(
-    context: &mut ExecutionContext,
-    input_message: InputMessage,
-) -> Result<()> {
-    debug!("Processing input message: {}", input_message.message);
+/// Apply a runtime config update between turns, returning the new config.
+///
+/// An updated approval policy is sent with every following turn. Codex keeps
+/// the system prompt it started the conversation with, so an updated prompt
+/// is given to the model with the next input instead.
+fn update_config(context: &mut ExecutionContext, patch: &ConfigPatch) -> Result<AgentConfig> {
+    let config = context.config.clone().with_patch(patch)?;
+    for name in &patch.disable_tools {
+        context.tools.unregister(name);
+    }
+    for tool in &patch.enable_tools {
+        context.tools.unregister(tool.name());
+        context.tools.register(tool.clone())?;
+    }
+    if patch.approval_policy.is_some() {
+        context.turn_context_changed = true;
+    }
+    if let Some(prompt) = &patch.system_prompt {
+        context.pending_instructions = Some(prompt.clone());
+    }
+    info!("Updated agent config: {:?}", patch);
+    context.config = config.clone();
+    Ok(config)
+}
 
-    // Increment turn count
-    context.controller.increment_turn_count();
-    let turn_id = context.controller.turn_count();
+/// Create an interval whose first tick is one period from now.
+fn periodic(period: Duration) -> tokio::time::Interval {
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    interval
+}
 
-    // Send start message
-    let start_message = OutputMessage::new(turn_id, OutputData::Start);
-    context.output_tx.send(start_message).await?;
+/// Log and emit the operational summary for the period that just ended.
+async fn emit_ops_summary(context: &ExecutionContext) -> Result<()> {
+    let (Some(ops), Some(period)) = (&context.ops, context.config.ops_summary()) else {
+        return Ok(());
+    };
 
-    // Convert input message to Codex format
-    let mut input_items = vec![InputItem::Text {
-        text: input_message.message,
-    }];
+    let summary = ops.lock().await.take(period);
+    info!(
+        turns_completed = summary.turns_completed,
+        total_tokens = summary.total_tokens,
+        errors = summary.errors,
+        warnings = summary.warnings,
+        top_tools = ?summary.top_tools,
+        current_step = ?summary.current_step,
+        "Agent operational summary"
+    );
+
+    let message = OutputMessage::new(
+        context.controller.turn_count(),
+        OutputData::OpsSummary { summary },
+    );
+    context.emit(message).await
+}
 
-    // Add images if any
-    for image in input_message.images {
-        input_items.push(InputItem::Image {
-            image_url: image.data, // Base64 data URL format expected
-        });
+/// Wait for the next tick of an optional interval, or forever if it is disabled.
+async fn next_tick(interval: &mut Option<tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
     }
+}
+
+/// Wait for the follow-up suggestions being generated, or forever if none
+/// are.
+async fn next_suggestions(
+    task: &Mutex<Option<JoinHandle<Option<OutputMessage>>>>,
+) -> Option<OutputMessage> {
+    let mut task = task.lock().await;
+    let Some(running) = task.as_mut() else {
+        return std::future::pending().await;
+    };
+    let message = running.await.ok().flatten();
+    *task = None;
+    message
+}
+
+/// Sleep until an optional deadline, or forever if there is none.
+async fn sleep_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Run the tool middleware's `before_call` hooks on a command or patch Codex
+/// asks approval for or starts, unless they passed it already, returning
+/// the error of a rejection. Rewritten arguments are only seen by later
+/// hooks, as Codex runs the call as it is.
+async fn check_codex_call(
+    context: &ExecutionContext,
+    turn_id: u64,
+    event: &Event,
+) -> Option<OutputError> {
+    let middleware = context.config.tool_middleware();
+    if middleware.is_empty() {
+        return None;
+    }
+    let (call_id, tool_name, arguments) = protocol::codex_tool_call(&event.msg)?;
+    let mut tool_calls = context.tool_calls.lock().await;
+    if tool_calls.contains_key(call_id) {
+        return None;
+    }
+    let mut call = ToolCall {
+        tool_name: tool_name.to_string(),
+        arguments,
+        turn_id,
+    };
+    for hook in middleware {
+        if let Err(e) = hook.before_call(&mut call) {
+            return Some(OutputError::ToolExecutionFailed {
+                tool_name: tool_name.to_string(),
+                error: format!("Tool call rejected: {e}"),
+            });
+        }
+    }
+    tool_calls.insert(call_id.to_string(), call);
+    None
+}
+
+/// Terminate a command Codex runs that outlived the bash tool's timeout,
+/// leaving the turn and Codex's other commands running; Codex reports the
+/// command as failed.
+#[cfg(target_os = "linux")]
+async fn terminate_codex_command(_context: &ExecutionContext, command: Vec<String>) -> Result<()> {
+    tokio::spawn(async move {
+        let policy = crate::limits::KillPolicy::default();
+        let reaped = crate::process::terminate_command(&command, &policy).await;
+        debug!("Terminated timed-out command {:?}: {:?}", command, reaped);
+    });
+    Ok(())
+}
+
+/// Interrupt the turn of a command Codex runs that outlived the bash tool's
+/// timeout; Codex's commands can only be found among the agent's child
+/// processes on Linux.
+#[cfg(not(target_os = "linux"))]
+async fn terminate_codex_command(context: &ExecutionContext, _command: Vec<String>) -> Result<()> {
+    context.codex_conversation.submit(Op::Interrupt).await?;
+    Ok(())
+}
 
-    // Create submission
+/// Reject a command Codex runs with its own shell while the bash tool runs
+/// commands on the host, where its resource limits, kill policy, and the
+/// container backend apply.
+fn check_codex_shell(context: &ExecutionContext, event: &Event) -> Option<OutputError> {
+    let (_, tool_name, _) = protocol::codex_tool_call(&event.msg)?;
+    if tool_name != "exec_command" || !context.tools.runs_shell_on_host() {
+        return None;
+    }
+    Some(OutputError::ToolExecutionFailed {
+        tool_name: tool_name.to_string(),
+        error: format!(
+            "Commands run through the bash tool of the {} server",
+            tool_server::SERVER_NAME
+        ),
+    })
+}
+
+/// Process a single input message.
+async fn process_input_message(
+    context: &mut ExecutionContext,
+    input_message: InputMessage,
+) -> Result<()> {
+    debug!("Processing input message: {}", input_message.message);
+    let options = input_message.options.unwrap_or_default();
+
+    // Increment turn count
+    context.controller.increment_turn_count();
+    let turn_id = context.controller.turn_count();
+
+    // Send start message
+    let start_message = OutputMessage::new(turn_id, OutputData::Start);
+    context.emit(start_message).await?;
+
+    // Give the model a system prompt updated since the conversation started
+    let mut message = match context.pending_instructions.take() {
+        Some(instructions) => format!(
+            "The system instructions have been updated. From now on, follow these \
+             instructions instead of the previous ones:\n\n{}\n\n---\n\n{}",
+            instructions, input_message.message
+        ),
+        None => input_message.message,
+    };
+
+    // Write attached files into the working directory and list them
+    if !input_message.attachments.is_empty() {
+        let paths = attachment::materialize(
+            &input_message.attachments,
+            context.config.working_directory(),
+            turn_id,
+        )
+        .await?;
+        message.push_str(&attachment::prompt_section(&paths));
+    }
+
+    // Convert input message to Codex format
+    let text = match context.config.response_schema() {
+        Some(schema) => structured_prompt(&message, schema),
+        None => message,
+    };
+    let mut input_items = vec![InputItem::Text { text }];
+
+    // Add images if any
+    for image in input_message.images {
+        input_items.push(InputItem::Image {
+            image_url: image.data, // Base64 data URL format expected
+        });
+    }
+
+    // Reject input while the workspace is over its disk quota
+    let mut quota_warned = false;
+    if check_disk_quota(context, turn_id, &mut quota_warned).await? {
+        return Ok(());
+    }
+
+    // Checkpoint the workspace so the turn's edits can be reset
+    context.checkpoint = None;
+    if context.config.git_checkpoints() {
+        match checkpoint_turn(context.config.working_directory(), turn_id).await {
+            Ok(checkpoint) => context.checkpoint = checkpoint,
+            Err(e) => warn!(
+                "Failed to checkpoint workspace before turn {}: {}",
+                turn_id, e
+            ),
+        }
+    }
+
+    // Snapshot the workspace so file changes can be summarized at turn end
+    let workspace_before = if context.config.workspace_summary() {
+        match scan_workspace(context.config.working_directory().clone()).await {
+            Ok(index) => Some(index),
+            Err(e) => {
+                warn!("Failed to index workspace: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // The conversation's provider is fixed, so the turn's model must share it
+    let (provider, model) = context
+        .config
+        .resolve_model(options.model.as_deref().unwrap_or(context.config.model()));
+    if options.model.is_some() && provider != context.config.resolve_model(context.config.model()).0
+    {
+        let error = OutputMessage::new(
+            turn_id,
+            OutputData::error(OutputError::ConfigurationError {
+                error: format!(
+                    "Model {} is served by another provider than this conversation; \
+                     run it on an agent configured with that model",
+                    options.model.as_deref().unwrap_or_default()
+                ),
+            }),
+        );
+        context.emit(error).await?;
+        return Ok(());
+    }
+
+    *context.turn_spans.lock().await = Some(TurnSpans::start(turn_id, &model));
+    context.tool_server.start_turn(turn_id, &context.config);
+
+    // Create submission, overriding the model settings for this turn if requested
+    let op = if options.overrides_model() || context.turn_context_changed {
+        Op::UserTurn {
+            items: input_items,
+            cwd: context.config.working_directory().clone(),
+            approval_policy: context.config.codex_approval_policy(),
+            sandbox_policy: context.config.sandbox_policy().clone(),
+            model,
+            effort: options
+                .reasoning_effort
+                .or(context.config.reasoning_effort())
+                .unwrap_or(context.codex_reasoning.0),
+            summary: context
+                .config
+                .reasoning_summary()
+                .unwrap_or(context.codex_reasoning.1),
+        }
+    } else {
+        Op::UserInput { items: input_items }
+    };
     let submission = Submission {
         id: uuid::Uuid::new_v4().to_string(),
-        op: Op::UserInput { items: input_items },
+        op,
     };
 
     // Submit to Codex and process events
+    context.throttle().await;
     context
         .codex_conversation
         .submit_with_id(submission)
         .await?;
 
+    // Cancellation requests waiting for the aborted turn to drain
+    let mut pending_cancels = Vec::new();
+
+    // Approval request held back while paused before its tool
+    let mut held_event: Option<Event> = None;
+
+    // Approval request of a previewed patch, approved once resumed
+    let mut held_patch: Option<String> = None;
+
+    // Approval requests awaiting the host's decision, by id
+    let mut pending_approvals: HashMap<String, ApprovalRequest> = HashMap::new();
+
+    // Verification state: whether the turn applied patches, repair rounds used
+    let mut turn_patched = false;
+    let mut repair_attempts = 0;
+
+    // Retry state: transient model error held back, retries used
+    let mut retry_error: Option<String> = None;
+    let mut retry_attempts = 0;
+
+    // Interrupt the turn once its timeout elapses
+    let deadline = options
+        .timeout
+        .map(|timeout| tokio::time::Instant::now() + timeout);
+    let mut timed_out = false;
+
+    // Commands run by Codex are terminated once they outlive the bash tool's
+    // timeout, as Codex has no timeout of its own to give them; the deadline
+    // and arguments of each running command are kept by call id
+    let command_timeout = match context.tools.get("bash") {
+        Some(ToolConfig::Bash {
+            timeout: Some(seconds),
+            ..
+        }) => Some(Duration::from_secs(seconds)),
+        _ => None,
+    };
+    let mut command_deadlines: HashMap<String, (tokio::time::Instant, Vec<String>)> =
+        HashMap::new();
+
+    // Periodic disk quota monitoring while the turn runs
+    let mut quota_exceeded = false;
+    let mut quota_interval = context.config.disk_quota().map(|quota| {
+        let period = Duration::from_secs(quota.check_interval.max(1));
+        tokio::time::interval_at(tokio::time::Instant::now() + period, period)
+    });
+
     // Process events one by one
     loop {
-        // Check if we should stop or pause
+        // Check if we should stop
         if context.controller.should_stop() {
+            context.tools.cancel_calls();
             break;
         }
 
-        context.controller.wait_if_paused().await;
+        // Apply a previewed patch once the host resumes
+        if !context.controller.is_paused()
+            && let Some(id) = held_patch.take()
+        {
+            let decision = ReviewDecision::Approved;
+            context
+                .codex_conversation
+                .submit(Op::PatchApproval { id, decision })
+                .await?;
+            continue;
+        }
 
-        // Get next event
-        match context.codex_conversation.next_event().await {
+        // Take up a held approval request once resumed, before new events
+        let released = if context.controller.is_paused() {
+            None
+        } else {
+            held_event.take()
+        };
+        let stepped = released.is_some();
+
+        // Get next control command or event; events are held back while paused
+        let next_event = if let Some(event) = released {
+            Ok(event)
+        } else {
+            tokio::select! {
+                control_command = context.control_rx.recv() => {
+                    match control_command {
+                        Some(ControlCommand::CancelTurn(response_tx)) => {
+                            debug!("Interrupting turn {}", turn_id);
+                            context.tools.cancel_calls();
+                            if let Some(event) = held_event.take()
+                                && let Some(request) = protocol::approval_request(&event.msg)
+                            {
+                                let decision = ApprovalDecision::Denied;
+                                let op = protocol::approval_op(event.id, &request, decision);
+                                context.codex_conversation.submit(op).await?;
+                            }
+                            if let Some(id) = held_patch.take() {
+                                let decision = ReviewDecision::Denied;
+                                context
+                                    .codex_conversation
+                                    .submit(Op::PatchApproval { id, decision })
+                                    .await?;
+                            }
+                            match context.codex_conversation.submit(Op::Interrupt).await {
+                                Ok(_) => pending_cancels.push(response_tx),
+                                Err(e) => {
+                                    let _ = response_tx.send(Err(e.into()));
+                                }
+                            }
+                        }
+                        Some(ControlCommand::Approve { approval_id, decision, response_tx }) => {
+                            let result = match pending_approvals.remove(&approval_id) {
+                                Some(request) => {
+                                    debug!("Approval {}: {:?}", approval_id, decision);
+                                    let op = protocol::approval_op(approval_id, &request, decision);
+                                    context.codex_conversation.submit(op).await.map(|_| ()).map_err(Into::into)
+                                }
+                                None => Err(AgentError::Execution {
+                                    message: format!("No pending approval {}", approval_id),
+                                }),
+                            };
+                            let _ = response_tx.send(result);
+                        }
+                        Some(ControlCommand::UpdateConfig { patch, response_tx }) => {
+                            // Applied once the turn ends
+                            context.config_updates.push((patch, response_tx));
+                        }
+                        Some(command) => {
+                            debug!("Received control command: {:?}", command);
+                            context.controller.handle_control_command(command).await;
+                        }
+                        None => break,
+                    }
+                    continue;
+                }
+                _ = sleep_until(deadline), if !timed_out => {
+                    timed_out = true;
+                    warn!("Turn {} timed out", turn_id);
+                    context.tools.cancel_calls();
+                    let error = OutputMessage::new(
+                        turn_id,
+                        OutputData::error(OutputError::ResourceLimitExceeded {
+                            resource: "turn_time".to_string(),
+                            limit: format!("{:?}", options.timeout.unwrap_or_default()),
+                        }),
+                    );
+                    context.emit(error).await?;
+                    context.codex_conversation.submit(Op::Interrupt).await?;
+                    continue;
+                }
+                _ = sleep_until(command_deadlines.values().map(|(deadline, _)| *deadline).min()),
+                    if !command_deadlines.is_empty() =>
+                {
+                    let now = tokio::time::Instant::now();
+                    let expired: Vec<String> = command_deadlines
+                        .iter()
+                        .filter(|(_, (deadline, _))| *deadline <= now)
+                        .map(|(call_id, _)| call_id.clone())
+                        .collect();
+                    let timeout = command_timeout.unwrap_or_default();
+                    for call_id in expired {
+                        let Some((_, command)) = command_deadlines.remove(&call_id) else {
+                            continue;
+                        };
+                        warn!("Command {} in turn {} timed out after {:?}", call_id, turn_id, timeout);
+                        let error = OutputError::ToolExecutionFailed {
+                            tool_name: "bash".to_string(),
+                            error: format!("Timed out after {:.1}s", timeout.as_secs_f64()),
+                        };
+                        context.emit(OutputMessage::new(turn_id, OutputData::error(error))).await?;
+                        terminate_codex_command(context, command).await?;
+                    }
+                    continue;
+                }
+                _ = next_tick(&mut context.ops_interval) => {
+                    emit_ops_summary(context).await?;
+                    continue;
+                }
+                _ = next_tick(&mut context.delta_interval) => {
+                    context.flush_delta().await?;
+                    continue;
+                }
+                _ = next_tick(&mut quota_interval), if !quota_exceeded => {
+                    if check_disk_quota(context, turn_id, &mut quota_warned).await? {
+                        quota_exceeded = true;
+                        context.codex_conversation.submit(Op::Interrupt).await?;
+                    }
+                    continue;
+                }
+                event = context.codex_conversation.next_event(),
+                    if !context.controller.is_paused() && held_event.is_none() => event,
+            }
+        };
+
+        match next_event {
             Ok(event) => {
-                // Check for task completion
-                let is_complete = matches!(event.msg, EventMsg::TaskComplete(_));
+                if let Some(timeout) = command_timeout
+                    && let Some((call_id, command)) = protocol::exec_begin(&event.msg)
+                {
+                    let deadline = tokio::time::Instant::now() + timeout;
+                    command_deadlines.insert(call_id.to_string(), (deadline, command.to_vec()));
+                }
+                if let Some(call_id) = protocol::exec_end(&event.msg) {
+                    command_deadlines.remove(call_id);
+                }
+
+                // Let tool middleware reject the commands and patches Codex
+                // runs, and keep Codex's own shell from running commands the
+                // bash tool runs on the host
+                let rejection = match check_codex_shell(context, &event) {
+                    Some(error) => Some(error),
+                    None => check_codex_call(context, turn_id, &event).await,
+                };
+                if let Some(error) = rejection {
+                    warn!("Tool call rejected: {}", error);
+                    context
+                        .emit(OutputMessage::new(turn_id, OutputData::error(error)))
+                        .await?;
+                    let op = match protocol::approval_request(&event.msg) {
+                        Some(request) => {
+                            protocol::approval_op(event.id, &request, ApprovalDecision::Denied)
+                        }
+                        // Calls without an approval request are already running
+                        None => Op::Interrupt,
+                    };
+                    context.codex_conversation.submit(op).await?;
+                    continue;
+                }
+
+                // Deny patches touching paths denied to the file write tool,
+                // and decide the ones Codex only asks approval for to have
+                // them checked
+                if let Some(files) = protocol::patch_approval_request(&event.msg) {
+                    let decision = if check_patch_paths(context, turn_id, &files).await? {
+                        Some(ReviewDecision::Denied)
+                    } else {
+                        implicit_patch_decision(&context.config, &files)
+                    };
+                    if let Some(decision) = decision {
+                        context
+                            .codex_conversation
+                            .submit(Op::PatchApproval {
+                                id: event.id,
+                                decision,
+                            })
+                            .await?;
+                        continue;
+                    }
+                }
+
+                // Pause before the commands and patches Codex asks approval for
+                // in step-through mode or at a breakpoint
+                if !stepped
+                    && protocol::approval_request(&event.msg).is_some()
+                    && let Some((_, tool_name, _)) = protocol::codex_tool_call(&event.msg)
+                    && context.controller.pause_before_tool(tool_name).await
+                {
+                    held_event = Some(event);
+                    continue;
+                }
+
+                // Check, back up, and preview patches Codex asks approval for
+                if let Some(files) = protocol::patch_approval_request(&event.msg)
+                    && let Some(ToolConfig::ApplyPatch {
+                        max_patch_size,
+                        create_backup,
+                        validate_syntax,
+                        dry_run,
+                    }) = context.tools.get("apply_patch")
+                {
+                    let rejection = if validate_syntax {
+                        patch::validate(&files, max_patch_size).err()
+                    } else {
+                        None
+                    };
+                    let rejection = match rejection {
+                        None if create_backup => backup_patch(context, turn_id, &files).await,
+                        rejection => rejection,
+                    };
+
+                    if let Some(reason) = rejection {
+                        warn!("Patch rejected: {}", reason);
+                        let error = OutputError::ToolExecutionFailed {
+                            tool_name: "apply_patch".to_string(),
+                            error: reason,
+                        };
+                        context
+                            .emit(OutputMessage::new(turn_id, OutputData::error(error)))
+                            .await?;
+                        let decision = ReviewDecision::Denied;
+                        context
+                            .codex_conversation
+                            .submit(Op::PatchApproval {
+                                id: event.id,
+                                decision,
+                            })
+                            .await?;
+                        continue;
+                    }
+
+                    if dry_run {
+                        let diff = patch::render(&files);
+                        context
+                            .emit(OutputMessage::new(
+                                turn_id,
+                                OutputData::PatchPreview { diff },
+                            ))
+                            .await?;
+                        context.controller.pause_for_patch().await;
+                        held_patch = Some(event.id);
+                        continue;
+                    }
+                }
+
+                // Ask the host to approve commands and patches Codex waits on
+                if let Some(request) = protocol::approval_request(&event.msg) {
+                    pending_approvals.insert(event.id.clone(), request.clone());
+                    let approval = OutputData::ApprovalRequired {
+                        approval_id: event.id,
+                        request,
+                    };
+                    context.emit(OutputMessage::new(turn_id, approval)).await?;
+                    continue;
+                }
+
+                if protocol::patch_applied(&event.msg) {
+                    turn_patched = true;
+                }
+
+                // Stop the turn when a patch Codex applies without asking
+                // approval touches paths denied to the file write tool
+                if let Some((_, files)) = protocol::patch_begin(&event.msg)
+                    && check_patch_paths(context, turn_id, &files).await?
+                {
+                    context.codex_conversation.submit(Op::Interrupt).await?;
+                }
+
+                // Stop the turn once writes push the workspace over its quota
+                if protocol::may_write_files(&event.msg)
+                    && !quota_exceeded
+                    && check_disk_quota(context, turn_id, &mut quota_warned).await?
+                {
+                    quota_exceeded = true;
+                    context.codex_conversation.submit(Op::Interrupt).await?;
+                }
 
-                // Convert Codex event to output message
-                if let Some(output_data) = convert_event_to_output(&event) {
-                    let output_message = OutputMessage::new(turn_id, output_data);
-                    context.output_tx.send(output_message).await?;
+                // Hold back transient model errors the retry policy covers
+                if let Some(error) = protocol::model_error(&event.msg)
+                    && let Some(policy) = context.config.retry_policy()
+                    && retry_attempts < policy.max_attempts
+                    && retry::is_transient(error)
+                {
+                    retry_error = Some(error.to_string());
+                    continue;
                 }
 
-                // Handle plan updates
-                if let Event {
-                    msg: EventMsg::PlanUpdate(update_args),
-                    id: _,
-                } = &event
+                // Continue the failed turn once Codex has ended it
+                if protocol::is_task_complete(&event.msg)
+                    && let Some(error) = retry_error.take()
+                    && let Some(policy) = context.config.retry_policy()
                 {
-                    // Convert UpdatePlanArgs to PlanMessage
-                    let plan_message = PlanMessage::from_update_plan_args(update_args.clone());
-                    context.plan_tx.send(plan_message).await?;
+                    retry_attempts += 1;
+                    retry_turn(context, turn_id, policy, retry_attempts, &error).await?;
+                    continue;
                 }
 
-                // Break if task is complete
-                if is_complete {
+                // Verify edits before completing the turn, asking for repairs on failure
+                if protocol::is_task_complete(&event.msg)
+                    && let Some(verify) = context.config.verify()
+                    && (turn_patched || verify.always)
+                    && verify_turn(context, turn_id, verify, &mut repair_attempts).await?
+                {
+                    turn_patched = false;
+                    continue;
+                }
+
+                if forward_event(context, turn_id, event, workspace_before.as_ref()).await? {
                     break;
                 }
             }
@@ -406,137 +1916,749 @@ async fn process_input_message(
                         },
                     },
                 );
-                context.output_tx.send(error_output).await?;
+                context.emit(error_output).await?;
                 break;
             }
         }
     }
 
+    // The aborted turn has drained, acknowledge cancellation requests
+    for response_tx in pending_cancels {
+        context.controller.acknowledge(ControlAck::TurnCancelled);
+        let _ = response_tx.send(Ok(()));
+    }
+
     Ok(())
 }
 
-/// Convert a Codex event to output data.
-fn convert_event_to_output(event: &Event) -> Option<OutputData> {
-    match &event.msg {
-        EventMsg::AgentMessage(msg) => Some(OutputData::Primary {
-            content: msg.message.clone(),
-        }),
-        EventMsg::AgentMessageDelta(delta) => Some(OutputData::PrimaryDelta {
-            content: delta.delta.clone(),
-        }),
-        EventMsg::AgentReasoning(reasoning) => Some(OutputData::Reasoning {
-            content: reasoning.text.clone(),
-        }),
-        EventMsg::AgentReasoningDelta(delta) => Some(OutputData::ReasoningDelta {
-            content: delta.delta.clone(),
-        }),
-        EventMsg::AgentReasoningRawContent(content) => Some(OutputData::Reasoning {
-            content: content.text.clone(),
-        }),
-        EventMsg::AgentReasoningRawContentDelta(delta) => Some(OutputData::ReasoningDelta {
-            content: delta.delta.clone(),
-        }),
-        EventMsg::TaskComplete(_) => Some(OutputData::Completed),
-        EventMsg::TaskStarted => Some(OutputData::Start),
-        EventMsg::Error(error) => Some(OutputData::Error {
-            error: OutputError::General {
-                message: error.message.clone(),
-            },
-        }),
-        EventMsg::ExecCommandBegin(exec) => Some(OutputData::ToolStart {
-            tool_name: "exec_command".to_string(),
-            arguments: serde_json::json!({ "command": exec.command }),
-        }),
-        EventMsg::ExecCommandEnd(exec) => Some(OutputData::ToolComplete {
-            tool_name: "exec_command".to_string(),
-            result: serde_json::json!({
-                "exit_code": exec.exit_code,
-                "call_id": exec.call_id
-            }),
-        }),
-        EventMsg::McpToolCallBegin(mcp) => Some(OutputData::ToolStart {
-            tool_name: mcp.invocation.tool.clone(),
-            arguments: serde_json::json!({
-                "server": mcp.invocation.server,
-                "arguments": mcp.invocation.arguments
-            }),
-        }),
-        EventMsg::McpToolCallEnd(mcp) => Some(OutputData::ToolComplete {
-            tool_name: mcp.invocation.tool.clone(),
-            result: serde_json::json!({
-                "server": mcp.invocation.server,
-                "success": mcp.is_success(),
-                "result": mcp.result
+/// Forward a Codex event to the output and plan channels.
+///
+/// Returns whether the event ended the turn.
+async fn forward_event(
+    context: &ExecutionContext,
+    turn_id: u64,
+    event: Event,
+    workspace_before: Option<&FileIndex>,
+) -> Result<bool> {
+    // Check for task completion or an aborted turn
+    let is_complete = protocol::ends_turn(&event.msg);
+
+    // Track token usage for the turn record, operational summary, and rate limit
+    if let Some(usage) = protocol::token_usage(&event.msg) {
+        *context.turn_usage.lock().await += usage;
+        if let Some(limiter) = &context.rate_limiter {
+            let reserved = context.request_reserved.swap(false, Ordering::Relaxed);
+            limiter.record(usage.total_tokens, reserved);
+        }
+        if let Some(ops) = &context.ops {
+            ops.lock().await.record_tokens(usage);
+        }
+    }
+
+    // Trace the turn's model requests and tool calls
+    {
+        let mut turn_spans = context.turn_spans.lock().await;
+        if let Some(spans) = turn_spans.as_mut() {
+            spans.record(&event.msg);
+        }
+        if is_complete && let Some(spans) = turn_spans.take() {
+            spans.finish(if protocol::is_task_complete(&event.msg) {
+                TurnStatus::Completed
+            } else {
+                TurnStatus::Aborted
+            });
+        }
+    }
+    if let Some(ops) = &context.ops
+        && protocol::is_task_complete(&event.msg)
+    {
+        ops.lock().await.record_turn();
+    }
+
+    if let Some(sink) = context.config.telemetry()
+        && protocol::is_task_complete(&event.msg)
+    {
+        sink.record(&TelemetryEvent::TurnCompleted);
+    }
+
+    // Check in on autonomous runs once enough tool calls were made
+    if protocol::tool_start_name(&event.msg).is_some()
+        && let Some(autonomy) = context.autonomy.lock().await.as_mut()
+        && let Some(reason) = autonomy.record_tool_call()
+    {
+        emit_check_in(context, autonomy, reason).await?;
+    }
+
+    // Report final responses that do not conform to the response schema
+    if let Some(schema) = context.config.response_schema()
+        && let Some(response) = protocol::final_message(&event.msg)
+    {
+        let errors = schema_errors(response, schema);
+        if !errors.is_empty() {
+            warn!("Response of turn {} does not match its schema", turn_id);
+            let error = OutputError::ResponseSchemaMismatch { errors };
+            context
+                .emit(OutputMessage::new(turn_id, OutputData::error(error)))
+                .await?;
+        }
+    }
+
+    // Run turn-end hooks before the completion marker
+    if protocol::is_task_complete(&event.msg) {
+        finish_turn(
+            context,
+            turn_id,
+            workspace_before,
+            protocol::final_message(&event.msg),
+        )
+        .await?;
+    }
+
+    // Cut the middle of command output over the bash tool's limit
+    let output_limit = context
+        .config
+        .tools()
+        .iter()
+        .find(|tool| matches!(tool, ToolConfig::Bash { .. }))
+        .and_then(ToolConfig::output_limit);
+    if let Some(max_bytes) = output_limit {
+        if let Some((call_id, chunk)) = protocol::exec_output(&event.msg) {
+            let output = context
+                .tool_outputs
+                .lock()
+                .await
+                .entry(call_id.to_string())
+                .or_insert_with(|| TruncatedStream::new(max_bytes))
+                .push(&String::from_utf8_lossy(chunk));
+            if let Some(output) = output {
+                emit_tool_output(context, turn_id, output).await?;
+            }
+            return Ok(is_complete);
+        }
+        if let Some(call_id) = protocol::exec_end(&event.msg)
+            && let Some(stream) = context.tool_outputs.lock().await.remove(call_id)
+        {
+            let (rest, omitted) = stream.finish();
+            if let Some(output) = rest {
+                emit_tool_output(context, turn_id, output).await?;
+            }
+            if let Some(omitted_bytes) = omitted {
+                let notice = OutputData::ToolOutputTruncated {
+                    tool_name: "exec_command".to_string(),
+                    omitted_bytes,
+                    max_output_bytes: max_bytes,
+                };
+                context.emit(OutputMessage::new(turn_id, notice)).await?;
+            }
+        }
+    }
+
+    // Let tool middleware rewrite the results of commands and patches
+    let mut middleware_result = None;
+    if let Some((call_id, mut result)) = protocol::codex_tool_result(&event.msg)
+        && let Some(call) = context.tool_calls.lock().await.remove(call_id)
+    {
+        for hook in context.config.tool_middleware().iter().rev() {
+            hook.after_call(&call, &mut result);
+        }
+        middleware_result = Some(result);
+    }
+
+    // Convert Codex event to output message
+    let output_data = protocol::to_output(&event.msg).map(|mut output_data| {
+        if let Some(result) = middleware_result
+            && let OutputData::ToolComplete { result: value, .. } = &mut output_data
+        {
+            value["success"] = result.success.into();
+            value["output"] = result.output.into();
+            value["exit_code"] = result.exit_code.into();
+            if !result.metadata.is_empty() {
+                value["metadata"] = serde_json::json!(result.metadata);
+            }
+        }
+        output_data
+    });
+    let output_data = output_data.and_then(|output_data| match context.config.event_converter() {
+        Some(converter) => converter.convert(output_data),
+        None => Some(output_data),
+    });
+    if let Some(output_data) = output_data {
+        let output_message = OutputMessage::new(turn_id, output_data);
+        context.emit(output_message).await?;
+    }
+
+    // Report images returned by MCP tools, such as generated charts
+    for image in protocol::mcp_images(&event.msg) {
+        context.emit(OutputMessage::new(turn_id, image)).await?;
+    }
+
+    // Report the host tool calls cancelled after outliving their timeout
+    if protocol::tool_end(&event.msg).is_some() {
+        for error in context.tool_server.take_timeouts() {
+            context
+                .emit(OutputMessage::new(turn_id, OutputData::error(error)))
+                .await?;
+        }
+    }
+
+    // Report the pages the model cites after searching the web
+    if let Some(query) = protocol::web_search_query(&event.msg) {
+        context.web_searches.lock().await.push(query.to_string());
+    }
+    if let Some(message) = protocol::agent_message(&event.msg) {
+        let mut web_searches = context.web_searches.lock().await;
+        if !web_searches.is_empty() {
+            let results = web_search::citations(message);
+            if !results.is_empty() {
+                let queries = std::mem::take(&mut *web_searches);
+                let output = OutputData::WebSearchResults { queries, results };
+                context.emit(OutputMessage::new(turn_id, output)).await?;
+            }
+        }
+    }
+    if is_complete {
+        context.web_searches.lock().await.clear();
+        context.tool_calls.lock().await.clear();
+        // Host tool calls still running belong to a turn that is over, e.g.
+        // one Codex aborted
+        context.tools.cancel_calls();
+    }
+
+    // Report the files a patch changed once it applied
+    if let Some((call_id, files)) = protocol::patch_begin(&event.msg) {
+        context
+            .patches
+            .lock()
+            .await
+            .insert(call_id.to_string(), files);
+    }
+    if let Some((call_id, success)) = protocol::patch_end(&event.msg)
+        && let Some(files) = context.patches.lock().await.remove(call_id)
+        && success
+    {
+        context
+            .file_changes
+            .lock()
+            .await
+            .entry(turn_id)
+            .or_default()
+            .extend(files.iter().cloned());
+        for file in files {
+            let change = OutputData::FileChange {
+                path: file.path,
+                kind: file.kind,
+                diff: file.diff,
+            };
+            context.emit(OutputMessage::new(turn_id, change)).await?;
+        }
+    }
+
+    // Handle plan updates
+    if let Some(update_args) = protocol::plan_update(&event.msg) {
+        // Convert UpdatePlanArgs to PlanMessage
+        let plan_message = PlanMessage::from_update_plan_args(update_args.clone());
+        if let Some(ops) = &context.ops {
+            ops.lock().await.record_plan(&plan_message);
+        }
+        if let Some(autonomy) = context.autonomy.lock().await.as_mut() {
+            autonomy.record_plan(&plan_message.todos);
+        }
+        context.plan_tx.send(plan_message).await?;
+    }
+
+    Ok(is_complete)
+}
+
+async fn emit_tool_output(context: &ExecutionContext, turn_id: u64, output: String) -> Result<()> {
+    let output = OutputData::ToolOutput {
+        tool_name: "exec_command".to_string(),
+        output,
+    };
+    context.emit(OutputMessage::new(turn_id, output)).await
+}
+
+/// Back up the files a patch is about to change.
+///
+/// Returns the reason to reject the patch if the backup failed.
+async fn backup_patch(
+    context: &ExecutionContext,
+    turn_id: u64,
+    files: &[PatchFile],
+) -> Option<String> {
+    match patch::backup(files, context.config.working_directory(), turn_id).await {
+        Ok(backups) => {
+            debug!("Backed up {} files before patching", backups.len());
+            None
+        }
+        Err(e) => Some(format!("Failed to back up files before patching: {}", e)),
+    }
+}
+
+/// Check the paths of a patch against the file write tool's path globs.
+///
+/// Emits a `PermissionDenied` error for the first rejected path and returns
+/// whether one was rejected.
+async fn check_patch_paths(
+    context: &ExecutionContext,
+    turn_id: u64,
+    files: &[PatchFile],
+) -> Result<bool> {
+    if !context
+        .config
+        .tools()
+        .iter()
+        .any(|tool| tool.name() == "file_write" && tool.has_path_globs())
+    {
+        return Ok(false);
+    }
+
+    let paths = files
+        .iter()
+        .flat_map(|file| std::iter::once(&file.path).chain(&file.move_path));
+    for path in paths {
+        if let Err(e) = context.config.check_tool_path("file_write", path) {
+            warn!("Patch rejected: {}", e);
+            let error = OutputError::PermissionDenied {
+                operation: format!("write {}", path.display()),
+                reason: e.to_string(),
+            };
+            context
+                .emit(OutputMessage::new(turn_id, OutputData::error(error)))
+                .await?;
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Decide a patch Codex asked approval for only because it runs under
+/// [`AgentConfig::codex_approval_policy`], as Codex would have under the
+/// configured approval policy.
+///
+/// Patches within the sandbox's writable roots are approved, and others are
+/// denied under `Never`; the rest are left to the host.
+fn implicit_patch_decision(config: &AgentConfig, files: &[PatchFile]) -> Option<ReviewDecision> {
+    let policy = *config.approval_policy();
+    if config.codex_approval_policy() == policy {
+        return None;
+    }
+    let writable = files
+        .iter()
+        .flat_map(|file| std::iter::once(&file.path).chain(&file.move_path))
+        .all(|path| config.sandbox_allows_write(path));
+    if writable {
+        Some(ReviewDecision::Approved)
+    } else if policy == AskForApproval::Never {
+        Some(ReviewDecision::Denied)
+    } else {
+        None
+    }
+}
+
+/// Check workspace disk usage against the configured quota.
+///
+/// Emits a warning once per turn past the soft threshold and a
+/// `ResourceLimitExceeded` error past the quota. Returns whether the quota is
+/// exceeded.
+async fn check_disk_quota(
+    context: &ExecutionContext,
+    turn_id: u64,
+    warned: &mut bool,
+) -> Result<bool> {
+    let Some(quota) = context.config.disk_quota() else {
+        return Ok(false);
+    };
+
+    let used_bytes = match measure_disk_usage(context.config.working_directory().clone()).await {
+        Ok(used_bytes) => used_bytes,
+        Err(e) => {
+            warn!("Failed to measure disk usage: {}", e);
+            return Ok(false);
+        }
+    };
+
+    match quota.status(used_bytes) {
+        QuotaStatus::Ok => Ok(false),
+        QuotaStatus::Warning => {
+            if !*warned {
+                *warned = true;
+                let warning = OutputMessage::new(
+                    turn_id,
+                    OutputData::warning(format!(
+                        "Workspace uses {} of {} bytes allowed by the disk quota",
+                        used_bytes, quota.max_bytes
+                    )),
+                );
+                context.emit(warning).await?;
+            }
+            Ok(false)
+        }
+        QuotaStatus::Exceeded => {
+            warn!(
+                "Workspace disk quota exceeded: {} of {} bytes",
+                used_bytes, quota.max_bytes
+            );
+            let error = OutputMessage::new(
+                turn_id,
+                OutputData::error(OutputError::ResourceLimitExceeded {
+                    resource: "disk".to_string(),
+                    limit: format!("{} bytes ({} used)", quota.max_bytes, used_bytes),
+                }),
+            );
+            context.emit(error).await?;
+            Ok(true)
+        }
+    }
+}
+
+/// Wait out the backoff of a retry, then ask the model to continue the turn
+/// a transient error failed.
+async fn retry_turn(
+    context: &ExecutionContext,
+    turn_id: u64,
+    policy: &RetryPolicy,
+    attempt: u32,
+    error: &str,
+) -> Result<()> {
+    let delay = policy.delay(attempt, error);
+    info!(
+        "Retrying turn {} in {:?} ({}/{}): {}",
+        turn_id, delay, attempt, policy.max_attempts, error
+    );
+    let retrying = OutputData::Retrying {
+        attempt,
+        max_attempts: policy.max_attempts,
+        delay_ms: delay.as_millis() as u64,
+        reason: error.to_string(),
+    };
+    context.emit(OutputMessage::new(turn_id, retrying)).await?;
+    tokio::time::sleep(delay).await;
+
+    let prompt = retry::resume_prompt().to_string();
+    context.throttle().await;
+    context
+        .codex_conversation
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text { text: prompt }],
+        })
+        .await?;
+    Ok(())
+}
+
+/// Run the verification step for a turn.
+///
+/// Returns whether a repair round was submitted to the model.
+async fn verify_turn(
+    context: &ExecutionContext,
+    turn_id: u64,
+    verify: &VerifyConfig,
+    repair_attempts: &mut u32,
+) -> Result<bool> {
+    let start_message = OutputMessage::new(
+        turn_id,
+        OutputData::tool_start(
+            "verify",
+            serde_json::json!({
+                "command": verify.command,
+                "attempt": *repair_attempts + 1,
             }),
-        }),
-        EventMsg::WebSearchBegin(search) => Some(OutputData::ToolStart {
-            tool_name: "web_search".to_string(),
-            arguments: serde_json::json!({ "query": search.query }),
-        }),
-        EventMsg::PatchApplyBegin(patch) => Some(OutputData::ToolStart {
-            tool_name: "apply_patch".to_string(),
-            arguments: serde_json::json!({ "changes_count": patch.changes.len() }),
-        }),
-        EventMsg::PatchApplyEnd(patch) => Some(OutputData::ToolComplete {
-            tool_name: "apply_patch".to_string(),
-            result: serde_json::json!({
-                "success": patch.success,
-                "message": "Patch application finished"
+        ),
+    );
+    context.emit(start_message).await?;
+
+    let outcome = match (
+        context.config.tool_environment(),
+        context.config.tool_working_directory("bash"),
+    ) {
+        (Ok(environment), Ok(working_directory)) => {
+            run_verification(
+                verify,
+                context.config.sandbox_backend(),
+                &working_directory,
+                &environment,
+            )
+            .await
+        }
+        (Err(e), _) | (_, Err(e)) => Err(e),
+    };
+    let outcome = match outcome {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            warn!("Failed to run verification command: {}", e);
+            return Ok(false);
+        }
+    };
+
+    let complete_message = OutputMessage::new(
+        turn_id,
+        OutputData::tool_complete(
+            "verify",
+            serde_json::json!({
+                "success": outcome.success,
+                "exit_code": outcome.exit_code,
+                "timed_out": outcome.timed_out,
+                "output": outcome.output,
+                "diagnostics": outcome.diagnostics,
+                "reaped": outcome.reaped,
             }),
-        }),
-        EventMsg::ExecCommandOutputDelta(output) => Some(OutputData::ToolOutput {
-            tool_name: "exec_command".to_string(),
-            output: String::from_utf8_lossy(&output.chunk).to_string(),
-        }),
-        EventMsg::StreamError(error) => Some(OutputData::Error {
-            error: OutputError::General {
-                message: error.message.clone(),
-            },
-        }),
-        EventMsg::TokenCount(_) => None, // Token count events don't need to be converted to output
-        EventMsg::SessionConfigured(_) => None, // Session configured events are internal
-        EventMsg::ConversationHistory(_) => None, // History events are internal
-        EventMsg::McpListToolsResponse(_) => None, // Tool list responses are internal
-        EventMsg::GetHistoryEntryResponse(_) => None, // History entry responses are internal
-        EventMsg::TurnAborted(_) => Some(OutputData::Error {
-            error: OutputError::General {
-                message: "Turn was aborted".to_string(),
-            },
-        }),
-        EventMsg::ShutdownComplete => Some(OutputData::Completed),
-        _ => None, // Handle any remaining event types
+        ),
+    );
+    context.emit(complete_message).await?;
+
+    if outcome.success || *repair_attempts >= verify.max_attempts {
+        return Ok(false);
+    }
+
+    *repair_attempts += 1;
+    info!(
+        "Verification failed for turn {}, starting repair attempt {}",
+        turn_id, repair_attempts
+    );
+    let prompt = outcome.repair_prompt(verify, *repair_attempts);
+    context.throttle().await;
+    context
+        .codex_conversation
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text { text: prompt }],
+        })
+        .await?;
+
+    Ok(true)
+}
+
+/// Queue the next turn of an autonomous run, or end the run.
+async fn continue_autonomy(context: &ExecutionContext, failed: bool) -> Result<()> {
+    let mut guard = context.autonomy.lock().await;
+    let Some(autonomy) = guard.as_mut() else {
+        return Ok(());
+    };
+
+    let turn_id = context.controller.turn_count();
+    let response = context
+        .turn_records
+        .lock()
+        .await
+        .last()
+        .filter(|record| record.turn_id == turn_id)
+        .and_then(|record| record.summary.clone());
+
+    match autonomy.record_turn(response, failed) {
+        NextStep::Continue(prompt) => context.inputs.push(InputMessage::new(prompt)).await,
+        NextStep::CheckIn(reason, prompt) => {
+            context.inputs.push(InputMessage::new(prompt)).await;
+            emit_check_in(context, autonomy, reason).await?;
+        }
+        NextStep::Finish(reason) => {
+            emit_check_in(context, autonomy, reason).await?;
+            *guard = None;
+        }
+    }
+    Ok(())
+}
+
+/// Emit a check-in of an autonomous run, pausing for approval if required.
+async fn emit_check_in(
+    context: &ExecutionContext,
+    autonomy: &mut Autonomy,
+    reason: CheckInReason,
+) -> Result<()> {
+    let check_in = autonomy.check_in(reason);
+    let number = check_in.number;
+    let awaiting_approval = check_in.awaiting_approval;
+    info!("Autonomous run check-in {} ({:?})", number, reason);
+
+    let output_message = OutputMessage::new(
+        context.controller.turn_count(),
+        OutputData::CheckIn { check_in },
+    );
+    context.emit(output_message).await?;
+    if awaiting_approval {
+        context.controller.pause_for_check_in(number).await;
+    }
+    Ok(())
+}
+
+/// Ask the model for the commit message of a turn's changes, falling back to
+/// its final message for the turn if the request fails.
+async fn describe_changes(
+    context: &ExecutionContext,
+    turn_id: u64,
+    last_agent_message: Option<&str>,
+    delta: Option<&WorkspaceDelta>,
+) -> Option<String> {
+    let prompt = commit_message_prompt(last_agent_message, delta);
+    let description = match Agent::new(context.config.for_side_query()) {
+        Ok(agent) => {
+            let mut agent = match &context.backend {
+                Some(backend) => agent.with_backend(backend.clone()),
+                None => agent,
+            };
+            // Boxed, as the future of the query's turns contains this one
+            let query: futures::future::BoxFuture<'_, Result<String>> =
+                Box::pin(agent.query(prompt));
+            query.await
+        }
+        Err(e) => Err(e),
+    };
+    match description {
+        Ok(description) => Some(description),
+        Err(e) => {
+            warn!(
+                "Failed to write the commit message of turn {}: {}",
+                turn_id, e
+            );
+            last_agent_message.map(str::to_string)
+        }
+    }
+}
+
+/// Run the configured turn-end hooks (workspace summary, auto-commit) and
+/// record the turn.
+async fn finish_turn(
+    context: &ExecutionContext,
+    turn_id: u64,
+    workspace_before: Option<&FileIndex>,
+    last_agent_message: Option<&str>,
+) -> Result<()> {
+    let working_directory = context.config.working_directory();
+    let mut record = TurnRecord::new(turn_id, last_agent_message.map(str::to_string));
+    if !context.input_text.is_empty() {
+        record.input = Some(context.input_text.clone());
     }
+    record.checkpoint = context.checkpoint.clone();
+
+    if let Some(before) = workspace_before {
+        match scan_workspace(working_directory.clone()).await {
+            Ok(after) => {
+                let delta = before.diff(&after);
+                record.delta = Some(delta.clone());
+                let output_message =
+                    OutputMessage::new(turn_id, OutputData::workspace_delta(delta));
+                context.emit(output_message).await?;
+            }
+            Err(e) => warn!("Failed to index workspace: {}", e),
+        }
+    }
+
+    if let Some(auto_commit) = context.config.auto_commit() {
+        let describe =
+            describe_changes(context, turn_id, last_agent_message, record.delta.as_ref());
+        match auto_commit_turn(working_directory, auto_commit, turn_id, describe).await {
+            Ok(Some(sha)) => {
+                info!(
+                    "Committed turn {} changes to {} ({})",
+                    turn_id, auto_commit.branch, sha
+                );
+                record.commit = Some(sha);
+            }
+            Ok(None) => debug!("No workspace changes to commit for turn {}", turn_id),
+            Err(e) => warn!("Failed to auto-commit turn {}: {}", turn_id, e),
+        }
+    }
+
+    record.usage = *context.turn_usage.lock().await;
+    record.events = std::mem::take(&mut *context.tape.lock().await);
+    context.turn_records.lock().await.push(record);
+
+    // Suggest follow-ups in the background, so the turn ends without waiting
+    // on another model call
+    if let Some(suggestions) = context.config.suggestions()
+        && let Some(response) = last_agent_message
+    {
+        let generation = generate_suggestions(
+            context.config.clone(),
+            context.backend.clone(),
+            suggestions.clone(),
+            context.input_text.clone(),
+            response.to_string(),
+        );
+        let input_id = context.input_id.clone();
+        let metadata = context.input_metadata.clone();
+        let task = tokio::spawn(async move {
+            match generation.await {
+                Ok(prompts) if !prompts.is_empty() => {
+                    let mut message =
+                        OutputMessage::new(turn_id, OutputData::Suggestions { prompts });
+                    message.input_id = input_id;
+                    message.metadata = metadata;
+                    Some(message)
+                }
+                Ok(_) => None,
+                Err(e) => {
+                    warn!("Failed to generate suggestions for turn {}: {}", turn_id, e);
+                    None
+                }
+            }
+        });
+        if let Some(previous) = context.suggestions.lock().await.replace(task) {
+            previous.abort();
+        }
+    }
+
+    Ok(())
 }
 
 impl Agent {
+    /// Create the default backend, authenticated with the API key, the shared
+    /// auth manager, or the login stored in the codex home.
+    async fn _create_codex_backend(&self) -> Result<CodexBackend> {
+        let codex_config = self._create_codex_config()?;
+        let conversation_manager = if let Some(api_key) = self.config.api_key() {
+            ConversationManager::with_auth(CodexAuth::from_api_key(api_key))
+        } else {
+            let auth_manager = match &self.auth_manager {
+                Some(auth_manager) => auth_manager.clone(),
+                None => auth::auth_manager(self.config.auth_method()),
+            };
+            auth::check_auth(&auth_manager, self.config.auth_method()).await?;
+            ConversationManager::new(auth_manager)
+        };
+        // Codex looks bare names up on `PATH` only, so pass the path found
+        let bridge = tool_server::resolve_bridge(self.config.tool_bridge()).unwrap_or_else(|| {
+            self.config
+                .tool_bridge()
+                .unwrap_or(Path::new(DEFAULT_BRIDGE))
+                .to_path_buf()
+        });
+        let backend = CodexBackend::new(conversation_manager, codex_config, bridge);
+        #[cfg(feature = "model-bridge")]
+        if let (Some(provider), _) = self.config.resolve_model(self.config.model())
+            && provider.needs_bridge()
+        {
+            let api_key = self.config.api_key().map(str::to_string);
+            return Ok(backend.with_model_bridge(provider, api_key));
+        }
+        Ok(backend)
+    }
+
     /// Create Codex configuration from agent configuration.
     fn _create_codex_config(&self) -> Result<CodexConfig> {
-        // Determine which tools to enable based on agent configuration
-        let tools_web_search_request = self
-            .config
-            .tools()
+        // Determine which tools to enable from the tools registered so far
+        let tools = self.tools.tools();
+        // Web search tools with a provider are served as host tools instead,
+        // so the model's own search stays on for the others
+        let tools_web_search_request = tools
             .iter()
-            .any(|tool| matches!(tool, crate::tools::ToolConfig::WebSearch { .. }));
+            .any(|tool| matches!(tool, ToolConfig::WebSearch { .. }) && tool.handler().is_none());
 
-        let include_apply_patch_tool = self
-            .config
-            .tools()
+        let include_apply_patch_tool = tools
             .iter()
-            .any(|tool| matches!(tool, crate::tools::ToolConfig::ApplyPatch { .. }));
+            .any(|tool| matches!(tool, ToolConfig::ApplyPatch { .. }));
 
+        let (model_provider, model) = self.config.resolve_model(self.config.model());
         let overrides = ConfigOverrides {
-            model: Some(self.config.model().to_string()),
+            model: Some(model),
             cwd: Some(self.config.working_directory().clone()),
-            approval_policy: Some(*self.config.approval_policy()),
+            approval_policy: Some(self.config.codex_approval_policy()),
             sandbox_mode: Some(self._convert_sandbox_policy()),
-            model_provider: None, // Use default
+            model_provider: None, // Custom providers are registered below
             config_profile: None,
             codex_linux_sandbox_exe: None,
             base_instructions: self.config.system_prompt().map(|s| s.to_string()),
             include_plan_tool: Some(true), // Enable plan tool for better integration
             include_apply_patch_tool: Some(include_apply_patch_tool),
             disable_response_storage: Some(false),
-            show_raw_agent_reasoning: Some(false),
+            show_raw_agent_reasoning: Some(self.config.show_raw_reasoning()),
             tools_web_search_request: Some(tools_web_search_request),
         };
 
@@ -547,6 +2669,24 @@ impl Agent {
             }
         })?;
 
+        // Reasoning settings have no overrides of their own
+        if let Some(effort) = self.config.reasoning_effort() {
+            config.model_reasoning_effort = effort;
+        }
+        if let Some(summary) = self.config.reasoning_summary() {
+            config.model_reasoning_summary = summary;
+        }
+
+        // Send model requests to the routed or custom provider, if any
+        if let Some(provider) = &model_provider {
+            let info = self._convert_model_provider(provider)?;
+            config
+                .model_providers
+                .insert(provider.name.clone(), info.clone());
+            config.model_provider_id = provider.name.clone();
+            config.model_provider = info;
+        }
+
         // Convert and add MCP server configurations
         config
             .mcp_servers
@@ -555,6 +2695,28 @@ impl Agent {
                 (server.name().to_string(), codex_server)
             }));
 
+        // Point the model at the bash tool running commands on the host, as
+        // commands of Codex's own shell are rejected
+        if self.tools.runs_shell_on_host() {
+            let note = format!(
+                "Run shell commands with the `bash` tool of the `{}` MCP server; the \
+                 built-in shell tool is disabled.",
+                tool_server::SERVER_NAME
+            );
+            config.user_instructions = Some(match config.user_instructions.take() {
+                Some(instructions) => format!("{}\n\n{}", instructions, note),
+                None => note,
+            });
+        }
+
+        // Scope tool executions to the resolved environment instead of
+        // inheriting the host's
+        config.shell_environment_policy = ShellEnvironmentPolicy {
+            inherit: ShellEnvironmentPolicyInherit::None,
+            r#set: self.config.tool_environment()?,
+            ..Default::default()
+        };
+
         Ok(config)
     }
 
@@ -570,6 +2732,31 @@ impl Agent {
         }
     }
 
+    /// Convert a custom model provider to codex-core ModelProviderInfo.
+    ///
+    /// Goes through the provider's TOML-facing serde shape, the same one a
+    /// `[model_providers]` entry in the Codex config file uses.
+    fn _convert_model_provider(&self, provider: &ModelProvider) -> Result<ModelProviderInfo> {
+        // Bridged providers are reached over Chat Completions; the backend
+        // points Codex at the bridge once it listens
+        let wire_api = if provider.needs_bridge() {
+            WireApi::Chat
+        } else {
+            provider.wire_api
+        };
+        let info = serde_json::json!({
+            "name": provider.name,
+            "base_url": provider.base_url,
+            "env_key": provider.env_key,
+            "wire_api": wire_api,
+            "http_headers": provider.headers,
+            "query_params": provider.query_params,
+        });
+        serde_json::from_value(info).map_err(|e| AgentError::Config {
+            message: format!("Invalid model provider {}: {}", provider.name, e),
+        })
+    }
+
     /// Convert AgentConfig MCP server to codex-core McpServerConfig.
     fn _convert_mcp_server_config(
         &self,
@@ -0,0 +1,252 @@
+//! Anthropic Messages API adapter of the model bridge.
+//!
+//! Translates Codex's Chat Completions requests into Messages API requests
+//! and the streamed Messages events back into Chat Completions chunks, so
+//! Claude models run on Anthropic's native API rather than its OpenAI
+//! compatibility layer. The system messages become the `system` field, tool
+//! calls and results become `tool_use` and `tool_result` content blocks, and
+//! images are sent as image blocks.
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::model_bridge::{
+    BridgeError, ChatRequest, ChatStream, FinishReason, Upstream, parse_data_url,
+};
+
+/// Version of the Messages API requests are made against.
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Output token cap of each reply, which the Messages API requires.
+const MAX_TOKENS: u32 = 32_000;
+
+/// Answer `request` through the Messages API.
+pub(crate) async fn complete(
+    upstream: &Upstream,
+    request: ChatRequest,
+    stream: &mut ChatStream<'_>,
+) -> Result<(), BridgeError> {
+    let body = messages_request(&request);
+    let mut auth = vec![("anthropic-version", ANTHROPIC_VERSION.to_string())];
+    if let Some(key) = &upstream.api_key {
+        auth.push(("x-api-key", key.clone()));
+    }
+    let mut events = upstream
+        .post(&upstream.url("messages"), auth, &body)
+        .await?;
+
+    let mut tool_use: Option<ToolUse> = None;
+    let mut reason = FinishReason::Stop;
+    while let Some(event) = events.next().await? {
+        let event: StreamEvent = serde_json::from_str(&event.data)
+            .map_err(|e| BridgeError::new(502, format!("Invalid Messages event: {}", e)))?;
+        match event {
+            StreamEvent::ContentBlockStart { content_block } => {
+                if let ContentBlock::ToolUse { id, name } = content_block {
+                    tool_use = Some(ToolUse {
+                        id,
+                        name,
+                        input: String::new(),
+                    });
+                }
+            }
+            StreamEvent::ContentBlockDelta { delta } => match delta {
+                BlockDelta::TextDelta { text } => stream.text(&text).await?,
+                BlockDelta::InputJsonDelta { partial_json } => {
+                    if let Some(tool_use) = &mut tool_use {
+                        tool_use.input.push_str(&partial_json);
+                    }
+                }
+                BlockDelta::Other => {}
+            },
+            StreamEvent::ContentBlockStop => {
+                if let Some(call) = tool_use.take() {
+                    // Calls without arguments stream no input at all
+                    let input = if call.input.trim().is_empty() {
+                        "{}"
+                    } else {
+                        &call.input
+                    };
+                    stream.tool_call(&call.id, &call.name, input).await?;
+                }
+            }
+            StreamEvent::MessageDelta { delta } => {
+                if delta.stop_reason.as_deref() == Some("max_tokens") {
+                    reason = FinishReason::Length;
+                }
+            }
+            StreamEvent::MessageStop => break,
+            StreamEvent::Error { error } => {
+                // Codex retries server errors, overloads included
+                let status = match error.kind.as_str() {
+                    "overloaded_error" => 529,
+                    "rate_limit_error" => 429,
+                    _ => 502,
+                };
+                return Err(BridgeError::new(status, error.message));
+            }
+            StreamEvent::Other => {}
+        }
+    }
+    stream.finish(reason).await
+}
+
+/// Messages API request body for `request`.
+fn messages_request(request: &ChatRequest) -> Value {
+    let mut system = Vec::new();
+    let mut messages: Vec<(String, Vec<Value>)> = Vec::new();
+    for message in &request.messages {
+        let (role, mut blocks) = match message.role.as_str() {
+            "system" | "developer" => {
+                system.push(message.text());
+                continue;
+            }
+            "assistant" => {
+                let mut blocks = text_block(message.text());
+                blocks.extend(message.tool_calls.iter().map(|call| {
+                    json!({
+                        "type": "tool_use",
+                        "id": call.id,
+                        "name": call.function.name,
+                        "input": call.input(),
+                    })
+                }));
+                ("assistant", blocks)
+            }
+            "tool" => {
+                let result = json!({
+                    "type": "tool_result",
+                    "tool_use_id": message.tool_call_id,
+                    "content": message.text(),
+                });
+                ("user", vec![result])
+            }
+            _ => ("user", text_block(message.text())),
+        };
+        blocks.extend(message.image_urls().into_iter().map(image_block));
+        if blocks.is_empty() {
+            continue;
+        }
+        // Tool results of one reply must share a single user message
+        match messages.last_mut() {
+            Some((last, content)) if last == role => content.append(&mut blocks),
+            _ => messages.push((role.to_string(), blocks)),
+        }
+    }
+
+    let mut body = json!({
+        "model": request.model,
+        "max_tokens": MAX_TOKENS,
+        "stream": true,
+        "messages": messages
+            .into_iter()
+            .map(|(role, content)| json!({ "role": role, "content": content }))
+            .collect::<Vec<_>>(),
+    });
+    if !system.is_empty() {
+        body["system"] = json!(system.join("\n\n"));
+    }
+    let tools: Vec<Value> = request
+        .functions()
+        .map(|function| {
+            json!({
+                "name": function.name,
+                "description": function.description,
+                "input_schema": function.schema(),
+            })
+        })
+        .collect();
+    if !tools.is_empty() {
+        body["tools"] = json!(tools);
+    }
+    body
+}
+
+/// Text block, unless the text is empty, which the Messages API rejects.
+fn text_block(text: String) -> Vec<Value> {
+    if text.is_empty() {
+        Vec::new()
+    } else {
+        vec![json!({ "type": "text", "text": text })]
+    }
+}
+
+/// Image block of an image URL, inlined if it is a data URL.
+fn image_block(url: &str) -> Value {
+    let source = match parse_data_url(url) {
+        Some((media_type, data)) => {
+            json!({ "type": "base64", "media_type": media_type, "data": data })
+        }
+        None => json!({ "type": "url", "url": url }),
+    };
+    json!({ "type": "image", "source": source })
+}
+
+/// Tool call of the reply being streamed.
+struct ToolUse {
+    id: String,
+    name: String,
+    /// Arguments JSON received so far
+    input: String,
+}
+
+/// Event of a streamed Messages API reply.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    ContentBlockStart {
+        content_block: ContentBlock,
+    },
+    ContentBlockDelta {
+        delta: BlockDelta,
+    },
+    ContentBlockStop,
+    MessageDelta {
+        delta: MessageDelta,
+    },
+    MessageStop,
+    Error {
+        error: ApiError,
+    },
+    /// `message_start` and `ping`, carrying nothing Codex needs
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    ToolUse {
+        id: String,
+        name: String,
+    },
+    /// Text, whose content arrives in deltas, and thinking blocks
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BlockDelta {
+    TextDelta {
+        text: String,
+    },
+    InputJsonDelta {
+        partial_json: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageDelta {
+    #[serde(default)]
+    stop_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    #[serde(rename = "type")]
+    kind: String,
+    message: String,
+}
@@ -0,0 +1,46 @@
+//! Approvals Codex asks the host for before running commands or applying
+//! patches.
+//!
+//! Depending on the approval policy, Codex stops before some tool calls and
+//! waits for a decision. The agent emits each request as
+//! `OutputData::ApprovalRequired` and forwards the decision given with
+//! `AgentController::approve`. Codex keeps the turn waiting until then.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Action Codex asks the host to approve.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ApprovalRequest {
+    /// Run a command
+    Exec {
+        command: Vec<String>,
+        cwd: PathBuf,
+        reason: Option<String>,
+    },
+
+    /// Apply a patch, given as a unified diff
+    Patch {
+        diff: String,
+        reason: Option<String>,
+    },
+}
+
+/// Host decision on an approval request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    /// Run the action
+    Approved,
+
+    /// Run the action and similar ones for the rest of the session
+    ApprovedForSession,
+
+    /// Skip the action and let the model continue
+    Denied,
+
+    /// Skip the action and end the turn
+    Abort,
+}
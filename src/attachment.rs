@@ -0,0 +1,121 @@
+//! Files attached to input messages.
+//!
+//! Attachments are written into the working directory before the turn runs,
+//! under `.agent-core/attachments/turn-<turn_id>`, and listed at the end of the
+//! prompt so the model can open them with its usual tools. Hosts hand over a
+//! path or the file's bytes and never manage temporary files themselves.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AgentError, Result};
+
+/// Directory attachments are written to, relative to the working directory.
+const ATTACHMENT_DIR: &str = ".agent-core/attachments";
+
+/// File attached to an input message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    /// File name the attachment is written under
+    pub name: String,
+
+    /// Content of the attachment
+    pub source: AttachmentSource,
+}
+
+/// Where the content of an attachment comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentSource {
+    /// File on the host, copied into the working directory
+    Path(PathBuf),
+
+    /// Content of the file
+    Bytes(Vec<u8>),
+}
+
+impl Attachment {
+    /// Attach the file at `path`, keeping its file name.
+    pub fn from_path<P: Into<PathBuf>>(path: P) -> Self {
+        let path = path.into();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "attachment".to_string());
+        Self {
+            name,
+            source: AttachmentSource::Path(path),
+        }
+    }
+
+    /// Attach `bytes` as a file named `name`.
+    pub fn from_bytes<S: Into<String>, B: Into<Vec<u8>>>(name: S, bytes: B) -> Self {
+        Self {
+            name: name.into(),
+            source: AttachmentSource::Bytes(bytes.into()),
+        }
+    }
+
+    /// File name with any directories and special components removed.
+    fn file_name(&self) -> String {
+        Path::new(&self.name)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "attachment".to_string())
+    }
+}
+
+/// Write the attachments of a turn into the working directory.
+///
+/// Returns the paths written, relative to the working directory. Attachments
+/// sharing a file name get a numeric suffix.
+pub(crate) async fn materialize(
+    attachments: &[Attachment],
+    working_directory: &Path,
+    turn_id: u64,
+) -> Result<Vec<PathBuf>> {
+    let relative_dir = Path::new(ATTACHMENT_DIR).join(format!("turn-{turn_id}"));
+    let dir = working_directory.join(&relative_dir);
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let mut written: Vec<PathBuf> = Vec::with_capacity(attachments.len());
+    for attachment in attachments {
+        let name = attachment.file_name();
+        let mut relative = relative_dir.join(&name);
+        let mut suffix = 1;
+        while written.contains(&relative) {
+            suffix += 1;
+            relative = relative_dir.join(match name.rsplit_once('.') {
+                Some((stem, extension)) if !stem.is_empty() => {
+                    format!("{stem}-{suffix}.{extension}")
+                }
+                _ => format!("{name}-{suffix}"),
+            });
+        }
+
+        let path = working_directory.join(&relative);
+        match &attachment.source {
+            AttachmentSource::Path(source) => {
+                tokio::fs::copy(source, &path)
+                    .await
+                    .map_err(|e| AgentError::Execution {
+                        message: format!("Failed to attach {}: {}", source.display(), e),
+                    })?;
+            }
+            AttachmentSource::Bytes(bytes) => tokio::fs::write(&path, bytes).await?,
+        }
+        written.push(relative);
+    }
+    Ok(written)
+}
+
+/// Prompt text listing the attached files.
+pub(crate) fn prompt_section(paths: &[PathBuf]) -> String {
+    let mut section = String::from("\n\nAttached files:\n");
+    for path in paths {
+        section.push_str(&format!("- {}\n", path.display()));
+    }
+    section
+}
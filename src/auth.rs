@@ -0,0 +1,59 @@
+//! Authentication with credentials stored by the Codex CLI.
+//!
+//! Without an API key, agents use the login in the codex home
+//! (`~/.codex/auth.json`): either the API key saved by `codex login
+//! --api-key`, or the ChatGPT account signed in with `codex login`. ChatGPT
+//! access tokens are refreshed when they go stale, and again whenever the
+//! model API rejects them.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use codex_login::AuthManager;
+use codex_protocol::mcp_protocol::AuthMode;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AgentError, Result};
+
+/// Credentials agents without an API key use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    /// The API key stored in the codex home, falling back to a ChatGPT login
+    #[default]
+    ApiKey,
+
+    /// The ChatGPT account signed in with `codex login`
+    #[serde(rename = "chatgpt")]
+    ChatGpt,
+}
+
+/// Auth manager loading credentials from the codex home, preferring `method`.
+pub(crate) fn auth_manager(method: AuthMethod) -> Arc<AuthManager> {
+    let codex_home = codex_core::config::find_codex_home().unwrap_or_else(|_| PathBuf::from("."));
+    let mode = match method {
+        AuthMethod::ApiKey => AuthMode::ApiKey,
+        AuthMethod::ChatGpt => AuthMode::ChatGPT,
+    };
+    Arc::new(AuthManager::new(codex_home, mode))
+}
+
+/// Check that a ChatGPT login exists and its tokens are usable, refreshing
+/// them if they are stale.
+pub(crate) async fn check_auth(manager: &AuthManager, method: AuthMethod) -> Result<()> {
+    if method != AuthMethod::ChatGpt {
+        return Ok(());
+    }
+    let auth = manager
+        .auth()
+        .filter(|auth| auth.mode == AuthMode::ChatGPT)
+        .ok_or_else(|| AgentError::Config {
+            message: "No ChatGPT login found; sign in with `codex login`".to_string(),
+        })?;
+    auth.get_token()
+        .await
+        .map_err(|e| AgentError::AuthExpired {
+            message: e.to_string(),
+        })?;
+    Ok(())
+}
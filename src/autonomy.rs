@@ -0,0 +1,294 @@
+//! Time-boxed autonomous mode with periodic check-ins.
+//!
+//! Started with `AgentHandle::run_autonomously`, the agent keeps working
+//! through its plan without new user input: after every turn that leaves plan
+//! steps open, a continuation prompt is queued. The library emits an
+//! `OutputData::CheckIn` summary every configured interval or number of tool
+//! calls, optionally pausing until the host approves with `resume()`.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::plan::{StepStatus, TodoItem};
+
+/// When an autonomous run checks in and when it ends.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckInPolicy {
+    /// Check in after this much time since the last check-in
+    pub interval: Option<Duration>,
+
+    /// Check in after this many tool calls since the last check-in
+    pub tool_calls: Option<u32>,
+
+    /// Pause at every check-in until resumed
+    pub require_approval: bool,
+
+    /// End the run once this much time has passed
+    pub time_box: Option<Duration>,
+
+    /// End the run after this many turns
+    pub max_turns: Option<u32>,
+}
+
+impl CheckInPolicy {
+    /// Check in at the given interval.
+    pub fn every(interval: Duration) -> Self {
+        Self {
+            interval: Some(interval),
+            ..Self::default()
+        }
+    }
+
+    /// Check in every `count` tool calls.
+    pub fn every_tool_calls(count: u32) -> Self {
+        Self {
+            tool_calls: Some(count.max(1)),
+            ..Self::default()
+        }
+    }
+
+    /// Also check in at the given interval.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Also check in every `count` tool calls.
+    pub fn tool_calls(mut self, count: u32) -> Self {
+        self.tool_calls = Some(count.max(1));
+        self
+    }
+
+    /// Pause at every check-in until the host calls `resume()`.
+    pub fn require_approval(mut self) -> Self {
+        self.require_approval = true;
+        self
+    }
+
+    /// End the run after the given duration.
+    pub fn time_box(mut self, duration: Duration) -> Self {
+        self.time_box = Some(duration);
+        self
+    }
+
+    /// End the run after the given number of turns.
+    pub fn max_turns(mut self, turns: u32) -> Self {
+        self.max_turns = Some(turns);
+        self
+    }
+}
+
+/// Why a check-in was emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckInReason {
+    /// The check-in interval elapsed
+    Interval,
+
+    /// The tool call budget between check-ins was used
+    ToolCalls,
+
+    /// Every plan step is completed, or the agent left no plan
+    Finished,
+
+    /// The run's time box expired
+    TimeBoxExpired,
+
+    /// The run reached its turn limit
+    TurnLimit,
+
+    /// A turn failed
+    Failed,
+}
+
+impl CheckInReason {
+    /// Whether the autonomous run ends with this check-in.
+    pub fn is_final(&self) -> bool {
+        !matches!(self, CheckInReason::Interval | CheckInReason::ToolCalls)
+    }
+}
+
+/// Progress summary of an autonomous run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckIn {
+    /// Check-in number, starting at 1
+    pub number: u32,
+
+    /// Why the check-in was emitted
+    pub reason: CheckInReason,
+
+    /// Goal of the run
+    pub goal: String,
+
+    /// Seconds since the run started
+    pub elapsed_secs: u64,
+
+    /// Turns completed in the run
+    pub turns: u32,
+
+    /// Tool calls since the previous check-in
+    pub tool_calls: u32,
+
+    /// Plan steps completed
+    pub completed_steps: usize,
+
+    /// Plan steps in total
+    pub total_steps: usize,
+
+    /// Plan step in progress, if any
+    pub current_step: Option<String>,
+
+    /// Last response of the agent
+    pub last_response: Option<String>,
+
+    /// Whether the agent is paused until the host resumes it
+    pub awaiting_approval: bool,
+}
+
+/// What to do after an autonomous turn.
+pub(crate) enum NextStep {
+    /// Queue the continuation prompt
+    Continue(String),
+
+    /// Check in, then keep going
+    CheckIn(CheckInReason, String),
+
+    /// Emit the final check-in
+    Finish(CheckInReason),
+}
+
+/// State of an autonomous run.
+#[derive(Debug)]
+pub(crate) struct Autonomy {
+    goal: String,
+    policy: CheckInPolicy,
+    started: Instant,
+    last_check_in: Instant,
+    check_ins: u32,
+    turns: u32,
+    tool_calls: u32,
+    plan: Vec<TodoItem>,
+    last_response: Option<String>,
+}
+
+impl Autonomy {
+    pub(crate) fn new(goal: String, policy: CheckInPolicy) -> Self {
+        let now = Instant::now();
+        Self {
+            goal,
+            policy,
+            started: now,
+            last_check_in: now,
+            check_ins: 0,
+            turns: 0,
+            tool_calls: 0,
+            plan: Vec::new(),
+            last_response: None,
+        }
+    }
+
+    /// Prompt starting the run.
+    pub(crate) fn initial_prompt(&self) -> String {
+        format!(
+            "Work autonomously toward the goal below without waiting for user input. \
+             Start by writing a plan with the plan tool, keep it updated as you go, and \
+             mark every step completed when done.\n\nGoal:\n{}",
+            self.goal
+        )
+    }
+
+    /// Count a tool call, returning the reason if a check-in is due.
+    pub(crate) fn record_tool_call(&mut self) -> Option<CheckInReason> {
+        self.tool_calls += 1;
+        if self
+            .policy
+            .tool_calls
+            .is_some_and(|limit| self.tool_calls >= limit)
+        {
+            return Some(CheckInReason::ToolCalls);
+        }
+        self.interval_elapsed().then_some(CheckInReason::Interval)
+    }
+
+    pub(crate) fn record_plan(&mut self, plan: &[TodoItem]) {
+        self.plan = plan.to_vec();
+    }
+
+    /// Record a completed turn and decide how the run continues.
+    pub(crate) fn record_turn(&mut self, response: Option<String>, failed: bool) -> NextStep {
+        self.turns += 1;
+        if response.is_some() {
+            self.last_response = response;
+        }
+
+        let open_steps = self
+            .plan
+            .iter()
+            .any(|todo| !matches!(todo.status, StepStatus::Completed));
+        if failed {
+            NextStep::Finish(CheckInReason::Failed)
+        } else if !open_steps {
+            NextStep::Finish(CheckInReason::Finished)
+        } else if self
+            .policy
+            .time_box
+            .is_some_and(|time_box| self.started.elapsed() >= time_box)
+        {
+            NextStep::Finish(CheckInReason::TimeBoxExpired)
+        } else if self
+            .policy
+            .max_turns
+            .is_some_and(|max_turns| self.turns >= max_turns)
+        {
+            NextStep::Finish(CheckInReason::TurnLimit)
+        } else if self.interval_elapsed() {
+            NextStep::CheckIn(CheckInReason::Interval, self.continuation_prompt())
+        } else {
+            NextStep::Continue(self.continuation_prompt())
+        }
+    }
+
+    /// Build a check-in and start counting towards the next one.
+    pub(crate) fn check_in(&mut self, reason: CheckInReason) -> CheckIn {
+        self.check_ins += 1;
+        let check_in = CheckIn {
+            number: self.check_ins,
+            reason,
+            goal: self.goal.clone(),
+            elapsed_secs: self.started.elapsed().as_secs(),
+            turns: self.turns,
+            tool_calls: self.tool_calls,
+            completed_steps: self
+                .plan
+                .iter()
+                .filter(|todo| matches!(todo.status, StepStatus::Completed))
+                .count(),
+            total_steps: self.plan.len(),
+            current_step: self
+                .plan
+                .iter()
+                .find(|todo| matches!(todo.status, StepStatus::InProgress))
+                .map(|todo| todo.content.clone()),
+            last_response: self.last_response.clone(),
+            awaiting_approval: self.policy.require_approval && !reason.is_final(),
+        };
+        self.last_check_in = Instant::now();
+        self.tool_calls = 0;
+        check_in
+    }
+
+    fn interval_elapsed(&self) -> bool {
+        self.policy
+            .interval
+            .is_some_and(|interval| self.last_check_in.elapsed() >= interval)
+    }
+
+    fn continuation_prompt(&self) -> String {
+        format!(
+            "Continue working on the remaining plan steps for the goal below. Keep the \
+             plan updated.\n\nGoal:\n{}",
+            self.goal
+        )
+    }
+}
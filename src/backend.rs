@@ -0,0 +1,188 @@
+//! Backends the execution loop runs conversations on.
+//!
+//! The loop only submits operations and reads events through
+//! [`ConversationBackend`], and starts conversations through [`LlmBackend`].
+//! Codex is the default backend; alternate backends and test doubles
+//! implement the same traits and are plugged in with `Agent::with_backend`.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use codex_core::config::Config as CodexConfig;
+use codex_core::{CodexConversation, ConversationManager};
+use codex_protocol::config_types::{ReasoningEffort, ReasoningSummary};
+use codex_protocol::protocol::{Event, Op, Submission};
+use futures::future::BoxFuture;
+
+use crate::error::{AgentError, Result};
+#[cfg(feature = "model-bridge")]
+use crate::model_bridge::ModelBridge;
+#[cfg(feature = "model-bridge")]
+use crate::provider::ModelProvider;
+use crate::tool_server::{SERVER_NAME, ToolListener, ToolServer};
+
+/// Conversation the execution loop submits operations to and reads events
+/// from.
+pub(crate) trait ConversationBackend: Send + Sync {
+    /// Submit an operation, returning the id of its submission.
+    fn submit(&self, op: Op) -> BoxFuture<'_, Result<String>>;
+
+    /// Submit an operation under a caller-chosen id.
+    fn submit_with_id(&self, submission: Submission) -> BoxFuture<'_, Result<()>>;
+
+    /// Wait for the next event of the conversation.
+    fn next_event(&self) -> BoxFuture<'_, Result<Event>>;
+}
+
+impl std::fmt::Debug for dyn ConversationBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ConversationBackend")
+    }
+}
+
+/// Model backend starting conversations.
+pub(crate) trait LlmBackend: Send + Sync {
+    /// Start a new conversation, offering the model the host tools served
+    /// by `tools`.
+    fn start_conversation(
+        &self,
+        tools: ToolServer,
+    ) -> BoxFuture<'_, Result<Arc<dyn ConversationBackend>>>;
+}
+
+impl std::fmt::Debug for dyn LlmBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LlmBackend")
+    }
+}
+
+impl ConversationBackend for CodexConversation {
+    fn submit(&self, op: Op) -> BoxFuture<'_, Result<String>> {
+        Box::pin(async move { Ok(CodexConversation::submit(self, op).await?) })
+    }
+
+    fn submit_with_id(&self, submission: Submission) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move { Ok(CodexConversation::submit_with_id(self, submission).await?) })
+    }
+
+    fn next_event(&self) -> BoxFuture<'_, Result<Event>> {
+        Box::pin(async move { Ok(CodexConversation::next_event(self).await?) })
+    }
+}
+
+/// Default backend, running conversations on Codex.
+pub(crate) struct CodexBackend {
+    manager: ConversationManager,
+    config: CodexConfig,
+    /// Relay Codex launches to reach the host tools
+    bridge: PathBuf,
+    /// Provider Codex reaches through a model bridge, with the agent's API
+    /// key
+    #[cfg(feature = "model-bridge")]
+    model_bridge: Option<(ModelProvider, Option<String>)>,
+}
+
+impl CodexBackend {
+    /// Start conversations with the given manager and Codex configuration,
+    /// serving host tools through the `bridge` relay.
+    pub(crate) fn new(manager: ConversationManager, config: CodexConfig, bridge: PathBuf) -> Self {
+        Self {
+            manager,
+            config,
+            bridge,
+            #[cfg(feature = "model-bridge")]
+            model_bridge: None,
+        }
+    }
+
+    /// Reasoning effort and summary the Codex config sets.
+    pub(crate) fn reasoning(&self) -> (ReasoningEffort, ReasoningSummary) {
+        (
+            self.config.model_reasoning_effort,
+            self.config.model_reasoning_summary,
+        )
+    }
+
+    /// Send each conversation's model requests through a model bridge to
+    /// `provider`, a provider Codex cannot talk to directly.
+    #[cfg(feature = "model-bridge")]
+    pub(crate) fn with_model_bridge(
+        mut self,
+        provider: ModelProvider,
+        api_key: Option<String>,
+    ) -> Self {
+        self.model_bridge = Some((provider, api_key));
+        self
+    }
+}
+
+impl LlmBackend for CodexBackend {
+    fn start_conversation(
+        &self,
+        tools: ToolServer,
+    ) -> BoxFuture<'_, Result<Arc<dyn ConversationBackend>>> {
+        Box::pin(async move {
+            let mut config = self.config.clone();
+            let listener = if tools.is_empty() {
+                None
+            } else {
+                let listener = tools.listen().await?;
+                config
+                    .mcp_servers
+                    .insert(SERVER_NAME.to_string(), listener.mcp_server(&self.bridge));
+                Some(listener)
+            };
+            #[cfg(feature = "model-bridge")]
+            let model_bridge = match &self.model_bridge {
+                Some((provider, api_key)) => {
+                    let bridge = ModelBridge::listen(provider.clone(), api_key.clone()).await?;
+                    let info = bridge.codex_provider(&config.model_provider_id)?;
+                    config
+                        .model_providers
+                        .insert(config.model_provider_id.clone(), info.clone());
+                    config.model_provider = info;
+                    Some(bridge)
+                }
+                None => None,
+            };
+            let new_conversation =
+                self.manager
+                    .new_conversation(config)
+                    .await
+                    .map_err(|e| AgentError::Config {
+                        message: format!("Failed to create conversation: {:?}", e),
+                    })?;
+            let conversation: Arc<dyn ConversationBackend> = Arc::new(CodexSession {
+                conversation: new_conversation.conversation,
+                _tools: listener,
+                #[cfg(feature = "model-bridge")]
+                _model_bridge: model_bridge,
+            });
+            Ok(conversation)
+        })
+    }
+}
+
+/// Codex conversation, with the listener its host tools are served on.
+struct CodexSession {
+    conversation: Arc<CodexConversation>,
+    /// Kept open for as long as the conversation lives
+    _tools: Option<ToolListener>,
+    /// Kept serving model requests for as long as the conversation lives
+    #[cfg(feature = "model-bridge")]
+    _model_bridge: Option<ModelBridge>,
+}
+
+impl ConversationBackend for CodexSession {
+    fn submit(&self, op: Op) -> BoxFuture<'_, Result<String>> {
+        ConversationBackend::submit(self.conversation.as_ref(), op)
+    }
+
+    fn submit_with_id(&self, submission: Submission) -> BoxFuture<'_, Result<()>> {
+        ConversationBackend::submit_with_id(self.conversation.as_ref(), submission)
+    }
+
+    fn next_event(&self) -> BoxFuture<'_, Result<Event>> {
+        ConversationBackend::next_event(self.conversation.as_ref())
+    }
+}
@@ -0,0 +1,8 @@
+//! Relay Codex launches as the MCP server of an agent's host tools.
+//!
+//! Not meant to be run by hand; see `agent_core::tool_server`.
+
+#[tokio::main]
+async fn main() -> agent_core::Result<()> {
+    agent_core::tool_server::relay().await
+}
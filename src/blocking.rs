@@ -0,0 +1,121 @@
+//! Helpers for running CPU-heavy custom tools without starving the async
+//! runtime that drives the agent.
+//!
+//! [`CustomToolHandler::execute`] is synchronous. Calling it directly from an
+//! async task occupies a runtime worker for as long as the tool runs, which
+//! stalls event processing, control commands and heartbeats. Run heavy
+//! handlers through [`run_tool`] or [`run_blocking`] instead, and poll a
+//! [`YieldPoint`] built from `ToolExecutionContext::cancellation` in long
+//! loops so the work stops promptly once cancelled.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub use tokio_util::sync::CancellationToken;
+
+use crate::error::{AgentError, Result};
+use crate::tools::{CustomToolHandler, ToolExecutionContext, ToolExecutionResult};
+
+/// Default time slice between yields to the scheduler.
+const DEFAULT_YIELD_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Run a blocking closure on tokio's blocking thread pool.
+pub async fn run_blocking<F, T>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| AgentError::Tool {
+            message: format!("Blocking tool task failed: {e}"),
+        })?
+}
+
+/// Execute a custom tool handler on the blocking thread pool.
+pub async fn run_tool(
+    handler: Arc<dyn CustomToolHandler>,
+    parameters: serde_json::Value,
+    context: ToolExecutionContext,
+) -> Result<ToolExecutionResult> {
+    run_blocking(move || handler.execute(parameters, &context)).await
+}
+
+/// Run a closure on the global rayon pool and await its result.
+///
+/// Use this for data-parallel tools that already use rayon, so they share
+/// its pool instead of pinning one blocking thread per call.
+#[cfg(feature = "rayon")]
+pub async fn run_on_rayon<F, T>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    rayon::spawn(move || {
+        // The receiver is gone if the caller stopped waiting
+        let _ = tx.send(f());
+    });
+    rx.await.map_err(|_| AgentError::Tool {
+        message: "Rayon tool task panicked".to_string(),
+    })?
+}
+
+/// Cooperative checkpoint for long-running tool loops.
+///
+/// Synchronous code calls [`check`](Self::check) to bail out once the token
+/// is cancelled. Async code calls [`tick`](Self::tick), which additionally
+/// yields to the scheduler whenever the configured time slice has elapsed.
+#[derive(Debug, Clone)]
+pub struct YieldPoint {
+    token: CancellationToken,
+    interval: Duration,
+    last_yield: Instant,
+}
+
+impl YieldPoint {
+    /// Create a checkpoint tied to a cancellation token.
+    pub fn new(token: CancellationToken) -> Self {
+        Self {
+            token,
+            interval: DEFAULT_YIELD_INTERVAL,
+            last_yield: Instant::now(),
+        }
+    }
+
+    /// Set the time slice between yields (default 10ms).
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Get the underlying cancellation token.
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Check if the work has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Return an error if the work has been cancelled.
+    pub fn check(&self) -> Result<()> {
+        if self.token.is_cancelled() {
+            return Err(AgentError::Tool {
+                message: "Tool execution cancelled".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Yield to the scheduler if the time slice is used up, then check for
+    /// cancellation.
+    pub async fn tick(&mut self) -> Result<()> {
+        if self.last_yield.elapsed() >= self.interval {
+            tokio::task::yield_now().await;
+            self.last_yield = Instant::now();
+        }
+        self.check()
+    }
+}
@@ -0,0 +1,283 @@
+//! Caching of responses to single-turn queries.
+//!
+//! With a [`ResponseCache`] configured, `Agent::query` answers a prompt it
+//! has answered before from the cache instead of asking the model, so batch
+//! pipelines repeating identical questions pay for them once. Entries are
+//! keyed by the prompt, with whitespace normalized, and by a fingerprint of
+//! the settings that shape the answer: model, provider, system prompt, tools
+//! and their settings, MCP servers, sandbox and approval policies, response
+//! schema, reasoning settings, and working directory.
+//!
+//! Only queries that start a conversation are cached, as later answers
+//! depend on the conversation so far; a cached answer is not added to the
+//! agent's conversation. Entries live in a [`ResponseStore`]: in memory with
+//! [`MemoryStore`], or in a store of the host's such as Redis. With a TTL,
+//! entries older than it are evicted whenever a response is stored.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::config::AgentConfig;
+use crate::error::Result;
+
+/// Version of the key format, changed when keys of equal queries change.
+const KEY_VERSION: &str = "v2";
+
+/// A cached response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedResponse {
+    /// Response of the query
+    pub response: String,
+
+    /// When the response was stored, in seconds since the Unix epoch
+    pub stored_at: u64,
+}
+
+/// Storage of cached responses.
+pub trait ResponseStore: Send + Sync {
+    /// Get the entry stored under `key`.
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<CachedResponse>>>;
+
+    /// Store an entry under `key`, replacing any previous one.
+    fn put<'a>(&'a self, key: &'a str, entry: CachedResponse) -> BoxFuture<'a, Result<()>>;
+
+    /// Remove the entries stored before `stored_at`, in seconds since the
+    /// Unix epoch. Stores expiring entries on their own can keep the default,
+    /// which removes nothing.
+    fn evict_before(&self, stored_at: u64) -> BoxFuture<'_, Result<()>> {
+        let _ = stored_at;
+        Box::pin(async { Ok(()) })
+    }
+}
+
+impl std::fmt::Debug for dyn ResponseStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ResponseStore")
+    }
+}
+
+/// In-memory store holding at most a number of entries, evicting the
+/// oldest first, and evicting the entries past the cache's TTL.
+#[derive(Debug)]
+pub struct MemoryStore {
+    max_entries: usize,
+    entries: Mutex<(HashMap<String, CachedResponse>, VecDeque<String>)>,
+}
+
+impl MemoryStore {
+    /// Create a store of at most `max_entries` entries.
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries: max_entries.max(1),
+            entries: Mutex::new((HashMap::new(), VecDeque::new())),
+        }
+    }
+
+    /// Number of entries stored.
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .0
+            .len()
+    }
+
+    /// Whether the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ResponseStore for MemoryStore {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<CachedResponse>>> {
+        let entry = self
+            .entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .0
+            .get(key)
+            .cloned();
+        Box::pin(async move { Ok(entry) })
+    }
+
+    fn put<'a>(&'a self, key: &'a str, entry: CachedResponse) -> BoxFuture<'a, Result<()>> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let (map, order) = &mut *entries;
+        if map.insert(key.to_string(), entry).is_none() {
+            order.push_back(key.to_string());
+        }
+        while map.len() > self.max_entries {
+            match order.pop_front() {
+                Some(oldest) => map.remove(&oldest),
+                None => break,
+            };
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+    fn evict_before(&self, stored_at: u64) -> BoxFuture<'_, Result<()>> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let (map, order) = &mut *entries;
+        map.retain(|_, entry| entry.stored_at >= stored_at);
+        order.retain(|key| map.contains_key(key));
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Response cache of single-turn queries.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    store: Arc<dyn ResponseStore>,
+    ttl: Option<Duration>,
+}
+
+impl ResponseCache {
+    /// Cache up to `max_entries` responses in memory.
+    pub fn memory(max_entries: usize) -> Self {
+        Self::new(Arc::new(MemoryStore::new(max_entries)))
+    }
+
+    /// Cache responses in the given store.
+    pub fn new(store: Arc<dyn ResponseStore>) -> Self {
+        Self { store, ttl: None }
+    }
+
+    /// Ignore entries older than `ttl`.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Cached response under `key`, if one is fresh. Store errors are
+    /// logged and treated as misses.
+    pub(crate) async fn get(&self, key: &str) -> Option<String> {
+        let entry = match self.store.get(key).await {
+            Ok(entry) => entry?,
+            Err(e) => {
+                warn!("Failed to read response cache: {}", e);
+                return None;
+            }
+        };
+        let age = Duration::from_secs(now().saturating_sub(entry.stored_at));
+        match self.ttl {
+            Some(ttl) if age > ttl => None,
+            _ => Some(entry.response),
+        }
+    }
+
+    /// Cache the response under `key`, evicting the entries past the TTL.
+    /// Store errors are logged.
+    pub(crate) async fn put(&self, key: &str, response: &str) {
+        let stored_at = now();
+        if let Some(ttl) = self.ttl
+            && let Err(e) = self
+                .store
+                .evict_before(stored_at.saturating_sub(ttl.as_secs()))
+                .await
+        {
+            warn!("Failed to evict from response cache: {}", e);
+        }
+        let entry = CachedResponse {
+            response: response.to_string(),
+            stored_at,
+        };
+        if let Err(e) = self.store.put(key, entry).await {
+            warn!("Failed to write response cache: {}", e);
+        }
+    }
+}
+
+/// Key of `prompt` asked of an agent with `config`.
+pub(crate) fn cache_key(prompt: &str, config: &AgentConfig) -> String {
+    let prompt = prompt.split_whitespace().collect::<Vec<_>>().join(" ");
+    let (provider, model) = config.resolve_model(config.model());
+    let mut tools = config.tools().to_vec();
+    tools.sort_by(|a, b| a.name().cmp(b.name()));
+
+    let fingerprint = [
+        model,
+        provider
+            .map(|provider| provider.base_url)
+            .unwrap_or_default(),
+        config.system_prompt().unwrap_or_default().to_string(),
+        canonical_json(&tools),
+        canonical_json(config.mcp_servers()),
+        canonical_json(config.sandbox_policy()),
+        canonical_json(config.approval_policy()),
+        config
+            .response_schema()
+            .map(ToString::to_string)
+            .unwrap_or_default(),
+        format!(
+            "{:?}/{:?}",
+            config.reasoning_effort(),
+            config.reasoning_summary()
+        ),
+        config.working_directory().display().to_string(),
+        prompt,
+    ];
+    let mut hash = Fnv64::default();
+    for part in &fingerprint {
+        hash.write(part.as_bytes());
+        hash.write(&[0]);
+    }
+    format!("{}:{:016x}", KEY_VERSION, hash.0)
+}
+
+/// JSON of `value` with the keys of its objects sorted, so hash maps of
+/// equal settings give equal keys.
+fn canonical_json<T: Serialize + ?Sized>(value: &T) -> String {
+    fn sorted(value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(object) => {
+                let mut fields: Vec<_> = object.into_iter().collect();
+                fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+                serde_json::Value::Object(
+                    fields
+                        .into_iter()
+                        .map(|(key, value)| (key, sorted(value)))
+                        .collect(),
+                )
+            }
+            serde_json::Value::Array(items) => {
+                serde_json::Value::Array(items.into_iter().map(sorted).collect())
+            }
+            value => value,
+        }
+    }
+
+    serde_json::to_value(value)
+        .map(sorted)
+        .map(|value| value.to_string())
+        .unwrap_or_default()
+}
+
+/// FNV-1a hash, stable across builds so persistent stores keep their keys.
+struct Fnv64(u64);
+
+impl Default for Fnv64 {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv64 {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,217 @@
+//! Side-by-side comparison of one prompt run against two configurations.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::Agent;
+use crate::config::AgentConfig;
+use crate::messages::{OutputData, OutputMessage};
+use crate::ops::{TokenUsage, ToolUsage};
+
+/// Outcome of running the prompt against one configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TurnResult {
+    /// Model of the configuration
+    pub model: String,
+
+    /// Response, or the error the query failed with
+    pub response: std::result::Result<String, String>,
+
+    /// Tokens used
+    pub usage: TokenUsage,
+
+    /// Wall-clock time of the query in milliseconds
+    pub latency_ms: u64,
+
+    /// Tools invoked, most used first
+    pub tools: Vec<ToolUsage>,
+
+    /// Outputs of the turn, excluding streaming fragments
+    pub events: Vec<OutputMessage>,
+}
+
+impl TurnResult {
+    fn tool_calls(&self) -> u64 {
+        self.tools.iter().map(|tool| tool.count).sum()
+    }
+
+    fn answer(&self) -> &str {
+        match &self.response {
+            Ok(response) => response,
+            Err(_) => "",
+        }
+    }
+}
+
+/// Differences between the two runs; deltas are `b - a`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComparisonSummary {
+    /// Whether both runs gave the same answer, ignoring surrounding whitespace
+    pub same_answer: bool,
+
+    /// Word overlap of the two answers (Jaccard index, 0 to 1)
+    pub answer_similarity: f64,
+
+    /// Answer lines only in run A
+    pub only_in_a: Vec<String>,
+
+    /// Answer lines only in run B
+    pub only_in_b: Vec<String>,
+
+    /// Difference in total tokens
+    pub token_delta: i64,
+
+    /// Difference in latency in milliseconds
+    pub latency_delta_ms: i64,
+
+    /// Difference in the number of tool calls
+    pub tool_call_delta: i64,
+
+    /// Tools only run A used
+    pub tools_only_in_a: Vec<String>,
+
+    /// Tools only run B used
+    pub tools_only_in_b: Vec<String>,
+}
+
+impl std::fmt::Display for ComparisonSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.same_answer {
+            writeln!(f, "Answers: identical")?;
+        } else {
+            writeln!(
+                f,
+                "Answers: {:.0}% word overlap, {} lines only in A, {} only in B",
+                self.answer_similarity * 100.0,
+                self.only_in_a.len(),
+                self.only_in_b.len()
+            )?;
+        }
+        writeln!(f, "Tokens: {:+}", self.token_delta)?;
+        writeln!(f, "Latency: {:+} ms", self.latency_delta_ms)?;
+        write!(f, "Tool calls: {:+}", self.tool_call_delta)?;
+        if !self.tools_only_in_a.is_empty() {
+            write!(f, "\nTools only in A: {}", self.tools_only_in_a.join(", "))?;
+        }
+        if !self.tools_only_in_b.is_empty() {
+            write!(f, "\nTools only in B: {}", self.tools_only_in_b.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Both runs of a comparison and how they differ.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comparison {
+    /// Run with the first configuration
+    pub a: TurnResult,
+
+    /// Run with the second configuration
+    pub b: TurnResult,
+
+    /// Differences between the runs
+    pub summary: ComparisonSummary,
+}
+
+/// Run `prompt` against two configurations concurrently, each on a fresh
+/// agent, and summarize how the results differ.
+pub async fn compare<S: Into<String>>(
+    config_a: AgentConfig,
+    config_b: AgentConfig,
+    prompt: S,
+) -> Comparison {
+    let prompt = prompt.into();
+    let (a, b) = tokio::join!(run(config_a, prompt.clone()), run(config_b, prompt));
+    let summary = summarize(&a, &b);
+    Comparison { a, b, summary }
+}
+
+async fn run(config: AgentConfig, prompt: String) -> TurnResult {
+    let model = config.model().to_string();
+    let started = Instant::now();
+    let mut agent = match Agent::new(config) {
+        Ok(agent) => agent,
+        Err(e) => {
+            return TurnResult {
+                model,
+                response: Err(e.to_string()),
+                usage: TokenUsage::default(),
+                latency_ms: 0,
+                tools: Vec::new(),
+                events: Vec::new(),
+            };
+        }
+    };
+    let response = agent.query(prompt).await.map_err(|e| e.to_string());
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let mut usage = TokenUsage::default();
+    let mut events = Vec::new();
+    for record in agent.turn_records().await {
+        usage += record.usage;
+        events.extend(record.events);
+    }
+
+    let mut counts: BTreeMap<String, u64> = BTreeMap::new();
+    for event in &events {
+        if let OutputData::ToolStart { tool_name, .. } = &event.data {
+            *counts.entry(tool_name.clone()).or_default() += 1;
+        }
+    }
+    let mut tools: Vec<ToolUsage> = counts
+        .into_iter()
+        .map(|(name, count)| ToolUsage { name, count })
+        .collect();
+    tools.sort_by(|a, b| b.count.cmp(&a.count));
+
+    TurnResult {
+        model,
+        response,
+        usage,
+        latency_ms,
+        tools,
+        events,
+    }
+}
+
+fn summarize(a: &TurnResult, b: &TurnResult) -> ComparisonSummary {
+    let lines = |text: &str| -> BTreeSet<String> {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    let words = |text: &str| -> BTreeSet<String> {
+        text.split_whitespace().map(str::to_lowercase).collect()
+    };
+    let tool_names = |result: &TurnResult| -> BTreeSet<String> {
+        result.tools.iter().map(|tool| tool.name.clone()).collect()
+    };
+
+    let (lines_a, lines_b) = (lines(a.answer()), lines(b.answer()));
+    let (words_a, words_b) = (words(a.answer()), words(b.answer()));
+    let union = words_a.union(&words_b).count();
+    let answer_similarity = if union == 0 {
+        1.0
+    } else {
+        words_a.intersection(&words_b).count() as f64 / union as f64
+    };
+    let (tools_a, tools_b) = (tool_names(a), tool_names(b));
+
+    ComparisonSummary {
+        same_answer: a.response.is_ok()
+            && b.response.is_ok()
+            && a.answer().trim() == b.answer().trim(),
+        answer_similarity,
+        only_in_a: lines_a.difference(&lines_b).cloned().collect(),
+        only_in_b: lines_b.difference(&lines_a).cloned().collect(),
+        token_delta: b.usage.total_tokens as i64 - a.usage.total_tokens as i64,
+        latency_delta_ms: b.latency_ms as i64 - a.latency_ms as i64,
+        tool_call_delta: b.tool_calls() as i64 - a.tool_calls() as i64,
+        tools_only_in_a: tools_a.difference(&tools_b).cloned().collect(),
+        tools_only_in_b: tools_b.difference(&tools_a).cloned().collect(),
+    }
+}
@@ -2,24 +2,76 @@
 
 use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
+use codex_protocol::config_types::{ReasoningEffort, ReasoningSummary};
 use codex_protocol::protocol::{AskForApproval, SandboxPolicy};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
 
+use crate::auth::AuthMethod;
+use crate::cache::ResponseCache;
+use crate::converter::EventConverter;
 use crate::error::{AgentError, Result};
+use crate::lexicon::LexiconFilter;
 use crate::mcp::McpServerConfig;
-use crate::tools::ToolConfig;
+use crate::middleware::ToolMiddleware;
+use crate::paths::glob_matches;
+use crate::processors::OutputProcessor;
+use crate::profile::{ConfigProfile, ConfigProfiles};
+use crate::provider::{self, ModelProvider, ProviderRoute, WireApi};
+use crate::rate_limit::{OPENAI_PROVIDER, RateLimit};
+use crate::retry::RetryPolicy;
+use crate::sandbox::SandboxBackend;
+use crate::suggestions::SuggestionsConfig;
+use crate::telemetry::TelemetrySink;
+use crate::tool_server;
+use crate::tools::{ToolConcurrency, ToolConfig};
+use crate::verify::VerifyConfig;
+use crate::workspace::{AutoCommitConfig, DiskQuota};
+
+/// Host variables every tool execution inherits so commands can run at all.
+const CORE_ENV_VARS: &[&str] = &[
+    "HOME", "LOGNAME", "PATH", "SHELL", "USER", "USERNAME", "TMPDIR", "TEMP", "TMP",
+];
+
+/// Prefixes of the model families the Codex backend serves.
+const KNOWN_MODEL_PREFIXES: &[&str] = &["gpt-", "o1", "o3", "o4", "codex-"];
+
+/// Default number of retries for invalid structured output.
+const DEFAULT_JSON_RETRIES: u32 = 2;
 
 /// Main configuration for an AI agent.
-#[derive(Debug, Clone)]
+///
+/// Configs serialize to and from JSON (or any serde format) for persistence
+/// and transport; missing fields take the builder's defaults. The API key is
+/// never serialized, so it does not leak into stored sessions, and code-only
+/// fields are skipped: the telemetry sink, output processors, tool middleware,
+/// event converter, response cache, and the handlers and search providers of
+/// tools. After deserializing, re-attach them with
+/// [`AgentConfig::into_builder`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AgentConfig {
     /// Model identifier (e.g., "gpt-4", "gpt-5-mini")
     model: String,
 
     /// API key for the model provider
+    #[serde(skip_serializing)]
     api_key: Option<String>,
 
+    /// Credentials from the codex home used without an API key
+    auth_method: AuthMethod,
+
+    /// Endpoint model requests are sent to instead of OpenAI
+    model_provider: Option<ModelProvider>,
+
+    /// Providers serving models by name prefix, taking precedence over
+    /// `model_provider`
+    provider_routes: Vec<ProviderRoute>,
+
     /// System prompt/instructions for the agent
     system_prompt: Option<String>,
 
@@ -32,6 +84,16 @@ pub struct AgentConfig {
     /// Maximum number of conversation turns
     max_turns: Option<u32>,
 
+    /// Reasoning effort of reasoning models, overridable per query
+    reasoning_effort: Option<ReasoningEffort>,
+
+    /// Verbosity of the reasoning summaries reasoning models emit
+    reasoning_summary: Option<ReasoningSummary>,
+
+    /// Whether to emit the model's raw reasoning instead of summaries, for
+    /// models and providers that expose it
+    show_raw_reasoning: bool,
+
     /// Working directory for agent operations
     working_directory: PathBuf,
 
@@ -44,8 +106,90 @@ pub struct AgentConfig {
     /// Environment variables for the agent
     environment: HashMap<String, String>,
 
+    /// Host environment variables passed through to tools and `${VAR}` templates
+    env_passthrough: Vec<String>,
+
     /// Additional configuration options
     additional_config: HashMap<String, serde_json::Value>,
+
+    /// Whether to emit a workspace change summary at the end of each turn
+    workspace_summary: bool,
+
+    /// Whether to emit only complete responses and reasoning, without deltas
+    coalesce_deltas: bool,
+
+    /// Maximum number of delta messages emitted per second, batching the rest
+    debounce_deltas: Option<u32>,
+
+    /// Commit workspace changes to a dedicated branch at the end of each turn
+    auto_commit: Option<AutoCommitConfig>,
+
+    /// Whether to checkpoint the workspace in git before each turn
+    git_checkpoints: bool,
+
+    /// Verification step run after the agent edits files
+    verify: Option<VerifyConfig>,
+
+    /// Retries of turns failed by transient model errors
+    retry_policy: Option<RetryPolicy>,
+
+    /// Interval between heartbeat messages while idle
+    heartbeat: Option<Duration>,
+
+    /// Interval between operational summaries
+    ops_summary: Option<Duration>,
+
+    /// Opt-in receiver of anonymized usage events
+    #[serde(skip)]
+    telemetry: Option<Arc<dyn TelemetrySink>>,
+
+    /// Transformations applied to final response content, in order
+    #[serde(skip)]
+    output_processors: Vec<Arc<dyn OutputProcessor>>,
+
+    /// Hooks run around custom tool calls, in order
+    #[serde(skip)]
+    tool_middleware: Vec<Arc<dyn ToolMiddleware>>,
+
+    /// Strategy rewriting the outputs converted from Codex events
+    #[serde(skip)]
+    event_converter: Option<Arc<dyn EventConverter>>,
+
+    /// Caps on concurrently running custom tool calls
+    tool_concurrency: ToolConcurrency,
+
+    /// Brand-safety filter applied to final and streamed responses
+    lexicon_filter: Option<LexiconFilter>,
+
+    /// Byte quota on the working directory
+    disk_quota: Option<DiskQuota>,
+
+    /// Request and token limits by provider name, shared by all agents using
+    /// the provider
+    rate_limits: HashMap<String, RateLimit>,
+
+    /// Where commands spawned by agent-core run
+    sandbox_backend: SandboxBackend,
+
+    /// Retries for structured output queries returning invalid JSON
+    json_retries: u32,
+
+    /// JSON Schema every final response must conform to
+    response_schema: Option<serde_json::Value>,
+
+    /// Follow-up prompts suggested after each completed turn
+    suggestions: Option<SuggestionsConfig>,
+
+    /// Cache of responses to single-turn queries
+    #[serde(skip)]
+    response_cache: Option<ResponseCache>,
+
+    /// JSONL file the conversation's Codex events and submissions are
+    /// recorded to
+    recording: Option<PathBuf>,
+
+    /// Relay Codex launches to call the host tools, if not the default
+    tool_bridge: Option<PathBuf>,
 }
 
 impl AgentConfig {
@@ -54,6 +198,402 @@ impl AgentConfig {
         AgentConfigBuilder::default()
     }
 
+    /// Create empty named profiles, to be given a base configuration and
+    /// presets in code; see also [`ConfigProfiles::from_file`].
+    pub fn profiles() -> ConfigProfiles {
+        ConfigProfiles::new()
+    }
+
+    /// Apply the overrides of a profile.
+    pub fn with_profile(mut self, profile: &ConfigProfile) -> Result<Self> {
+        if let Some(model) = &profile.model {
+            self.model = model.clone();
+        }
+        if let Some(prompt) = &profile.system_prompt {
+            self.system_prompt = Some(prompt.clone());
+        }
+        if let Some(policy) = &profile.sandbox_policy {
+            self.sandbox_policy = policy.clone();
+        }
+        if let Some(policy) = profile.approval_policy {
+            self.approval_policy = policy;
+        }
+        if let Some(max_turns) = profile.max_turns {
+            self.max_turns = Some(max_turns);
+        }
+        if let Some(tools) = &profile.tools {
+            self.tools = tools.clone();
+        }
+        self.tools.retain(|tool| {
+            !profile
+                .disabled_tools
+                .iter()
+                .any(|name| name == tool.name())
+        });
+
+        // The profile's sandbox or tools may contradict the rest of the config
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Apply the settings of a runtime update, validating the result.
+    pub fn with_patch(mut self, patch: &ConfigPatch) -> Result<Self> {
+        if let Some(prompt) = &patch.system_prompt {
+            self.system_prompt = Some(prompt.clone());
+        }
+        if let Some(policy) = patch.approval_policy {
+            self.approval_policy = policy;
+        }
+        self.tools
+            .retain(|tool| !patch.disable_tools.iter().any(|name| name == tool.name()));
+        for tool in &patch.enable_tools {
+            self.tools.retain(|existing| existing.name() != tool.name());
+            self.tools.push(tool.clone());
+        }
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Configuration of a one-off model request made on the agent's behalf,
+    /// e.g. for a commit message: the same model and provider, without tools
+    /// or the workflows run around turns.
+    pub(crate) fn for_side_query(&self) -> Self {
+        let mut config = self.clone();
+        config.tools.clear();
+        config.mcp_servers.clear();
+        config.workspace_summary = false;
+        config.auto_commit = None;
+        config.git_checkpoints = false;
+        config.verify = None;
+        config.suggestions = None;
+        config.response_schema = None;
+        config.response_cache = None;
+        config.recording = None;
+        config
+    }
+
+    /// Check the configuration for contradictions and invalid values.
+    ///
+    /// Every problem found is reported at once in an
+    /// [`AgentError::InvalidConfig`], each with the path of the offending
+    /// field (e.g. `tools[2].timeout`). Called by `AgentConfigBuilder::build`.
+    pub fn validate(&self) -> Result<()> {
+        let mut issues = Vec::new();
+        let mut issue = |path: String, message: String| issues.push(ConfigIssue { path, message });
+
+        if let Some(provider) = &self.model_provider
+            && !is_http_url(&provider.base_url)
+        {
+            issue(
+                "model_provider.base_url".to_string(),
+                format!("\"{}\" is not an http(s) URL", provider.base_url),
+            );
+        }
+        for (i, route) in self.provider_routes.iter().enumerate() {
+            if route.prefix.is_empty() {
+                issue(
+                    format!("provider_routes[{}].prefix", i),
+                    "must not be empty".to_string(),
+                );
+            }
+            if !is_http_url(&route.provider.base_url) {
+                issue(
+                    format!("provider_routes[{}].provider.base_url", i),
+                    format!("\"{}\" is not an http(s) URL", route.provider.base_url),
+                );
+            }
+        }
+        let providers =
+            self.model_provider
+                .iter()
+                .map(|provider| ("model_provider".to_string(), provider))
+                .chain(self.provider_routes.iter().enumerate().map(|(i, route)| {
+                    (format!("provider_routes[{}].provider", i), &route.provider)
+                }));
+        for (path, provider) in providers {
+            if provider.wire_api == WireApi::Anthropic && !cfg!(feature = "provider-anthropic") {
+                issue(
+                    format!("{}.wire_api", path),
+                    "the Messages API needs the `provider-anthropic` feature".to_string(),
+                );
+            }
+            if provider.wire_api == WireApi::Gemini && !cfg!(feature = "provider-gemini") {
+                issue(
+                    format!("{}.wire_api", path),
+                    "the Gemini API needs the `provider-gemini` feature".to_string(),
+                );
+            }
+            if !provider.prompt_tools {
+                continue;
+            }
+            if provider.wire_api != WireApi::Chat {
+                issue(
+                    format!("{}.wire_api", path),
+                    "prompt-based tool calling needs the Chat Completions API".to_string(),
+                );
+            }
+            if !cfg!(feature = "prompt-tools") {
+                issue(
+                    format!("{}.prompt_tools", path),
+                    "needs the `prompt-tools` feature".to_string(),
+                );
+            }
+        }
+        if self.auth_method == AuthMethod::ChatGpt && self.api_key.is_some() {
+            issue(
+                "auth_method".to_string(),
+                "ChatGPT login cannot be combined with an API key".to_string(),
+            );
+        }
+        if self.max_turns == Some(0) {
+            issue("max_turns".to_string(), "must be at least 1".to_string());
+        }
+
+        let network_access = match &self.sandbox_policy {
+            SandboxPolicy::DangerFullAccess => true,
+            SandboxPolicy::ReadOnly => false,
+            SandboxPolicy::WorkspaceWrite { network_access, .. } => *network_access,
+        };
+        let read_only = matches!(self.sandbox_policy, SandboxPolicy::ReadOnly);
+        for (index, tool) in self.tools.iter().enumerate() {
+            let path = format!("tools[{}]", index);
+            if self.tools[..index]
+                .iter()
+                .any(|other| other.name() == tool.name())
+            {
+                issue(
+                    format!("{}.name", path),
+                    format!("duplicate tool name \"{}\"", tool.name()),
+                );
+            }
+            if let Err(AgentError::Config { message }) = self.tool_working_directory(tool.name()) {
+                issue(format!("{}.working_directory", path), message);
+            }
+            match tool {
+                ToolConfig::WebSearch { .. } if !network_access => issue(
+                    path,
+                    "web_search needs network access, which sandbox_policy denies".to_string(),
+                ),
+                ToolConfig::FileWrite { .. } | ToolConfig::ApplyPatch { .. } if read_only => issue(
+                    path,
+                    format!(
+                        "{} cannot write under a read-only sandbox_policy",
+                        tool.name()
+                    ),
+                ),
+                ToolConfig::Bash {
+                    timeout: Some(0), ..
+                } => issue(format!("{}.timeout", path), "must be positive".to_string()),
+                ToolConfig::Bash { .. }
+                    if tool.runs_on_host()
+                        && matches!(self.sandbox_backend, SandboxBackend::Native)
+                        && !matches!(self.sandbox_policy, SandboxPolicy::DangerFullAccess) =>
+                {
+                    issue(
+                        format!("{}.limits", path),
+                        "commands under resource limits or a kill policy run on the host, \
+                         outside Codex's sandbox; use a container sandbox_backend or the \
+                         DangerFullAccess sandbox_policy"
+                            .to_string(),
+                    )
+                }
+                ToolConfig::ApplyPatch { dry_run: true, .. }
+                    if self.approval_policy == AskForApproval::Never =>
+                {
+                    issue(
+                        format!("{}.dry_run", path),
+                        "Codex asks approval for no patch to preview under the Never \
+                         approval_policy"
+                            .to_string(),
+                    )
+                }
+                _ => {}
+            }
+        }
+
+        for (index, server) in self.mcp_servers.iter().enumerate() {
+            if server.name() == tool_server::SERVER_NAME {
+                issue(
+                    format!("mcp_servers[{}].name", index),
+                    format!(
+                        "\"{}\" is reserved for the agent's own tools",
+                        tool_server::SERVER_NAME
+                    ),
+                );
+            }
+        }
+
+        if tool_server::needs_bridge(&self.tools, &self.sandbox_backend)
+            && tool_server::resolve_bridge(self.tool_bridge.as_deref()).is_none()
+        {
+            let bridge = self
+                .tool_bridge
+                .as_deref()
+                .unwrap_or(Path::new(tool_server::DEFAULT_BRIDGE));
+            issue(
+                "tool_bridge".to_string(),
+                format!(
+                    "relay {} was not found; Codex calls the host tools through it",
+                    bridge.display()
+                ),
+            );
+        }
+
+        if let Some(verify) = &self.verify
+            && verify.timeout == 0
+        {
+            issue("verify.timeout".to_string(), "must be positive".to_string());
+        }
+        if let Some(policy) = &self.retry_policy
+            && policy.max_backoff < policy.initial_backoff
+        {
+            issue(
+                "retry_policy.max_backoff".to_string(),
+                "must not be less than initial_backoff".to_string(),
+            );
+        }
+        if self.heartbeat == Some(Duration::ZERO) {
+            issue(
+                "heartbeat".to_string(),
+                "interval must be positive".to_string(),
+            );
+        }
+        if self.debounce_deltas == Some(0) {
+            issue(
+                "debounce_deltas".to_string(),
+                "rate must be positive".to_string(),
+            );
+        }
+        if self.ops_summary == Some(Duration::ZERO) {
+            issue(
+                "ops_summary".to_string(),
+                "interval must be positive".to_string(),
+            );
+        }
+        if let Some(quota) = &self.disk_quota
+            && !(quota.warn_ratio > 0.0 && quota.warn_ratio <= 1.0)
+        {
+            issue(
+                "disk_quota.warn_ratio".to_string(),
+                "must be in (0, 1]".to_string(),
+            );
+        }
+        for (provider, limit) in &self.rate_limits {
+            if limit.requests_per_minute == Some(0) {
+                issue(
+                    format!("rate_limits.{}.requests_per_minute", provider),
+                    "must be positive".to_string(),
+                );
+            }
+            if limit.tokens_per_minute == Some(0) {
+                issue(
+                    format!("rate_limits.{}.tokens_per_minute", provider),
+                    "must be positive".to_string(),
+                );
+            }
+        }
+        if let Some(suggestions) = &self.suggestions
+            && suggestions.timeout.is_zero()
+        {
+            issue(
+                "suggestions.timeout".to_string(),
+                "must be positive".to_string(),
+            );
+        }
+
+        for warning in self.warnings() {
+            warn!("Questionable config: {}", warning);
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(AgentError::InvalidConfig { issues })
+        }
+    }
+
+    /// Settings that are valid but likely mistaken, like model names outside
+    /// the families the Codex backend serves. Logged by
+    /// [`validate`](Self::validate) and reported as warnings by preflight.
+    pub fn warnings(&self) -> Vec<ConfigIssue> {
+        let mut warnings = Vec::new();
+        let models = std::iter::once(("model", &self.model)).chain(
+            self.suggestions
+                .as_ref()
+                .map(|suggestions| ("suggestions.model", &suggestions.model)),
+        );
+        for (path, model) in models {
+            // Custom providers serve models of their own
+            if self.resolve_model(model).0.is_none() && !is_known_model(model) {
+                warnings.push(ConfigIssue {
+                    path: path.to_string(),
+                    message: format!("unknown model \"{}\"", model),
+                });
+            }
+        }
+        if self.checks_tool_paths()
+            && matches!(self.sandbox_backend, SandboxBackend::Native)
+            && !matches!(self.sandbox_policy, SandboxPolicy::DangerFullAccess)
+        {
+            warnings.push(ConfigIssue {
+                path: "tools".to_string(),
+                message: "commands run on the host to check the paths of file tools, outside \
+                          Codex's sandbox; only the writes they name are bound to its \
+                          writable roots"
+                    .to_string(),
+            });
+        }
+        warnings
+    }
+
+    /// Turn the configuration back into a builder, e.g. to re-attach the
+    /// handlers skipped when it was serialized.
+    pub fn into_builder(self) -> AgentConfigBuilder {
+        AgentConfigBuilder {
+            model: Some(self.model),
+            api_key: self.api_key,
+            auth_method: self.auth_method,
+            model_provider: self.model_provider,
+            provider_routes: self.provider_routes,
+            system_prompt: self.system_prompt,
+            sandbox_policy: Some(self.sandbox_policy),
+            approval_policy: Some(self.approval_policy),
+            max_turns: self.max_turns,
+            reasoning_effort: self.reasoning_effort,
+            reasoning_summary: self.reasoning_summary,
+            show_raw_reasoning: self.show_raw_reasoning,
+            working_directory: Some(self.working_directory),
+            tools: self.tools,
+            mcp_servers: self.mcp_servers,
+            environment: self.environment,
+            env_passthrough: self.env_passthrough,
+            additional_config: self.additional_config,
+            workspace_summary: self.workspace_summary,
+            coalesce_deltas: self.coalesce_deltas,
+            debounce_deltas: self.debounce_deltas,
+            auto_commit: self.auto_commit,
+            git_checkpoints: self.git_checkpoints,
+            verify: self.verify,
+            retry_policy: self.retry_policy,
+            heartbeat: self.heartbeat,
+            ops_summary: self.ops_summary,
+            telemetry: self.telemetry,
+            output_processors: self.output_processors,
+            tool_middleware: self.tool_middleware,
+            event_converter: self.event_converter,
+            tool_concurrency: self.tool_concurrency,
+            lexicon_filter: self.lexicon_filter,
+            disk_quota: self.disk_quota,
+            rate_limits: self.rate_limits,
+            sandbox_backend: self.sandbox_backend,
+            json_retries: Some(self.json_retries),
+            response_schema: self.response_schema,
+            suggestions: self.suggestions,
+            response_cache: self.response_cache,
+            recording: self.recording,
+            tool_bridge: self.tool_bridge,
+        }
+    }
+
     /// Get the model identifier.
     pub fn model(&self) -> &str {
         &self.model
@@ -64,6 +604,37 @@ impl AgentConfig {
         self.api_key.as_deref()
     }
 
+    /// Get the credentials used without an API key.
+    pub fn auth_method(&self) -> AuthMethod {
+        self.auth_method
+    }
+
+    /// Get the custom model provider, if any.
+    pub fn model_provider(&self) -> Option<&ModelProvider> {
+        self.model_provider.as_ref()
+    }
+
+    /// Get the providers routed to by model-name prefix.
+    pub fn provider_routes(&self) -> &[ProviderRoute] {
+        &self.provider_routes
+    }
+
+    /// Provider serving `model` and the model name to request from it.
+    ///
+    /// The route with the longest matching prefix wins, then the custom
+    /// provider, then the provider of Claude and Gemini models. `None` means
+    /// OpenAI.
+    pub fn resolve_model(&self, model: &str) -> (Option<ModelProvider>, String) {
+        if let Some((provider, model)) = provider::route(&self.provider_routes, model) {
+            return (Some(provider.clone()), model.to_string());
+        }
+        let provider = self
+            .model_provider
+            .clone()
+            .or_else(|| ModelProvider::for_model(model));
+        (provider, model.to_string())
+    }
+
     /// Get the system prompt.
     pub fn system_prompt(&self) -> Option<&str> {
         self.system_prompt.as_deref()
@@ -84,6 +655,21 @@ impl AgentConfig {
         self.max_turns
     }
 
+    /// Get the reasoning effort, if set.
+    pub fn reasoning_effort(&self) -> Option<ReasoningEffort> {
+        self.reasoning_effort
+    }
+
+    /// Get the reasoning summary verbosity, if set.
+    pub fn reasoning_summary(&self) -> Option<ReasoningSummary> {
+        self.reasoning_summary
+    }
+
+    /// Check if raw reasoning is emitted.
+    pub fn show_raw_reasoning(&self) -> bool {
+        self.show_raw_reasoning
+    }
+
     /// Get the working directory.
     pub fn working_directory(&self) -> &PathBuf {
         &self.working_directory
@@ -104,10 +690,323 @@ impl AgentConfig {
         &self.environment
     }
 
+    /// Resolve the working directory a tool runs in.
+    ///
+    /// Overrides are relative to the agent's working directory and must stay
+    /// within the sandbox roots (the working directory plus any writable
+    /// roots) unless the sandbox grants full access. Tools without an
+    /// override, or not configured at all, use the agent's working directory.
+    pub fn tool_working_directory(&self, tool_name: &str) -> Result<PathBuf> {
+        let tool = self.tools.iter().find(|tool| tool.name() == tool_name);
+        match tool.and_then(ToolConfig::working_directory_override) {
+            Some(directory) => self.resolve_tool_directory(tool_name, directory),
+            None => Ok(self.working_directory.clone()),
+        }
+    }
+
+    fn resolve_tool_directory(&self, tool_name: &str, directory: &str) -> Result<PathBuf> {
+        let resolved = canonicalize_lexically(&self.working_directory.join(directory));
+
+        let roots = match &self.sandbox_policy {
+            SandboxPolicy::DangerFullAccess => return Ok(resolved),
+            SandboxPolicy::ReadOnly => vec![self.working_directory.clone()],
+            SandboxPolicy::WorkspaceWrite { writable_roots, .. } => {
+                std::iter::once(self.working_directory.clone())
+                    .chain(writable_roots.iter().cloned())
+                    .collect()
+            }
+        };
+        if roots
+            .iter()
+            .any(|root| resolved.starts_with(canonicalize_lexically(root)))
+        {
+            return Ok(resolved);
+        }
+
+        Err(AgentError::Config {
+            message: format!(
+                "Working directory \"{}\" of tool {} escapes the sandbox roots",
+                directory, tool_name
+            ),
+        })
+    }
+
+    /// Resolve a path accessed by a file tool, following symlinks, and check
+    /// it against the tool's path globs, which file read and file write
+    /// tools have.
+    ///
+    /// Relative paths and globs are resolved against the tool's working
+    /// directory. Paths matching a denied glob, or no allowed glob when any
+    /// are set, are rejected.
+    pub fn check_tool_path(&self, tool_name: &str, path: &Path) -> Result<PathBuf> {
+        let base = canonicalize_lexically(&self.tool_working_directory(tool_name)?);
+        let resolved = canonicalize_lexically(&base.join(path));
+        let Some((allowed, denied)) = self
+            .tools
+            .iter()
+            .find(|tool| tool.name() == tool_name)
+            .and_then(ToolConfig::path_globs)
+        else {
+            return Ok(resolved);
+        };
+
+        let matches = |glob: &String| glob_matches(glob, &base, &resolved);
+        if let Some(glob) = denied.iter().find(|glob| matches(glob)) {
+            return Err(AgentError::Tool {
+                message: format!(
+                    "Path {} is denied to tool {} by {}",
+                    resolved.display(),
+                    tool_name,
+                    glob
+                ),
+            });
+        }
+        if !allowed.is_empty() && !allowed.iter().any(matches) {
+            return Err(AgentError::Tool {
+                message: format!(
+                    "Path {} is outside the paths allowed to tool {}",
+                    resolved.display(),
+                    tool_name
+                ),
+            });
+        }
+        Ok(resolved)
+    }
+
+    /// Whether a file read or file write tool has path globs, which run the
+    /// bash tool on the host to check the paths of its commands.
+    pub(crate) fn checks_tool_paths(&self) -> bool {
+        self.tools.iter().any(ToolConfig::has_path_globs)
+    }
+
+    /// Approval policy Codex runs under.
+    ///
+    /// Under the path globs of a file write tool, Codex asks approval for
+    /// every patch so that the patches are checked before they are written;
+    /// the agent then decides the ones the configured policy would not have
+    /// asked for.
+    pub(crate) fn codex_approval_policy(&self) -> AskForApproval {
+        let checks_patches = self
+            .tools
+            .iter()
+            .any(|tool| tool.name() == "file_write" && tool.has_path_globs());
+        if checks_patches {
+            AskForApproval::UnlessTrusted
+        } else {
+            self.approval_policy
+        }
+    }
+
+    /// Whether the sandbox policy lets commands write `path`: within the
+    /// working directory, a writable root, or the temporary directories it
+    /// does not exclude under `WorkspaceWrite`, and anywhere under
+    /// `DangerFullAccess`.
+    pub(crate) fn sandbox_allows_write(&self, path: &Path) -> bool {
+        let path = canonicalize_lexically(&self.working_directory.join(path));
+        match &self.sandbox_policy {
+            SandboxPolicy::DangerFullAccess => true,
+            SandboxPolicy::ReadOnly => false,
+            SandboxPolicy::WorkspaceWrite {
+                writable_roots,
+                exclude_tmpdir_env_var,
+                exclude_slash_tmp,
+                ..
+            } => {
+                let tmpdir = env::var_os("TMPDIR")
+                    .filter(|_| !exclude_tmpdir_env_var)
+                    .map(PathBuf::from);
+                let slash_tmp = (!exclude_slash_tmp).then(|| PathBuf::from("/tmp"));
+                std::iter::once(self.working_directory.clone())
+                    .chain(writable_roots.iter().cloned())
+                    .chain(tmpdir)
+                    .chain(slash_tmp)
+                    .any(|root| path.starts_with(canonicalize_lexically(&root)))
+            }
+        }
+    }
+
+    /// Get the host environment variables passed through to tools.
+    pub fn env_passthrough(&self) -> &[String] {
+        &self.env_passthrough
+    }
+
+    /// Resolve the environment every tool execution runs with.
+    ///
+    /// Starts from a minimal set of host variables (`PATH`, `HOME`, ...) plus
+    /// the passthrough allowlist, then applies the agent environment and any
+    /// per-tool environment on top, with `${VAR}` references interpolated from
+    /// the host. Only allowlisted host variables can be referenced; the rest of
+    /// the host environment is never exposed.
+    pub fn tool_environment(&self) -> Result<HashMap<String, String>> {
+        let mut resolved: HashMap<String, String> = CORE_ENV_VARS
+            .iter()
+            .copied()
+            .chain(self.env_passthrough.iter().map(String::as_str))
+            .filter_map(|key| env::var(key).ok().map(|value| (key.to_string(), value)))
+            .collect();
+
+        let tool_environments = self.tools.iter().filter_map(|tool| match tool {
+            ToolConfig::Bash { environment, .. } => Some(environment),
+            _ => None,
+        });
+        for environment in std::iter::once(&self.environment).chain(tool_environments) {
+            for (key, value) in environment {
+                resolved.insert(key.clone(), interpolate(value, &self.env_passthrough)?);
+            }
+        }
+
+        Ok(resolved)
+    }
+
     /// Get additional configuration.
     pub fn additional_config(&self) -> &HashMap<String, serde_json::Value> {
         &self.additional_config
     }
+
+    /// Check if workspace change summaries are enabled.
+    pub fn workspace_summary(&self) -> bool {
+        self.workspace_summary
+    }
+
+    /// Check if streaming deltas are suppressed.
+    pub fn coalesce_deltas(&self) -> bool {
+        self.coalesce_deltas
+    }
+
+    /// Get the maximum number of delta messages emitted per second.
+    pub fn debounce_deltas(&self) -> Option<u32> {
+        self.debounce_deltas
+    }
+
+    /// Get the auto-commit configuration.
+    pub fn auto_commit(&self) -> Option<&AutoCommitConfig> {
+        self.auto_commit.as_ref()
+    }
+
+    /// Check if git checkpoints before each turn are enabled.
+    pub fn git_checkpoints(&self) -> bool {
+        self.git_checkpoints
+    }
+
+    /// Get the verification step configuration.
+    pub fn verify(&self) -> Option<&VerifyConfig> {
+        self.verify.as_ref()
+    }
+
+    /// Get the retry policy for transient model errors, if enabled.
+    pub fn retry_policy(&self) -> Option<&RetryPolicy> {
+        self.retry_policy.as_ref()
+    }
+
+    /// Get the heartbeat interval.
+    pub fn heartbeat(&self) -> Option<Duration> {
+        self.heartbeat
+    }
+
+    /// Get the operational summary interval.
+    pub fn ops_summary(&self) -> Option<Duration> {
+        self.ops_summary
+    }
+
+    /// Get the output processors.
+    pub fn output_processors(&self) -> &[Arc<dyn OutputProcessor>] {
+        &self.output_processors
+    }
+
+    /// Get the tool middleware.
+    pub fn tool_middleware(&self) -> &[Arc<dyn ToolMiddleware>] {
+        &self.tool_middleware
+    }
+
+    /// Get the event converter, if any.
+    pub fn event_converter(&self) -> Option<&Arc<dyn EventConverter>> {
+        self.event_converter.as_ref()
+    }
+
+    /// Get the host tool concurrency limits.
+    pub fn tool_concurrency(&self) -> &ToolConcurrency {
+        &self.tool_concurrency
+    }
+
+    /// Get the lexicon filter, if any.
+    pub fn lexicon_filter(&self) -> Option<&LexiconFilter> {
+        self.lexicon_filter.as_ref()
+    }
+
+    /// Get the telemetry sink, if telemetry is enabled.
+    pub fn telemetry(&self) -> Option<&dyn TelemetrySink> {
+        self.telemetry.as_deref()
+    }
+
+    /// Get the working directory disk quota.
+    pub fn disk_quota(&self) -> Option<&DiskQuota> {
+        self.disk_quota.as_ref()
+    }
+
+    /// Get the rate limits by provider name.
+    pub fn rate_limits(&self) -> &HashMap<String, RateLimit> {
+        &self.rate_limits
+    }
+
+    /// Provider name, credential, and rate limit of the agent's model, if it
+    /// is limited.
+    ///
+    /// The credential is the API key, the key in the provider's `env_key`
+    /// variable, or else the login of the codex home.
+    pub(crate) fn model_rate_limit(&self) -> Option<(String, String, &RateLimit)> {
+        let provider = self.resolve_model(&self.model).0;
+        let name = match &provider {
+            Some(provider) => provider.name.clone(),
+            None => OPENAI_PROVIDER.to_string(),
+        };
+        let limit = self.rate_limits.get(&name)?;
+        let credential = self
+            .api_key
+            .clone()
+            .or_else(|| {
+                let variable = provider?.env_key?;
+                std::env::var(variable).ok()
+            })
+            .unwrap_or_else(|| format!("{:?} login", self.auth_method));
+        Some((name, credential, limit))
+    }
+
+    /// Get the backend commands spawned by agent-core run in.
+    pub fn sandbox_backend(&self) -> &SandboxBackend {
+        &self.sandbox_backend
+    }
+
+    /// Get the number of retries for invalid structured output.
+    pub fn json_retries(&self) -> u32 {
+        self.json_retries
+    }
+
+    /// Get the JSON Schema final responses must conform to, if any.
+    pub fn response_schema(&self) -> Option<&serde_json::Value> {
+        self.response_schema.as_ref()
+    }
+
+    /// Get the follow-up suggestion settings, if enabled.
+    pub fn suggestions(&self) -> Option<&SuggestionsConfig> {
+        self.suggestions.as_ref()
+    }
+
+    /// Get the response cache, if caching is enabled.
+    pub fn response_cache(&self) -> Option<&ResponseCache> {
+        self.response_cache.as_ref()
+    }
+
+    /// Get the file conversations are recorded to, if recording is enabled.
+    pub fn recording(&self) -> Option<&Path> {
+        self.recording.as_deref()
+    }
+
+    /// Get the relay Codex launches to call the host tools, if not the
+    /// `agent-core-tool-bridge` binary on the `PATH` or next to the
+    /// executable.
+    pub fn tool_bridge(&self) -> Option<&Path> {
+        self.tool_bridge.as_deref()
+    }
 }
 
 /// Builder for AgentConfig with a fluent interface.
@@ -115,19 +1014,53 @@ impl AgentConfig {
 pub struct AgentConfigBuilder {
     model: Option<String>,
     api_key: Option<String>,
+    auth_method: AuthMethod,
+    model_provider: Option<ModelProvider>,
+    provider_routes: Vec<ProviderRoute>,
     system_prompt: Option<String>,
     sandbox_policy: Option<SandboxPolicy>,
     approval_policy: Option<AskForApproval>,
     max_turns: Option<u32>,
+    reasoning_effort: Option<ReasoningEffort>,
+    reasoning_summary: Option<ReasoningSummary>,
+    show_raw_reasoning: bool,
     working_directory: Option<PathBuf>,
     tools: Vec<ToolConfig>,
     mcp_servers: Vec<McpServerConfig>,
     environment: HashMap<String, String>,
+    env_passthrough: Vec<String>,
     additional_config: HashMap<String, serde_json::Value>,
+    workspace_summary: bool,
+    coalesce_deltas: bool,
+    debounce_deltas: Option<u32>,
+    auto_commit: Option<AutoCommitConfig>,
+    git_checkpoints: bool,
+    verify: Option<VerifyConfig>,
+    retry_policy: Option<RetryPolicy>,
+    heartbeat: Option<Duration>,
+    ops_summary: Option<Duration>,
+    telemetry: Option<Arc<dyn TelemetrySink>>,
+    output_processors: Vec<Arc<dyn OutputProcessor>>,
+    tool_middleware: Vec<Arc<dyn ToolMiddleware>>,
+    event_converter: Option<Arc<dyn EventConverter>>,
+    tool_concurrency: ToolConcurrency,
+    lexicon_filter: Option<LexiconFilter>,
+    disk_quota: Option<DiskQuota>,
+    rate_limits: HashMap<String, RateLimit>,
+    sandbox_backend: SandboxBackend,
+    json_retries: Option<u32>,
+    response_schema: Option<serde_json::Value>,
+    suggestions: Option<SuggestionsConfig>,
+    response_cache: Option<ResponseCache>,
+    recording: Option<PathBuf>,
+    tool_bridge: Option<PathBuf>,
 }
 
 impl AgentConfigBuilder {
     /// Set the model identifier.
+    ///
+    /// Claude and Gemini models select their provider unless one is set with
+    /// [`model_provider`](Self::model_provider).
     pub fn model<S: Into<String>>(mut self, model: S) -> Self {
         self.model = Some(model.into());
         self
@@ -148,6 +1081,55 @@ impl AgentConfigBuilder {
         Ok(self)
     }
 
+    /// Choose the credentials stored by the Codex CLI to use when no API key
+    /// is set, e.g. [`AuthMethod::ChatGpt`] for a ChatGPT login.
+    pub fn auth_method(mut self, method: AuthMethod) -> Self {
+        self.auth_method = method;
+        self
+    }
+
+    /// Send model requests to a custom provider instead of OpenAI.
+    pub fn model_provider(mut self, provider: ModelProvider) -> Self {
+        self.model_provider = Some(provider);
+        self
+    }
+
+    /// Send requests for models whose name starts with `prefix` to
+    /// `provider`, e.g. `ollama/` to [`ModelProvider::ollama`].
+    ///
+    /// Routes take precedence over [`model_provider`](Self::model_provider),
+    /// so one configuration serves models of several providers chosen per
+    /// agent or per query.
+    pub fn route_provider<S: Into<String>>(mut self, prefix: S, provider: ModelProvider) -> Self {
+        self.provider_routes
+            .push(ProviderRoute::new(prefix, provider));
+        self
+    }
+
+    /// Send model requests to an OpenAI-compatible endpoint at `base_url`,
+    /// e.g. `http://localhost:8000/v1` for a vLLM server.
+    pub fn provider_base_url<S: Into<String>>(mut self, base_url: S) -> Self {
+        self.model_provider.get_or_insert_default().base_url = base_url.into();
+        self
+    }
+
+    /// Set the API style of the model provider.
+    pub fn wire_api(mut self, wire_api: WireApi) -> Self {
+        self.model_provider.get_or_insert_default().wire_api = wire_api;
+        self
+    }
+
+    /// Add a header sent with every model request.
+    pub fn provider_header<S1, S2>(mut self, name: S1, value: S2) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        let provider = self.model_provider.get_or_insert_default();
+        provider.headers.insert(name.into(), value.into());
+        self
+    }
+
     /// Set the system prompt.
     pub fn system_prompt<S: Into<String>>(mut self, prompt: S) -> Self {
         self.system_prompt = Some(prompt.into());
@@ -172,6 +1154,28 @@ impl AgentConfigBuilder {
         self
     }
 
+    /// Set how much reasoning models think before answering; lower effort
+    /// answers faster. Queries can override it with
+    /// `QueryOptions::reasoning_effort`.
+    pub fn reasoning_effort(mut self, effort: ReasoningEffort) -> Self {
+        self.reasoning_effort = Some(effort);
+        self
+    }
+
+    /// Set how detailed the reasoning summaries are, or disable them.
+    pub fn reasoning_summary(mut self, summary: ReasoningSummary) -> Self {
+        self.reasoning_summary = Some(summary);
+        self
+    }
+
+    /// Emit the model's raw reasoning as `OutputData::Reasoning` instead of
+    /// summaries, for models and providers that expose it (e.g. open-weight
+    /// models).
+    pub fn show_raw_reasoning(mut self, enable: bool) -> Self {
+        self.show_raw_reasoning = enable;
+        self
+    }
+
     /// Set the working directory.
     pub fn working_directory<P: Into<PathBuf>>(mut self, path: P) -> Self {
         self.working_directory = Some(path.into());
@@ -231,6 +1235,26 @@ impl AgentConfigBuilder {
         self
     }
 
+    /// Pass a host environment variable through to tool executions.
+    ///
+    /// Passed-through variables are also the only ones `${VAR}` templates in
+    /// environment values may reference.
+    pub fn env_passthrough<S: Into<String>>(mut self, key: S) -> Self {
+        self.env_passthrough.push(key.into());
+        self
+    }
+
+    /// Pass multiple host environment variables through to tool executions.
+    pub fn env_passthroughs<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.env_passthrough
+            .extend(keys.into_iter().map(Into::into));
+        self
+    }
+
     /// Set additional configuration value.
     pub fn config<K, V>(mut self, key: K, value: V) -> Result<Self>
     where
@@ -242,40 +1266,480 @@ impl AgentConfigBuilder {
         Ok(self)
     }
 
+    /// Emit an `OutputData::WorkspaceDelta` summary at the end of each turn.
+    pub fn workspace_summary(mut self, enable: bool) -> Self {
+        self.workspace_summary = enable;
+        self
+    }
+
+    /// Suppress `PrimaryDelta` and `ReasoningDelta` outputs, emitting only the
+    /// complete `Primary` and `Reasoning` messages Codex sends at the end of
+    /// each message and reasoning section.
+    pub fn coalesce_deltas(mut self, enable: bool) -> Self {
+        self.coalesce_deltas = enable;
+        self
+    }
+
+    /// Emit at most `max_per_second` `PrimaryDelta` and `ReasoningDelta`
+    /// messages per second, concatenating the content of deltas that arrive
+    /// in between. Pending content is flushed before any other output, so
+    /// ordering is preserved.
+    pub fn debounce_deltas(mut self, max_per_second: u32) -> Self {
+        self.debounce_deltas = Some(max_per_second.max(1));
+        self
+    }
+
+    /// Commit workspace changes to a dedicated branch at the end of each turn.
+    pub fn auto_commit(mut self, config: AutoCommitConfig) -> Self {
+        self.auto_commit = Some(config);
+        self
+    }
+
+    /// Checkpoint the workspace on the `agent-core/checkpoints` branch before
+    /// each turn, recording the commit on the turn's record.
+    ///
+    /// `git checkout <sha> -- .` then restores the files as they were before
+    /// the turn. A new commit is only created when the workspace changed since
+    /// the last checkpoint.
+    pub fn git_checkpoints(mut self, enable: bool) -> Self {
+        self.git_checkpoints = enable;
+        self
+    }
+
+    /// Run a verification command after the agent edits files, feeding
+    /// failures back to the model for repair.
+    pub fn verify(mut self, config: VerifyConfig) -> Self {
+        self.verify = Some(config);
+        self
+    }
+
+    /// Retry turns failed by rate limits, server errors, and dropped
+    /// streams with exponential backoff, emitting `OutputData::Retrying`
+    /// before each attempt.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Emit `OutputData::Heartbeat` at the given interval while the agent is
+    /// idle. The execution loop is otherwise purely event-driven.
+    pub fn heartbeat(mut self, interval: Duration) -> Self {
+        self.heartbeat = Some(interval);
+        self
+    }
+
+    /// Emit an `OutputData::OpsSummary` digest (turns, tokens, errors, top
+    /// tools, current plan step) at the given interval, also logged via
+    /// tracing.
+    pub fn ops_summary(mut self, interval: Duration) -> Self {
+        self.ops_summary = Some(interval);
+        self
+    }
+
+    /// Report anonymized usage events (turn counts, error categories,
+    /// feature usage) to the given sink. Telemetry is off unless set.
+    pub fn telemetry(mut self, sink: Arc<dyn TelemetrySink>) -> Self {
+        self.telemetry = Some(sink);
+        self
+    }
+
+    /// Append a processor to the chain applied to final responses, e.g.
+    /// `EmojiPolicy::Strip`, `LinkRewriter`, or `HeadingLevels`.
+    pub fn output_processor<P: OutputProcessor + 'static>(mut self, processor: P) -> Self {
+        self.output_processors.push(Arc::new(processor));
+        self
+    }
+
+    /// Append middleware run around every tool call, including commands and
+    /// patches run by Codex, able to reject the call or rewrite its result;
+    /// see [`crate::middleware`].
+    pub fn tool_middleware<M: ToolMiddleware + 'static>(mut self, middleware: M) -> Self {
+        self.tool_middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Rewrite or drop the outputs converted from Codex events, e.g. with
+    /// `ReasoningAsPrimary` or `ToolRenamer`.
+    pub fn event_converter<C: EventConverter + 'static>(mut self, converter: C) -> Self {
+        self.event_converter = Some(Arc::new(converter));
+        self
+    }
+
+    /// Cap how many host tool calls, like custom tools and search, run at
+    /// once, globally and per tool; excess calls queue until a running call
+    /// finishes. Shell commands are run by Codex and not limited.
+    pub fn tool_concurrency(mut self, concurrency: ToolConcurrency) -> Self {
+        self.tool_concurrency = concurrency;
+        self
+    }
+
+    /// Mask, replace, or block listed terms in `Primary` and `PrimaryDelta`
+    /// content before emission. Streamed fragments are held back while they
+    /// may end in a listed term; the final `Primary` message always carries
+    /// the complete filtered response.
+    pub fn lexicon_filter(mut self, filter: LexiconFilter) -> Self {
+        self.lexicon_filter = Some(filter);
+        self
+    }
+
+    /// Enforce a byte quota on the working directory.
+    pub fn disk_quota(mut self, quota: DiskQuota) -> Self {
+        self.disk_quota = Some(quota);
+        self
+    }
+
+    /// Limit the requests and tokens per minute sent to a provider, by name
+    /// (`"openai"` for the default provider).
+    ///
+    /// The limit is shared by every agent of the process using the provider,
+    /// e.g. the agents of a pool; the first agent using it sets its limits.
+    pub fn rate_limit<S: Into<String>>(mut self, provider: S, limit: RateLimit) -> Self {
+        self.rate_limits.insert(provider.into(), limit);
+        self
+    }
+
+    /// Run commands spawned by agent-core, such as verification, natively
+    /// (the default) or in a container. Commands executed by Codex are not
+    /// affected.
+    pub fn sandbox_backend(mut self, backend: SandboxBackend) -> Self {
+        self.sandbox_backend = backend;
+        self
+    }
+
+    /// Set how many times `query_json` asks the model to fix invalid JSON.
+    pub fn json_retries(mut self, retries: u32) -> Self {
+        self.json_retries = Some(retries);
+        self
+    }
+
+    /// Constrain every final response to JSON conforming to `schema`.
+    ///
+    /// Each turn's input asks the model for such JSON, since the Codex
+    /// protocol has no structured output setting, and the final response is
+    /// validated against the schema. A response that does not conform is
+    /// reported with an `OutputError::ResponseSchemaMismatch` error before the
+    /// turn completes.
+    pub fn response_schema(mut self, schema: serde_json::Value) -> Self {
+        self.response_schema = Some(schema);
+        self
+    }
+
+    /// Suggest follow-up prompts after each completed turn, generated in the
+    /// background and emitted as `OutputData::Suggestions` once ready.
+    /// Suggestions not ready when the next input starts are dropped.
+    pub fn suggestions(mut self, suggestions: SuggestionsConfig) -> Self {
+        self.suggestions = Some(suggestions);
+        self
+    }
+
+    /// Answer repeated single-turn queries from a cache, e.g.
+    /// `ResponseCache::memory(1000)`, instead of asking the model again.
+    pub fn response_cache(mut self, cache: ResponseCache) -> Self {
+        self.response_cache = Some(cache);
+        self
+    }
+
+    /// Record the conversation's Codex events and submissions to a JSONL
+    /// file, which `Replay::load` plays back for regression tests and bug
+    /// reproductions. The file is replaced when the conversation starts.
+    pub fn record_to<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.recording = Some(path.into());
+        self
+    }
+
+    /// Launch another relay than the `agent-core-tool-bridge` binary on the
+    /// `PATH` or next to the executable for Codex to call the host tools
+    /// through; see [`crate::tool_server`]. Building fails if host tools
+    /// are configured and the relay does not exist.
+    pub fn tool_bridge<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.tool_bridge = Some(path.into());
+        self
+    }
+
     /// Build the configuration.
     pub fn build(self) -> Result<AgentConfig> {
-        let model = self.model.unwrap_or_else(|| "gpt-4".to_string());
+        let model = self.model.unwrap_or_else(default_model);
         let working_directory = self
             .working_directory
-            .unwrap_or_else(|| env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
+            .unwrap_or_else(default_working_directory);
 
         // Use provided policies or sensible defaults
-        let sandbox_policy = self
-            .sandbox_policy
-            .unwrap_or(SandboxPolicy::WorkspaceWrite {
-                writable_roots: Vec::new(),
-                network_access: false,
-                exclude_tmpdir_env_var: false,
-                exclude_slash_tmp: false,
-            });
+        let sandbox_policy = self.sandbox_policy.unwrap_or_else(default_sandbox_policy);
         let approval_policy = self.approval_policy.unwrap_or(AskForApproval::Never);
 
-        Ok(AgentConfig {
+        let config = AgentConfig {
             model,
             api_key: self.api_key,
+            auth_method: self.auth_method,
+            model_provider: self.model_provider,
+            provider_routes: self.provider_routes,
             system_prompt: self.system_prompt,
             sandbox_policy,
             approval_policy,
             max_turns: self.max_turns,
+            reasoning_effort: self.reasoning_effort,
+            reasoning_summary: self.reasoning_summary,
+            show_raw_reasoning: self.show_raw_reasoning,
             working_directory,
             tools: self.tools,
             mcp_servers: self.mcp_servers,
             environment: self.environment,
+            env_passthrough: self.env_passthrough,
             additional_config: self.additional_config,
-        })
+            workspace_summary: self.workspace_summary,
+            coalesce_deltas: self.coalesce_deltas,
+            debounce_deltas: self.debounce_deltas,
+            auto_commit: self.auto_commit,
+            git_checkpoints: self.git_checkpoints,
+            verify: self.verify,
+            retry_policy: self.retry_policy,
+            heartbeat: self.heartbeat,
+            ops_summary: self.ops_summary,
+            telemetry: self.telemetry,
+            output_processors: self.output_processors,
+            tool_middleware: self.tool_middleware,
+            event_converter: self.event_converter,
+            tool_concurrency: self.tool_concurrency,
+            lexicon_filter: self.lexicon_filter,
+            disk_quota: self.disk_quota,
+            rate_limits: self.rate_limits,
+            sandbox_backend: self.sandbox_backend,
+            json_retries: self.json_retries.unwrap_or(DEFAULT_JSON_RETRIES),
+            response_schema: self.response_schema,
+            suggestions: self.suggestions,
+            response_cache: self.response_cache,
+            recording: self.recording,
+            tool_bridge: self.tool_bridge,
+        };
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// Settings changed on a running agent with `AgentHandle::update_config`.
+///
+/// Only settings that are safe to change between turns of the same
+/// conversation can be updated; unset fields keep their current value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigPatch {
+    /// System prompt replacing the current one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+
+    /// Approval policy for command execution
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approval_policy: Option<AskForApproval>,
+
+    /// Tools added, replacing any tool with the same name
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub enable_tools: Vec<ToolConfig>,
+
+    /// Names of tools removed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub disable_tools: Vec<String>,
+}
+
+impl ConfigPatch {
+    /// Create an update changing nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the system prompt.
+    pub fn system_prompt<S: Into<String>>(mut self, prompt: S) -> Self {
+        self.system_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Change the approval policy.
+    pub fn approval_policy(mut self, policy: AskForApproval) -> Self {
+        self.approval_policy = Some(policy);
+        self
+    }
+
+    /// Add a tool, replacing any tool with the same name.
+    pub fn enable_tool(mut self, tool: ToolConfig) -> Self {
+        self.enable_tools.push(tool);
+        self
+    }
+
+    /// Remove a tool by name.
+    pub fn disable_tool<S: Into<String>>(mut self, name: S) -> Self {
+        self.disable_tools.push(name.into());
+        self
+    }
+}
+
+/// Problem found by [`AgentConfig::validate`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigIssue {
+    /// Path of the offending field, e.g. `tools[2].timeout`
+    pub path: String,
+
+    /// What is wrong with it
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Whether the URL uses the http or https scheme.
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Whether the model belongs to a family the Codex backend serves.
+fn is_known_model(model: &str) -> bool {
+    KNOWN_MODEL_PREFIXES
+        .iter()
+        .any(|prefix| model.starts_with(prefix))
+}
+
+impl Default for AgentConfig {
+    /// Configuration with the builder's defaults and no tools.
+    fn default() -> Self {
+        AgentConfig {
+            model: default_model(),
+            api_key: None,
+            auth_method: AuthMethod::default(),
+            model_provider: None,
+            provider_routes: Vec::new(),
+            system_prompt: None,
+            sandbox_policy: default_sandbox_policy(),
+            approval_policy: AskForApproval::Never,
+            max_turns: None,
+            reasoning_effort: None,
+            reasoning_summary: None,
+            show_raw_reasoning: false,
+            working_directory: default_working_directory(),
+            tools: Vec::new(),
+            mcp_servers: Vec::new(),
+            environment: HashMap::new(),
+            env_passthrough: Vec::new(),
+            additional_config: HashMap::new(),
+            workspace_summary: false,
+            coalesce_deltas: false,
+            debounce_deltas: None,
+            auto_commit: None,
+            git_checkpoints: false,
+            verify: None,
+            retry_policy: None,
+            heartbeat: None,
+            ops_summary: None,
+            telemetry: None,
+            output_processors: Vec::new(),
+            tool_middleware: Vec::new(),
+            event_converter: None,
+            tool_concurrency: ToolConcurrency::default(),
+            lexicon_filter: None,
+            disk_quota: None,
+            rate_limits: HashMap::new(),
+            sandbox_backend: SandboxBackend::default(),
+            json_retries: DEFAULT_JSON_RETRIES,
+            response_schema: None,
+            suggestions: None,
+            response_cache: None,
+            recording: None,
+            tool_bridge: None,
+        }
+    }
+}
+
+fn default_model() -> String {
+    "gpt-4".to_string()
+}
+
+fn default_working_directory() -> PathBuf {
+    env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
+}
+
+fn default_sandbox_policy() -> SandboxPolicy {
+    SandboxPolicy::WorkspaceWrite {
+        writable_roots: Vec::new(),
+        network_access: false,
+        exclude_tmpdir_env_var: false,
+        exclude_slash_tmp: false,
     }
 }
 
+/// Resolve `.` and `..` components, then follow symlinks for the longest
+/// existing prefix so links cannot point out of a root.
+fn canonicalize_lexically(path: &Path) -> PathBuf {
+    let absolute = match env::current_dir() {
+        Ok(cwd) if path.is_relative() => cwd.join(path),
+        _ => path.to_path_buf(),
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+
+    let mut existing = normalized.as_path();
+    let mut missing = Vec::new();
+    while !existing.exists() {
+        let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+            return normalized;
+        };
+        missing.push(name.to_os_string());
+        existing = parent;
+    }
+
+    match std::fs::canonicalize(existing) {
+        Ok(mut resolved) => {
+            resolved.extend(missing.iter().rev());
+            resolved
+        }
+        Err(_) => normalized,
+    }
+}
+
+/// Interpolate `${VAR}` references from the host environment.
+///
+/// Only variables in the passthrough allowlist may be referenced.
+fn interpolate(value: &str, passthrough: &[String]) -> Result<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let Some(end) = rest[start + 2..].find('}') else {
+            return Err(AgentError::Config {
+                message: format!("Unterminated variable reference in \"{}\"", value),
+            });
+        };
+
+        let name = &rest[start + 2..start + 2 + end];
+        if !passthrough.iter().any(|key| key == name) {
+            return Err(AgentError::Config {
+                message: format!(
+                    "Environment variable {} is not in the passthrough allowlist",
+                    name
+                ),
+            });
+        }
+        let host_value = env::var(name).map_err(|_| AgentError::Config {
+            message: format!("Environment variable {} not found", name),
+        })?;
+        result.push_str(&host_value);
+        rest = &rest[start + 3 + end..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
 /// Convenience methods for common sandbox policies
 impl AgentConfigBuilder {
     /// Set sandbox policy to allow workspace write operations
@@ -288,6 +1752,16 @@ impl AgentConfigBuilder {
         })
     }
 
+    /// Set sandbox policy to allow workspace write operations and network access
+    pub fn sandbox_workspace_write_with_network(self) -> Self {
+        self.sandbox_policy(SandboxPolicy::WorkspaceWrite {
+            writable_roots: Vec::new(),
+            network_access: true,
+            exclude_tmpdir_env_var: false,
+            exclude_slash_tmp: false,
+        })
+    }
+
     /// Set sandbox policy to read-only mode
     pub fn sandbox_read_only(self) -> Self {
         self.sandbox_policy(SandboxPolicy::ReadOnly)
@@ -1,9 +1,12 @@
 //! Agent controller for managing agent execution state.
 
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use tokio::sync::{Mutex, oneshot};
+use tokio::sync::{Mutex, broadcast, oneshot, watch};
 
+use crate::approval::ApprovalDecision;
+use crate::config::{AgentConfig, ConfigPatch};
 use crate::error::{AgentError, Result};
 
 /// Controller for managing agent execution state.
@@ -28,8 +31,20 @@ struct AgentState {
     /// Whether the agent should stop execution
     should_stop: AtomicBool,
 
+    /// Whether to pause automatically before each tool execution
+    step_mode: AtomicBool,
+
+    /// Tool names that pause execution right before they run
+    breakpoints: Mutex<HashSet<String>>,
+
     /// Channel for sending control commands
     control_sender: Mutex<Option<tokio::sync::mpsc::UnboundedSender<ControlCommand>>>,
+
+    /// Broadcasts state snapshots to subscribers
+    state_watch: watch::Sender<AgentExecutionState>,
+
+    /// Broadcasts acknowledgements of handled control commands
+    acks: broadcast::Sender<ControlAck>,
 }
 
 /// Internal execution state of the agent.
@@ -42,7 +57,7 @@ pub(crate) enum ExecutionState {
     Running,
 
     /// Agent is paused but can be resumed
-    Paused,
+    Paused(PauseReason),
 
     /// Agent has been stopped
     Stopped,
@@ -51,6 +66,56 @@ pub(crate) enum ExecutionState {
     Error(String),
 }
 
+/// Why the agent is paused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PauseReason {
+    /// Paused through [`AgentController::pause`]
+    Requested,
+
+    /// Paused before a tool in step-through mode
+    Step { tool_name: String },
+
+    /// Paused by a breakpoint on the tool
+    Breakpoint { tool_name: String },
+
+    /// Paused at an autonomous run's check-in, awaiting approval
+    CheckIn { number: u32 },
+
+    /// Paused on a previewed patch, awaiting confirmation to apply it
+    PatchPreview,
+}
+
+impl std::fmt::Display for PauseReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PauseReason::Requested => write!(f, "requested"),
+            PauseReason::Step { tool_name } => write!(f, "step before {}", tool_name),
+            PauseReason::Breakpoint { tool_name } => write!(f, "breakpoint on {}", tool_name),
+            PauseReason::CheckIn { number } => write!(f, "check-in {}", number),
+            PauseReason::PatchPreview => write!(f, "patch preview"),
+        }
+    }
+}
+
+/// Acknowledgement that a control command took effect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlAck {
+    /// The agent paused
+    Paused,
+
+    /// The agent resumed
+    Resumed,
+
+    /// The agent stopped
+    Stopped,
+
+    /// The in-flight turn was cancelled
+    TurnCancelled,
+
+    /// The agent stepped past a paused tool
+    Stepped,
+}
+
 /// Control commands that can be sent to the agent.
 #[derive(Debug)]
 pub(crate) enum ControlCommand {
@@ -62,39 +127,85 @@ pub(crate) enum ControlCommand {
 
     /// Stop the agent permanently
     Stop(oneshot::Sender<Result<()>>),
+
+    /// Interrupt the in-flight turn, keeping the agent running
+    CancelTurn(oneshot::Sender<Result<()>>),
+
+    /// Release the tool execution the agent is paused before
+    Step(oneshot::Sender<Result<()>>),
+
+    /// Answer an approval request of the running turn
+    Approve {
+        approval_id: String,
+        decision: ApprovalDecision,
+        response_tx: oneshot::Sender<Result<()>>,
+    },
+
+    /// Change settings of the running agent, applied between turns
+    UpdateConfig {
+        patch: ConfigPatch,
+        response_tx: oneshot::Sender<Result<AgentConfig>>,
+    },
 }
 
 impl AgentController {
     /// Create a new agent controller.
-    pub(crate) fn new() -> (Self, tokio::sync::mpsc::UnboundedReceiver<ControlCommand>) {
-        let (control_tx, control_rx) = tokio::sync::mpsc::unbounded_channel();
-
+    ///
+    /// The controller is inactive until [`AgentController::connect`] hands a
+    /// control channel to an execution.
+    pub(crate) fn new() -> Self {
         let state = Arc::new(AgentState {
             execution_state: Mutex::new(ExecutionState::Idle),
             turn_count: AtomicU64::new(0),
             is_paused: AtomicBool::new(false),
             should_stop: AtomicBool::new(false),
-            control_sender: Mutex::new(Some(control_tx)),
+            step_mode: AtomicBool::new(false),
+            breakpoints: Mutex::new(HashSet::new()),
+            control_sender: Mutex::new(None),
+            state_watch: watch::Sender::new(AgentExecutionState {
+                execution_state: PublicExecutionState::Idle,
+                turn_count: 0,
+                is_paused: false,
+                should_stop: false,
+            }),
+            acks: broadcast::channel(16).0,
         });
 
-        let controller = AgentController { state };
+        AgentController { state }
+    }
 
-        (controller, control_rx)
+    /// Attach the controller to a new execution.
+    ///
+    /// Installs a fresh control channel and clears the stop and pause flags so
+    /// the agent can run again after a previous execution finished or was
+    /// stopped. A still-running previous execution sees its control channel
+    /// close and shuts down.
+    pub(crate) async fn connect(&self) -> tokio::sync::mpsc::UnboundedReceiver<ControlCommand> {
+        let (control_tx, control_rx) = tokio::sync::mpsc::unbounded_channel();
+        *self.state.control_sender.lock().await = Some(control_tx);
+        self.state.should_stop.store(false, Ordering::Relaxed);
+        self.state.is_paused.store(false, Ordering::Relaxed);
+        control_rx
     }
 
     /// Get the current execution state.
     pub async fn state(&self) -> AgentExecutionState {
         let execution_state = self.state.execution_state.lock().await;
-        let turn_count = self.state.turn_count.load(Ordering::Relaxed);
-        let is_paused = self.state.is_paused.load(Ordering::Relaxed);
-        let should_stop = self.state.should_stop.load(Ordering::Relaxed);
+        self.snapshot(&execution_state)
+    }
 
-        AgentExecutionState {
-            execution_state: execution_state.clone().into(),
-            turn_count,
-            is_paused,
-            should_stop,
-        }
+    /// Subscribe to execution state changes.
+    ///
+    /// The receiver always holds the latest state and is notified on every
+    /// Running/Paused/Stopped/Error transition and turn count change, so UIs
+    /// can update reactively instead of polling [`AgentController::state`].
+    pub fn subscribe(&self) -> watch::Receiver<AgentExecutionState> {
+        self.state.state_watch.subscribe()
+    }
+
+    /// Subscribe to acknowledgements of handled control commands.
+    pub fn subscribe_acks(&self) -> broadcast::Receiver<ControlAck> {
+        self.state.acks.subscribe()
     }
 
     /// Get the current turn count.
@@ -112,7 +223,65 @@ impl AgentController {
         self.state.should_stop.load(Ordering::Relaxed)
     }
 
+    /// Check if step-through mode is enabled.
+    pub fn is_step_mode(&self) -> bool {
+        self.state.step_mode.load(Ordering::Relaxed)
+    }
+
+    /// Enable or disable step-through mode.
+    ///
+    /// In step-through mode the agent pauses right before each tool execution
+    /// and waits for [`AgentController::step`] (or `resume()`), so a run can be
+    /// single-stepped like a debugger.
+    ///
+    /// Commands and patches run by Codex (`exec_command` and `apply_patch`)
+    /// are paused on when Codex asks approval for them, which the configured
+    /// approval policy decides; the host still answers the request once
+    /// stepped past. Calls Codex runs without asking are not paused on.
+    pub fn set_step_mode(&self, enabled: bool) {
+        self.state.step_mode.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Pause execution right before the named tool runs.
+    ///
+    /// The agent reports `Paused { reason: Breakpoint }` and can be continued
+    /// with `resume()` or `step()`. Codex's commands and patches break as
+    /// `exec_command` and `apply_patch` when Codex asks approval for them,
+    /// which the configured approval policy decides; the host still answers
+    /// the request once the agent continues.
+    pub async fn break_on<S: Into<String>>(&self, tool_name: S) {
+        self.state.breakpoints.lock().await.insert(tool_name.into());
+    }
+
+    /// Remove a breakpoint, returning whether it was set.
+    pub async fn clear_breakpoint(&self, tool_name: &str) -> bool {
+        self.state.breakpoints.lock().await.remove(tool_name)
+    }
+
+    /// Remove all breakpoints.
+    pub async fn clear_breakpoints(&self) {
+        self.state.breakpoints.lock().await.clear();
+    }
+
+    /// Get the tool names with breakpoints.
+    pub async fn breakpoints(&self) -> Vec<String> {
+        self.state
+            .breakpoints
+            .lock()
+            .await
+            .iter()
+            .cloned()
+            .collect()
+    }
+
     /// Pause the agent execution.
+    ///
+    /// A paused agent does not poll: the execution loop stops taking queued
+    /// input and reading Codex's events, and sleeps until a control command
+    /// (resume, step, stop, or cancel turn) arrives. The event being handled
+    /// when the pause lands is finished first. Codex itself keeps running:
+    /// the model keeps streaming and commands already started keep running,
+    /// their events held until the agent resumes.
     pub async fn pause(&self) -> Result<()> {
         let (response_tx, response_rx) = oneshot::channel();
 
@@ -178,15 +347,134 @@ impl AgentController {
         }
     }
 
+    /// Interrupt the current turn without stopping the agent.
+    ///
+    /// The in-flight generation is aborted and the agent stays ready for the
+    /// next input message. Returns once the aborted turn has been drained; if
+    /// no turn is running this is a no-op.
+    pub async fn cancel_turn(&self) -> Result<()> {
+        let (response_tx, response_rx) = oneshot::channel();
+
+        let control_sender = self.state.control_sender.lock().await;
+        if let Some(sender) = control_sender.as_ref() {
+            sender
+                .send(ControlCommand::CancelTurn(response_tx))
+                .map_err(|_| AgentError::ChannelSend {
+                    message: "Failed to send cancel turn command".to_string(),
+                })?;
+            // Draining the turn can take a while; let resume and stop through
+            drop(control_sender);
+
+            response_rx.await.map_err(|_| AgentError::ChannelReceive {
+                message: "Failed to receive cancel turn response".to_string(),
+            })?
+        } else {
+            Err(AgentError::Execution {
+                message: "Agent controller is not active".to_string(),
+            })
+        }
+    }
+
+    /// Execute the tool the agent is paused before, then pause again at the
+    /// next tool while step-through mode stays enabled.
+    pub async fn step(&self) -> Result<()> {
+        let (response_tx, response_rx) = oneshot::channel();
+
+        let control_sender = self.state.control_sender.lock().await;
+        if let Some(sender) = control_sender.as_ref() {
+            sender
+                .send(ControlCommand::Step(response_tx))
+                .map_err(|_| AgentError::ChannelSend {
+                    message: "Failed to send step command".to_string(),
+                })?;
+
+            response_rx.await.map_err(|_| AgentError::ChannelReceive {
+                message: "Failed to receive step response".to_string(),
+            })?
+        } else {
+            Err(AgentError::Execution {
+                message: "Agent controller is not active".to_string(),
+            })
+        }
+    }
+
+    /// Answer an approval request emitted as `OutputData::ApprovalRequired`.
+    ///
+    /// Fails if no request with this id is pending in the running turn.
+    pub async fn approve(&self, approval_id: &str, decision: ApprovalDecision) -> Result<()> {
+        let (response_tx, response_rx) = oneshot::channel();
+
+        let control_sender = self.state.control_sender.lock().await;
+        if let Some(sender) = control_sender.as_ref() {
+            sender
+                .send(ControlCommand::Approve {
+                    approval_id: approval_id.to_string(),
+                    decision,
+                    response_tx,
+                })
+                .map_err(|_| AgentError::ChannelSend {
+                    message: "Failed to send approval".to_string(),
+                })?;
+
+            response_rx.await.map_err(|_| AgentError::ChannelReceive {
+                message: "Failed to receive approval response".to_string(),
+            })?
+        } else {
+            Err(AgentError::Execution {
+                message: "Agent controller is not active".to_string(),
+            })
+        }
+    }
+
+    /// Change settings of the running agent once the running turn, if any,
+    /// ends, returning the updated configuration.
+    pub(crate) async fn update_config(&self, patch: ConfigPatch) -> Result<AgentConfig> {
+        let (response_tx, response_rx) = oneshot::channel();
+
+        let control_sender = self.state.control_sender.lock().await;
+        if let Some(sender) = control_sender.as_ref() {
+            sender
+                .send(ControlCommand::UpdateConfig { patch, response_tx })
+                .map_err(|_| AgentError::ChannelSend {
+                    message: "Failed to send config update".to_string(),
+                })?;
+            drop(control_sender);
+
+            response_rx.await.map_err(|_| AgentError::ChannelReceive {
+                message: "Failed to receive config update response".to_string(),
+            })?
+        } else {
+            Err(AgentError::Execution {
+                message: "Agent controller is not active".to_string(),
+            })
+        }
+    }
+
     /// Internal method to update the turn count.
     pub(crate) fn increment_turn_count(&self) {
-        self.state.turn_count.fetch_add(1, Ordering::Relaxed);
+        let turn_count = self.state.turn_count.fetch_add(1, Ordering::Relaxed) + 1;
+        self.state
+            .state_watch
+            .send_modify(|state| state.turn_count = turn_count);
     }
 
     /// Internal method to set execution state.
     pub(crate) async fn set_execution_state(&self, state: ExecutionState) {
         let mut execution_state = self.state.execution_state.lock().await;
         *execution_state = state;
+        self.state
+            .state_watch
+            .send_replace(self.snapshot(&execution_state));
+    }
+
+    /// Build the public state snapshot for the given execution state.
+    fn snapshot(&self, execution_state: &ExecutionState) -> AgentExecutionState {
+        AgentExecutionState {
+            execution_state: execution_state.clone().into(),
+            turn_count: self.state.turn_count.load(Ordering::Relaxed),
+            is_paused: self.state.is_paused.load(Ordering::Relaxed),
+            should_stop: self.state.should_stop.load(Ordering::Relaxed),
+        }
     }
 
     /// Internal method to handle control commands.
@@ -194,21 +482,111 @@ impl AgentController {
         match command {
             ControlCommand::Pause(response_tx) => {
                 self.state.is_paused.store(true, Ordering::Relaxed);
-                self.set_execution_state(ExecutionState::Paused).await;
+                self.set_execution_state(ExecutionState::Paused(PauseReason::Requested))
+                    .await;
+                self.acknowledge(ControlAck::Paused);
                 let _ = response_tx.send(Ok(()));
             }
             ControlCommand::Resume(response_tx) => {
                 self.state.is_paused.store(false, Ordering::Relaxed);
                 self.set_execution_state(ExecutionState::Running).await;
+                self.acknowledge(ControlAck::Resumed);
                 let _ = response_tx.send(Ok(()));
             }
             ControlCommand::Stop(response_tx) => {
                 self.state.should_stop.store(true, Ordering::Relaxed);
                 self.state.is_paused.store(false, Ordering::Relaxed);
                 self.set_execution_state(ExecutionState::Stopped).await;
+                self.acknowledge(ControlAck::Stopped);
                 let _ = response_tx.send(Ok(()));
             }
+            ControlCommand::CancelTurn(response_tx) => {
+                // No turn is in flight, so there is nothing to cancel
+                self.acknowledge(ControlAck::TurnCancelled);
+                let _ = response_tx.send(Ok(()));
+            }
+            ControlCommand::Step(response_tx) => {
+                if self.is_paused() {
+                    self.state.is_paused.store(false, Ordering::Relaxed);
+                    self.set_execution_state(ExecutionState::Running).await;
+                    self.acknowledge(ControlAck::Stepped);
+                    let _ = response_tx.send(Ok(()));
+                } else {
+                    let _ = response_tx.send(Err(AgentError::Execution {
+                        message: "Agent is not paused before a tool".to_string(),
+                    }));
+                }
+            }
+            ControlCommand::Approve {
+                approval_id,
+                response_tx,
+                ..
+            } => {
+                // No turn is in flight, so no approval is pending
+                let _ = response_tx.send(Err(AgentError::Execution {
+                    message: format!("No pending approval {}", approval_id),
+                }));
+            }
+            ControlCommand::UpdateConfig { response_tx, .. } => {
+                // Updates are applied by the execution loop, which owns the config
+                let _ = response_tx.send(Err(AgentError::Execution {
+                    message: "Config updates are not handled by the controller".to_string(),
+                }));
+            }
+        }
+    }
+
+    /// Broadcast that a control command took effect.
+    pub(crate) fn acknowledge(&self, ack: ControlAck) {
+        // Sending only fails when nobody is subscribed
+        let _ = self.state.acks.send(ack);
+    }
+
+    /// Pause before a tool runs if step-through mode or a breakpoint requires it.
+    ///
+    /// Returns whether the agent was paused.
+    pub(crate) async fn pause_before_tool(&self, tool_name: &str) -> bool {
+        if self.should_stop() {
+            return false;
+        }
+
+        let reason = if self.state.breakpoints.lock().await.contains(tool_name) {
+            PauseReason::Breakpoint {
+                tool_name: tool_name.to_string(),
+            }
+        } else if self.is_step_mode() {
+            PauseReason::Step {
+                tool_name: tool_name.to_string(),
+            }
+        } else {
+            return false;
+        };
+
+        tracing::debug!("Pausing before tool {}: {}", tool_name, reason);
+        self.state.is_paused.store(true, Ordering::Relaxed);
+        self.set_execution_state(ExecutionState::Paused(reason))
+            .await;
+        true
+    }
+
+    /// Pause for approval of an autonomous run's check-in.
+    pub(crate) async fn pause_for_check_in(&self, number: u32) {
+        if self.should_stop() {
+            return;
         }
+        self.state.is_paused.store(true, Ordering::Relaxed);
+        self.set_execution_state(ExecutionState::Paused(PauseReason::CheckIn { number }))
+            .await;
+    }
+
+    /// Pause for confirmation of a previewed patch.
+    pub(crate) async fn pause_for_patch(&self) {
+        if self.should_stop() {
+            return;
+        }
+        self.state.is_paused.store(true, Ordering::Relaxed);
+        self.set_execution_state(ExecutionState::Paused(PauseReason::PatchPreview))
+            .await;
     }
 
     /// Check if the agent can continue execution (not paused and not stopped).
@@ -217,13 +595,6 @@ impl AgentController {
         !self.is_paused() && !self.should_stop()
     }
 
-    /// Wait for the agent to be resumed if it's currently paused.
-    pub(crate) async fn wait_if_paused(&self) {
-        while self.is_paused() && !self.should_stop() {
-            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-        }
-    }
-
     /// Mark the agent as having encountered an error.
     pub(crate) async fn set_error<S: Into<String>>(&self, error: S) {
         self.set_execution_state(ExecutionState::Error(error.into()))
@@ -257,7 +628,7 @@ pub enum PublicExecutionState {
     Running,
 
     /// Agent is paused but can be resumed
-    Paused,
+    Paused { reason: PauseReason },
 
     /// Agent has been stopped
     Stopped,
@@ -271,7 +642,7 @@ impl std::fmt::Display for PublicExecutionState {
         match self {
             PublicExecutionState::Idle => write!(f, "Idle"),
             PublicExecutionState::Running => write!(f, "Running"),
-            PublicExecutionState::Paused => write!(f, "Paused"),
+            PublicExecutionState::Paused { reason } => write!(f, "Paused ({})", reason),
             PublicExecutionState::Stopped => write!(f, "Stopped"),
             PublicExecutionState::Error => write!(f, "Error"),
         }
@@ -283,7 +654,7 @@ impl From<ExecutionState> for PublicExecutionState {
         match state {
             ExecutionState::Idle => PublicExecutionState::Idle,
             ExecutionState::Running => PublicExecutionState::Running,
-            ExecutionState::Paused => PublicExecutionState::Paused,
+            ExecutionState::Paused(reason) => PublicExecutionState::Paused { reason },
             ExecutionState::Stopped => PublicExecutionState::Stopped,
             ExecutionState::Error(_) => PublicExecutionState::Error,
         }
@@ -295,7 +666,7 @@ impl AgentExecutionState {
     pub fn is_active(&self) -> bool {
         matches!(
             self.execution_state,
-            PublicExecutionState::Running | PublicExecutionState::Paused
+            PublicExecutionState::Running | PublicExecutionState::Paused { .. }
         )
     }
 
@@ -0,0 +1,69 @@
+//! Stateful multi-turn chat over a single agent execution.
+
+use async_channel::{Receiver, Sender};
+
+use crate::agent::{AgentHandle, collect_response};
+use crate::controller::AgentController;
+use crate::error::Result;
+use crate::messages::{InputMessage, OutputMessage};
+use crate::plan::PlanMessage;
+
+/// Handle to an ongoing conversation with an agent.
+///
+/// Created with [`Agent::conversation`](crate::Agent::conversation). Each call
+/// to [`Conversation::chat`] runs one turn on the same Codex conversation, so
+/// the model sees the full history of earlier turns.
+pub struct Conversation {
+    input_tx: Sender<InputMessage>,
+    plan_rx: Receiver<PlanMessage>,
+    output_rx: Receiver<OutputMessage>,
+    handle: AgentHandle,
+    latest_plan: Option<PlanMessage>,
+}
+
+impl Conversation {
+    /// Create a conversation over a running execution.
+    pub(crate) fn new(
+        input_tx: Sender<InputMessage>,
+        plan_rx: Receiver<PlanMessage>,
+        output_rx: Receiver<OutputMessage>,
+        handle: AgentHandle,
+    ) -> Self {
+        Self {
+            input_tx,
+            plan_rx,
+            output_rx,
+            handle,
+            latest_plan: None,
+        }
+    }
+
+    /// Send a message and wait for the agent's response.
+    pub async fn chat<M: Into<InputMessage>>(&mut self, message: M) -> Result<String> {
+        self.input_tx.send(message.into()).await?;
+        let response = collect_response(&self.output_rx).await;
+
+        // Keep only the most recent plan
+        while let Ok(plan) = self.plan_rx.try_recv() {
+            self.latest_plan = Some(plan);
+        }
+
+        response
+    }
+
+    /// Get the most recent plan produced during the conversation.
+    pub fn plan(&self) -> Option<&PlanMessage> {
+        self.latest_plan.as_ref()
+    }
+
+    /// Get the agent controller.
+    pub fn controller(&self) -> &AgentController {
+        self.handle.controller()
+    }
+
+    /// End the conversation and wait for the execution to finish.
+    pub async fn close(self) -> Result<()> {
+        self.input_tx.close();
+        self.handle.await
+    }
+}
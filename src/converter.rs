@@ -0,0 +1,71 @@
+//! Customization of how Codex events map to agent-core outputs.
+//!
+//! Each Codex event is first converted by the built-in mapping. The converter
+//! configured with `AgentConfigBuilder::event_converter` then receives every
+//! converted output and may rewrite or drop it, e.g. to merge reasoning into
+//! the primary response or to rename tools, without forking the execution
+//! loop. Outputs agent-core produces itself, such as check-ins or workspace
+//! summaries, do not go through the converter.
+
+use std::collections::HashMap;
+
+use crate::messages::OutputData;
+
+/// Strategy rewriting the outputs converted from Codex events.
+pub trait EventConverter: Send + Sync {
+    /// Rewrite an output converted from a Codex event, or return `None` to
+    /// drop it.
+    fn convert(&self, output: OutputData) -> Option<OutputData>;
+}
+
+impl std::fmt::Debug for dyn EventConverter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EventConverter")
+    }
+}
+
+/// Converter emitting reasoning as primary response content.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReasoningAsPrimary;
+
+impl EventConverter for ReasoningAsPrimary {
+    fn convert(&self, output: OutputData) -> Option<OutputData> {
+        Some(match output {
+            OutputData::Reasoning { content } => OutputData::Primary { content },
+            OutputData::ReasoningDelta { content } => OutputData::PrimaryDelta { content },
+            output => output,
+        })
+    }
+}
+
+/// Converter renaming tools in tool start, output, and completion events.
+#[derive(Debug, Clone, Default)]
+pub struct ToolRenamer {
+    names: HashMap<String, String>,
+}
+
+impl ToolRenamer {
+    /// Create a renamer leaving every tool name unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report the tool `from` as `to`.
+    pub fn rename<S1: Into<String>, S2: Into<String>>(mut self, from: S1, to: S2) -> Self {
+        self.names.insert(from.into(), to.into());
+        self
+    }
+}
+
+impl EventConverter for ToolRenamer {
+    fn convert(&self, mut output: OutputData) -> Option<OutputData> {
+        if let OutputData::ToolStart { tool_name, .. }
+        | OutputData::ToolOutput { tool_name, .. }
+        | OutputData::ToolComplete { tool_name, .. } = &mut output
+            && let Some(name) = self.names.get(tool_name.as_str())
+        {
+            *tool_name = name.clone();
+        }
+        Some(output)
+    }
+}
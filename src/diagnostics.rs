@@ -0,0 +1,349 @@
+//! Structured diagnostics parsing for common toolchain output.
+//!
+//! Turns raw cargo, pytest, eslint, and tsc output into machine-readable
+//! diagnostics so both the model and host UIs get structured errors.
+
+use serde::{Deserialize, Serialize};
+
+/// Severity of a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Compilation error or test failure
+    Error,
+
+    /// Warning
+    Warning,
+
+    /// Informational note
+    Info,
+}
+
+/// Toolchain that produced a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Toolchain {
+    /// Rust compiler and test harness output via cargo
+    Cargo,
+
+    /// Python pytest output
+    Pytest,
+
+    /// ESLint stylish formatter output
+    Eslint,
+
+    /// TypeScript compiler output
+    Tsc,
+}
+
+/// A single structured diagnostic.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// File the diagnostic refers to
+    pub file: String,
+
+    /// 1-based line number, if known
+    pub line: Option<u32>,
+
+    /// 1-based column number, if known
+    pub column: Option<u32>,
+
+    /// Diagnostic severity
+    pub severity: Severity,
+
+    /// Human-readable message
+    pub message: String,
+
+    /// Tool-specific code (e.g., "E0308", "TS2322", "no-unused-vars")
+    pub code: Option<String>,
+
+    /// Toolchain that produced the diagnostic
+    pub toolchain: Toolchain,
+}
+
+/// Parse diagnostics from output of any supported toolchain.
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = parse_cargo(output);
+    diagnostics.extend(parse_pytest(output));
+    diagnostics.extend(parse_eslint(output));
+    diagnostics.extend(parse_tsc(output));
+    diagnostics
+}
+
+/// Parse output from a specific toolchain.
+pub fn parse_with(toolchain: Toolchain, output: &str) -> Vec<Diagnostic> {
+    match toolchain {
+        Toolchain::Cargo => parse_cargo(output),
+        Toolchain::Pytest => parse_pytest(output),
+        Toolchain::Eslint => parse_eslint(output),
+        Toolchain::Tsc => parse_tsc(output),
+    }
+}
+
+/// Parse rustc diagnostics and test panics from cargo output.
+pub fn parse_cargo(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut pending: Option<(Severity, Option<String>, String)> = None;
+    let mut lines = output.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+
+        if let Some((severity, rest)) = strip_severity(line) {
+            // error[E0308]: mismatched types
+            let (code, message) = match rest.strip_prefix('[').and_then(|r| r.split_once("]: ")) {
+                Some((code, message)) => (Some(code.to_string()), message),
+                None => match rest.strip_prefix(": ") {
+                    Some(message) => (None, message),
+                    None => continue,
+                },
+            };
+            if message.starts_with("aborting due to") || message.starts_with("could not compile") {
+                continue;
+            }
+            pending = Some((severity, code, message.to_string()));
+        } else if let Some(location) = trimmed.strip_prefix("--> ") {
+            if let Some((severity, code, message)) = pending.take() {
+                let (file, line, column) = split_location(location);
+                diagnostics.push(Diagnostic {
+                    file,
+                    line,
+                    column,
+                    severity,
+                    message,
+                    code,
+                    toolchain: Toolchain::Cargo,
+                });
+            }
+        } else if let Some(rest) = trimmed.strip_prefix("thread '")
+            && let Some((test, rest)) = rest.split_once("' panicked at ")
+        {
+            // thread 'tests::it_works' panicked at src/lib.rs:10:5:
+            let location = rest.trim_end_matches(':');
+            let (file, line, column) = split_location(location);
+            let message = lines
+                .next_if(|next| !next.starts_with("note:"))
+                .map(|next| next.trim().to_string())
+                .unwrap_or_default();
+            diagnostics.push(Diagnostic {
+                file,
+                line,
+                column,
+                severity: Severity::Error,
+                message: format!("test {} panicked: {}", test, message),
+                code: None,
+                toolchain: Toolchain::Cargo,
+            });
+        }
+    }
+
+    diagnostics
+}
+
+/// Parse pytest failure locations and short test summary lines.
+pub fn parse_pytest(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for line in output.lines() {
+        if let Some(rest) = line
+            .strip_prefix("FAILED ")
+            .or_else(|| line.strip_prefix("ERROR "))
+        {
+            // FAILED tests/test_app.py::test_add - AssertionError: assert 3 == 4
+            let (node, message) = rest.split_once(" - ").unwrap_or((rest, ""));
+            let Some((file, test)) = node.split_once("::") else {
+                continue;
+            };
+            diagnostics.push(Diagnostic {
+                file: file.to_string(),
+                line: None,
+                column: None,
+                severity: Severity::Error,
+                message: if message.is_empty() {
+                    format!("{} failed", test)
+                } else {
+                    format!("{} failed: {}", test, message)
+                },
+                code: None,
+                toolchain: Toolchain::Pytest,
+            });
+        } else if let Some((file, rest)) = line.split_once(".py:")
+            && !file.contains(' ')
+            && let Some((line_no, message)) = rest.split_once(": ")
+            && let Ok(line_no) = line_no.parse::<u32>()
+        {
+            // tests/test_app.py:12: AssertionError
+            diagnostics.push(Diagnostic {
+                file: format!("{}.py", file),
+                line: Some(line_no),
+                column: None,
+                severity: Severity::Error,
+                message: message.trim().to_string(),
+                code: None,
+                toolchain: Toolchain::Pytest,
+            });
+        }
+    }
+
+    diagnostics
+}
+
+/// Parse ESLint's default "stylish" formatter output.
+pub fn parse_eslint(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut current_file: Option<&str> = None;
+
+    for line in output.lines() {
+        if line.is_empty() {
+            current_file = None;
+            continue;
+        }
+
+        if !line.starts_with(char::is_whitespace) {
+            current_file = Some(line.trim());
+            continue;
+        }
+
+        let Some(file) = current_file else {
+            continue;
+        };
+
+        //   1:10  error  'x' is defined but never used  no-unused-vars
+        let mut parts = line.trim().splitn(2, char::is_whitespace);
+        let (Some(position), Some(rest)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let Some((line_no, column)) = position.split_once(':') else {
+            continue;
+        };
+        let (Ok(line_no), Ok(column)) = (line_no.parse::<u32>(), column.parse::<u32>()) else {
+            continue;
+        };
+
+        let rest = rest.trim_start();
+        let (severity, rest) = if let Some(rest) = rest.strip_prefix("error") {
+            (Severity::Error, rest)
+        } else if let Some(rest) = rest.strip_prefix("warning") {
+            (Severity::Warning, rest)
+        } else {
+            continue;
+        };
+
+        let rest = rest.trim();
+        let (message, code) = match rest.rsplit_once("  ") {
+            Some((message, rule)) if !rule.contains(' ') => {
+                (message.trim().to_string(), Some(rule.to_string()))
+            }
+            _ => (rest.to_string(), None),
+        };
+
+        diagnostics.push(Diagnostic {
+            file: file.to_string(),
+            line: Some(line_no),
+            column: Some(column),
+            severity,
+            message,
+            code,
+            toolchain: Toolchain::Eslint,
+        });
+    }
+
+    diagnostics
+}
+
+/// Parse TypeScript compiler output in both plain and pretty formats.
+pub fn parse_tsc(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for line in output.lines() {
+        // src/index.ts(3,7): error TS2322: Type 'string' is not assignable...
+        // src/index.ts:3:7 - error TS2322: Type 'string' is not assignable...
+        let parsed = if let Some((location, rest)) = line.split_once("): ")
+            && let Some((file, position)) = location.rsplit_once('(')
+            && let Some((line_no, column)) = position.split_once(',')
+        {
+            Some((file, line_no, column, rest))
+        } else if let Some((location, rest)) = line.split_once(" - ")
+            && let Some((file_line, column)) = location.rsplit_once(':')
+            && let Some((file, line_no)) = file_line.rsplit_once(':')
+        {
+            Some((file, line_no, column, rest))
+        } else {
+            None
+        };
+
+        let Some((file, line_no, column, rest)) = parsed else {
+            continue;
+        };
+        let (Ok(line_no), Ok(column)) = (line_no.parse::<u32>(), column.parse::<u32>()) else {
+            continue;
+        };
+        let Some((severity, rest)) = strip_severity(rest) else {
+            continue;
+        };
+        let Some((code, message)) = rest.trim_start().split_once(": ") else {
+            continue;
+        };
+        if !code.starts_with("TS") {
+            continue;
+        }
+
+        diagnostics.push(Diagnostic {
+            file: file.trim().to_string(),
+            line: Some(line_no),
+            column: Some(column),
+            severity,
+            message: message.trim().to_string(),
+            code: Some(code.to_string()),
+            toolchain: Toolchain::Tsc,
+        });
+    }
+
+    diagnostics
+}
+
+/// Format diagnostics as a compact list for feeding back to the model.
+pub fn format_for_model(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|d| {
+            let location = match (d.line, d.column) {
+                (Some(line), Some(column)) => format!("{}:{}:{}", d.file, line, column),
+                (Some(line), None) => format!("{}:{}", d.file, line),
+                _ => d.file.clone(),
+            };
+            let severity = match d.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Info => "info",
+            };
+            match &d.code {
+                Some(code) => format!("- {} {}[{}]: {}", location, severity, code, d.message),
+                None => format!("- {} {}: {}", location, severity, d.message),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Strip a leading "error"/"warning" keyword, returning the remainder.
+fn strip_severity(line: &str) -> Option<(Severity, &str)> {
+    if let Some(rest) = line.strip_prefix("error") {
+        Some((Severity::Error, rest))
+    } else if let Some(rest) = line.strip_prefix("warning") {
+        Some((Severity::Warning, rest))
+    } else {
+        None
+    }
+}
+
+/// Split "file:line:column" into its parts.
+fn split_location(location: &str) -> (String, Option<u32>, Option<u32>) {
+    let mut parts = location.trim().rsplitn(3, ':');
+    let column = parts.next().and_then(|c| c.parse().ok());
+    let line = parts.next().and_then(|l| l.parse().ok());
+    match (parts.next(), line, column) {
+        (Some(file), Some(line), Some(column)) => (file.to_string(), Some(line), Some(column)),
+        _ => (location.trim().to_string(), None, None),
+    }
+}
@@ -2,6 +2,8 @@
 
 use thiserror::Error;
 
+use crate::config::ConfigIssue;
+
 /// Result type alias for agent-core operations.
 pub type Result<T> = std::result::Result<T, AgentError>;
 
@@ -44,11 +46,27 @@ pub enum AgentError {
     #[error("MCP server error: {message}")]
     Mcp { message: String },
 
+    /// ChatGPT login expired and its tokens could not be refreshed
+    #[error("Authentication expired: {message}; sign in again with `codex login`")]
+    AuthExpired { message: String },
+
+    /// Invalid configuration, with every problem found
+    #[error("Invalid configuration: {}", join_issues(issues))]
+    InvalidConfig { issues: Vec<ConfigIssue> },
+
     /// Generic error
     #[error("Agent error: {message}")]
     Generic { message: String },
 }
 
+fn join_issues(issues: &[ConfigIssue]) -> String {
+    issues
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 /// Output error types that can be sent via OutputData::Error
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum OutputError {
@@ -70,10 +88,29 @@ pub enum OutputError {
     /// Resource limit exceeded
     ResourceLimitExceeded { resource: String, limit: String },
 
+    /// Final response does not conform to the configured response schema
+    ResponseSchemaMismatch { errors: Vec<String> },
+
     /// General error
     General { message: String },
 }
 
+impl OutputError {
+    /// Name of the error's kind, e.g. `tool_execution_failed`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            OutputError::ToolExecutionFailed { .. } => "tool_execution_failed",
+            OutputError::ModelRequestFailed { .. } => "model_request_failed",
+            OutputError::ConfigurationError { .. } => "configuration_error",
+            OutputError::SandboxViolation { .. } => "sandbox_violation",
+            OutputError::PermissionDenied { .. } => "permission_denied",
+            OutputError::ResourceLimitExceeded { .. } => "resource_limit_exceeded",
+            OutputError::ResponseSchemaMismatch { .. } => "response_schema_mismatch",
+            OutputError::General { .. } => "general",
+        }
+    }
+}
+
 impl From<&str> for AgentError {
     fn from(message: &str) -> Self {
         AgentError::Generic {
@@ -0,0 +1,176 @@
+//! Unified event stream merging output, plan, lifecycle, and control events.
+
+use async_channel::{Receiver, Sender, TrySendError};
+use tokio::sync::{broadcast, watch};
+
+use crate::controller::{AgentExecutionState, ControlAck};
+use crate::messages::OutputMessage;
+use crate::plan::PlanMessage;
+
+/// A single event from a running agent.
+#[derive(Debug, Clone)]
+pub enum AgentEvent {
+    /// Output produced by the agent
+    Output(OutputMessage),
+
+    /// Plan update
+    Plan(PlanMessage),
+
+    /// Execution state changed
+    Lifecycle(AgentExecutionState),
+
+    /// A control command took effect
+    ControlAck(ControlAck),
+
+    /// Events with sequence numbers `first_seq..=last_seq` were dropped
+    /// because the consumer fell behind and the buffer overflowed
+    GapDetected { first_seq: u64, last_seq: u64 },
+}
+
+/// An event tagged with its position in the stream.
+///
+/// Sequence numbers increase by one per event, so a consumer can verify it saw
+/// every event. Dropped events keep their numbers and are reported by a
+/// following [`AgentEvent::GapDetected`].
+#[derive(Debug, Clone)]
+pub struct SequencedEvent {
+    /// Monotonically increasing sequence number, starting at 0
+    pub seq: u64,
+
+    /// The event
+    pub event: AgentEvent,
+}
+
+/// Assigns sequence numbers and drops events instead of blocking the agent
+/// when the consumer falls behind.
+struct Sequencer {
+    event_tx: Sender<SequencedEvent>,
+    next_seq: u64,
+    gap: Option<(u64, u64)>,
+}
+
+impl Sequencer {
+    fn new(event_tx: Sender<SequencedEvent>) -> Self {
+        Self {
+            event_tx,
+            next_seq: 0,
+            gap: None,
+        }
+    }
+
+    fn next_seq(&mut self) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        seq
+    }
+
+    /// Emit an event, reporting any preceding gap first.
+    ///
+    /// Returns false once the receiver is closed.
+    fn emit(&mut self, event: AgentEvent) -> bool {
+        if let Some((first_seq, last_seq)) = self.gap {
+            let seq = self.next_seq;
+            match self.try_send(
+                seq,
+                AgentEvent::GapDetected {
+                    first_seq,
+                    last_seq,
+                },
+            ) {
+                Some(true) => {
+                    self.next_seq += 1;
+                    self.gap = None;
+                }
+                Some(false) => {}
+                None => return false,
+            }
+        }
+
+        let seq = self.next_seq();
+        if self.gap.is_none() {
+            match self.try_send(seq, event) {
+                Some(true) => return true,
+                Some(false) => {}
+                None => return false,
+            }
+        }
+
+        // Buffer full: record the dropped sequence number
+        tracing::debug!("Event buffer full, dropping event {}", seq);
+        let first_seq = self.gap.map_or(seq, |(first_seq, _)| first_seq);
+        self.gap = Some((first_seq, seq));
+        true
+    }
+
+    /// Try to send without waiting: `Some(true)` if sent, `Some(false)` if the
+    /// buffer is full, `None` if the receiver is closed.
+    fn try_send(&self, seq: u64, event: AgentEvent) -> Option<bool> {
+        match self.event_tx.try_send(SequencedEvent { seq, event }) {
+            Ok(()) => Some(true),
+            Err(TrySendError::Full(_)) => Some(false),
+            Err(TrySendError::Closed(_)) => None,
+        }
+    }
+}
+
+/// Merge the agent streams into a single sequenced event channel.
+///
+/// Runs until the execution closes its output and plan channels or the
+/// event receiver is dropped.
+pub(crate) async fn merge_events(
+    output_rx: Receiver<OutputMessage>,
+    plan_rx: Receiver<PlanMessage>,
+    mut state_rx: watch::Receiver<AgentExecutionState>,
+    mut ack_rx: broadcast::Receiver<ControlAck>,
+    event_tx: Sender<SequencedEvent>,
+) {
+    let mut sequencer = Sequencer::new(event_tx);
+    let mut output_open = true;
+    let mut plan_open = true;
+    let mut state_open = true;
+    let mut acks_open = true;
+
+    while output_open || plan_open {
+        let event = tokio::select! {
+            output = output_rx.recv(), if output_open => match output {
+                Ok(output) => AgentEvent::Output(output),
+                Err(_) => {
+                    output_open = false;
+                    continue;
+                }
+            },
+            plan = plan_rx.recv(), if plan_open => match plan {
+                Ok(plan) => AgentEvent::Plan(plan),
+                Err(_) => {
+                    plan_open = false;
+                    continue;
+                }
+            },
+            changed = state_rx.changed(), if state_open => match changed {
+                Ok(()) => AgentEvent::Lifecycle(state_rx.borrow_and_update().clone()),
+                Err(_) => {
+                    state_open = false;
+                    continue;
+                }
+            },
+            ack = ack_rx.recv(), if acks_open => match ack {
+                Ok(ack) => AgentEvent::ControlAck(ack),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => {
+                    acks_open = false;
+                    continue;
+                }
+            },
+        };
+
+        if !sequencer.emit(event) {
+            return;
+        }
+    }
+
+    // Deliver the final state reached as the execution wound down
+    if state_rx.has_changed().unwrap_or(false) {
+        let state = state_rx.borrow_and_update().clone();
+        sequencer.emit(AgentEvent::Lifecycle(state));
+    }
+}
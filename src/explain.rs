@@ -0,0 +1,97 @@
+//! Post-hoc explanations of the decisions an agent made during a turn.
+
+use serde::{Deserialize, Serialize};
+
+use crate::workspace::TurnRecord;
+
+/// Maximum characters of a single tape event included in the prompt.
+const MAX_EVENT_CHARS: usize = 2000;
+
+/// Structured explanation of a turn, for post-incident review.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TurnExplanation {
+    /// Turn that was explained
+    #[serde(default)]
+    pub turn_id: u64,
+
+    /// What the agent set out to do and what it achieved
+    pub summary: String,
+
+    /// Actions taken, in order, with the reasoning behind them
+    pub decisions: Vec<Decision>,
+
+    /// Risky or questionable steps a reviewer should look at
+    #[serde(default)]
+    pub concerns: Vec<String>,
+}
+
+/// A single action taken during a turn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Decision {
+    /// The tool call or action taken
+    pub action: String,
+
+    /// Why the agent chose it
+    pub rationale: String,
+
+    /// Options the agent could have taken instead
+    #[serde(default)]
+    pub alternatives: Vec<String>,
+}
+
+/// JSON Schema the model's explanation must conform to.
+pub(crate) fn explanation_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "required": ["summary", "decisions"],
+        "properties": {
+            "summary": { "type": "string" },
+            "decisions": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["action", "rationale"],
+                    "properties": {
+                        "action": { "type": "string" },
+                        "rationale": { "type": "string" },
+                        "alternatives": { "type": "array", "items": { "type": "string" } }
+                    }
+                }
+            },
+            "concerns": { "type": "array", "items": { "type": "string" } }
+        }
+    })
+}
+
+/// Build the prompt asking the model to explain a recorded turn.
+pub(crate) fn explain_prompt(record: &TurnRecord) -> String {
+    let mut prompt = format!(
+        "Below is the recorded event tape of turn {} of an AI agent. Explain why the \
+         agent chose each tool call and action, what alternatives it had, and any \
+         steps a reviewer should be concerned about. Do not run any tools; answer \
+         only from the tape.\n\n",
+        record.turn_id
+    );
+
+    for event in &record.events {
+        let mut line = serde_json::to_string(&event.data).unwrap_or_default();
+        if line.len() > MAX_EVENT_CHARS {
+            let mut end = MAX_EVENT_CHARS;
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            line.truncate(end);
+            line.push_str("...");
+        }
+        prompt.push_str(&line);
+        prompt.push('\n');
+    }
+
+    if let Some(summary) = &record.summary {
+        prompt.push_str("\nFinal message of the turn:\n");
+        prompt.push_str(summary);
+        prompt.push('\n');
+    }
+
+    prompt
+}
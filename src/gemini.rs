@@ -0,0 +1,216 @@
+//! Gemini API adapter of the model bridge.
+//!
+//! Translates Codex's Chat Completions requests into `streamGenerateContent`
+//! requests and the streamed responses back into Chat Completions chunks, so
+//! Gemini models run on Google's native API rather than its OpenAI
+//! compatibility layer. The system messages become the system instruction,
+//! tool calls and results become `functionCall` and `functionResponse`
+//! parts, and images are sent inline.
+//!
+//! Gemini function calls carry no id, so the bridge makes one up. Calls may
+//! carry a thought signature that must be sent back with the call on the next
+//! request; it is appended to the id after a `.`, which Codex echoes back
+//! unchanged.
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::model_bridge::{
+    BridgeError, ChatRequest, ChatStream, FinishReason, Upstream, parse_data_url,
+};
+
+/// Answer `request` through the Gemini API.
+pub(crate) async fn complete(
+    upstream: &Upstream,
+    request: ChatRequest,
+    stream: &mut ChatStream<'_>,
+) -> Result<(), BridgeError> {
+    let body = generate_request(&request);
+    let url = upstream.url(&format!(
+        "models/{}:streamGenerateContent?alt=sse",
+        request.model
+    ));
+    let auth = upstream
+        .api_key
+        .as_ref()
+        .map(|key| ("x-goog-api-key", key.clone()));
+    let mut events = upstream.post(&url, auth, &body).await?;
+
+    let mut reason = FinishReason::Stop;
+    while let Some(event) = events.next().await? {
+        let response: StreamResponse = serde_json::from_str(&event.data)
+            .map_err(|e| BridgeError::new(502, format!("Invalid Gemini response: {}", e)))?;
+        if let Some(error) = response.error {
+            return Err(BridgeError::new(error.code, error.message));
+        }
+        for candidate in response.candidates.into_iter().take(1) {
+            for part in candidate.content.parts {
+                if part.thought {
+                    continue;
+                }
+                if let Some(text) = &part.text {
+                    stream.text(text).await?;
+                }
+                if let Some(call) = part.function_call {
+                    let mut id = format!("call_{}", uuid::Uuid::new_v4().simple());
+                    if let Some(signature) = &part.thought_signature {
+                        id.push('.');
+                        id.push_str(signature);
+                    }
+                    let arguments = if call.args.is_null() {
+                        json!({})
+                    } else {
+                        call.args
+                    };
+                    stream
+                        .tool_call(&id, &call.name, &arguments.to_string())
+                        .await?;
+                }
+            }
+            if candidate.finish_reason.as_deref() == Some("MAX_TOKENS") {
+                reason = FinishReason::Length;
+            }
+        }
+    }
+    stream.finish(reason).await
+}
+
+/// `generateContent` request body for `request`.
+fn generate_request(request: &ChatRequest) -> Value {
+    let mut system = Vec::new();
+    let mut contents: Vec<(&str, Vec<Value>)> = Vec::new();
+    for message in &request.messages {
+        let (role, mut parts) = match message.role.as_str() {
+            "system" | "developer" => {
+                system.push(message.text());
+                continue;
+            }
+            "assistant" => {
+                let mut parts = text_part(message.text());
+                parts.extend(message.tool_calls.iter().map(|call| {
+                    let mut part = json!({
+                        "functionCall": { "name": call.function.name, "args": call.input() },
+                    });
+                    if let Some((_, signature)) = call.id.split_once('.') {
+                        part["thoughtSignature"] = json!(signature);
+                    }
+                    part
+                }));
+                ("model", parts)
+            }
+            "tool" => {
+                let id = message.tool_call_id.as_deref().unwrap_or_default();
+                let response = json!({
+                    "functionResponse": {
+                        "name": request.tool_name(id).unwrap_or_default(),
+                        "response": { "content": message.text() },
+                    },
+                });
+                ("user", vec![response])
+            }
+            _ => ("user", text_part(message.text())),
+        };
+        parts.extend(message.image_urls().into_iter().map(image_part));
+        if parts.is_empty() {
+            continue;
+        }
+        // Responses to the calls of one reply must share a single content
+        match contents.last_mut() {
+            Some((last, existing)) if *last == role => existing.append(&mut parts),
+            _ => contents.push((role, parts)),
+        }
+    }
+
+    let mut body = json!({
+        "contents": contents
+            .into_iter()
+            .map(|(role, parts)| json!({ "role": role, "parts": parts }))
+            .collect::<Vec<_>>(),
+    });
+    if !system.is_empty() {
+        body["systemInstruction"] = json!({ "parts": [{ "text": system.join("\n\n") }] });
+    }
+    let declarations: Vec<Value> = request
+        .functions()
+        .map(|function| {
+            // Full JSON Schema; `parameters` only takes an OpenAPI subset
+            json!({
+                "name": function.name,
+                "description": function.description,
+                "parametersJsonSchema": function.schema(),
+            })
+        })
+        .collect();
+    if !declarations.is_empty() {
+        body["tools"] = json!([{ "functionDeclarations": declarations }]);
+    }
+    body
+}
+
+/// Text part, unless the text is empty.
+fn text_part(text: String) -> Vec<Value> {
+    if text.is_empty() {
+        Vec::new()
+    } else {
+        vec![json!({ "text": text })]
+    }
+}
+
+/// Part of an image URL, inlined if it is a data URL.
+fn image_part(url: &str) -> Value {
+    match parse_data_url(url) {
+        Some((mime_type, data)) => json!({ "inlineData": { "mimeType": mime_type, "data": data } }),
+        None => json!({ "fileData": { "fileUri": url } }),
+    }
+}
+
+/// Response chunk of a streamed `generateContent` request.
+#[derive(Debug, Deserialize)]
+struct StreamResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+    #[serde(default)]
+    error: Option<ApiError>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Candidate {
+    #[serde(default)]
+    content: Content,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Content {
+    #[serde(default)]
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Part {
+    #[serde(default)]
+    text: Option<String>,
+    /// Whether the part is a thought summary rather than reply text
+    #[serde(default)]
+    thought: bool,
+    #[serde(default)]
+    function_call: Option<FunctionCall>,
+    #[serde(default)]
+    thought_signature: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FunctionCall {
+    name: String,
+    #[serde(default)]
+    args: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    code: u16,
+    message: String,
+}
@@ -0,0 +1,226 @@
+//! Thin async wrappers around the `git` command line used by workspace features.
+
+use std::ffi::OsStr;
+use std::path::Path;
+
+use tokio::process::Command;
+use tracing::debug;
+
+use crate::error::{AgentError, Result};
+
+/// Author identity used for commits created by agent-core.
+#[derive(Debug, Clone)]
+pub(crate) struct GitAuthor<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+}
+
+/// Run a git command in `cwd` and return its trimmed stdout.
+pub(crate) async fn run_git<I, S>(cwd: &Path, args: I) -> Result<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    run_git_with_env(cwd, args, std::iter::empty::<(&str, &OsStr)>()).await
+}
+
+/// Run a git command in `cwd` with extra environment variables.
+pub(crate) async fn run_git_with_env<I, S, E, K, V>(cwd: &Path, args: I, envs: E) -> Result<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+    E: IntoIterator<Item = (K, V)>,
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+{
+    let args: Vec<_> = args
+        .into_iter()
+        .map(|a| a.as_ref().to_os_string())
+        .collect();
+    let output = Command::new("git")
+        .args(&args)
+        .envs(envs)
+        .current_dir(cwd)
+        .output()
+        .await?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(AgentError::Execution {
+            message: format!(
+                "git {} failed: {}",
+                args.iter()
+                    .map(|a| a.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        })
+    }
+}
+
+/// Check if `cwd` is inside a git work tree.
+pub(crate) async fn is_repository(cwd: &Path) -> bool {
+    run_git(cwd, ["rev-parse", "--is-inside-work-tree"])
+        .await
+        .is_ok_and(|out| out == "true")
+}
+
+/// Resolve a revision to a commit SHA, returning `None` if it does not exist.
+pub(crate) async fn resolve(cwd: &Path, rev: &str) -> Option<String> {
+    run_git(cwd, ["rev-parse", "--verify", "--quiet", rev])
+        .await
+        .ok()
+        .filter(|sha| !sha.is_empty())
+}
+
+/// State of the work tree written as a tree, to be committed onto a branch.
+#[derive(Debug, Clone)]
+pub(crate) struct WorktreeSnapshot {
+    branch_ref: String,
+    tree: String,
+    parent: Option<String>,
+    /// Branch tip the snapshot was taken on, `None` if the branch did not exist
+    tip: Option<String>,
+}
+
+/// Commit the current state of the work tree onto `branch` without touching
+/// HEAD, the user's index, or the checked-out files.
+///
+/// Returns `None` when there is nothing to commit.
+pub(crate) async fn commit_worktree_to_branch(
+    cwd: &Path,
+    branch: &str,
+    message: &str,
+    author: &GitAuthor<'_>,
+) -> Result<Option<String>> {
+    match snapshot_worktree(cwd, branch).await? {
+        Some(snapshot) => commit_snapshot(cwd, snapshot, message, author)
+            .await
+            .map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Write the current state of the work tree as a tree to commit onto
+/// `branch`, returning `None` when it matches the branch tip.
+///
+/// A temporary index is populated from the work tree (respecting `.gitignore`)
+/// and compared with the branch tip (or HEAD when the branch does not exist
+/// yet). Each call has an index of its own, so agents sharing a repository
+/// do not collide.
+pub(crate) async fn snapshot_worktree(
+    cwd: &Path,
+    branch: &str,
+) -> Result<Option<WorktreeSnapshot>> {
+    let index_name = format!("agent-core-index-{}", uuid::Uuid::new_v4());
+    let index_path = run_git(cwd, ["rev-parse", "--git-path", index_name.as_str()]).await?;
+    let index_path = cwd.join(index_path);
+    let index_env = [("GIT_INDEX_FILE", index_path.as_os_str())];
+
+    let branch_ref = format!("refs/heads/{}", branch);
+    let tip = resolve(cwd, &branch_ref).await;
+    let parent = match &tip {
+        Some(sha) => Some(sha.clone()),
+        None => resolve(cwd, "HEAD").await,
+    };
+
+    let tree = async {
+        run_git_with_env(cwd, ["add", "-A"], index_env).await?;
+        run_git_with_env(cwd, ["write-tree"], index_env).await
+    }
+    .await;
+    let _ = tokio::fs::remove_file(&index_path).await;
+    let tree = tree?;
+
+    if let Some(parent) = &parent {
+        let parent_tree_rev = format!("{}^{{tree}}", parent);
+        let parent_tree = run_git(cwd, ["rev-parse", parent_tree_rev.as_str()]).await?;
+        if parent_tree == tree {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(WorktreeSnapshot {
+        branch_ref,
+        tree,
+        parent,
+        tip,
+    }))
+}
+
+/// Attempts at moving a branch that keeps moving under a commit.
+const UPDATE_REF_ATTEMPTS: usize = 3;
+
+/// Commit a snapshot onto its branch, returning the commit SHA.
+///
+/// The branch only moves if it is still where the snapshot was taken (or
+/// still does not exist), so a commit made meanwhile, e.g. by another agent,
+/// is never dropped: the snapshot is committed again on top of it, up to
+/// a few times.
+pub(crate) async fn commit_snapshot(
+    cwd: &Path,
+    snapshot: WorktreeSnapshot,
+    message: &str,
+    author: &GitAuthor<'_>,
+) -> Result<String> {
+    let WorktreeSnapshot {
+        branch_ref,
+        tree,
+        mut parent,
+        mut tip,
+    } = snapshot;
+    let author_env = [
+        ("GIT_AUTHOR_NAME", author.name),
+        ("GIT_AUTHOR_EMAIL", author.email),
+        ("GIT_COMMITTER_NAME", author.name),
+        ("GIT_COMMITTER_EMAIL", author.email),
+    ];
+
+    let mut attempt = 1;
+    loop {
+        let mut args = vec![
+            "commit-tree".to_string(),
+            tree.clone(),
+            "-m".to_string(),
+            message.to_string(),
+        ];
+        if let Some(parent) = &parent {
+            args.push("-p".to_string());
+            args.push(parent.clone());
+        }
+        let commit = run_git_with_env(cwd, &args, author_env).await?;
+
+        // An empty old value requires the branch not to exist yet
+        let expected = tip.clone().unwrap_or_default();
+        let updated = run_git(
+            cwd,
+            [
+                "update-ref",
+                branch_ref.as_str(),
+                commit.as_str(),
+                expected.as_str(),
+            ],
+        )
+        .await;
+        match updated {
+            Ok(_) => return Ok(commit),
+            Err(e) => {
+                let current = resolve(cwd, &branch_ref).await;
+                if current == tip || attempt == UPDATE_REF_ATTEMPTS {
+                    return Err(e);
+                }
+                debug!(
+                    "{} moved during the commit, retrying on top of it",
+                    branch_ref
+                );
+                if current.is_some() {
+                    parent = current.clone();
+                }
+                tip = current;
+                attempt += 1;
+            }
+        }
+    }
+}
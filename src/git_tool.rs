@@ -0,0 +1,376 @@
+//! Built-in tool exposing structured git operations.
+//!
+//! Operations are a fixed set (status, diff, log, branch, commit, push)
+//! rather than arbitrary git arguments, so what an agent may do to a
+//! repository is auditable and restricted by [`GitPolicy`].
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::error::{AgentError, Result};
+use crate::git::run_git;
+use crate::tools::{CustomToolHandler, ToolExecutionContext, ToolExecutionResult};
+
+/// Maximum number of log entries returned per call.
+const MAX_LOG_ENTRIES: usize = 100;
+
+/// What the git tool may do besides reading the repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitPolicy {
+    /// Whether commits may be created
+    #[serde(default = "default_true")]
+    pub allow_commit: bool,
+
+    /// Whether branches may be created and switched
+    #[serde(default = "default_true")]
+    pub allow_branch: bool,
+
+    /// Whether commits may be pushed
+    #[serde(default)]
+    pub allow_push: bool,
+
+    /// Whether pushes may be forced
+    #[serde(default)]
+    pub allow_force: bool,
+
+    /// Remotes pushes may go to (empty means those configured in the
+    /// repository, listed by `git remote`)
+    #[serde(default)]
+    pub allowed_remotes: Vec<String>,
+
+    /// Template for commit messages; `{message}` is replaced by the model's message
+    #[serde(default)]
+    pub commit_template: Option<String>,
+}
+
+impl Default for GitPolicy {
+    fn default() -> Self {
+        Self {
+            allow_commit: true,
+            allow_branch: true,
+            allow_push: false,
+            allow_force: false,
+            allowed_remotes: Vec::new(),
+            commit_template: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+enum GitOperation {
+    Status,
+    Diff {
+        #[serde(default)]
+        staged: bool,
+        #[serde(default)]
+        path: Option<String>,
+    },
+    Log {
+        #[serde(default = "default_log_entries")]
+        max_count: usize,
+    },
+    Branch {
+        #[serde(default)]
+        create: Option<String>,
+        #[serde(default)]
+        switch: Option<String>,
+    },
+    Commit {
+        message: String,
+        #[serde(default)]
+        paths: Vec<String>,
+    },
+    Push {
+        #[serde(default)]
+        remote: Option<String>,
+        #[serde(default)]
+        branch: Option<String>,
+        #[serde(default)]
+        force: bool,
+    },
+}
+
+/// Tool handler running git operations in the tool's working directory.
+///
+/// `execute` blocks on the git command, so call it through
+/// [`crate::blocking::run_tool`].
+#[derive(Debug, Clone)]
+pub struct GitTool {
+    policy: GitPolicy,
+}
+
+impl GitTool {
+    /// Create a handler restricted by `policy`.
+    pub fn new(policy: GitPolicy) -> Self {
+        Self { policy }
+    }
+
+    /// Get the policy restricting the tool.
+    pub fn policy(&self) -> &GitPolicy {
+        &self.policy
+    }
+
+    /// Push `branch` to `remote` as the model's push operation would, if the
+    /// policy allows it.
+    #[cfg(feature = "pull-request")]
+    pub(crate) async fn push(&self, cwd: &Path, remote: &str, branch: &str) -> Result<String> {
+        let operation = GitOperation::Push {
+            remote: Some(remote.to_string()),
+            branch: Some(branch.to_string()),
+            force: false,
+        };
+        let args = self
+            .arguments(cwd, operation)
+            .await
+            .map_err(|message| AgentError::Tool { message })?;
+        run_git(cwd, &args).await
+    }
+
+    /// Translate an operation into git arguments, or explain why it is not
+    /// allowed.
+    ///
+    /// Branch and remote names come from the model, so they are checked
+    /// before reaching git: names looking like options (`--force`,
+    /// `--receive-pack=...`) or forced refspecs (`+main`) are refused,
+    /// branch names must pass `git check-ref-format --branch`, and remotes
+    /// must be allowed by the policy or configured in the repository.
+    async fn arguments(
+        &self,
+        cwd: &Path,
+        operation: GitOperation,
+    ) -> std::result::Result<Vec<String>, String> {
+        match operation {
+            GitOperation::Status => Ok(strings(&["status", "--short", "--branch"])),
+            GitOperation::Diff { staged, path } => {
+                let mut diff = strings(&["diff", "--no-color"]);
+                if staged {
+                    diff.push("--staged".to_string());
+                }
+                if let Some(path) = path {
+                    diff.extend(["--".to_string(), path]);
+                }
+                Ok(diff)
+            }
+            GitOperation::Log { max_count } => Ok(vec![
+                "log".to_string(),
+                format!("--max-count={}", max_count.clamp(1, MAX_LOG_ENTRIES)),
+                "--format=%h %ad %an %s".to_string(),
+                "--date=short".to_string(),
+            ]),
+            GitOperation::Branch {
+                create: None,
+                switch: None,
+            } => Ok(strings(&["branch", "--list"])),
+            GitOperation::Branch { .. } if !self.policy.allow_branch => {
+                Err("Creating and switching branches is not allowed".to_string())
+            }
+            GitOperation::Branch {
+                create: Some(name), ..
+            } => {
+                check_branch(cwd, &name).await?;
+                Ok(vec!["switch".to_string(), format!("--create={}", name)])
+            }
+            GitOperation::Branch {
+                switch: Some(name), ..
+            } => {
+                check_branch(cwd, &name).await?;
+                Ok(strings(&["switch", "--end-of-options", name.as_str()]))
+            }
+            GitOperation::Commit { .. } if !self.policy.allow_commit => {
+                Err("Committing is not allowed".to_string())
+            }
+            GitOperation::Commit { message, .. } if message.trim().is_empty() => {
+                Err("Commit message is empty".to_string())
+            }
+            GitOperation::Commit { message, paths } => {
+                let message = match &self.policy.commit_template {
+                    Some(template) => template.replace("{message}", message.trim()),
+                    None => message,
+                };
+                let mut commit = vec!["commit".to_string(), "-m".to_string(), message];
+                if paths.is_empty() {
+                    commit.push("--all".to_string());
+                } else {
+                    commit.push("--".to_string());
+                    commit.extend(paths);
+                }
+                Ok(commit)
+            }
+            GitOperation::Push { .. } if !self.policy.allow_push => {
+                Err("Pushing is not allowed".to_string())
+            }
+            GitOperation::Push { force: true, .. } if !self.policy.allow_force => {
+                Err("Force pushing is not allowed".to_string())
+            }
+            GitOperation::Push {
+                remote,
+                branch,
+                force,
+            } => {
+                // A branch is pushed to origin unless a remote is given
+                let remote = remote.or_else(|| branch.as_ref().map(|_| "origin".to_string()));
+                if let Some(branch) = &branch {
+                    check_branch(cwd, branch).await?;
+                }
+                if let Some(remote) = &remote {
+                    check_remote(cwd, remote, &self.policy.allowed_remotes).await?;
+                }
+
+                let mut push = vec!["push".to_string()];
+                if force {
+                    push.push("--force-with-lease".to_string());
+                }
+                if remote.is_some() {
+                    push.push("--end-of-options".to_string());
+                }
+                push.extend(remote);
+                push.extend(branch);
+                Ok(push)
+            }
+        }
+    }
+}
+
+impl CustomToolHandler for GitTool {
+    fn execute(
+        &self,
+        parameters: Value,
+        context: &ToolExecutionContext,
+    ) -> Result<ToolExecutionResult> {
+        let operation: GitOperation = serde_json::from_value(parameters)?;
+        let runtime = tokio::runtime::Handle::try_current().map_err(|_| AgentError::Tool {
+            message: "The git tool must run inside a Tokio runtime".to_string(),
+        })?;
+        let cwd = &context.working_directory;
+
+        runtime.block_on(async {
+            let args = match self.arguments(cwd, operation).await {
+                Ok(args) => args,
+                Err(reason) => return Ok(ToolExecutionResult::error(reason)),
+            };
+            match run_git(cwd, &args).await {
+                Ok(output) if output.is_empty() => Ok(ToolExecutionResult::success("(no output)")),
+                Ok(output) => Ok(ToolExecutionResult::success(output)),
+                Err(e) => Ok(ToolExecutionResult::error(e.to_string())),
+            }
+        })
+    }
+
+    fn parameter_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "operation": {
+                    "type": "string",
+                    "enum": ["status", "diff", "log", "branch", "commit", "push"]
+                },
+                "staged": {
+                    "type": "boolean",
+                    "description": "diff: show staged changes"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "diff: limit to this path"
+                },
+                "max_count": {
+                    "type": "integer",
+                    "description": "log: number of commits to show"
+                },
+                "create": {
+                    "type": "string",
+                    "description": "branch: create and switch to this branch"
+                },
+                "switch": {
+                    "type": "string",
+                    "description": "branch: switch to this existing branch"
+                },
+                "message": {
+                    "type": "string",
+                    "description": "commit: commit message"
+                },
+                "paths": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "commit: paths to commit (default: all tracked changes)"
+                },
+                "remote": { "type": "string", "description": "push: name of a configured remote" },
+                "branch": { "type": "string", "description": "push: branch name" },
+                "force": { "type": "boolean", "description": "push: force with lease" }
+            },
+            "required": ["operation"]
+        })
+    }
+
+    fn description(&self) -> String {
+        let mut operations = vec!["status", "diff", "log", "branch"];
+        if self.policy.allow_commit {
+            operations.push("commit");
+        }
+        if self.policy.allow_push {
+            operations.push("push");
+        }
+        format!(
+            "Run git operations on the repository: {}",
+            operations.join(", ")
+        )
+    }
+}
+
+/// Refuse a name the model gave that git would read as an option or a
+/// forced refspec.
+fn check_name(kind: &str, name: &str) -> std::result::Result<(), String> {
+    if name.starts_with('-') || name.starts_with('+') {
+        return Err(format!(
+            "{} \"{}\" must not start with '-' or '+'",
+            kind, name
+        ));
+    }
+    Ok(())
+}
+
+/// Refuse a remote the model gave unless it is in `allowed`, or, without an
+/// allowlist, configured in the repository; a URL would push the repository
+/// anywhere.
+async fn check_remote(
+    cwd: &Path,
+    name: &str,
+    allowed: &[String],
+) -> std::result::Result<(), String> {
+    check_name("Remote", name)?;
+    if !allowed.is_empty() {
+        if allowed.iter().any(|remote| remote == name) {
+            return Ok(());
+        }
+        return Err(format!("Pushing to remote \"{}\" is not allowed", name));
+    }
+    let remotes = run_git(cwd, ["remote"]).await.map_err(|e| e.to_string())?;
+    if remotes.lines().any(|remote| remote.trim() == name) {
+        Ok(())
+    } else {
+        Err(format!("\"{}\" is not a remote of the repository", name))
+    }
+}
+
+/// Refuse a branch name the model gave unless it is a valid branch name.
+async fn check_branch(cwd: &Path, name: &str) -> std::result::Result<(), String> {
+    check_name("Branch", name)?;
+    match run_git(cwd, ["check-ref-format", "--branch", name]).await {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("\"{}\" is not a valid branch name", name)),
+    }
+}
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|item| item.to_string()).collect()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_log_entries() -> usize {
+    20
+}
@@ -0,0 +1,303 @@
+//! Deterministic lexicon-based filtering of model responses.
+//!
+//! A [`LexiconFilter`] configured with `AgentConfigBuilder::lexicon_filter`
+//! runs on every `Primary` and `PrimaryDelta` message before it is emitted,
+//! after any output processors. Streaming fragments are held back until a
+//! listed term can no longer straddle the fragment boundary, so a term split
+//! across deltas is still caught.
+
+use serde::{Deserialize, Serialize};
+
+use crate::processors::OutputProcessor;
+
+/// Message sent in place of a response containing a blocked term.
+const DEFAULT_BLOCK_MESSAGE: &str = "[response withheld]";
+
+/// Languages written without spaces between words; terms match anywhere.
+const UNSEGMENTED_LANGUAGES: &[&str] = &["zh", "ja", "th", "lo", "km", "my"];
+
+/// What to do with a listed term found in a response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LexiconAction {
+    /// Replace every character of the term with `*`
+    Mask,
+
+    /// Replace the term with the given text
+    Replace(String),
+
+    /// Withhold the whole response
+    Block,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LexiconEntry {
+    term: String,
+    action: LexiconAction,
+}
+
+/// Filter masking, replacing, or blocking listed words and phrases.
+///
+/// Matching ignores case using the rules of the configured locale (e.g.
+/// Turkish dotted and dotless `i`), and only matches whole words except in
+/// languages written without spaces.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LexiconFilter {
+    entries: Vec<LexiconEntry>,
+    locale: String,
+    block_message: String,
+}
+
+/// A term found in a response, as a range of character indices.
+struct LexiconMatch {
+    start: usize,
+    end: usize,
+    entry: usize,
+}
+
+impl LexiconFilter {
+    /// Create an empty filter.
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            locale: "und".to_string(),
+            block_message: DEFAULT_BLOCK_MESSAGE.to_string(),
+        }
+    }
+
+    /// Set the locale (BCP 47 tag, e.g. `tr` or `ja-JP`) used for case
+    /// folding and word boundaries.
+    pub fn locale<S: Into<String>>(mut self, locale: S) -> Self {
+        self.locale = locale.into();
+        self
+    }
+
+    /// Mask the given words or phrases.
+    pub fn mask<I, S>(self, terms: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.terms(terms, LexiconAction::Mask)
+    }
+
+    /// Replace the given words or phrases with `replacement`.
+    pub fn replace<I, S, R>(self, terms: I, replacement: R) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+        R: Into<String>,
+    {
+        self.terms(terms, LexiconAction::Replace(replacement.into()))
+    }
+
+    /// Withhold responses containing any of the given words or phrases.
+    pub fn block<I, S>(self, terms: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.terms(terms, LexiconAction::Block)
+    }
+
+    /// Add a wordlist with one term per line; blank lines and lines starting
+    /// with `#` are ignored.
+    pub fn wordlist(self, contents: &str, action: LexiconAction) -> Self {
+        let terms = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        self.terms(terms, action)
+    }
+
+    /// Set the message sent in place of a blocked response.
+    pub fn block_message<S: Into<String>>(mut self, message: S) -> Self {
+        self.block_message = message.into();
+        self
+    }
+
+    /// Filter a complete response.
+    pub fn filter(&self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let matches = self.find_matches(&chars, true);
+        self.render(&chars, &matches)
+            .unwrap_or_else(|| self.block_message.clone())
+    }
+
+    fn terms<I, S>(mut self, terms: I, action: LexiconAction) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.entries.extend(
+            terms
+                .into_iter()
+                .map(Into::into)
+                .filter(|term| !term.is_empty())
+                .map(|term| LexiconEntry {
+                    term,
+                    action: action.clone(),
+                }),
+        );
+        self
+    }
+
+    fn language(&self) -> &str {
+        self.locale.split(['-', '_']).next().unwrap_or("")
+    }
+
+    fn word_boundaries(&self) -> bool {
+        !UNSEGMENTED_LANGUAGES.contains(&self.language())
+    }
+
+    /// Lowercase one character, keeping a one-to-one mapping to the input.
+    fn fold(&self, c: char) -> char {
+        match (self.language(), c) {
+            ("tr" | "az", 'I') => 'ı',
+            ("tr" | "az", 'İ') => 'i',
+            _ => c.to_lowercase().next().unwrap_or(c),
+        }
+    }
+
+    fn max_term_chars(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|entry| entry.term.chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Find non-overlapping terms, preferring the longest at each position.
+    ///
+    /// Unless `complete`, text may continue after `chars`, so a whole-word
+    /// term ending at the very end is not yet a match.
+    fn find_matches(&self, chars: &[char], complete: bool) -> Vec<LexiconMatch> {
+        let folded: Vec<char> = chars.iter().map(|c| self.fold(*c)).collect();
+        let terms: Vec<Vec<char>> = self
+            .entries
+            .iter()
+            .map(|entry| entry.term.chars().map(|c| self.fold(c)).collect())
+            .collect();
+        let word_boundaries = self.word_boundaries();
+
+        let mut matches = Vec::new();
+        let mut i = 0;
+        while i < folded.len() {
+            let at_boundary = !word_boundaries || i == 0 || !is_word_char(folded[i - 1]);
+            let longest = terms
+                .iter()
+                .enumerate()
+                .filter(|(_, term)| at_boundary && folded[i..].starts_with(term))
+                .filter(|(_, term)| {
+                    let end = i + term.len();
+                    match folded.get(end) {
+                        _ if !word_boundaries => true,
+                        Some(next) => !is_word_char(*next),
+                        None => complete,
+                    }
+                })
+                .max_by_key(|(_, term)| term.len());
+
+            match longest {
+                Some((entry, term)) => {
+                    matches.push(LexiconMatch {
+                        start: i,
+                        end: i + term.len(),
+                        entry,
+                    });
+                    i += term.len();
+                }
+                None => i += 1,
+            }
+        }
+        matches
+    }
+
+    /// Apply matches to the text, or `None` if it must be blocked.
+    fn render(&self, chars: &[char], matches: &[LexiconMatch]) -> Option<String> {
+        let mut result = String::with_capacity(chars.len());
+        let mut position = 0;
+        for m in matches {
+            result.extend(&chars[position..m.start]);
+            match &self.entries[m.entry].action {
+                LexiconAction::Mask => result.extend(std::iter::repeat_n('*', m.end - m.start)),
+                LexiconAction::Replace(replacement) => result.push_str(replacement),
+                LexiconAction::Block => return None,
+            }
+            position = m.end;
+        }
+        result.extend(&chars[position..]);
+        Some(result)
+    }
+}
+
+impl Default for LexiconFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OutputProcessor for LexiconFilter {
+    fn process(&self, content: String) -> String {
+        self.filter(&content)
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Filtering state of a stream of response fragments.
+#[derive(Debug, Default)]
+pub(crate) struct LexiconStream {
+    /// Tail of the fragments seen so far that may still start a term
+    carry: String,
+    /// Whether a blocked term was found, suppressing the rest of the stream
+    blocked: bool,
+}
+
+impl LexiconStream {
+    /// Filter the next fragment, returning the text that is safe to emit.
+    pub fn push(&mut self, filter: &LexiconFilter, fragment: &str) -> Option<String> {
+        if self.blocked {
+            return None;
+        }
+        self.carry.push_str(fragment);
+
+        let chars: Vec<char> = self.carry.chars().collect();
+        let matches = filter.find_matches(&chars, false);
+
+        // Hold back anything a term could still extend into
+        let mut cut = chars
+            .len()
+            .saturating_sub(filter.max_term_chars().saturating_sub(1));
+        if filter.word_boundaries() {
+            while cut > 0 && is_word_char(chars[cut - 1]) {
+                cut -= 1;
+            }
+        }
+        for m in &matches {
+            if m.start < cut && m.end > cut {
+                cut = m.start;
+            }
+        }
+
+        let emitted: Vec<LexiconMatch> = matches.into_iter().filter(|m| m.end <= cut).collect();
+        let text = filter.render(&chars[..cut], &emitted);
+        self.carry = chars[cut..].iter().collect();
+
+        match text {
+            Some(text) => (!text.is_empty()).then_some(text),
+            None => {
+                self.blocked = true;
+                Some(filter.block_message.clone())
+            }
+        }
+    }
+
+    /// Start a new response.
+    pub fn reset(&mut self) {
+        self.carry.clear();
+        self.blocked = false;
+    }
+}
@@ -33,13 +33,62 @@
 #![deny(clippy::expect_used)]
 
 pub mod agent;
+pub mod approval;
+pub mod attachment;
+pub mod auth;
+pub mod autonomy;
+mod backend;
+pub mod blocking;
+pub mod cache;
+pub mod compare;
 pub mod config;
 pub mod controller;
+pub mod conversation;
+pub mod converter;
+pub mod diagnostics;
 pub mod error;
+pub mod event;
+pub mod explain;
+mod git;
+pub mod git_tool;
+pub mod lexicon;
+pub mod limits;
+pub mod markdown;
 pub mod mcp;
 pub mod messages;
+pub mod middleware;
+pub mod ops;
+pub mod orchestrator;
+pub mod patch;
+mod paths;
+pub mod pipeline;
 pub mod plan;
+pub mod pool;
+pub mod preflight;
+mod process;
+pub mod processors;
+pub mod profile;
+mod protocol;
+pub mod provider;
+pub mod queue;
+pub mod rate_limit;
+pub mod replay;
+pub mod retry;
+pub mod sandbox;
+pub mod scratchpad;
+pub mod search;
+pub mod shell;
+mod spans;
+pub mod structured;
+pub mod sub_agent;
+pub mod suggestions;
+pub mod telemetry;
+pub mod tool_server;
 pub mod tools;
+mod truncation;
+pub mod verify;
+pub mod web_search;
+pub mod workspace;
 
 // Optional features
 #[cfg(feature = "session")]
@@ -48,17 +97,104 @@ pub mod session;
 #[cfg(feature = "utils")]
 pub mod utils;
 
+#[cfg(feature = "lsp")]
+pub mod lsp;
+
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+#[cfg(feature = "metrics-server")]
+pub mod metrics_server;
+
+#[cfg(feature = "model-bridge")]
+mod model_bridge;
+
+#[cfg(feature = "provider-anthropic")]
+mod anthropic;
+
+#[cfg(feature = "provider-gemini")]
+mod gemini;
+
+#[cfg(feature = "otel")]
+pub mod otel;
+
+#[cfg(feature = "prompt-tools")]
+mod prompt_tools;
+
+#[cfg(feature = "registry")]
+pub mod registry;
+
+#[cfg(feature = "testing")]
+pub mod snapshot;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
 // Re-exports for convenience
-pub use agent::{Agent, AgentHandle};
-pub use config::{AgentConfig, AgentConfigBuilder};
-pub use controller::AgentController;
+pub use agent::{Agent, AgentHandle, BatchResult};
+pub use approval::{ApprovalDecision, ApprovalRequest};
+pub use attachment::{Attachment, AttachmentSource};
+pub use auth::AuthMethod;
+pub use autonomy::{CheckIn, CheckInPolicy, CheckInReason};
+pub use blocking::{CancellationToken, YieldPoint};
+pub use cache::{CachedResponse, MemoryStore, ResponseCache, ResponseStore};
+pub use compare::{Comparison, ComparisonSummary, TurnResult, compare};
+pub use config::{AgentConfig, AgentConfigBuilder, ConfigIssue, ConfigPatch};
+pub use controller::{AgentController, ControlAck, PauseReason};
+pub use conversation::Conversation;
+pub use converter::{EventConverter, ReasoningAsPrimary, ToolRenamer};
+pub use diagnostics::{Diagnostic, Severity, Toolchain};
 pub use error::{AgentError, OutputError, Result};
+pub use event::{AgentEvent, SequencedEvent};
+pub use explain::{Decision, TurnExplanation};
+pub use git_tool::{GitPolicy, GitTool};
+pub use lexicon::{LexiconAction, LexiconFilter};
+pub use limits::{KillPolicy, ResourceLimits};
+pub use markdown::CodeBlock;
 pub use mcp::McpServerConfig;
-pub use messages::{ImageInput, InputMessage, OutputData, OutputMessage};
+pub use messages::{ImageInput, InputMessage, OutputData, OutputMessage, QueryOptions};
+#[cfg(feature = "metrics-server")]
+pub use metrics_server::MetricsServer;
+pub use middleware::{ToolCall, ToolMiddleware};
+pub use ops::{OpsSummary, TokenUsage, ToolUsage};
+pub use orchestrator::{OrchestrationResult, Orchestrator, TaggedOutput};
+pub use patch::FileChangeKind;
+pub use pipeline::{Pipeline, PipelineResult, StepResult, StepRetryPolicy};
 pub use plan::{PlanMessage, PlanMetadata, TodoItem, TodoStatus};
-pub use tools::{CustomToolHandler, ToolConfig};
+pub use pool::{AgentPool, PoolMetrics, PooledAgent};
+pub use preflight::{PreflightArea, PreflightIssue, PreflightReport};
+pub use processors::{EmojiPolicy, HeadingLevels, LinkRewriter, OutputProcessor};
+pub use profile::{ConfigProfile, ConfigProfiles};
+pub use protocol::CODEX_PROTOCOL_VERSION;
+pub use provider::{ModelProvider, ProviderRoute, WireApi};
+pub use queue::PendingInput;
+pub use rate_limit::RateLimit;
+pub use replay::Replay;
+pub use retry::RetryPolicy;
+pub use sandbox::{ContainerRuntime, ContainerSandbox, SandboxBackend};
+pub use scratchpad::{Scratchpad, ScratchpadTool};
+pub use search::{SearchMatch, SearchTool};
+pub use shell::ShellTool;
+pub use sub_agent::SubAgentTool;
+pub use suggestions::SuggestionsConfig;
+pub use telemetry::{TelemetryEvent, TelemetrySink};
+#[cfg(feature = "schemars")]
+pub use tools::TypedToolHandler;
+pub use tools::{
+    CustomToolHandler, ToolConcurrency, ToolConfig, ToolExecutionResult, ToolImage, ToolRegistry,
+};
+pub use verify::{VerificationOutcome, VerifyConfig};
+#[cfg(feature = "search-bing")]
+pub use web_search::BingSearch;
+#[cfg(feature = "search-brave")]
+pub use web_search::BraveSearch;
+#[cfg(feature = "search-searxng")]
+pub use web_search::SearxngSearch;
+pub use web_search::{SearchProvider, WebSearchResult, WebSearchTool};
+pub use workspace::{AutoCommitConfig, DiskQuota, FileIndex, TurnRecord, WorkspaceDelta};
 
 // Re-export codex types for convenience
+pub use codex_protocol::config_types::{ReasoningEffort, ReasoningSummary};
 pub use codex_protocol::protocol::{AskForApproval, SandboxPolicy};
 
 #[cfg(test)]
@@ -72,4 +208,2782 @@ mod tests {
 
         assert_eq!(config.model(), "gpt-4");
     }
+
+    #[test]
+    fn test_config_serde_round_trip() {
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .api_key("secret")
+            .tool(ToolConfig::bash())
+            .build()
+            .unwrap();
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(!json.contains("secret"));
+
+        let restored: AgentConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.model(), "gpt-5-mini");
+        assert_eq!(restored.tools().len(), 1);
+        assert_eq!(restored.api_key(), None);
+    }
+
+    #[test]
+    fn test_unknown_model_is_a_warning() {
+        let config = AgentConfig::builder().model("my-model").build().unwrap();
+
+        let warnings = config.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, "model");
+    }
+
+    #[test]
+    fn test_file_path_globs_are_checked_under_any_approval_policy() {
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .approval_policy(AskForApproval::Never)
+            .tool(ToolConfig::file_read().deny_paths(["~/.ssh/**"]))
+            .tool(ToolConfig::file_write().allow_paths(["src/**"]))
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+
+        let home = std::path::PathBuf::from(std::env::var_os("HOME").unwrap());
+        assert!(
+            config
+                .check_tool_path("file_read", &home.join(".ssh/id_rsa"))
+                .is_err()
+        );
+        assert!(
+            config
+                .check_tool_path("file_write", std::path::Path::new("secrets/key"))
+                .is_err()
+        );
+        assert!(
+            config
+                .check_tool_path("file_write", std::path::Path::new("src/main.rs"))
+                .is_ok()
+        );
+        // Codex asks approval for every patch so the agent can check them
+        assert_eq!(
+            config.codex_approval_policy(),
+            AskForApproval::UnlessTrusted
+        );
+        assert_eq!(*config.approval_policy(), AskForApproval::Never);
+    }
+
+    #[test]
+    fn test_sub_agent_is_offered_to_the_model() {
+        let nested = AgentConfig::builder().model("gpt-5-mini").build().unwrap();
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(ToolConfig::sub_agent(
+                "researcher",
+                "Research a topic",
+                nested,
+            ))
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let server = tool_server::ToolServer::new(
+            ToolRegistry::new(config.tools().to_vec()),
+            AgentController::new(),
+            config,
+        );
+
+        let listing = server.list();
+        assert_eq!(listing["tools"][0]["name"], "researcher");
+        assert_eq!(listing["tools"][0]["description"], "Research a topic");
+        assert_eq!(listing["tools"][0]["inputSchema"]["required"][0], "task");
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    fn test_typed_tool_is_offered_with_a_plain_schema() {
+        #[derive(serde::Deserialize, schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct Filter {
+            path: String,
+        }
+
+        #[derive(serde::Deserialize, schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct Params {
+            query: String,
+            limit: Option<u32>,
+            filter: Option<Filter>,
+        }
+
+        let tool = ToolConfig::custom_typed("lookup", "Look something up", |_: Params, _| {
+            Ok(ToolExecutionResult::success("found"))
+        });
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(tool)
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let server = tool_server::ToolServer::new(
+            ToolRegistry::new(config.tools().to_vec()),
+            AgentController::new(),
+            config,
+        );
+
+        let listing = server.list();
+        let schema = &listing["tools"][0]["inputSchema"];
+        assert_eq!(listing["tools"][0]["name"], "lookup");
+        assert_eq!(schema["type"], "object");
+        assert!(schema.get("$schema").is_none());
+        assert!(schema.get("$defs").is_none());
+        assert_eq!(schema["properties"]["limit"]["type"], "integer");
+        assert_eq!(schema["properties"]["filter"]["type"], "object");
+        assert_eq!(
+            schema["properties"]["filter"]["properties"]["path"]["type"],
+            "string"
+        );
+    }
+
+    #[test]
+    fn test_recordings_of_other_codex_versions_are_replayed() {
+        let lines = [
+            serde_json::json!({ "kind": "header", "protocol_version": "0.40.0" }),
+            serde_json::json!({ "kind": "submission", "submission": {
+                "id": "1", "op": { "type": "user_turn_v2", "items": [] } } }),
+            serde_json::json!({ "kind": "event", "event": { "id": "1",
+                "msg": { "type": "agent_message", "message": "Done." } } }),
+            // Event types and shapes of a later Codex version
+            serde_json::json!({ "kind": "event", "event": { "id": "1",
+                "msg": { "type": "entered_review_mode", "prompt": "Review" } } }),
+            serde_json::json!({ "kind": "event", "event": { "id": "1",
+                "msg": { "type": "token_count", "info": {
+                    "total_token_usage": { "input_tokens": 30, "output_tokens": 6, "total_tokens": 36 },
+                    "last_token_usage": { "input_tokens": 10, "output_tokens": 2, "total_tokens": 12 },
+                }, "rate_limits": null } } }),
+            serde_json::json!({ "kind": "event", "event": { "id": "1",
+                "msg": { "type": "token_count", "info": null } } }),
+        ];
+        let jsonl: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+
+        let replay = Replay::parse(&jsonl).unwrap();
+        assert_eq!(replay.submissions(), 1);
+
+        let usage = protocol::decode_event(lines[4]["event"].clone())
+            .unwrap()
+            .and_then(|event| protocol::token_usage(&event.msg))
+            .unwrap();
+        assert_eq!(usage.input_tokens, 10);
+        assert_eq!(usage.total_tokens, 12);
+        assert!(
+            protocol::decode_event(lines[3]["event"].clone())
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            protocol::decode_event(lines[5]["event"].clone())
+                .unwrap()
+                .is_none()
+        );
+        let message = protocol::decode_event(lines[2]["event"].clone())
+            .unwrap()
+            .unwrap();
+        assert_eq!(protocol::agent_message(&message.msg), Some("Done."));
+    }
+
+    #[test]
+    fn test_undecodable_recorded_events_are_reported_with_their_line() {
+        let lines = [
+            serde_json::json!({ "kind": "header", "protocol_version": CODEX_PROTOCOL_VERSION }),
+            serde_json::json!({ "kind": "submission", "submission": {
+                "id": "1", "op": { "type": "interrupt" } } }),
+            // Known event type missing its message
+            serde_json::json!({ "kind": "event", "event": { "id": "1",
+                "msg": { "type": "agent_message" } } }),
+        ];
+        let jsonl: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+
+        let error = Replay::parse(&jsonl).unwrap_err().to_string();
+        assert!(error.contains("line 3"), "{}", error);
+        assert!(error.contains("agent_message"), "{}", error);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_replay_fails_on_a_submission_differing_from_the_recording() {
+        let recording = temp_dir().join("run.jsonl");
+        let config = || AgentConfig::builder().model("gpt-5-mini");
+        let backend = testing::MockBackend::new().respond("The tests pass.");
+        let mut agent = Agent::new(config().record_to(&recording).build().unwrap())
+            .unwrap()
+            .with_mock_backend(backend);
+        assert_eq!(
+            agent.query("Run the tests").await.unwrap(),
+            "The tests pass."
+        );
+
+        let replay = Replay::load(&recording).unwrap();
+        let mut same = Agent::new(config().build().unwrap())
+            .unwrap()
+            .with_replay(replay.clone());
+        assert_eq!(
+            same.query("Run the tests").await.unwrap(),
+            "The tests pass."
+        );
+
+        // Same kind of submission, other input
+        let mut other = Agent::new(config().build().unwrap())
+            .unwrap()
+            .with_replay(replay);
+        let error = other
+            .query("Delete the tests")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Replay diverged"), "{}", error);
+        assert!(error.contains("another items"), "{}", error);
+    }
+
+    #[test]
+    fn test_cache_key_covers_tool_sandbox_approval_and_mcp_settings() {
+        let base = || {
+            AgentConfig::builder()
+                .model("gpt-5-mini")
+                .tool_bridge(test_bridge())
+        };
+        let key = |builder: AgentConfigBuilder| {
+            cache::cache_key("Summarize the log", &builder.build().unwrap())
+        };
+        // Two variables, so the server's hash map may list them either way
+        let docs = |root: &str| {
+            McpServerConfig::command("docs", "docs-server")
+                .env_var("DOCS_ROOT", root)
+                .env_var("DOCS_MODE", "read-only")
+                .build()
+        };
+
+        let keys = [
+            key(base()),
+            key(base().tool(ToolConfig::bash())),
+            key(base().tool(ToolConfig::bash().timeout(5))),
+            key(base().sandbox_policy(SandboxPolicy::DangerFullAccess)),
+            key(base().approval_policy(AskForApproval::Never)),
+            key(base().mcp_server(docs("/docs"))),
+            key(base().mcp_server(docs("/srv/docs"))),
+        ];
+        let distinct: std::collections::HashSet<_> = keys.iter().collect();
+        assert_eq!(distinct.len(), keys.len());
+        assert_eq!(
+            cache::cache_key(
+                "Summarize  the\nlog",
+                &base().mcp_server(docs("/docs")).build().unwrap()
+            ),
+            keys[5]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_response_cache_evicts_stale_entries_on_put() {
+        let store = std::sync::Arc::new(MemoryStore::new(10));
+        let cache = ResponseCache::new(store.clone()).ttl(std::time::Duration::from_secs(60));
+        let stored_at = |age: u64| CachedResponse {
+            response: "cached".to_string(),
+            stored_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+                - age,
+        };
+        store.put("stale", stored_at(3600)).await.unwrap();
+        store.put("fresh", stored_at(10)).await.unwrap();
+
+        cache.put("new", "answer").await;
+
+        assert_eq!(store.len(), 2);
+        assert!(store.get("stale").await.unwrap().is_none());
+        assert_eq!(cache.get("fresh").await.as_deref(), Some("cached"));
+        assert_eq!(cache.get("new").await.as_deref(), Some("answer"));
+    }
+
+    #[test]
+    fn test_rate_limiters_are_shared_per_provider_and_credential() {
+        let limited = |key: &str, requests: u32| {
+            AgentConfig::builder()
+                .model("gpt-5-mini")
+                .api_key(key)
+                .rate_limit(
+                    rate_limit::OPENAI_PROVIDER,
+                    RateLimit::new().requests_per_minute(requests),
+                )
+                .build()
+                .unwrap()
+        };
+        let limiter = |config: &AgentConfig| {
+            let (provider, credential, limit) = config.model_rate_limit().unwrap();
+            rate_limit::limiter(&provider, &credential, limit)
+        };
+        let key = format!("sk-{}", uuid::Uuid::new_v4());
+        let other_key = format!("sk-{}", uuid::Uuid::new_v4());
+
+        let first = limiter(&limited(&key, 60));
+        assert!(std::sync::Arc::ptr_eq(&first, &limiter(&limited(&key, 60))));
+        // A differing limit is warned about and the first one is kept
+        assert!(std::sync::Arc::ptr_eq(&first, &limiter(&limited(&key, 10))));
+        assert!(!std::sync::Arc::ptr_eq(
+            &first,
+            &limiter(&limited(&other_key, 60))
+        ));
+    }
+
+    #[cfg(all(feature = "pull-request", feature = "testing"))]
+    #[tokio::test]
+    async fn test_pull_requests_push_through_the_git_policy_to_known_forges() {
+        async fn open(remote: &str, policy: GitPolicy) -> String {
+            let dir = temp_repository();
+            let added = std::process::Command::new("git")
+                .args(["remote", "add", "origin", remote])
+                .current_dir(&dir)
+                .status()
+                .unwrap();
+            assert!(added.success());
+            let config = AgentConfig::builder()
+                .model("gpt-5-mini")
+                .working_directory(&dir)
+                .auto_commit(AutoCommitConfig::new("agent/changes"))
+                .tool(ToolConfig::git_with(policy))
+                .tool_bridge(test_bridge())
+                .build()
+                .unwrap();
+            let mut agent = Agent::new(config)
+                .unwrap()
+                .with_mock_backend(testing::MockBackend::new());
+            let (_input_tx, input_rx) = async_channel::unbounded();
+            let (plan_tx, _plan_rx) = async_channel::unbounded();
+            let (output_tx, _output_rx) = async_channel::unbounded();
+            let handle = agent.execute(input_rx, plan_tx, output_tx).await.unwrap();
+            workspace::open_pull_request(&handle, "Add notes")
+                .await
+                .unwrap_err()
+                .to_string()
+        }
+        let pushing = GitPolicy {
+            allow_push: true,
+            ..GitPolicy::default()
+        };
+
+        let error = open("https://git.example.com/team/app.git", pushing).await;
+        assert!(
+            error.contains("Unsupported forge host git.example.com"),
+            "{}",
+            error
+        );
+        let error = open("git@github.com:team/app.git", GitPolicy::default()).await;
+        assert!(error.contains("Pushing is not allowed"), "{}", error);
+    }
+
+    #[cfg(feature = "metrics-server")]
+    #[tokio::test]
+    async fn test_metrics_server_serves_metrics_and_health() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        async fn get(address: std::net::SocketAddr, path: &str) -> String {
+            let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+            let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        }
+
+        let agent =
+            Agent::new(AgentConfig::builder().model("gpt-5-mini").build().unwrap()).unwrap();
+        let server = MetricsServer::serve(("127.0.0.1", 0), [agent.controller().clone()])
+            .await
+            .unwrap();
+        crate::metrics::error("tool_error");
+
+        let health = get(server.local_addr(), "/healthz").await;
+        assert!(health.starts_with("HTTP/1.1 200 OK"));
+        assert!(health.ends_with("ok\n"));
+        let scraped = get(server.local_addr(), "/metrics").await;
+        assert!(scraped.contains("version=0.0.4"));
+        assert!(scraped.contains("agent_errors_total{type=\"tool_error\"} 1"));
+        assert!(
+            get(server.local_addr(), "/other")
+                .await
+                .starts_with("HTTP/1.1 404")
+        );
+    }
+
+    /// Outputs of a turn run on the agent's backend.
+    #[cfg(feature = "testing")]
+    async fn run_turn(agent: &mut Agent, input: &str) -> Vec<OutputData> {
+        use futures::StreamExt;
+
+        agent.query_stream(input).await.unwrap().collect().await
+    }
+
+    /// Result of the first call to a tool in the outputs of a turn.
+    #[cfg(feature = "testing")]
+    fn tool_result(outputs: &[OutputData], tool: &str) -> serde_json::Value {
+        outputs
+            .iter()
+            .find_map(|output| match output {
+                OutputData::ToolComplete { tool_name, result } if tool_name == tool => {
+                    Some(result.clone())
+                }
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    /// Text the tool call of a `ToolComplete` result returned.
+    #[cfg(feature = "testing")]
+    fn tool_text(result: &serde_json::Value) -> &str {
+        result["result"]["Ok"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+    }
+
+    /// Relay of configurations with host tools, which the tests never
+    /// launch.
+    fn test_bridge() -> std::path::PathBuf {
+        std::env::current_exe().unwrap()
+    }
+
+    /// Empty directory for a test to work in.
+    fn temp_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("agent-core-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Create a git repository in a temporary directory.
+    fn temp_repository() -> std::path::PathBuf {
+        let dir = temp_dir();
+        let init = std::process::Command::new("git")
+            .arg("init")
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert!(init.status.success());
+        dir
+    }
+
+    /// Full message of the commit at a revision.
+    fn commit_message(dir: &std::path::Path, revision: &str) -> String {
+        let output = std::process::Command::new("git")
+            .args(["log", "-1", "--format=%B", revision])
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_agents_commit_to_one_repository_at_once() {
+        let dir = temp_repository();
+        std::fs::write(dir.join("notes.txt"), "shared\n").unwrap();
+        let author = git::GitAuthor {
+            name: "agent-core",
+            email: "agent-core@localhost",
+        };
+
+        let (first, second) = tokio::join!(
+            git::commit_worktree_to_branch(&dir, "agent/first", "First", &author),
+            git::commit_worktree_to_branch(&dir, "agent/second", "Second", &author),
+        );
+
+        assert!(first.unwrap().is_some());
+        assert!(second.unwrap().is_some());
+        assert_eq!(commit_message(&dir, "agent/first"), "First");
+        assert_eq!(commit_message(&dir, "agent/second"), "Second");
+    }
+
+    #[tokio::test]
+    async fn test_commit_keeps_a_branch_moved_since_the_snapshot() {
+        let dir = temp_repository();
+        std::fs::write(dir.join("notes.txt"), "mine\n").unwrap();
+        let author = git::GitAuthor {
+            name: "agent-core",
+            email: "agent-core@localhost",
+        };
+        let snapshot = git::snapshot_worktree(&dir, "agent/changes")
+            .await
+            .unwrap()
+            .unwrap();
+        std::fs::write(dir.join("other.txt"), "theirs\n").unwrap();
+        git::commit_worktree_to_branch(&dir, "agent/changes", "Theirs", &author)
+            .await
+            .unwrap();
+
+        git::commit_snapshot(&dir, snapshot, "Mine", &author)
+            .await
+            .unwrap();
+
+        assert_eq!(commit_message(&dir, "agent/changes"), "Mine");
+        assert_eq!(commit_message(&dir, "agent/changes~1"), "Theirs");
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_auto_commit_message_is_written_by_the_model() {
+        let dir = temp_repository();
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .working_directory(&dir)
+            .auto_commit(AutoCommitConfig::new("agent/changes"))
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new()
+            .respond("I wrote the release notes.")
+            .respond("Add release notes\n\nList the changes of the release.");
+        let mut agent = Agent::new(config)
+            .unwrap()
+            .with_mock_backend(backend.clone());
+        std::fs::write(dir.join("NOTES.md"), "# Release notes\n").unwrap();
+
+        agent.query("Write the release notes").await.unwrap();
+
+        let message = commit_message(&dir, "agent/changes");
+        assert!(message.starts_with("Add release notes\n\nList the changes of the release."));
+        assert!(message.contains("Agent-Turn:"));
+        let inputs = backend.inputs();
+        assert_eq!(inputs.len(), 2);
+        assert!(inputs[1].contains("I wrote the release notes."));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_auto_commit_skips_the_message_without_changes() {
+        let dir = temp_repository();
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .working_directory(&dir)
+            .auto_commit(AutoCommitConfig::new("agent/changes"))
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new().respond("Nothing to change.");
+        let mut agent = Agent::new(config)
+            .unwrap()
+            .with_mock_backend(backend.clone());
+
+        agent.query("Look around").await.unwrap();
+
+        assert_eq!(backend.inputs().len(), 1);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_patch_checks_keep_the_sandbox() {
+        let sandbox_policy = SandboxPolicy::WorkspaceWrite {
+            writable_roots: Vec::new(),
+            network_access: false,
+            exclude_tmpdir_env_var: false,
+            exclude_slash_tmp: false,
+        };
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .approval_policy(AskForApproval::Never)
+            .sandbox_policy(sandbox_policy.clone())
+            .tool(ToolConfig::apply_patch())
+            .build()
+            .unwrap();
+        let request = ApprovalRequest::Exec {
+            command: vec!["cargo".to_string(), "test".to_string()],
+            cwd: temp_dir(),
+            reason: None,
+        };
+        let backend = testing::MockBackend::new().turn([
+            OutputData::ApprovalRequired {
+                approval_id: "call-1".to_string(),
+                request: request.clone(),
+            },
+            OutputData::Primary {
+                content: "Tested".to_string(),
+            },
+        ]);
+        let mut agent = Agent::new(config)
+            .unwrap()
+            .with_mock_backend(backend.clone());
+
+        let outputs = run_turn(&mut agent, "Run the tests").await;
+
+        assert_eq!(
+            backend.turn_policies(),
+            vec![(AskForApproval::Never, sandbox_policy)]
+        );
+        // The command is left to the host instead of approved out of the sandbox
+        assert!(outputs.iter().any(|output| matches!(
+            output,
+            OutputData::ApprovalRequired { request: asked, .. } if *asked == request
+        )));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_search_tool_is_called_by_the_model() {
+        let dir = temp_dir();
+        std::fs::write(dir.join("notes.txt"), "first line\nthe needle is here\n").unwrap();
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .working_directory(&dir)
+            .tool(ToolConfig::search())
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new().turn([
+            OutputData::tool_start("search", serde_json::json!({ "pattern": "needle" })),
+            OutputData::Primary {
+                content: "Found it".to_string(),
+            },
+        ]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+
+        let outputs = run_turn(&mut agent, "Find the needle").await;
+
+        let result = tool_result(&outputs, "search");
+        assert_eq!(result["success"], true);
+        assert!(tool_text(&result).contains("notes.txt"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_git_tool_is_called_by_the_model() {
+        let dir = temp_repository();
+        std::fs::write(dir.join("new.txt"), "untracked\n").unwrap();
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .working_directory(&dir)
+            .tool(ToolConfig::git())
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new().turn([
+            OutputData::tool_start("git", serde_json::json!({ "operation": "status" })),
+            OutputData::tool_start("git", serde_json::json!({ "operation": "push" })),
+        ]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+
+        let outputs = run_turn(&mut agent, "What changed?").await;
+
+        let results: Vec<&serde_json::Value> = outputs
+            .iter()
+            .filter_map(|output| match output {
+                OutputData::ToolComplete { tool_name, result } if tool_name == "git" => {
+                    Some(result)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["success"], true);
+        assert!(tool_text(results[0]).contains("?? new.txt"));
+        // Pushes are off unless the policy allows them
+        assert_eq!(results[1]["success"], false);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_git_tool_pushes_only_to_known_remotes() {
+        let dir = temp_repository();
+        let remote = temp_dir();
+        let init = std::process::Command::new("git")
+            .args(["init", "--bare"])
+            .current_dir(&remote)
+            .output()
+            .unwrap();
+        assert!(init.status.success());
+        let add = std::process::Command::new("git")
+            .args(["remote", "add", "backup"])
+            .arg(&remote)
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert!(add.status.success());
+        let push = |remote: &str| {
+            OutputData::tool_start(
+                "git",
+                serde_json::json!({ "operation": "push", "remote": remote }),
+            )
+        };
+        let policy = GitPolicy {
+            allow_push: true,
+            ..GitPolicy::default()
+        };
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .working_directory(&dir)
+            .tool(ToolConfig::git_with(policy.clone()))
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new().turn([
+            push(&remote.display().to_string()),
+            push("https://example.com/exfiltrate.git"),
+            OutputData::tool_start(
+                "git",
+                serde_json::json!({ "operation": "push", "branch": "main" }),
+            ),
+        ]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+
+        let outputs = run_turn(&mut agent, "Push my changes").await;
+
+        let results = outputs
+            .iter()
+            .filter_map(|output| match output {
+                OutputData::ToolComplete { tool_name, result } if tool_name == "git" => {
+                    Some(result)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        for result in &results {
+            assert_eq!(result["success"], false);
+            assert!(tool_text(result).contains("is not a remote of the repository"));
+        }
+
+        let policy = GitPolicy {
+            allowed_remotes: vec!["upstream".to_string()],
+            ..policy
+        };
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .working_directory(&dir)
+            .tool(ToolConfig::git_with(policy))
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new().turn([push("backup")]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+
+        let outputs = run_turn(&mut agent, "Push my changes").await;
+
+        let results = outputs
+            .iter()
+            .filter_map(|output| match output {
+                OutputData::ToolComplete { tool_name, result } if tool_name == "git" => {
+                    Some(result)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(results[0]["success"], false);
+        assert!(tool_text(results[0]).contains("Pushing to remote \"backup\" is not allowed"));
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_dir_all(remote).unwrap();
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_git_tool_refuses_forcing_names() {
+        let dir = temp_repository();
+        let policy = GitPolicy {
+            allow_push: true,
+            ..GitPolicy::default()
+        };
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .working_directory(&dir)
+            .tool(ToolConfig::git_with(policy))
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new().turn([
+            OutputData::tool_start(
+                "git",
+                serde_json::json!({ "operation": "push", "remote": "--force" }),
+            ),
+            OutputData::tool_start(
+                "git",
+                serde_json::json!({ "operation": "push", "branch": "+main" }),
+            ),
+            OutputData::tool_start(
+                "git",
+                serde_json::json!({ "operation": "push", "remote": "--receive-pack=touch pwned" }),
+            ),
+            OutputData::tool_start(
+                "git",
+                serde_json::json!({ "operation": "branch", "create": "--force" }),
+            ),
+            OutputData::tool_start(
+                "git",
+                serde_json::json!({ "operation": "branch", "switch": "bad..name" }),
+            ),
+        ]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+
+        let outputs = run_turn(&mut agent, "Push my changes").await;
+
+        let results: Vec<&serde_json::Value> = outputs
+            .iter()
+            .filter_map(|output| match output {
+                OutputData::ToolComplete { tool_name, result } if tool_name == "git" => {
+                    Some(result)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(results.len(), 5);
+        for result in &results[..4] {
+            assert_eq!(result["success"], false);
+            assert!(tool_text(result).contains("must not start with '-' or '+'"));
+        }
+        assert_eq!(results[4]["success"], false);
+        assert!(tool_text(results[4]).contains("not a valid branch name"));
+        assert!(!dir.join("pwned").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(all(unix, feature = "testing"))]
+    #[tokio::test]
+    async fn test_bash_commands_run_under_resource_limits() {
+        let dir = temp_dir();
+        let limits = ResourceLimits::new().max_file_size_bytes(64 * 1024);
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .working_directory(&dir)
+            .sandbox_policy(SandboxPolicy::DangerFullAccess)
+            .tool(ToolConfig::bash().resource_limits(limits))
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new().turn([
+            OutputData::tool_start("bash", serde_json::json!({ "command": "echo small" })),
+            OutputData::tool_start(
+                "bash",
+                serde_json::json!({ "command": "head -c 1048576 /dev/zero > big.bin" }),
+            ),
+        ]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+
+        let outputs = run_turn(&mut agent, "Write a large file").await;
+
+        let results: Vec<&serde_json::Value> = outputs
+            .iter()
+            .filter_map(|output| match output {
+                OutputData::ToolComplete { tool_name, result } if tool_name == "bash" => {
+                    Some(result)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["success"], true);
+        assert_eq!(tool_text(results[0]).trim(), "small");
+        assert_eq!(results[1]["success"], false);
+        let written = std::fs::metadata(dir.join("big.bin")).unwrap().len();
+        assert!(written <= 64 * 1024);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(all(unix, feature = "testing"))]
+    #[tokio::test]
+    async fn test_bash_commands_are_checked_against_file_path_globs() {
+        let dir = temp_dir();
+        std::fs::create_dir(dir.join("secrets")).unwrap();
+        std::fs::write(dir.join("secrets/key"), "hunter2").unwrap();
+        std::fs::write(dir.join("notes.txt"), "hello").unwrap();
+        std::os::unix::fs::symlink(dir.join("secrets/key"), dir.join("link")).unwrap();
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .working_directory(&dir)
+            .tool(ToolConfig::file_read().deny_paths(["~/.ssh/**", "secrets/**"]))
+            .tool(ToolConfig::file_write().deny_paths(["notes.txt"]))
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let commands = [
+            "cat ~/.ssh/id_rsa",
+            "cat link",
+            "cat secre*/key",
+            "cd secrets && cat key",
+            "sh -c 'cat ~/.ssh/id_rsa'",
+            "cat $HOME/.ssh/id_rsa",
+            "echo bye > notes.txt",
+            "cat notes.txt",
+        ];
+        let backend = testing::MockBackend::new().turn(commands.map(|command| {
+            OutputData::tool_start("bash", serde_json::json!({ "command": command }))
+        }));
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+        assert!(agent.tool_registry().runs_shell_on_host());
+
+        let outputs = run_turn(&mut agent, "Read the key").await;
+
+        let results: Vec<&serde_json::Value> = outputs
+            .iter()
+            .filter_map(|output| match output {
+                OutputData::ToolComplete { tool_name, result } if tool_name == "bash" => {
+                    Some(result)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(results.len(), commands.len());
+        for result in &results[..7] {
+            assert_eq!(result["success"], false);
+            assert!(!tool_text(result).contains("hunter2"));
+        }
+        assert!(tool_text(results[1]).contains("denied to tool file_read"));
+        assert!(tool_text(results[5]).contains("expansions"));
+        assert!(tool_text(results[6]).contains("denied to tool file_write"));
+        assert_eq!(results[7]["success"], true);
+        assert_eq!(tool_text(results[7]).trim(), "hello");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_codex_shell_is_rejected_while_bash_runs_on_the_host() {
+        let limits = ResourceLimits::new().cpu_seconds(10);
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .sandbox_policy(SandboxPolicy::DangerFullAccess)
+            .tool(ToolConfig::bash().resource_limits(limits))
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new().turn([
+            OutputData::ApprovalRequired {
+                approval_id: "call-1".to_string(),
+                request: ApprovalRequest::Exec {
+                    command: vec!["yes".to_string()],
+                    cwd: temp_dir(),
+                    reason: None,
+                },
+            },
+            OutputData::Primary {
+                content: "Done".to_string(),
+            },
+        ]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+
+        let outputs = run_turn(&mut agent, "Burn some CPU").await;
+
+        assert!(
+            !outputs
+                .iter()
+                .any(|output| matches!(output, OutputData::ApprovalRequired { .. }))
+        );
+        assert!(outputs.iter().any(|output| matches!(
+            output,
+            OutputData::Error {
+                error: OutputError::ToolExecutionFailed { tool_name, .. }
+            } if tool_name == "exec_command"
+        )));
+    }
+
+    #[cfg(all(target_os = "linux", feature = "testing"))]
+    #[tokio::test]
+    async fn test_timed_out_bash_command_reaps_its_children() {
+        let dir = temp_dir();
+        let mut tool = ToolConfig::bash().kill_policy(KillPolicy::new().grace_period(1));
+        if let ToolConfig::Bash { timeout, .. } = &mut tool {
+            *timeout = Some(1);
+        }
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .working_directory(&dir)
+            .sandbox_policy(SandboxPolicy::DangerFullAccess)
+            .tool(tool)
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new().turn([OutputData::tool_start(
+            "bash",
+            serde_json::json!({ "command": "sleep 30 & echo $! > child.pid; sleep 30" }),
+        )]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+
+        let outputs = run_turn(&mut agent, "Start a server").await;
+
+        let result = tool_result(&outputs, "bash");
+        assert_eq!(result["success"], false);
+        let text = tool_text(&result);
+        assert!(text.starts_with("Timed out after 1.0s"));
+        assert!(text.contains("sleep ("));
+        // The background child is gone, or left for init to collect
+        let pid = std::fs::read_to_string(dir.join("child.pid")).unwrap();
+        let gone = match std::fs::read_to_string(format!("/proc/{}/stat", pid.trim())) {
+            Ok(stat) => stat
+                .rsplit_once(')')
+                .is_some_and(|(_, state)| state.trim_start().starts_with('Z')),
+            Err(_) => true,
+        };
+        assert!(gone);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(all(target_os = "linux", feature = "testing"))]
+    #[tokio::test]
+    async fn test_timed_out_codex_command_is_terminated_without_ending_the_turn() {
+        let mut tool = ToolConfig::bash();
+        if let ToolConfig::Bash { timeout, .. } = &mut tool {
+            *timeout = Some(1);
+        }
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(tool)
+            .build()
+            .unwrap();
+        let exec = |command: &[&str]| {
+            OutputData::tool_start("exec_command", serde_json::json!({ "command": command }))
+        };
+        let backend = testing::MockBackend::new().turn([
+            exec(&["sleep", "60"]),
+            exec(&["echo", "still running"]),
+            OutputData::Primary {
+                content: "Done".to_string(),
+            },
+        ]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+
+        let started = std::time::Instant::now();
+        let outputs = run_turn(&mut agent, "Wait for it").await;
+
+        assert!(started.elapsed() < std::time::Duration::from_secs(30));
+        let timeouts = outputs
+            .iter()
+            .filter(|output| {
+                matches!(
+                    output,
+                    OutputData::Error {
+                        error: OutputError::ToolExecutionFailed { tool_name, error }
+                    } if tool_name == "bash" && error.starts_with("Timed out after 1.0s")
+                )
+            })
+            .count();
+        assert_eq!(timeouts, 1);
+        let exit_codes: Vec<i64> = outputs
+            .iter()
+            .filter_map(|output| match output {
+                OutputData::ToolComplete { tool_name, result } if tool_name == "exec_command" => {
+                    result["exit_code"].as_i64()
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(exit_codes.len(), 2);
+        assert_ne!(exit_codes[0], 0);
+        assert_eq!(exit_codes[1], 0);
+        // The turn went on past the terminated command
+        assert!(outputs.iter().any(|output| matches!(
+            output,
+            OutputData::Primary { content } if content == "Done"
+        )));
+    }
+
+    #[test]
+    fn test_bash_limits_need_a_sandbox_outside_codex() {
+        let tool = ToolConfig::bash().resource_limits(ResourceLimits::new().cpu_seconds(10));
+
+        let result = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(tool.clone())
+            .tool_bridge(test_bridge())
+            .build();
+        assert!(result.is_err());
+
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .sandbox_backend(SandboxBackend::Container(ContainerSandbox::docker(
+                "alpine",
+            )))
+            .tool(tool)
+            .tool_bridge(test_bridge())
+            .build();
+        assert!(config.is_ok());
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_pooled_agents_start_a_fresh_conversation_per_checkout() {
+        let config = AgentConfig::builder().model("gpt-5-mini").build().unwrap();
+        let backend = testing::MockBackend::new()
+            .respond("Hello, Ann.")
+            .respond("I don't know your name.");
+        let pool = AgentPool::new(config, 1)
+            .unwrap()
+            .with_mock_backend(backend.clone());
+
+        let mut agent = pool.checkout().await.unwrap();
+        agent.query("I'm Ann.").await.unwrap();
+        drop(agent);
+
+        let mut agent = pool.checkout().await.unwrap();
+        assert!(agent.turn_records().await.is_empty());
+        let response = agent.query("What's my name?").await.unwrap();
+        assert_eq!(response, "I don't know your name.");
+        assert_eq!(backend.conversations(), 2);
+        // The same agent served both checkouts
+        assert_eq!(pool.metrics().created, 1);
+    }
+
+    #[test]
+    fn test_container_backend_runs_every_command_in_a_named_container() {
+        let backend = SandboxBackend::Container(ContainerSandbox::docker("alpine"));
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .sandbox_backend(backend.clone())
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let agent = Agent::new(config).unwrap();
+        // Codex's shell is refused in favor of the bash tool, even without limits
+        assert!(agent.tool_registry().runs_shell_on_host());
+        assert!(agent.tool_registry().handler("bash").is_some());
+
+        let command = backend.command(
+            "make test",
+            std::path::Path::new("/tmp"),
+            &std::collections::HashMap::new(),
+            &ResourceLimits::new(),
+        );
+        let args = command.args();
+        let name = args.iter().position(|arg| arg == "--name").unwrap();
+        assert!(args[name + 1].starts_with("agent-core-"));
+        assert_eq!(args[args.len() - 3..], ["sh", "-c", "make test"]);
+
+        let native = Agent::new(AgentConfig::builder().model("gpt-5-mini").build().unwrap());
+        assert!(!native.unwrap().tool_registry().runs_shell_on_host());
+    }
+
+    #[test]
+    fn test_host_tools_need_the_tool_bridge() {
+        let missing = "/nonexistent/agent-core-tool-bridge";
+        let result = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(ToolConfig::search())
+            .tool_bridge(missing)
+            .build();
+        let Err(AgentError::InvalidConfig { issues }) = result else {
+            panic!("a missing relay must fail the build");
+        };
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].path, "tool_bridge");
+
+        // Tools Codex runs itself need no relay
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(ToolConfig::bash())
+            .tool_bridge(missing)
+            .build();
+        assert!(config.is_ok());
+
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(ToolConfig::search())
+            .tool_bridge(test_bridge())
+            .build();
+        assert!(config.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_preflight_checks_the_bridge_of_registered_tools() {
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .api_key("sk-test")
+            .tool_bridge("/nonexistent/agent-core-tool-bridge")
+            .build()
+            .unwrap();
+        let agent = Agent::new(config).unwrap();
+        let report = agent.validate().await;
+        assert!(
+            report
+                .issues
+                .iter()
+                .all(|issue| issue.subject != "tool_bridge")
+        );
+
+        agent
+            .tool_registry()
+            .register(ToolConfig::search())
+            .unwrap();
+        let report = agent.validate().await;
+        let issue = report
+            .issues
+            .iter()
+            .find(|issue| issue.subject == "tool_bridge")
+            .unwrap();
+        assert_eq!(issue.area, PreflightArea::Tools);
+        assert_eq!(issue.severity, Severity::Error);
+        assert!(!report.is_ok());
+    }
+
+    /// Search provider answering every query with the same page.
+    #[cfg(feature = "testing")]
+    struct FixedSearch;
+
+    #[cfg(feature = "testing")]
+    impl SearchProvider for FixedSearch {
+        fn search<'a>(
+            &'a self,
+            query: &'a str,
+            _max_results: usize,
+        ) -> futures::future::BoxFuture<'a, Result<Vec<WebSearchResult>>> {
+            Box::pin(async move {
+                Ok(vec![WebSearchResult {
+                    title: format!("About {}", query),
+                    url: "https://example.com/rust".to_string(),
+                    snippet: None,
+                }])
+            })
+        }
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_web_search_provider_is_called_by_the_model() {
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .sandbox_policy(SandboxPolicy::DangerFullAccess)
+            .tool(ToolConfig::web_search_with(std::sync::Arc::new(
+                FixedSearch,
+            )))
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new().turn([OutputData::tool_start(
+            "web_search",
+            serde_json::json!({ "query": "rust" }),
+        )]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+
+        let outputs = run_turn(&mut agent, "Search the web").await;
+
+        let result = tool_result(&outputs, "web_search");
+        assert!(tool_text(&result).contains("https://example.com/rust"));
+    }
+
+    /// Tool recording how many of its calls run at once.
+    #[derive(Default)]
+    struct CountingTool {
+        running: std::sync::atomic::AtomicUsize,
+        peak: std::sync::atomic::AtomicUsize,
+    }
+
+    impl CustomToolHandler for CountingTool {
+        fn execute(
+            &self,
+            _parameters: serde_json::Value,
+            _context: &tools::ToolExecutionContext,
+        ) -> Result<ToolExecutionResult> {
+            use std::sync::atomic::Ordering;
+
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(ToolExecutionResult::success("counted"))
+        }
+
+        fn parameter_schema(&self) -> serde_json::Value {
+            serde_json::json!({ "type": "object", "properties": {} })
+        }
+
+        fn description(&self) -> String {
+            "Count running calls".to_string()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_concurrency_caps_parallel_model_calls() {
+        let counter = std::sync::Arc::new(CountingTool::default());
+        let tool = ToolConfig::custom(
+            "count",
+            "Count running calls",
+            serde_json::json!({ "type": "object" }),
+            counter.clone() as std::sync::Arc<dyn CustomToolHandler>,
+        );
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(tool)
+            .tool_concurrency(ToolConcurrency::new().per_tool("count", 2))
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let tools =
+            ToolRegistry::new(config.tools().to_vec()).with_concurrency(config.tool_concurrency());
+        let server = tool_server::ToolServer::new(tools, AgentController::new(), config);
+
+        let calls = (0..6).map(|_| server.call("count", serde_json::json!({})));
+        let results = futures::future::join_all(calls).await;
+
+        assert!(results.iter().all(|result| result["isError"] == false));
+        assert!(counter.peak.load(std::sync::atomic::Ordering::SeqCst) <= 2);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_tools_registered_mid_run_are_listed_to_the_model() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(ToolConfig::scratchpad())
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let mut agent = Agent::new(config.clone())
+            .unwrap()
+            .with_mock_backend(testing::MockBackend::new());
+        let server = tool_server::ToolServer::new(
+            agent.tool_registry().clone(),
+            agent.controller().clone(),
+            config,
+        );
+        let listener = server.listen().await.unwrap();
+        let env = listener
+            .mcp_server(std::path::Path::new("relay"))
+            .env
+            .unwrap();
+
+        // Connect as the relay Codex launches
+        let stream = tokio::net::TcpStream::connect(&env[tool_server::ADDRESS_VAR])
+            .await
+            .unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let token = format!("{}\n", env[tool_server::TOKEN_VAR]);
+        writer.write_all(token.as_bytes()).await.unwrap();
+        let mut lines = tokio::io::BufReader::new(reader).lines();
+        let mut send = async |message: serde_json::Value| {
+            let line = format!("{}\n", message);
+            writer.write_all(line.as_bytes()).await.unwrap();
+        };
+        let list =
+            |id: u64| serde_json::json!({ "jsonrpc": "2.0", "id": id, "method": "tools/list" });
+        let tool_names = |response: &serde_json::Value| -> Vec<String> {
+            response["result"]["tools"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|tool| tool["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let mut next = async || -> serde_json::Value {
+            let line = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            serde_json::from_str(&line).unwrap()
+        };
+
+        send(list(1)).await;
+        assert_eq!(tool_names(&next().await), ["scratchpad"]);
+
+        let (_input_tx, input_rx) = async_channel::unbounded();
+        let (plan_tx, _plan_rx) = async_channel::unbounded();
+        let (output_tx, _output_rx) = async_channel::unbounded();
+        let handle = agent.execute(input_rx, plan_tx, output_tx).await.unwrap();
+        handle.register_tool(ToolConfig::search()).unwrap();
+
+        let notification = next().await;
+        assert_eq!(notification["method"], "notifications/tools/list_changed");
+        send(list(2)).await;
+        assert_eq!(tool_names(&next().await), ["scratchpad", "search"]);
+    }
+
+    /// Tool blocking its thread until a message arrives.
+    #[cfg(feature = "testing")]
+    struct WaitingTool {
+        messages: std::sync::Mutex<std::sync::mpsc::Receiver<&'static str>>,
+    }
+
+    #[cfg(feature = "testing")]
+    impl CustomToolHandler for WaitingTool {
+        fn execute(
+            &self,
+            _parameters: serde_json::Value,
+            _context: &tools::ToolExecutionContext,
+        ) -> Result<ToolExecutionResult> {
+            let messages = self.messages.lock().unwrap();
+            match messages.recv_timeout(std::time::Duration::from_secs(5)) {
+                Ok(message) => Ok(ToolExecutionResult::success(message)),
+                Err(e) => Ok(ToolExecutionResult::error(e.to_string())),
+            }
+        }
+
+        fn parameter_schema(&self) -> serde_json::Value {
+            serde_json::json!({ "type": "object", "properties": {} })
+        }
+
+        fn description(&self) -> String {
+            "Wait for a message".to_string()
+        }
+    }
+
+    // A single-threaded runtime only delivers the message if the blocking
+    // tool runs off the runtime's thread
+    #[cfg(feature = "testing")]
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_blocking_tool_does_not_stall_the_runtime() {
+        let (sender, messages) = std::sync::mpsc::channel();
+        let tool = ToolConfig::custom(
+            "wait",
+            "Wait for a message",
+            serde_json::json!({ "type": "object" }),
+            std::sync::Arc::new(WaitingTool {
+                messages: std::sync::Mutex::new(messages),
+            }) as std::sync::Arc<dyn CustomToolHandler>,
+        );
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(tool)
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new()
+            .turn([OutputData::tool_start("wait", serde_json::json!({}))]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            sender.send("delivered").unwrap();
+        });
+
+        let outputs = run_turn(&mut agent, "Wait").await;
+
+        assert_eq!(tool_text(&tool_result(&outputs, "wait")), "delivered");
+    }
+
+    /// Tool spinning until its call is cancelled.
+    #[cfg(feature = "testing")]
+    struct SpinningTool {
+        started: tokio::sync::mpsc::UnboundedSender<()>,
+        cancelled: std::sync::atomic::AtomicBool,
+    }
+
+    #[cfg(feature = "testing")]
+    impl CustomToolHandler for SpinningTool {
+        fn execute(
+            &self,
+            _parameters: serde_json::Value,
+            context: &tools::ToolExecutionContext,
+        ) -> Result<ToolExecutionResult> {
+            let _ = self.started.send(());
+            let yield_point = YieldPoint::new(context.cancellation.clone());
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+            while std::time::Instant::now() < deadline {
+                if yield_point.check().is_err() {
+                    self.cancelled
+                        .store(true, std::sync::atomic::Ordering::SeqCst);
+                    return Ok(ToolExecutionResult::error("cancelled"));
+                }
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            Ok(ToolExecutionResult::success("finished"))
+        }
+
+        fn parameter_schema(&self) -> serde_json::Value {
+            serde_json::json!({ "type": "object", "properties": {} })
+        }
+
+        fn description(&self) -> String {
+            "Spin until cancelled".to_string()
+        }
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_cancel_turn_cancels_running_tool_calls() {
+        let (started, mut starts) = tokio::sync::mpsc::unbounded_channel();
+        let spinner = std::sync::Arc::new(SpinningTool {
+            started,
+            cancelled: std::sync::atomic::AtomicBool::new(false),
+        });
+        let tool = ToolConfig::custom(
+            "spin",
+            "Spin until cancelled",
+            serde_json::json!({ "type": "object" }),
+            spinner.clone() as std::sync::Arc<dyn CustomToolHandler>,
+        );
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(tool)
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new()
+            .turn([OutputData::tool_start("spin", serde_json::json!({}))]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+        let (input_tx, input_rx) = async_channel::unbounded();
+        let (plan_tx, _plan_rx) = async_channel::unbounded();
+        let (output_tx, _output_rx) = async_channel::unbounded();
+        let _handle = agent.execute(input_rx, plan_tx, output_tx).await.unwrap();
+
+        input_tx.send(InputMessage::new("Spin")).await.unwrap();
+        starts.recv().await.unwrap();
+        agent.controller().cancel_turn().await.unwrap();
+
+        tokio::time::timeout(std::time::Duration::from_secs(2), async {
+            while !spinner.cancelled.load(std::sync::atomic::Ordering::SeqCst) {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_custom_tool_call_is_cancelled_after_its_timeout() {
+        let (started, _starts) = tokio::sync::mpsc::unbounded_channel();
+        let spinner = std::sync::Arc::new(SpinningTool {
+            started,
+            cancelled: std::sync::atomic::AtomicBool::new(false),
+        });
+        let tool = ToolConfig::custom(
+            "spin",
+            "Spin until cancelled",
+            serde_json::json!({ "type": "object" }),
+            spinner.clone() as std::sync::Arc<dyn CustomToolHandler>,
+        )
+        .timeout(1);
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(tool)
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new()
+            .turn([OutputData::tool_start("spin", serde_json::json!({}))]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+
+        let started = std::time::Instant::now();
+        let outputs = run_turn(&mut agent, "Spin").await;
+
+        assert!(started.elapsed() < std::time::Duration::from_secs(4));
+        assert!(tool_text(&tool_result(&outputs, "spin")).contains("Timed out after 1.0s"));
+        assert!(outputs.iter().any(|output| matches!(
+            output,
+            OutputData::Error {
+                error: OutputError::ToolExecutionFailed { tool_name, error }
+            } if tool_name == "spin" && error.contains("Timed out")
+        )));
+        tokio::time::timeout(std::time::Duration::from_secs(2), async {
+            while !spinner.cancelled.load(std::sync::atomic::Ordering::SeqCst) {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_step_mode_pauses_before_host_tools() {
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(ToolConfig::scratchpad())
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new().turn([OutputData::tool_start(
+            "scratchpad",
+            serde_json::json!({ "operation": "write", "key": "plan", "value": "step" }),
+        )]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+        let controller = agent.controller().clone();
+        controller.set_step_mode(true);
+        let mut state = controller.subscribe();
+
+        let turn = tokio::spawn(async move { run_turn(&mut agent, "Plan").await });
+        let paused = state
+            .wait_for(|state| state.is_paused)
+            .await
+            .unwrap()
+            .clone();
+        assert_eq!(
+            paused.execution_state,
+            controller::PublicExecutionState::Paused {
+                reason: PauseReason::Step {
+                    tool_name: "scratchpad".to_string(),
+                },
+            }
+        );
+        controller.step().await.unwrap();
+        let outputs = turn.await.unwrap();
+
+        assert_eq!(tool_result(&outputs, "scratchpad")["success"], true);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_step_mode_keeps_the_approval_policy() {
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .approval_policy(AskForApproval::Never)
+            .build()
+            .unwrap();
+        let sandbox_policy = config.sandbox_policy().clone();
+        let backend = testing::MockBackend::new().respond("Done");
+        let mut agent = Agent::new(config)
+            .unwrap()
+            .with_mock_backend(backend.clone());
+        agent.controller().set_step_mode(true);
+
+        run_turn(&mut agent, "Build it").await;
+
+        assert_eq!(
+            backend.turn_policies(),
+            vec![(AskForApproval::Never, sandbox_policy)]
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_breakpoint_pauses_before_host_tool() {
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(ToolConfig::scratchpad())
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new().turn([OutputData::tool_start(
+            "scratchpad",
+            serde_json::json!({ "operation": "read", "key": "plan" }),
+        )]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+        let controller = agent.controller().clone();
+        controller.break_on("scratchpad").await;
+        let mut state = controller.subscribe();
+
+        let turn = tokio::spawn(async move { run_turn(&mut agent, "Plan").await });
+        let paused = state
+            .wait_for(|state| state.is_paused)
+            .await
+            .unwrap()
+            .clone();
+        assert_eq!(
+            paused.execution_state,
+            controller::PublicExecutionState::Paused {
+                reason: PauseReason::Breakpoint {
+                    tool_name: "scratchpad".to_string(),
+                },
+            }
+        );
+        controller.resume().await.unwrap();
+        let outputs = turn.await.unwrap();
+
+        assert!(tool_result(&outputs, "scratchpad").is_object());
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_explain_runs_on_the_agent_backend() {
+        let config = AgentConfig::builder().model("gpt-5-mini").build().unwrap();
+        let explanation = serde_json::json!({
+            "summary": "Renamed a module",
+            "decisions": [{ "action": "rename", "rationale": "clearer name" }],
+        });
+        let backend = testing::MockBackend::new()
+            .respond("Renamed the module.")
+            .respond(explanation.to_string());
+        let mut agent = Agent::new(config)
+            .unwrap()
+            .with_mock_backend(backend.clone());
+        let (input_tx, input_rx) = async_channel::unbounded();
+        let (plan_tx, _plan_rx) = async_channel::unbounded();
+        let (output_tx, _output_rx) = async_channel::unbounded();
+        let handle = agent.execute(input_rx, plan_tx, output_tx).await.unwrap();
+
+        input_tx
+            .send(InputMessage::new("Rename the module"))
+            .await
+            .unwrap();
+        let turn_id = tokio::time::timeout(std::time::Duration::from_secs(2), async {
+            loop {
+                if let Some(record) = handle.turn_records().await.first() {
+                    return record.turn_id;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let explanation = handle.explain(turn_id).await.unwrap();
+
+        assert_eq!(explanation.turn_id, turn_id);
+        assert_eq!(explanation.summary, "Renamed a module");
+        assert_eq!(explanation.decisions[0].action, "rename");
+        assert_eq!(backend.remaining_turns(), 0);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_suggestions_run_on_the_agent_backend_after_the_turn() {
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .suggestions(SuggestionsConfig::new("gpt-5-nano").count(2))
+            .build()
+            .unwrap();
+        let suggestions = serde_json::json!({ "prompts": ["Add tests", "Update the docs"] });
+        let backend = testing::MockBackend::new()
+            .respond("Renamed the module.")
+            .respond(suggestions.to_string());
+        let mut agent = Agent::new(config)
+            .unwrap()
+            .with_mock_backend(backend.clone());
+
+        let outputs = run_turn(&mut agent, "Rename the module").await;
+
+        let response = outputs
+            .iter()
+            .position(|output| matches!(output, OutputData::Primary { .. }))
+            .unwrap();
+        let suggested = outputs
+            .iter()
+            .position(|output| matches!(output, OutputData::Suggestions { .. }))
+            .unwrap();
+        assert!(suggested > response);
+        assert!(matches!(
+            &outputs[suggested],
+            OutputData::Suggestions { prompts } if prompts == &["Add tests", "Update the docs"]
+        ));
+        assert!(backend.inputs()[1].contains("Renamed the module."));
+        assert_eq!(backend.remaining_turns(), 0);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_breakpoint_keeps_the_approval_policy() {
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .approval_policy(AskForApproval::OnRequest)
+            .build()
+            .unwrap();
+        let sandbox_policy = config.sandbox_policy().clone();
+        let request = ApprovalRequest::Exec {
+            command: vec!["rm".to_string(), "-r".to_string(), "build".to_string()],
+            cwd: temp_dir(),
+            reason: Some("Clean the build".to_string()),
+        };
+        let backend = testing::MockBackend::new().turn([OutputData::ApprovalRequired {
+            approval_id: "call-1".to_string(),
+            request: request.clone(),
+        }]);
+        let mut agent = Agent::new(config)
+            .unwrap()
+            .with_mock_backend(backend.clone());
+        let controller = agent.controller().clone();
+        controller.break_on("exec_command").await;
+        let mut state = controller.subscribe();
+
+        let turn = tokio::spawn(async move { run_turn(&mut agent, "Clean up").await });
+        let paused = state
+            .wait_for(|state| state.is_paused)
+            .await
+            .unwrap()
+            .clone();
+        assert_eq!(
+            paused.execution_state,
+            controller::PublicExecutionState::Paused {
+                reason: PauseReason::Breakpoint {
+                    tool_name: "exec_command".to_string(),
+                },
+            }
+        );
+        controller.resume().await.unwrap();
+        let outputs = turn.await.unwrap();
+
+        assert_eq!(
+            backend.turn_policies(),
+            vec![(AskForApproval::OnRequest, sandbox_policy)]
+        );
+        assert!(outputs.iter().any(|output| matches!(
+            output,
+            OutputData::ApprovalRequired { request: asked, .. } if *asked == request
+        )));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_scratchpad_notes_persist_across_turns() {
+        let config = AgentConfig::builder()
+            .model("gpt-5-mini")
+            .tool(ToolConfig::scratchpad())
+            .tool_bridge(test_bridge())
+            .build()
+            .unwrap();
+        let backend = testing::MockBackend::new()
+            .turn([OutputData::tool_start(
+                "scratchpad",
+                serde_json::json!({ "operation": "write", "key": "plan", "value": "fix the parser" }),
+            )])
+            .turn([OutputData::tool_start(
+                "scratchpad",
+                serde_json::json!({ "operation": "read", "key": "plan" }),
+            )]);
+        let mut agent = Agent::new(config).unwrap().with_mock_backend(backend);
+
+        let first = run_turn(&mut agent, "Remember the plan").await;
+        assert_eq!(tool_result(&first, "scratchpad")["success"], true);
+        let second = run_turn(&mut agent, "What was the plan?").await;
+
+        assert_eq!(
+            tool_text(&tool_result(&second, "scratchpad")),
+            "fix the parser"
+        );
+    }
+
+    /// Model server answering one streamed request with `events`, returning
+    /// its base URL and a task resolving to the request's head and body.
+    #[cfg(feature = "model-bridge")]
+    async fn fake_model_server(
+        events: Vec<String>,
+    ) -> (String, tokio::task::JoinHandle<(String, serde_json::Value)>) {
+        let reply = events
+            .iter()
+            .map(|event| format!("{}\n\n", event))
+            .collect();
+        raw_model_server(vec![reply]).await
+    }
+
+    /// Model server answering one streamed request with a reply body sent in
+    /// `pieces`, flushed one at a time.
+    #[cfg(feature = "model-bridge")]
+    async fn raw_model_server(
+        pieces: Vec<String>,
+    ) -> (String, tokio::task::JoinHandle<(String, serde_json::Value)>) {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut head = String::new();
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).await.unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                head.push_str(&line.to_ascii_lowercase());
+            }
+            let length = head
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .map_or(0, |length| length.trim().parse().unwrap());
+            let mut body = vec![0; length];
+            stream.read_exact(&mut body).await.unwrap();
+
+            let length: usize = pieces.iter().map(String::len).sum();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\n\r\n",
+                length
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            for piece in pieces {
+                stream.write_all(piece.as_bytes()).await.unwrap();
+                stream.flush().await.unwrap();
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+            (head, serde_json::from_slice(&body).unwrap())
+        });
+        (url, server)
+    }
+
+    /// Chat Completions request Codex sends with a shell tool.
+    #[cfg(feature = "model-bridge")]
+    fn shell_request(model: &str) -> serde_json::Value {
+        serde_json::json!({
+            "model": model,
+            "stream": true,
+            "messages": [
+                { "role": "system", "content": "You are a coding agent." },
+                { "role": "user", "content": "What is in this directory?" },
+            ],
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "shell",
+                    "description": "Runs a shell command",
+                    "parameters": { "type": "object", "properties": { "command": { "type": "array" } } },
+                },
+            }],
+        })
+    }
+
+    /// Chunks the bridge streams back for `request`, sent as Codex would.
+    #[cfg(feature = "model-bridge")]
+    async fn bridge_chunks(
+        bridge: &model_bridge::ModelBridge,
+        request: &serde_json::Value,
+    ) -> Vec<serde_json::Value> {
+        let reply = reqwest::Client::new()
+            .post(format!("{}/chat/completions", bridge.url()))
+            .header("x-agent-core-token", bridge.token())
+            .json(request)
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(reply.contains("data: [DONE]"));
+        reply
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter(|data| *data != "[DONE]")
+            .map(|data| serde_json::from_str(data).unwrap())
+            .collect()
+    }
+
+    /// Streamed text and the single tool call of a bridged reply.
+    #[cfg(feature = "model-bridge")]
+    fn bridged_reply(chunks: &[serde_json::Value]) -> (String, serde_json::Value) {
+        let text = chunks
+            .iter()
+            .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+            .collect();
+        let call = chunks
+            .iter()
+            .find_map(|chunk| chunk["choices"][0]["delta"]["tool_calls"].get(0))
+            .unwrap();
+        assert_eq!(
+            chunks.last().unwrap()["choices"][0]["finish_reason"],
+            "tool_calls"
+        );
+        (text, call.clone())
+    }
+
+    #[cfg(feature = "prompt-tools")]
+    #[tokio::test]
+    async fn test_prompt_tools_turn_tool_call_blocks_into_tool_calls() {
+        // Local model splitting the tool call block across deltas
+        let events = [
+            "Let me look.\n<tool",
+            "_call>\n{\"name\": \"shell\", ",
+            "\"arguments\": {\"command\": [\"ls\"]}}\n</tool_call>",
+        ]
+        .iter()
+        .map(|delta| {
+            let chunk = serde_json::json!({ "choices": [{ "delta": { "content": delta } }] });
+            format!("data: {}", chunk)
+        })
+        .chain(["data: [DONE]".to_string()])
+        .collect();
+        let (url, server) = fake_model_server(events).await;
+        let provider = ModelProvider::llama_cpp(url).prompt_tools();
+        let bridge = model_bridge::ModelBridge::listen(provider, None)
+            .await
+            .unwrap();
+
+        let unauthorized = reqwest::Client::new()
+            .post(format!("{}/chat/completions", bridge.url()))
+            .json(&shell_request("gemma2"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unauthorized.status(), 401);
+        let chunks = bridge_chunks(&bridge, &shell_request("gemma2")).await;
+        let (text, call) = bridged_reply(&chunks);
+
+        assert_eq!(text, "Let me look.\n");
+        assert_eq!(call["function"]["name"], "shell");
+        let arguments: serde_json::Value =
+            serde_json::from_str(call["function"]["arguments"].as_str().unwrap()).unwrap();
+        assert_eq!(arguments, serde_json::json!({ "command": ["ls"] }));
+        let (_, sent) = server.await.unwrap();
+        assert!(sent.get("tools").is_none());
+        let system = sent["messages"][0]["content"].as_str().unwrap();
+        assert!(system.starts_with("You are a coding agent."));
+        assert!(system.contains("<tool_call>") && system.contains("## shell"));
+    }
+
+    #[cfg(feature = "prompt-tools")]
+    #[tokio::test]
+    async fn test_model_bridge_reads_server_sent_events_split_across_reads() {
+        let chunk = |text: &str| {
+            serde_json::json!({ "choices": [{ "delta": { "content": text } }] }).to_string()
+        };
+        let pieces = vec![
+            // Keep-alive comment, carrying no data
+            ": ping\r\n\r\n".to_string(),
+            // Event split in the middle of its field name
+            format!("data: {}\r\n\r\nda", chunk("Hel")),
+            format!("ta: {}\r\n\r\n", chunk("lo")),
+            // Data spread across two lines of one event
+            "event: delta\ndata: {\"choices\":\ndata: [{\"delta\":{\"content\":\" world\"}}]}\n\n"
+                .to_string(),
+            // Last event with no space after the colon and no blank line
+            format!("data:{}", chunk("!")),
+        ];
+        let (url, server) = raw_model_server(pieces).await;
+        let provider = ModelProvider::llama_cpp(url).prompt_tools();
+        let bridge = model_bridge::ModelBridge::listen(provider, None)
+            .await
+            .unwrap();
+
+        let chunks = bridge_chunks(&bridge, &shell_request("gemma2")).await;
+        let text: String = chunks
+            .iter()
+            .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+            .collect();
+
+        assert_eq!(text, "Hello world!");
+        assert_eq!(
+            chunks.last().unwrap()["choices"][0]["finish_reason"],
+            "stop"
+        );
+        server.await.unwrap();
+    }
+
+    #[cfg(feature = "prompt-tools")]
+    #[tokio::test]
+    async fn test_prompt_tools_write_earlier_calls_and_results_into_the_prompt() {
+        let events = ["The directory holds ", "a single file. <tool"]
+            .iter()
+            .map(|delta| {
+                let chunk = serde_json::json!({ "choices": [{ "delta": { "content": delta } }] });
+                format!("data: {}", chunk)
+            })
+            .chain(["data: [DONE]".to_string()])
+            .collect();
+        let (url, server) = fake_model_server(events).await;
+        let mut provider = ModelProvider::ollama().prompt_tools();
+        provider.base_url = url;
+        let bridge = model_bridge::ModelBridge::listen(provider, None)
+            .await
+            .unwrap();
+
+        // A second request, after the model listed the directory
+        let mut request = shell_request("qwen2.5-coder");
+        let messages = request["messages"].as_array_mut().unwrap();
+        messages.push(
+            serde_json::json!({ "role": "assistant", "content": "Listing it.", "tool_calls": [{
+            "id": "call_0", "type": "function",
+            "function": { "name": "shell", "arguments": "{\"command\":[\"ls\"]}" },
+        }] }),
+        );
+        messages.push(
+            serde_json::json!({ "role": "tool", "tool_call_id": "call_0", "content": "main.rs" }),
+        );
+        messages.push(serde_json::json!({ "role": "user", "content": "Summarize it." }));
+        let chunks = bridge_chunks(&bridge, &request).await;
+        let text: String = chunks
+            .iter()
+            .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+            .collect();
+
+        // A marker prefix the reply ended on is text after all
+        assert_eq!(text, "The directory holds a single file. <tool");
+        assert!(
+            chunks
+                .iter()
+                .all(|chunk| chunk["choices"][0]["delta"].get("tool_calls").is_none())
+        );
+        assert_eq!(
+            chunks.last().unwrap()["choices"][0]["finish_reason"],
+            "stop"
+        );
+        let (_, sent) = server.await.unwrap();
+        let sent = sent["messages"].as_array().unwrap();
+        let roles: Vec<&str> = sent
+            .iter()
+            .map(|message| message["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(roles, ["system", "user", "assistant", "user"]);
+        let call = sent[2]["content"].as_str().unwrap();
+        let block = call
+            .strip_prefix("Listing it.\n<tool_call>\n")
+            .and_then(|call| call.strip_suffix("\n</tool_call>"))
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(block).unwrap(),
+            serde_json::json!({ "name": "shell", "arguments": { "command": ["ls"] } })
+        );
+        // The result and the next message merge, as the roles must alternate
+        assert_eq!(
+            sent[3]["content"],
+            "<tool_result name=\"shell\">\nmain.rs\n</tool_result>\n\nSummarize it."
+        );
+    }
+
+    #[cfg(feature = "provider-anthropic")]
+    #[tokio::test]
+    async fn test_anthropic_provider_speaks_the_messages_api() {
+        let events = [
+            serde_json::json!({ "type": "message_start", "message": {} }),
+            serde_json::json!({ "type": "content_block_start", "index": 0,
+                "content_block": { "type": "text", "text": "" } }),
+            serde_json::json!({ "type": "content_block_delta", "index": 0,
+                "delta": { "type": "text_delta", "text": "Let me look." } }),
+            serde_json::json!({ "type": "content_block_stop", "index": 0 }),
+            serde_json::json!({ "type": "content_block_start", "index": 1,
+                "content_block": { "type": "tool_use", "id": "toolu_1", "name": "shell", "input": {} } }),
+            serde_json::json!({ "type": "content_block_delta", "index": 1,
+                "delta": { "type": "input_json_delta", "partial_json": "{\"command\": " } }),
+            serde_json::json!({ "type": "content_block_delta", "index": 1,
+                "delta": { "type": "input_json_delta", "partial_json": "[\"ls\"]}" } }),
+            serde_json::json!({ "type": "content_block_stop", "index": 1 }),
+            serde_json::json!({ "type": "message_delta", "delta": { "stop_reason": "tool_use" } }),
+            serde_json::json!({ "type": "message_stop" }),
+        ]
+        .iter()
+        .map(|event| format!("event: {}\ndata: {}", event["type"].as_str().unwrap(), event))
+        .collect();
+        let (url, server) = fake_model_server(events).await;
+        let mut provider = ModelProvider::anthropic();
+        provider.base_url = url;
+        provider.env_key = None;
+        let bridge = model_bridge::ModelBridge::listen(provider, Some("sk-ant".to_string()))
+            .await
+            .unwrap();
+
+        // A second request, after the model listed the directory
+        let mut request = shell_request("claude-sonnet-4-5");
+        let messages = request["messages"].as_array_mut().unwrap();
+        messages.push(
+            serde_json::json!({ "role": "assistant", "content": null, "tool_calls": [{
+            "id": "toolu_0", "type": "function",
+            "function": { "name": "shell", "arguments": "{\"command\":[\"pwd\"]}" },
+        }] }),
+        );
+        messages.push(
+            serde_json::json!({ "role": "tool", "tool_call_id": "toolu_0", "content": "/src" }),
+        );
+        let chunks = bridge_chunks(&bridge, &request).await;
+        let (text, call) = bridged_reply(&chunks);
+
+        assert_eq!(text, "Let me look.");
+        assert_eq!(call["id"], "toolu_1");
+        assert_eq!(call["function"]["arguments"], "{\"command\": [\"ls\"]}");
+        let (head, sent) = server.await.unwrap();
+        assert!(head.starts_with("post /v1/messages "));
+        assert!(head.contains("x-api-key: sk-ant"));
+        assert!(head.contains("anthropic-version: 2023-06-01"));
+        assert_eq!(sent["system"], "You are a coding agent.");
+        assert_eq!(sent["tools"][0]["name"], "shell");
+        assert_eq!(sent["tools"][0]["input_schema"]["type"], "object");
+        assert_eq!(sent["messages"][1]["content"][0]["type"], "tool_use");
+        assert_eq!(
+            sent["messages"][1]["content"][0]["input"]["command"][0],
+            "pwd"
+        );
+        assert_eq!(
+            sent["messages"][2]["content"][0],
+            serde_json::json!({ "type": "tool_result", "tool_use_id": "toolu_0", "content": "/src" })
+        );
+    }
+
+    #[cfg(feature = "provider-anthropic")]
+    #[tokio::test]
+    async fn test_anthropic_provider_matches_the_recorded_exchange() {
+        let stream = include_str!("../fixtures/anthropic/messages_stream.txt");
+        let (url, server) = raw_model_server(vec![stream.to_string()]).await;
+        let mut provider = ModelProvider::anthropic();
+        provider.base_url = url;
+        provider.env_key = None;
+        let bridge = model_bridge::ModelBridge::listen(provider, None)
+            .await
+            .unwrap();
+
+        let request: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/anthropic/chat_request.json")).unwrap();
+        let chunks = bridge_chunks(&bridge, &request).await;
+        let (text, call) = bridged_reply(&chunks);
+
+        // Thinking blocks are not passed on
+        assert_eq!(text, "The linker is missing, so I will install it.");
+        assert_eq!(call["id"], "toolu_02");
+        assert_eq!(call["function"]["name"], "shell");
+        assert_eq!(
+            call["function"]["arguments"],
+            "{\"command\": [\"apt-get\", \"install\", \"-y\", \"gcc\"]}"
+        );
+        let (_, sent) = server.await.unwrap();
+        let expected: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/anthropic/messages_request.json"))
+                .unwrap();
+        assert_eq!(sent, expected);
+    }
+
+    #[cfg(feature = "provider-anthropic")]
+    #[tokio::test]
+    async fn test_anthropic_overload_is_passed_on_for_codex_to_retry() {
+        let stream = include_str!("../fixtures/anthropic/overloaded_stream.txt");
+        let (url, server) = raw_model_server(vec![stream.to_string()]).await;
+        let mut provider = ModelProvider::anthropic();
+        provider.base_url = url;
+        provider.env_key = None;
+        let bridge = model_bridge::ModelBridge::listen(provider, None)
+            .await
+            .unwrap();
+
+        let request: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/anthropic/chat_request.json")).unwrap();
+        let response = reqwest::Client::new()
+            .post(format!("{}/chat/completions", bridge.url()))
+            .header("x-agent-core-token", bridge.token())
+            .json(&request)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 529);
+        assert!(response.text().await.unwrap().contains("Overloaded"));
+        server.await.unwrap();
+    }
+
+    #[cfg(feature = "provider-gemini")]
+    #[tokio::test]
+    async fn test_gemini_provider_speaks_the_gemini_api() {
+        let events = [
+            serde_json::json!({ "candidates": [{ "content": { "role": "model", "parts": [
+                { "text": "Planning the listing", "thought": true },
+                { "text": "Let me look." },
+            ] } }] }),
+            serde_json::json!({ "candidates": [{ "content": { "role": "model", "parts": [
+                { "functionCall": { "name": "shell", "args": { "command": ["ls"] } },
+                  "thoughtSignature": "c2lnbmF0dXJl" },
+            ] }, "finishReason": "STOP" }] }),
+        ]
+        .iter()
+        .map(|event| format!("data: {}", event))
+        .collect();
+        let (url, server) = fake_model_server(events).await;
+        let mut provider = ModelProvider::gemini();
+        provider.base_url = url;
+        provider.env_key = None;
+        let bridge = model_bridge::ModelBridge::listen(provider, Some("gm-key".to_string()))
+            .await
+            .unwrap();
+
+        // A second request, after the model printed the working directory
+        let mut request = shell_request("gemini-2.5-pro");
+        let messages = request["messages"].as_array_mut().unwrap();
+        messages.push(
+            serde_json::json!({ "role": "assistant", "content": null, "tool_calls": [{
+            "id": "call_0.c2ln", "type": "function",
+            "function": { "name": "shell", "arguments": "{\"command\":[\"pwd\"]}" },
+        }] }),
+        );
+        messages.push(
+            serde_json::json!({ "role": "tool", "tool_call_id": "call_0.c2ln", "content": "/src" }),
+        );
+        let chunks = bridge_chunks(&bridge, &request).await;
+        let (text, call) = bridged_reply(&chunks);
+
+        assert_eq!(text, "Let me look.");
+        assert!(call["id"].as_str().unwrap().ends_with(".c2lnbmF0dXJl"));
+        assert_eq!(call["function"]["arguments"], "{\"command\":[\"ls\"]}");
+        let (head, sent) = server.await.unwrap();
+        assert!(head.starts_with("post /v1/models/gemini-2.5-pro:streamgeneratecontent?alt=sse "));
+        assert!(head.contains("x-goog-api-key: gm-key"));
+        assert_eq!(
+            sent["systemInstruction"]["parts"][0]["text"],
+            "You are a coding agent."
+        );
+        let declaration = &sent["tools"][0]["functionDeclarations"][0];
+        assert_eq!(declaration["name"], "shell");
+        assert_eq!(declaration["parametersJsonSchema"]["type"], "object");
+        assert_eq!(sent["contents"][1]["role"], "model");
+        assert_eq!(sent["contents"][1]["parts"][0]["thoughtSignature"], "c2ln");
+        assert_eq!(
+            sent["contents"][2]["parts"][0]["functionResponse"],
+            serde_json::json!({ "name": "shell", "response": { "content": "/src" } })
+        );
+    }
+
+    #[cfg(feature = "provider-gemini")]
+    #[tokio::test]
+    async fn test_gemini_provider_matches_the_recorded_exchange() {
+        let stream = include_str!("../fixtures/gemini/generate_stream.txt");
+        let (url, server) = raw_model_server(vec![stream.to_string()]).await;
+        let mut provider = ModelProvider::gemini();
+        provider.base_url = url;
+        provider.env_key = None;
+        let bridge = model_bridge::ModelBridge::listen(provider, None)
+            .await
+            .unwrap();
+
+        let request: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/gemini/chat_request.json")).unwrap();
+        let chunks = bridge_chunks(&bridge, &request).await;
+        let (text, call) = bridged_reply(&chunks);
+
+        // Thought summaries are not passed on
+        assert_eq!(text, "The linker is missing, so I will install it.");
+        let id = call["id"].as_str().unwrap();
+        assert!(id.starts_with("call_") && id.ends_with(".Cs4BAdHtim8="));
+        assert_eq!(call["function"]["name"], "shell");
+        assert_eq!(
+            call["function"]["arguments"],
+            "{\"command\":[\"apt-get\",\"install\",\"-y\",\"gcc\"]}"
+        );
+        let (_, sent) = server.await.unwrap();
+        let expected: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/gemini/generate_request.json")).unwrap();
+        assert_eq!(sent, expected);
+    }
+
+    #[cfg(feature = "provider-gemini")]
+    #[tokio::test]
+    async fn test_gemini_overload_is_passed_on_for_codex_to_retry() {
+        let stream = include_str!("../fixtures/gemini/overloaded_stream.txt");
+        let (url, server) = raw_model_server(vec![stream.to_string()]).await;
+        let mut provider = ModelProvider::gemini();
+        provider.base_url = url;
+        provider.env_key = None;
+        let bridge = model_bridge::ModelBridge::listen(provider, None)
+            .await
+            .unwrap();
+
+        let request: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/gemini/chat_request.json")).unwrap();
+        let response = reqwest::Client::new()
+            .post(format!("{}/chat/completions", bridge.url()))
+            .header("x-agent-core-token", bridge.token())
+            .json(&request)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 503);
+        assert!(response.text().await.unwrap().contains("overloaded"));
+        server.await.unwrap();
+    }
+
+    #[test]
+    fn test_lexicon_filter_masks_replaces_and_blocks_whole_words() {
+        let filter = LexiconFilter::new()
+            .mask(["darn"])
+            .replace(["good grief"], "oh no");
+        assert_eq!(
+            filter.filter("Good grief, darn it. Darning is fine."),
+            "oh no, **** it. Darning is fine."
+        );
+
+        let filter = LexiconFilter::new()
+            .wordlist("# secrets\n\nforbidden\n", LexiconAction::Block)
+            .block_message("[nope]");
+        assert_eq!(filter.filter("A forbidden word"), "[nope]");
+        assert_eq!(
+            filter.filter("Nothing unforbidden here"),
+            "Nothing unforbidden here"
+        );
+    }
+
+    #[test]
+    fn test_lexicon_filter_follows_the_locale() {
+        // Turkish uppercase I folds to dotless ı
+        let turkish = LexiconFilter::new().locale("tr").mask(["kırmızı"]);
+        assert_eq!(turkish.filter("KIRMIZI bayrak"), "******* bayrak");
+        let default = LexiconFilter::new().mask(["kırmızı"]);
+        assert_eq!(default.filter("KIRMIZI bayrak"), "KIRMIZI bayrak");
+
+        // Japanese has no spaces, so terms match inside words
+        let japanese = LexiconFilter::new().locale("ja-JP").mask(["バカ"]);
+        assert_eq!(japanese.filter("このバカな"), "この**な");
+        let default = LexiconFilter::new().mask(["バカ"]);
+        assert_eq!(default.filter("このバカな"), "このバカな");
+    }
+
+    #[test]
+    fn test_lexicon_stream_catches_terms_split_across_deltas() {
+        let filter = LexiconFilter::new().mask(["darn"]);
+        let mut stream = lexicon::LexiconStream::default();
+        assert_eq!(
+            stream.push(&filter, "That was da").as_deref(),
+            Some("That ")
+        );
+        assert_eq!(stream.push(&filter, "r").as_deref(), Some("was "));
+        assert_eq!(stream.push(&filter, "n good.").as_deref(), Some("**** "));
+
+        let filter = LexiconFilter::new().block(["secret"]);
+        stream.reset();
+        // Text a term could still extend into is held back
+        assert_eq!(stream.push(&filter, "The sec").as_deref(), None);
+        assert_eq!(
+            stream.push(&filter, "ret is out").as_deref(),
+            Some("[response withheld]")
+        );
+        assert_eq!(stream.push(&filter, " and more.").as_deref(), None);
+    }
+
+    #[test]
+    fn test_output_processors_rewrite_links_emoji_and_headings() {
+        let links = LinkRewriter::new("https://docs.example.com/guide/");
+        assert_eq!(
+            links.process(
+                "[a](setup.md) ![b](./img/x.png) [c](/api) [d](#top) [e](https://x.io) [f](mailto:a@b.c)"
+                    .to_string()
+            ),
+            "[a](https://docs.example.com/guide/setup.md) ![b](https://docs.example.com/guide/img/x.png) \
+             [c](https://docs.example.com/api) [d](#top) [e](https://x.io) [f](mailto:a@b.c)"
+        );
+
+        let text = "Done 👍🏽 👨\u{200D}👩\u{200D}👧 ok ❤\u{FE0F}".to_string();
+        assert_eq!(EmojiPolicy::Keep.process(text.clone()), text);
+        assert_eq!(EmojiPolicy::Strip.process(text), "Done   ok ");
+
+        let headings = HeadingLevels::new(2, 3);
+        assert_eq!(
+            headings.process("# Title\n#### Deep\n```\n# comment\n```\n#hashtag".to_string()),
+            "## Title\n### Deep\n```\n# comment\n```\n#hashtag"
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_pipeline_transforms_inputs_and_retries_failed_steps() {
+        let overloaded = OutputData::error(OutputError::ModelRequestFailed {
+            error: "overloaded".to_string(),
+        });
+        let backend = testing::MockBackend::new()
+            .respond("Alice, 42")
+            .turn([overloaded])
+            .respond("{\"name\":\"Alice\",\"age\":42}");
+        let config = AgentConfig::builder().model("gpt-5-mini").build().unwrap();
+        let pipeline = Pipeline::new()
+            .step("extract", config.clone())
+            .step_with("format", config.clone(), |output| {
+                Ok(format!("Format as JSON: {}", output))
+            })
+            .retry(StepRetryPolicy::new(2, std::time::Duration::from_millis(1)))
+            .with_mock_backend(backend.clone());
+
+        let (output_tx, output_rx) = async_channel::unbounded();
+        let result = pipeline.run("Who is listed?", output_tx).await.unwrap();
+        assert_eq!(result.output, "{\"name\":\"Alice\",\"age\":42}");
+        assert_eq!(result.steps[1].input, "Format as JSON: Alice, 42");
+        let attempts: Vec<u32> = result.steps.iter().map(|step| step.attempts).collect();
+        assert_eq!(attempts, [1, 2]);
+        backend.assert_inputs(&[
+            "Who is listed?",
+            "Format as JSON: Alice, 42",
+            "Format as JSON: Alice, 42",
+        ]);
+
+        // Output is tagged with the step that produced it
+        let tagged = loop {
+            let tagged = output_rx.recv().await.unwrap();
+            if matches!(tagged.message.data, OutputData::Primary { .. }) {
+                break tagged;
+            }
+        };
+        assert_eq!(tagged.agent_id, "extract");
+
+        // A step failing all its attempts stops the pipeline
+        let pipeline = Pipeline::new()
+            .step("extract", config)
+            .with_mock_backend(testing::MockBackend::new());
+        let (output_tx, _output_rx) = async_channel::unbounded();
+        let error = pipeline.run("Who is listed?", output_tx).await.unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("Pipeline step extract failed after 1 attempts")
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_orchestrator_multiplexes_output_by_agent_id() {
+        let plan = r#"[{"worker":"researcher","task":"Find the capital of France"},
+            {"worker":"writer","task":"Write a line about Paris"}]"#;
+        // One subtask at a time, so the workers take the scripted turns in order
+        let backend = testing::MockBackend::new()
+            .respond(plan)
+            .respond("Paris")
+            .respond("Paris glows at dusk.")
+            .respond("The capital is Paris, which glows at dusk.");
+        let config = AgentConfig::builder().model("gpt-5-mini").build().unwrap();
+        let orchestrator = Orchestrator::new(config.clone())
+            .worker("researcher", "Looks up facts", config.clone())
+            .worker("writer", "Writes prose", config.clone())
+            .concurrency(1)
+            .with_mock_backend(backend.clone());
+
+        let (output_tx, output_rx) = async_channel::unbounded();
+        let result = orchestrator
+            .run("Tell me about the capital of France", output_tx)
+            .await
+            .unwrap();
+        assert_eq!(result.answer, "The capital is Paris, which glows at dusk.");
+        let subtasks: Vec<_> = result
+            .subtasks
+            .iter()
+            .map(|subtask| (subtask.agent_id.as_str(), subtask.result.clone().unwrap()))
+            .collect();
+        assert_eq!(
+            subtasks,
+            [
+                ("researcher#0", "Paris".to_string()),
+                ("writer#1", "Paris glows at dusk.".to_string())
+            ]
+        );
+        let inputs = backend.inputs();
+        assert_eq!(inputs[1], "Find the capital of France");
+        assert!(inputs[3].contains("## researcher#0 (Find the capital of France)\nParis\n"));
+
+        let mut responses = Vec::new();
+        while responses.len() < 4 {
+            let tagged = output_rx.recv().await.unwrap();
+            if let OutputData::Primary { content } = tagged.message.data {
+                responses.push((tagged.agent_id, content));
+            }
+        }
+        responses.sort();
+        let agent_ids: Vec<&str> = responses.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(
+            agent_ids,
+            ["researcher#0", "supervisor", "supervisor", "writer#1"]
+        );
+        assert_eq!(responses[3].1, "Paris glows at dusk.");
+
+        // Plans naming a worker that does not exist are refused
+        let orchestrator = Orchestrator::new(config.clone())
+            .worker("writer", "Writes prose", config)
+            .with_mock_backend(
+                testing::MockBackend::new().respond(r#"[{"worker":"painter","task":"Paint"}]"#),
+            );
+        let (output_tx, _output_rx) = async_channel::unbounded();
+        let error = orchestrator
+            .run("Paint Paris", output_tx)
+            .await
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("Supervisor assigned unknown worker painter")
+        );
+    }
+
+    #[cfg(feature = "registry")]
+    #[tokio::test]
+    async fn test_git_registry_resolves_versions_and_verifies_checksums() {
+        use registry::{AgentDefinition, Registry};
+        use semver::Version;
+
+        let git = |dir: &std::path::Path, args: &[&str]| {
+            let output = std::process::Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@localhost"])
+                .args(args)
+                .current_dir(dir)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{:?}", output);
+        };
+        let seed = temp_repository();
+        std::fs::write(seed.join("README.md"), "Agents\n").unwrap();
+        git(&seed, &["add", "README.md"]);
+        git(&seed, &["commit", "-m", "Create registry"]);
+        let remote = temp_dir().join("registry.git");
+        git(&seed, &["clone", "--bare", ".", remote.to_str().unwrap()]);
+
+        let registry = Registry::git(remote.to_string_lossy()).unwrap();
+        for version in ["1.0.0", "1.2.0", "2.0.0"] {
+            let mut definition =
+                AgentDefinition::new("reviewer", Version::parse(version).unwrap(), "gpt-5-mini");
+            definition.system_prompt = Some(format!("Review code ({})", version));
+            registry.publish(&definition).await.unwrap();
+        }
+        let versions: Vec<String> = registry
+            .versions("reviewer")
+            .await
+            .unwrap()
+            .iter()
+            .map(|entry| entry.version.to_string())
+            .collect();
+        assert_eq!(versions, ["1.0.0", "1.2.0", "2.0.0"]);
+
+        // The newest matching version is picked
+        let definition = registry.fetch("reviewer", "^1").await.unwrap();
+        assert_eq!(definition.version, Version::new(1, 2, 0));
+        let config = definition.to_builder().build().unwrap();
+        assert_eq!(config.model(), "gpt-5-mini");
+        assert!(registry.fetch("reviewer", "^3").await.is_err());
+
+        let republished = AgentDefinition::new("reviewer", Version::new(1, 0, 0), "gpt-5");
+        let error = registry.publish(&republished).await.unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("reviewer 1.0.0 is already published")
+        );
+        let invalid = AgentDefinition::new("../reviewer", Version::new(1, 0, 0), "gpt-5");
+        assert!(registry.publish(&invalid).await.is_err());
+
+        // Documents changed after publishing are refused
+        let checkout = temp_dir().join("checkout");
+        git(
+            &seed,
+            &[
+                "clone",
+                remote.to_str().unwrap(),
+                checkout.to_str().unwrap(),
+            ],
+        );
+        let document = checkout.join("reviewer/1.2.0.json");
+        let tampered = std::fs::read_to_string(&document)
+            .unwrap()
+            .replace("gpt-5-mini", "gpt-5");
+        std::fs::write(&document, tampered).unwrap();
+        git(&checkout, &["commit", "-am", "Swap the model"]);
+        git(&checkout, &["push", "origin", "HEAD"]);
+        let error = registry.fetch("reviewer", "^1").await.unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("Checksum mismatch for reviewer 1.2.0")
+        );
+    }
+
+    #[cfg(feature = "session")]
+    #[tokio::test]
+    async fn test_session_search_ranks_matching_turns() {
+        use session::{Embedder, SessionManager, SessionSnapshot};
+
+        /// Embeds texts as counts of "production" and "deploy".
+        struct KeywordEmbedder;
+
+        impl Embedder for KeywordEmbedder {
+            fn embed<'a>(
+                &'a self,
+                texts: &'a [String],
+            ) -> futures::future::BoxFuture<'a, Result<Vec<Vec<f32>>>> {
+                Box::pin(async move {
+                    Ok(texts
+                        .iter()
+                        .map(|text| {
+                            let lower = text.to_lowercase();
+                            vec![
+                                lower.matches("production").count() as f32,
+                                lower.matches("deploy").count() as f32,
+                            ]
+                        })
+                        .collect())
+                })
+            }
+        }
+
+        let manager = SessionManager::with_directory(temp_dir());
+        let snapshot = |id: &str, turns: &[(&str, &str)]| -> SessionSnapshot {
+            let turn_records: Vec<_> = turns
+                .iter()
+                .enumerate()
+                .map(|(i, (input, summary))| {
+                    serde_json::json!({
+                        "turn_id": i + 1,
+                        "input": input,
+                        "summary": summary,
+                        "completed_at": "2026-01-01T00:00:00Z",
+                    })
+                })
+                .collect();
+            serde_json::from_value(serde_json::json!({
+                "session_id": id,
+                "turn_count": turns.len(),
+                "paused": false,
+                "turn_records": turn_records,
+                "plan": null,
+                "pending_inputs": [],
+                "saved_at": "2026-01-01T00:00:00Z",
+            }))
+            .unwrap()
+        };
+        let alpha = snapshot(
+            "alpha",
+            &[
+                ("Is staging up?", "Yes, deploy when ready."),
+                ("Deploy to production", "Done."),
+            ],
+        );
+        let beta = snapshot(
+            "beta",
+            &[(
+                "Deploy the app to staging",
+                "Deployed to staging; the staging deploy passed.",
+            )],
+        );
+        manager.save_snapshot(&alpha).await.unwrap();
+        manager.save_snapshot(&beta).await.unwrap();
+
+        // Turns need every word; more occurrences rank higher
+        let hits = manager.search("Deploy STAGING").await.unwrap();
+        let ranking: Vec<_> = hits
+            .iter()
+            .map(|hit| (hit.session_id.as_str(), hit.turn_id, hit.score))
+            .collect();
+        assert_eq!(ranking, [("beta", 1, 6.0), ("alpha", 1, 2.0)]);
+        assert!(
+            hits[0]
+                .snippet
+                .starts_with("Deploy the app to staging Deployed")
+        );
+        assert!(manager.search("  ").await.unwrap().is_empty());
+
+        let hits = manager
+            .search_semantic("deploy to production", &KeywordEmbedder, 1)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].session_id.as_str(), hits[0].turn_id), ("alpha", 2));
+        assert!((hits[0].score - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_diagnostics_are_parsed_from_recorded_toolchain_output() {
+        let cargo = diagnostics::parse_with(
+            Toolchain::Cargo,
+            include_str!("../fixtures/diagnostics/cargo.txt"),
+        );
+        assert_eq!(cargo.len(), 3);
+        assert_eq!(cargo[0].severity, Severity::Warning);
+        assert_eq!(cargo[0].message, "unused variable: `count`");
+        assert_eq!((cargo[0].line, cargo[0].column), (Some(12), Some(9)));
+        assert_eq!(
+            cargo[1].message,
+            "test tests::it_works panicked: assertion `left == right` failed"
+        );
+        assert_eq!((cargo[1].line, cargo[1].column), (Some(20), Some(9)));
+        assert_eq!(cargo[2].code.as_deref(), Some("E0308"));
+        assert_eq!(cargo[2].file, "src/lib.rs");
+        assert_eq!(cargo[2].line, Some(4));
+
+        let pytest = diagnostics::parse_with(
+            Toolchain::Pytest,
+            include_str!("../fixtures/diagnostics/pytest.txt"),
+        );
+        assert_eq!(pytest.len(), 4);
+        assert_eq!(pytest[0].file, "tests/test_io.py");
+        assert_eq!(pytest[0].line, Some(7));
+        assert_eq!(pytest[1].message, "AssertionError");
+        assert_eq!(pytest[2].message, "test_add failed: assert 3 == 4");
+        assert!(
+            pytest[3]
+                .message
+                .starts_with("test_read_file failed: FileNotFoundError")
+        );
+
+        let eslint = diagnostics::parse_with(
+            Toolchain::Eslint,
+            include_str!("../fixtures/diagnostics/eslint.txt"),
+        );
+        assert_eq!(eslint.len(), 3);
+        assert_eq!(eslint[0].file, "/work/app/src/index.js");
+        assert_eq!(eslint[0].message, "'x' is defined but never used");
+        assert_eq!(eslint[0].code.as_deref(), Some("no-unused-vars"));
+        assert_eq!(eslint[1].severity, Severity::Warning);
+        assert_eq!(eslint[1].code.as_deref(), Some("no-console"));
+        assert_eq!(eslint[2].file, "/work/app/src/util.js");
+        assert_eq!(eslint[2].code, None);
+
+        let tsc = diagnostics::parse_with(
+            Toolchain::Tsc,
+            include_str!("../fixtures/diagnostics/tsc.txt"),
+        );
+        assert_eq!(tsc.len(), 3);
+        assert_eq!((tsc[0].line, tsc[0].column), (Some(3), Some(7)));
+        assert_eq!(tsc[1].code.as_deref(), Some("TS2345"));
+        assert_eq!(tsc[2].file, "src/app.ts");
+        assert_eq!(tsc[2].severity, Severity::Warning);
+    }
 }
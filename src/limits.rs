@@ -0,0 +1,165 @@
+//! Resource limits and termination policy for commands spawned on behalf of
+//! the agent.
+
+use serde::{Deserialize, Serialize};
+
+/// Per-process resource caps for executed commands.
+///
+/// On Unix these are applied as rlimits in the child right before it execs,
+/// and are inherited by everything the command spawns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// Maximum CPU time in seconds
+    #[serde(default)]
+    pub cpu_seconds: Option<u64>,
+
+    /// Maximum address space (virtual memory) in bytes
+    #[serde(default)]
+    pub max_memory_bytes: Option<u64>,
+
+    /// Maximum size of any file the command writes, in bytes
+    #[serde(default)]
+    pub max_file_size_bytes: Option<u64>,
+
+    /// Maximum number of processes for the user running the command
+    #[serde(default)]
+    pub max_processes: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Create an empty set of limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum CPU time in seconds.
+    pub fn cpu_seconds(mut self, seconds: u64) -> Self {
+        self.cpu_seconds = Some(seconds);
+        self
+    }
+
+    /// Set the maximum address space in bytes.
+    pub fn max_memory_bytes(mut self, bytes: u64) -> Self {
+        self.max_memory_bytes = Some(bytes);
+        self
+    }
+
+    /// Set the maximum size of written files in bytes.
+    pub fn max_file_size_bytes(mut self, bytes: u64) -> Self {
+        self.max_file_size_bytes = Some(bytes);
+        self
+    }
+
+    /// Set the maximum number of processes.
+    pub fn max_processes(mut self, processes: u64) -> Self {
+        self.max_processes = Some(processes);
+        self
+    }
+
+    /// Check if no limit is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Apply the limits to a command before it is spawned.
+    #[cfg(unix)]
+    pub(crate) fn apply(&self, command: &mut tokio::process::Command) {
+        if self.is_empty() {
+            return;
+        }
+
+        let limits = *self;
+        // SAFETY: the hook only calls setrlimit, which is async-signal-safe,
+        // and does not allocate
+        unsafe {
+            command.pre_exec(move || limits.set_rlimits());
+        }
+    }
+
+    /// Apply the limits to a command before it is spawned.
+    #[cfg(not(unix))]
+    pub(crate) fn apply(&self, _command: &mut tokio::process::Command) {
+        if !self.is_empty() {
+            tracing::warn!("Resource limits are not supported on this platform, ignoring");
+        }
+    }
+
+    #[cfg(unix)]
+    fn set_rlimits(&self) -> std::io::Result<()> {
+        set_rlimit(libc::RLIMIT_CPU, self.cpu_seconds)?;
+        set_rlimit(libc::RLIMIT_AS, self.max_memory_bytes)?;
+        set_rlimit(libc::RLIMIT_FSIZE, self.max_file_size_bytes)?;
+        set_rlimit(libc::RLIMIT_NPROC, self.max_processes)?;
+        Ok(())
+    }
+}
+
+/// How a command that outlives its timeout is terminated.
+///
+/// Commands run in their own process group. On timeout the whole group,
+/// including children and background daemons, receives SIGTERM, then SIGKILL
+/// once the grace period elapses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KillPolicy {
+    /// Seconds between SIGTERM and SIGKILL
+    #[serde(default = "default_grace_period")]
+    pub grace_period: u64,
+}
+
+impl KillPolicy {
+    /// Create the default policy (5 second grace period).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the seconds between SIGTERM and SIGKILL.
+    pub fn grace_period(mut self, seconds: u64) -> Self {
+        self.grace_period = seconds;
+        self
+    }
+}
+
+impl Default for KillPolicy {
+    fn default() -> Self {
+        Self {
+            grace_period: default_grace_period(),
+        }
+    }
+}
+
+/// A process terminated during process group cleanup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReapedProcess {
+    /// Process id
+    pub pid: u32,
+
+    /// Executable name
+    pub command: String,
+}
+
+fn default_grace_period() -> u64 {
+    5
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type Resource = libc::c_int;
+
+#[cfg(unix)]
+fn set_rlimit(resource: Resource, limit: Option<u64>) -> std::io::Result<()> {
+    let Some(limit) = limit else {
+        return Ok(());
+    };
+
+    let rlimit = libc::rlimit {
+        rlim_cur: limit as libc::rlim_t,
+        rlim_max: limit as libc::rlim_t,
+    };
+    // SAFETY: rlimit is a valid, initialized struct for the duration of the call
+    if unsafe { libc::setrlimit(resource, &rlimit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
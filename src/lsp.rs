@@ -0,0 +1,493 @@
+//! Language-server-powered code intelligence tool (optional feature).
+//!
+//! Spawns LSP servers such as rust-analyzer or pyright in the workspace and
+//! exposes go-to-definition, find-references, and diagnostics as structured
+//! tool calls.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::error::{AgentError, Result};
+use crate::tools::{CustomToolHandler, ToolExecutionContext, ToolExecutionResult};
+
+/// Configuration for a single language server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LspServerConfig {
+    /// LSP language identifier (e.g., "rust", "python")
+    pub language_id: String,
+
+    /// Command used to start the server
+    pub command: String,
+
+    /// Command line arguments
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// File extensions handled by this server
+    pub extensions: Vec<String>,
+}
+
+impl LspServerConfig {
+    /// Create a new language server configuration.
+    pub fn new<S1, S2, I, S3>(language_id: S1, command: S2, extensions: I) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+        I: IntoIterator<Item = S3>,
+        S3: Into<String>,
+    {
+        Self {
+            language_id: language_id.into(),
+            command: command.into(),
+            args: Vec::new(),
+            extensions: extensions.into_iter().map(|e| e.into()).collect(),
+        }
+    }
+
+    /// Configuration for rust-analyzer.
+    pub fn rust_analyzer() -> Self {
+        Self::new("rust", "rust-analyzer", ["rs"])
+    }
+
+    /// Configuration for pyright.
+    pub fn pyright() -> Self {
+        Self::new("python", "pyright-langserver", ["py"]).args(["--stdio"])
+    }
+
+    /// Set command line arguments.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = args.into_iter().map(|s| s.into()).collect();
+        self
+    }
+
+    fn handles(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.extensions.iter().any(|e| e == ext))
+    }
+}
+
+/// Custom tool handler that answers code intelligence queries via LSP.
+pub struct LspTool {
+    servers: Vec<LspServerConfig>,
+    request_timeout: Duration,
+    clients: Mutex<HashMap<String, LspClient>>,
+}
+
+impl LspTool {
+    /// Create a tool backed by the given language servers.
+    pub fn new(servers: Vec<LspServerConfig>) -> Self {
+        Self {
+            servers,
+            request_timeout: Duration::from_secs(30),
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Set the timeout for individual LSP requests.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    fn run(&self, params: LspParams, root: &Path) -> Result<Value> {
+        let path = root.join(&params.path);
+        let server = self
+            .servers
+            .iter()
+            .find(|s| s.handles(&path))
+            .ok_or_else(|| AgentError::Tool {
+                message: format!("No language server configured for {}", params.path),
+            })?;
+
+        let mut clients = self.clients.lock().map_err(|_| AgentError::Tool {
+            message: "Language server state is poisoned".to_string(),
+        })?;
+        if !clients.contains_key(&server.language_id) {
+            let client = LspClient::start(server, root, self.request_timeout)?;
+            clients.insert(server.language_id.clone(), client);
+        }
+        let client = clients
+            .get_mut(&server.language_id)
+            .ok_or_else(|| AgentError::Tool {
+                message: "Language server failed to start".to_string(),
+            })?;
+
+        let uri = client.open(&path, &server.language_id)?;
+        let position = json!({
+            "line": params.line.unwrap_or(1).saturating_sub(1),
+            "character": params.column.unwrap_or(1).saturating_sub(1),
+        });
+
+        match params.operation {
+            LspOperation::Definition => {
+                let result = client.request(
+                    "textDocument/definition",
+                    json!({ "textDocument": { "uri": uri }, "position": position }),
+                )?;
+                Ok(json!({ "locations": locations(&result, root) }))
+            }
+            LspOperation::References => {
+                let result = client.request(
+                    "textDocument/references",
+                    json!({
+                        "textDocument": { "uri": uri },
+                        "position": position,
+                        "context": { "includeDeclaration": true },
+                    }),
+                )?;
+                Ok(json!({ "locations": locations(&result, root) }))
+            }
+            LspOperation::Diagnostics => {
+                let diagnostics = client
+                    .wait_for_diagnostics(&uri)?
+                    .iter()
+                    .map(|d| {
+                        json!({
+                            "path": params.path,
+                            "line": d["range"]["start"]["line"].as_u64().unwrap_or_default() + 1,
+                            "column": d["range"]["start"]["character"].as_u64().unwrap_or_default() + 1,
+                            "severity": severity_name(d["severity"].as_u64()),
+                            "message": d["message"],
+                            "source": d["source"],
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                Ok(json!({ "diagnostics": diagnostics }))
+            }
+        }
+    }
+}
+
+impl CustomToolHandler for LspTool {
+    fn execute(
+        &self,
+        parameters: Value,
+        context: &ToolExecutionContext,
+    ) -> Result<ToolExecutionResult> {
+        let params: LspParams = serde_json::from_value(parameters)?;
+        match self.run(params, &context.working_directory) {
+            Ok(data) => Ok(ToolExecutionResult::success_with_data(
+                data.to_string(),
+                data,
+            )),
+            Err(e) => Ok(ToolExecutionResult::error(e.to_string())),
+        }
+    }
+
+    fn parameter_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "operation": {
+                    "type": "string",
+                    "enum": ["definition", "references", "diagnostics"],
+                    "description": "Code intelligence query to run"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "File path relative to the workspace root"
+                },
+                "line": {
+                    "type": "integer",
+                    "description": "1-based line of the symbol (definition/references)"
+                },
+                "column": {
+                    "type": "integer",
+                    "description": "1-based column of the symbol (definition/references)"
+                }
+            },
+            "required": ["operation", "path"]
+        })
+    }
+
+    fn description(&self) -> String {
+        "Query language servers for definitions, references, and diagnostics".to_string()
+    }
+}
+
+impl Drop for LspTool {
+    fn drop(&mut self) {
+        if let Ok(clients) = self.clients.get_mut() {
+            for client in clients.values_mut() {
+                client.shutdown();
+            }
+        }
+    }
+}
+
+/// Parameters accepted by the code intelligence tool.
+#[derive(Debug, Deserialize)]
+struct LspParams {
+    operation: LspOperation,
+    path: String,
+    #[serde(default)]
+    line: Option<u64>,
+    #[serde(default)]
+    column: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LspOperation {
+    Definition,
+    References,
+    Diagnostics,
+}
+
+/// Minimal synchronous JSON-RPC client speaking LSP over stdio.
+struct LspClient {
+    child: Child,
+    stdin: ChildStdin,
+    messages: mpsc::Receiver<Value>,
+    next_id: u64,
+    timeout: Duration,
+    opened: HashMap<String, i64>,
+    diagnostics: HashMap<String, Vec<Value>>,
+}
+
+impl LspClient {
+    fn start(config: &LspServerConfig, root: &Path, timeout: Duration) -> Result<Self> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdin = child.stdin.take().ok_or_else(|| AgentError::Tool {
+            message: "Language server stdin unavailable".to_string(),
+        })?;
+        let stdout = child.stdout.take().ok_or_else(|| AgentError::Tool {
+            message: "Language server stdout unavailable".to_string(),
+        })?;
+
+        let (tx, messages) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            while let Some(message) = read_message(&mut reader) {
+                if tx.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut client = Self {
+            child,
+            stdin,
+            messages,
+            next_id: 1,
+            timeout,
+            opened: HashMap::new(),
+            diagnostics: HashMap::new(),
+        };
+
+        client.request(
+            "initialize",
+            json!({
+                "processId": std::process::id(),
+                "rootUri": file_uri(root),
+                "capabilities": {
+                    "textDocument": {
+                        "definition": { "linkSupport": false },
+                        "publishDiagnostics": {}
+                    }
+                }
+            }),
+        )?;
+        client.notify("initialized", json!({}))?;
+
+        Ok(client)
+    }
+
+    /// Open (or refresh) a document and return its URI.
+    fn open(&mut self, path: &Path, language_id: &str) -> Result<String> {
+        let uri = file_uri(path);
+        let text = std::fs::read_to_string(path)?;
+
+        if let Some(version) = self.opened.get_mut(&uri) {
+            *version += 1;
+            let version = *version;
+            self.diagnostics.remove(&uri);
+            self.notify(
+                "textDocument/didChange",
+                json!({
+                    "textDocument": { "uri": uri, "version": version },
+                    "contentChanges": [{ "text": text }],
+                }),
+            )?;
+        } else {
+            self.opened.insert(uri.clone(), 1);
+            self.notify(
+                "textDocument/didOpen",
+                json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": language_id,
+                        "version": 1,
+                        "text": text,
+                    }
+                }),
+            )?;
+        }
+
+        Ok(uri)
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))?;
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let message = self.next_message(deadline)?;
+            if message.get("method").is_none() && message["id"].as_u64() == Some(id) {
+                if let Some(error) = message.get("error") {
+                    return Err(AgentError::Tool {
+                        message: format!("{} failed: {}", method, error),
+                    });
+                }
+                return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+            }
+        }
+    }
+
+    fn wait_for_diagnostics(&mut self, uri: &str) -> Result<Vec<Value>> {
+        let deadline = Instant::now() + self.timeout;
+        while !self.diagnostics.contains_key(uri) {
+            self.next_message(deadline)?;
+        }
+        Ok(self.diagnostics.get(uri).cloned().unwrap_or_default())
+    }
+
+    /// Receive the next message, handling notifications and server requests.
+    fn next_message(&mut self, deadline: Instant) -> Result<Value> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let message = self
+            .messages
+            .recv_timeout(remaining)
+            .map_err(|_| AgentError::Tool {
+                message: "Timed out waiting for language server".to_string(),
+            })?;
+
+        match (
+            message.get("method").and_then(Value::as_str),
+            message.get("id"),
+        ) {
+            (Some("textDocument/publishDiagnostics"), None) => {
+                if let Some(uri) = message["params"]["uri"].as_str() {
+                    let diagnostics = message["params"]["diagnostics"]
+                        .as_array()
+                        .cloned()
+                        .unwrap_or_default();
+                    self.diagnostics.insert(uri.to_string(), diagnostics);
+                }
+            }
+            (Some(_), Some(id)) => {
+                // Server-to-client requests (configuration, progress) get an empty reply
+                let id = id.clone();
+                self.send(json!({ "jsonrpc": "2.0", "id": id, "result": Value::Null }))?;
+            }
+            _ => {}
+        }
+
+        Ok(message)
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> Result<()> {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    fn send(&mut self, message: Value) -> Result<()> {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn shutdown(&mut self) {
+        let _ = self.send(json!({ "jsonrpc": "2.0", "id": 0, "method": "shutdown" }));
+        let _ = self.notify("exit", Value::Null);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Read one Content-Length framed JSON-RPC message.
+fn read_message<R: BufRead>(reader: &mut R) -> Option<Value> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            content_length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let mut body = vec![0; content_length?];
+    reader.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+/// Normalize Location / Location[] / LocationLink[] results.
+fn locations(result: &Value, root: &Path) -> Vec<Value> {
+    let items = match result {
+        Value::Array(items) => items.clone(),
+        Value::Null => Vec::new(),
+        other => vec![other.clone()],
+    };
+
+    items
+        .iter()
+        .filter_map(|item| {
+            let uri = item
+                .get("uri")
+                .or_else(|| item.get("targetUri"))?
+                .as_str()?;
+            let range = item
+                .get("range")
+                .or_else(|| item.get("targetSelectionRange"))?;
+            let path = PathBuf::from(uri.strip_prefix("file://").unwrap_or(uri));
+            let path = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            Some(json!({
+                "path": path,
+                "line": range["start"]["line"].as_u64().unwrap_or_default() + 1,
+                "column": range["start"]["character"].as_u64().unwrap_or_default() + 1,
+            }))
+        })
+        .collect()
+}
+
+fn severity_name(severity: Option<u64>) -> &'static str {
+    match severity {
+        Some(1) => "error",
+        Some(2) => "warning",
+        Some(3) => "information",
+        Some(4) => "hint",
+        _ => "unknown",
+    }
+}
+
+fn file_uri(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
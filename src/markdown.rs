@@ -0,0 +1,96 @@
+//! Markdown helpers for model responses.
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+/// A fenced code block in a markdown response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeBlock {
+    /// Language tag of the fence (e.g. `rust` in ```` ```rust ````), if any
+    pub language: Option<String>,
+
+    /// Code between the fences
+    pub code: String,
+
+    /// Byte range of the whole block, fences included, in the content
+    pub span: Range<usize>,
+}
+
+/// Extract fenced code blocks (```` ``` ```` or `~~~`) from markdown.
+///
+/// A block left open runs to the end of the content, so suggestions cut off
+/// mid-stream are still returned.
+pub fn extract_code_blocks(content: &str) -> Vec<CodeBlock> {
+    fenced_blocks(content)
+        .into_iter()
+        .map(|block| CodeBlock {
+            language: (!block.language.is_empty()).then(|| block.language.to_string()),
+            code: block.code.to_string(),
+            span: block.span,
+        })
+        .collect()
+}
+
+/// A fenced block borrowing from the content.
+pub(crate) struct FencedBlock<'a> {
+    pub language: &'a str,
+    pub code: &'a str,
+    pub span: Range<usize>,
+}
+
+pub(crate) fn fenced_blocks(content: &str) -> Vec<FencedBlock<'_>> {
+    let mut blocks = Vec::new();
+    // Opening fence: block start, code start, fence character and length, language
+    let mut open: Option<(usize, usize, char, usize, &str)> = None;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let text = line.trim_end_matches(['\n', '\r']);
+        let trimmed = text.trim_start();
+
+        let fence_char = match trimmed.chars().next() {
+            Some(c @ ('`' | '~')) => c,
+            _ => continue,
+        };
+        let fence_len = trimmed.chars().take_while(|c| *c == fence_char).count();
+        if fence_len < 3 || text.len() - trimmed.len() > 3 {
+            continue;
+        }
+        let info = trimmed[fence_len..].trim();
+
+        match open {
+            None => {
+                // Backtick fences can't have backticks in the info string
+                if fence_char == '`' && info.contains('`') {
+                    continue;
+                }
+                let language = info.split_whitespace().next().unwrap_or("");
+                open = Some((line_start, offset, fence_char, fence_len, language));
+            }
+            Some((start, code_start, c, len, language))
+                if c == fence_char && fence_len >= len && info.is_empty() =>
+            {
+                blocks.push(FencedBlock {
+                    language,
+                    code: content[code_start..line_start].trim_end_matches(['\n', '\r']),
+                    span: start..offset,
+                });
+                open = None;
+            }
+            Some(_) => {}
+        }
+    }
+
+    if let Some((start, code_start, _, _, language)) = open {
+        blocks.push(FencedBlock {
+            language,
+            code: &content[code_start..],
+            span: start..content.len(),
+        });
+    }
+
+    blocks
+}
@@ -1,33 +1,69 @@
 //! Message types for agent input and output communication.
 
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use codex_protocol::config_types::ReasoningEffort;
 use serde::{Deserialize, Serialize};
 
-use crate::error::OutputError;
+use crate::approval::ApprovalRequest;
+use crate::attachment::Attachment;
+use crate::error::{AgentError, OutputError, Result};
+use crate::patch::FileChangeKind;
+use crate::web_search::WebSearchResult;
 
 /// Input message from user to agent.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InputMessage {
+    /// Caller-chosen identifier echoed on every output of the turn; generated
+    /// when the message is queued if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
     /// The text message content
     pub message: String,
 
     /// Optional images attached to the message
     pub images: Vec<ImageInput>,
+
+    /// Files written into the working directory and listed in the prompt
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+
+    /// Overrides applied to the turn this message starts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<QueryOptions>,
+
+    /// Host data (user ids, trace ids, ...) copied onto every output of the turn
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, serde_json::Value>,
 }
 
 impl InputMessage {
     /// Create a new input message with text only.
     pub fn new<S: Into<String>>(message: S) -> Self {
         Self {
+            id: None,
             message: message.into(),
             images: Vec::new(),
+            attachments: Vec::new(),
+            options: None,
+            metadata: HashMap::new(),
         }
     }
 
     /// Create a new input message with text and images.
     pub fn with_images<S: Into<String>>(message: S, images: Vec<ImageInput>) -> Self {
         Self {
+            id: None,
             message: message.into(),
             images,
+            attachments: Vec::new(),
+            options: None,
+            metadata: HashMap::new(),
         }
     }
 
@@ -36,6 +72,84 @@ impl InputMessage {
         self.images.push(image);
         self
     }
+
+    /// Attach a file to the message.
+    pub fn add_attachment(mut self, attachment: Attachment) -> Self {
+        self.attachments.push(attachment);
+        self
+    }
+
+    /// Set the identifier used to correlate outputs with this message.
+    pub fn with_id<S: Into<String>>(mut self, id: S) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Attach a metadata entry that is echoed on the turn's outputs.
+    pub fn with_metadata<K, V>(mut self, key: K, value: V) -> Result<Self>
+    where
+        K: Into<String>,
+        V: Serialize,
+    {
+        self.metadata
+            .insert(key.into(), serde_json::to_value(value)?);
+        Ok(self)
+    }
+
+    /// Apply per-turn overrides to the message.
+    pub fn with_options(mut self, options: QueryOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+}
+
+/// Per-turn overrides of the agent configuration.
+///
+/// Lets a single agent run a cheap model for small questions and a bigger one
+/// for hard ones without being rebuilt. Unset fields fall back to the config.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueryOptions {
+    /// Model to use for the turn
+    #[serde(default)]
+    pub model: Option<String>,
+
+    /// Reasoning effort for the turn
+    #[serde(default)]
+    pub reasoning_effort: Option<ReasoningEffort>,
+
+    /// Maximum time the turn may run before it is interrupted
+    #[serde(default)]
+    pub timeout: Option<Duration>,
+}
+
+impl QueryOptions {
+    /// Create empty options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the model for the turn.
+    pub fn model<S: Into<String>>(mut self, model: S) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Set the reasoning effort for the turn.
+    pub fn reasoning_effort(mut self, effort: ReasoningEffort) -> Self {
+        self.reasoning_effort = Some(effort);
+        self
+    }
+
+    /// Set the turn timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Check if any model setting is overridden.
+    pub(crate) fn overrides_model(&self) -> bool {
+        self.model.is_some() || self.reasoning_effort.is_some()
+    }
 }
 
 impl<S: Into<String>> From<S> for InputMessage {
@@ -90,6 +204,79 @@ impl ImageInput {
         self.description = Some(description.into());
         self
     }
+
+    /// Read an image file into a base64 data URL.
+    ///
+    /// Fails if the file is not a PNG, JPEG, GIF, or WebP image, or is larger
+    /// than [`MAX_IMAGE_BYTES`].
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let size = std::fs::metadata(path)?.len();
+        if size > MAX_IMAGE_BYTES as u64 {
+            return Err(image_too_large(size));
+        }
+        Self::from_bytes(&std::fs::read(path)?).map_err(|e| AgentError::Generic {
+            message: format!("{}: {}", path.display(), e),
+        })
+    }
+
+    /// Encode image bytes into a base64 data URL, detecting the MIME type
+    /// from the content.
+    ///
+    /// Fails if the bytes are not a PNG, JPEG, GIF, or WebP image, or are
+    /// larger than [`MAX_IMAGE_BYTES`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() > MAX_IMAGE_BYTES {
+            return Err(image_too_large(bytes.len() as u64));
+        }
+        let mime_type = image_mime_type(bytes).ok_or_else(|| AgentError::Generic {
+            message: "Unsupported image format, expected PNG, JPEG, GIF, or WebP".to_string(),
+        })?;
+        let data = format!(
+            "data:{};base64,{}",
+            mime_type,
+            BASE64_STANDARD.encode(bytes)
+        );
+        Ok(Self::new(data, mime_type))
+    }
+}
+
+/// Largest image accepted by `ImageInput::from_path` and `from_bytes`.
+pub const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+
+/// MIME type of an image, from its magic bytes.
+fn image_mime_type(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => Some("image/png"),
+        [0xff, 0xd8, 0xff, ..] => Some("image/jpeg"),
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some("image/gif"),
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'E',
+            b'B',
+            b'P',
+            ..,
+        ] => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Error for an image over the size limit.
+fn image_too_large(size: u64) -> AgentError {
+    AgentError::Generic {
+        message: format!(
+            "Image of {} bytes exceeds the {} byte limit",
+            size, MAX_IMAGE_BYTES
+        ),
+    }
 }
 
 /// Output message from agent to user.
@@ -98,6 +285,20 @@ pub struct OutputMessage {
     /// Unique identifier for the turn
     pub turn_id: u64,
 
+    /// Position in the agent's output, starting at 1 and increasing by one
+    /// per message across turns; 0 for messages not sent by the agent.
+    /// Consumers can use it to detect gaps, reordering, and duplicates.
+    #[serde(default)]
+    pub seq: u64,
+
+    /// Identifier of the input message whose turn produced this output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_id: Option<String>,
+
+    /// Metadata of the input message whose turn produced this output
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, serde_json::Value>,
+
     /// The output data payload
     pub data: OutputData,
 
@@ -110,6 +311,9 @@ impl OutputMessage {
     pub fn new(turn_id: u64, data: OutputData) -> Self {
         Self {
             turn_id,
+            seq: 0,
+            input_id: None,
+            metadata: HashMap::new(),
             data,
             timestamp: chrono::Utc::now(),
         }
@@ -144,15 +348,83 @@ pub enum OutputData {
     /// Tool output stream
     ToolOutput { tool_name: String, output: String },
 
+    /// Tool output exceeded the tool's `max_output_bytes` and its middle was cut
+    ToolOutputTruncated {
+        tool_name: String,
+        omitted_bytes: usize,
+        max_output_bytes: usize,
+    },
+
+    /// Codex waits for the host to approve a command or patch with
+    /// `AgentController::approve`
+    ApprovalRequired {
+        approval_id: String,
+        request: ApprovalRequest,
+    },
+
+    /// Pages cited by the model after web searches of the turn
+    WebSearchResults {
+        queries: Vec<String>,
+        results: Vec<WebSearchResult>,
+    },
+
+    /// Diff of a patch awaiting the host's confirmation before it is applied
+    PatchPreview { diff: String },
+
+    /// File changed by a patch the agent applied
+    FileChange {
+        path: PathBuf,
+        kind: FileChangeKind,
+        diff: String,
+    },
+
     /// Agent reasoning process
     Reasoning { content: String },
 
     /// Reasoning content delta
     ReasoningDelta { content: String },
 
+    /// Image produced by a tool, such as a plot or screenshot
+    Image {
+        /// Base64 encoded image data
+        data: String,
+        mime_type: String,
+        caption: Option<String>,
+    },
+
     /// Todo list/plan update
     TodoUpdate { todos: Vec<crate::plan::TodoItem> },
 
+    /// Summary of workspace files changed during the turn
+    WorkspaceDelta {
+        created: Vec<std::path::PathBuf>,
+        modified: Vec<std::path::PathBuf>,
+        deleted: Vec<std::path::PathBuf>,
+    },
+
+    /// Non-fatal condition the host should surface (e.g., disk quota nearly full)
+    Warning { message: String },
+
+    /// Turn failed by a transient model error, retried after `delay_ms`
+    Retrying {
+        attempt: u32,
+        max_attempts: u32,
+        delay_ms: u64,
+        reason: String,
+    },
+
+    /// Periodic liveness signal while idle, if a heartbeat is configured
+    Heartbeat,
+
+    /// Periodic operational digest, if ops summaries are configured
+    OpsSummary { summary: crate::ops::OpsSummary },
+
+    /// Follow-up prompts suggested after the turn, if suggestions are configured
+    Suggestions { prompts: Vec<String> },
+
+    /// Progress summary of an autonomous run
+    CheckIn { check_in: crate::autonomy::CheckIn },
+
     /// Turn completed successfully
     Completed,
 
@@ -217,11 +489,40 @@ impl OutputData {
         }
     }
 
+    /// Create an image message from base64 encoded data.
+    pub fn image<S1, S2>(data: S1, mime_type: S2, caption: Option<String>) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        Self::Image {
+            data: data.into(),
+            mime_type: mime_type.into(),
+            caption,
+        }
+    }
+
     /// Create a todo update message.
     pub fn todo_update(todos: Vec<crate::plan::TodoItem>) -> Self {
         Self::TodoUpdate { todos }
     }
 
+    /// Create a workspace delta message.
+    pub fn workspace_delta(delta: crate::workspace::WorkspaceDelta) -> Self {
+        Self::WorkspaceDelta {
+            created: delta.created,
+            modified: delta.modified,
+            deleted: delta.deleted,
+        }
+    }
+
+    /// Create a warning message.
+    pub fn warning<S: Into<String>>(message: S) -> Self {
+        Self::Warning {
+            message: message.into(),
+        }
+    }
+
     /// Create an error message.
     pub fn error(error: OutputError) -> Self {
         Self::Error { error }
@@ -243,11 +544,92 @@ impl std::fmt::Display for OutputMessage {
             OutputData::ToolOutput { tool_name, output } => {
                 write!(f, "[{}] {}", tool_name, output)
             }
+            OutputData::ToolOutputTruncated {
+                tool_name,
+                omitted_bytes,
+                ..
+            } => write!(
+                f,
+                "[{}] {} bytes of output truncated",
+                tool_name, omitted_bytes
+            ),
+            OutputData::ApprovalRequired {
+                approval_id,
+                request,
+            } => match request {
+                ApprovalRequest::Exec { command, .. } => {
+                    write!(f, "[Approval {}] run {}", approval_id, command.join(" "))
+                }
+                ApprovalRequest::Patch { diff, .. } => {
+                    write!(f, "[Approval {}] apply patch\n{}", approval_id, diff)
+                }
+            },
+            OutputData::WebSearchResults { queries, results } => write!(
+                f,
+                "[Web search] {} results for {}",
+                results.len(),
+                queries.join(", ")
+            ),
+            OutputData::PatchPreview { diff } => write!(f, "[Patch preview]\n{}", diff),
+            OutputData::FileChange { path, kind, .. } => {
+                write!(f, "[File] {:?} {}", kind, path.display())
+            }
             OutputData::Reasoning { content } => write!(f, "[Reasoning] {}", content),
             OutputData::ReasoningDelta { content } => write!(f, "{}", content),
+            OutputData::Image {
+                mime_type, caption, ..
+            } => match caption {
+                Some(caption) => write!(f, "[Image] {} ({})", caption, mime_type),
+                None => write!(f, "[Image] {}", mime_type),
+            },
             OutputData::TodoUpdate { todos } => {
                 write!(f, "[Plan] {} todos", todos.len())
             }
+            OutputData::WorkspaceDelta {
+                created,
+                modified,
+                deleted,
+            } => write!(
+                f,
+                "[Workspace] {} created, {} modified, {} deleted",
+                created.len(),
+                modified.len(),
+                deleted.len()
+            ),
+            OutputData::Warning { message } => write!(f, "[Warning] {}", message),
+            OutputData::Retrying {
+                attempt,
+                max_attempts,
+                delay_ms,
+                reason,
+            } => write!(
+                f,
+                "[Retry {}/{}] in {}ms: {}",
+                attempt, max_attempts, delay_ms, reason
+            ),
+            OutputData::Heartbeat => write!(f, "[Turn {}] Heartbeat", self.turn_id),
+            OutputData::OpsSummary { summary } => write!(
+                f,
+                "[Ops] {} turns, {} tokens, {} errors, {} warnings",
+                summary.turns_completed, summary.total_tokens, summary.errors, summary.warnings
+            ),
+            OutputData::Suggestions { prompts } => {
+                write!(
+                    f,
+                    "[Turn {}] Suggestions: {}",
+                    self.turn_id,
+                    prompts.join(" | ")
+                )
+            }
+            OutputData::CheckIn { check_in } => write!(
+                f,
+                "[Check-in {}] {}/{} steps, {} tool calls, {}s",
+                check_in.number,
+                check_in.completed_steps,
+                check_in.total_steps,
+                check_in.tool_calls,
+                check_in.elapsed_secs
+            ),
             OutputData::Completed => write!(f, "[Turn {}] Completed", self.turn_id),
             OutputData::Error { error } => write!(f, "[Error] {:?}", error),
         }
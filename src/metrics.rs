@@ -0,0 +1,115 @@
+//! Metrics of agent activity, reported through the `metrics` crate.
+//!
+//! Agents report to whatever recorder the host installs, e.g.
+//! `metrics-exporter-prometheus`, so fleets of agents can be graphed and
+//! alerted on. Counts are aggregated across all agents of the process:
+//!
+//! | Metric | Kind | Labels |
+//! |---|---|---|
+//! | `agent_turns_total` | counter | `model`, `status` |
+//! | `agent_turn_duration_seconds` | histogram | `model` |
+//! | `agent_model_requests_total` | counter | `model` |
+//! | `agent_tokens_total` | counter | `model`, `direction` |
+//! | `agent_tool_calls_total` | counter | `tool`, `success` |
+//! | `agent_tool_duration_seconds` | histogram | `tool` |
+//! | `agent_errors_total` | counter | `type` |
+//! | `agent_input_queue_depth` | gauge | |
+//! | `agent_input_queue_wait_seconds` | histogram | |
+//!
+//! Call [`describe`] after installing the recorder to give each metric its
+//! description and unit.
+
+use std::time::Duration;
+
+use ::metrics::{
+    Unit, counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram,
+};
+
+use crate::ops::TokenUsage;
+
+/// Turns ended, by model and status: completed, aborted, or failed.
+pub const TURNS_TOTAL: &str = "agent_turns_total";
+
+/// Duration of turns, by model.
+pub const TURN_DURATION_SECONDS: &str = "agent_turn_duration_seconds";
+
+/// Model requests made, by model.
+pub const MODEL_REQUESTS_TOTAL: &str = "agent_model_requests_total";
+
+/// Tokens used, by model and direction: input or output.
+pub const TOKENS_TOTAL: &str = "agent_tokens_total";
+
+/// Tool calls ended, by tool and success.
+pub const TOOL_CALLS_TOTAL: &str = "agent_tool_calls_total";
+
+/// Duration of tool calls, by tool.
+pub const TOOL_DURATION_SECONDS: &str = "agent_tool_duration_seconds";
+
+/// Errors emitted to the host, by error type.
+pub const ERRORS_TOTAL: &str = "agent_errors_total";
+
+/// Input messages waiting for their turn.
+pub const INPUT_QUEUE_DEPTH: &str = "agent_input_queue_depth";
+
+/// Time input messages waited for their turn.
+pub const INPUT_QUEUE_WAIT_SECONDS: &str = "agent_input_queue_wait_seconds";
+
+/// Describe the metrics to the installed recorder.
+pub fn describe() {
+    describe_counter!(TURNS_TOTAL, "Turns ended, by model and status");
+    describe_histogram!(TURN_DURATION_SECONDS, Unit::Seconds, "Duration of turns");
+    describe_counter!(MODEL_REQUESTS_TOTAL, "Model requests made");
+    describe_counter!(TOKENS_TOTAL, "Tokens used, by direction");
+    describe_counter!(TOOL_CALLS_TOTAL, "Tool calls ended, by tool and success");
+    describe_histogram!(
+        TOOL_DURATION_SECONDS,
+        Unit::Seconds,
+        "Duration of tool calls"
+    );
+    describe_counter!(ERRORS_TOTAL, "Errors emitted to the host, by type");
+    describe_gauge!(INPUT_QUEUE_DEPTH, "Input messages waiting for their turn");
+    describe_histogram!(
+        INPUT_QUEUE_WAIT_SECONDS,
+        Unit::Seconds,
+        "Time input messages waited for their turn"
+    );
+}
+
+pub(crate) fn turn(model: &str, status: &'static str, duration: Duration) {
+    counter!(TURNS_TOTAL, "model" => model.to_string(), "status" => status).increment(1);
+    histogram!(TURN_DURATION_SECONDS, "model" => model.to_string()).record(duration.as_secs_f64());
+}
+
+pub(crate) fn model_request(model: &str) {
+    counter!(MODEL_REQUESTS_TOTAL, "model" => model.to_string()).increment(1);
+}
+
+pub(crate) fn tokens(model: &str, usage: TokenUsage) {
+    counter!(TOKENS_TOTAL, "model" => model.to_string(), "direction" => "input")
+        .increment(usage.input_tokens);
+    counter!(TOKENS_TOTAL, "model" => model.to_string(), "direction" => "output")
+        .increment(usage.output_tokens);
+}
+
+pub(crate) fn tool(tool_name: &str, success: bool, duration: Duration) {
+    let success = if success { "true" } else { "false" };
+    counter!(TOOL_CALLS_TOTAL, "tool" => tool_name.to_string(), "success" => success).increment(1);
+    histogram!(TOOL_DURATION_SECONDS, "tool" => tool_name.to_string())
+        .record(duration.as_secs_f64());
+}
+
+pub(crate) fn error(kind: &'static str) {
+    counter!(ERRORS_TOTAL, "type" => kind).increment(1);
+}
+
+pub(crate) fn input_queued() {
+    gauge!(INPUT_QUEUE_DEPTH).increment(1.0);
+}
+
+/// An input left the queue, after waiting `wait` if it is starting its turn.
+pub(crate) fn input_dequeued(wait: Option<Duration>) {
+    gauge!(INPUT_QUEUE_DEPTH).decrement(1.0);
+    if let Some(wait) = wait {
+        histogram!(INPUT_QUEUE_WAIT_SECONDS).record(wait.as_secs_f64());
+    }
+}
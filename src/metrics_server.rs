@@ -0,0 +1,157 @@
+//! Ready-made `/metrics` and `/healthz` endpoints for agent services.
+//!
+//! [`MetricsServer::serve`] installs a Prometheus recorder for the
+//! [`metrics`](crate::metrics) the agents report and serves them on an HTTP
+//! port, so a containerized agent service can be scraped and probed without
+//! extra code:
+//!
+//! - `GET /metrics` renders every metric in the Prometheus text format
+//! - `GET /healthz` answers `200 ok`, or `503` listing the agents whose
+//!   execution loop failed
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::controller::{AgentController, AgentExecutionState, PublicExecutionState};
+use crate::error::{AgentError, Result};
+
+/// Interval between upkeeps of the recorder, which drains histogram samples.
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Content type of the Prometheus text format.
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// HTTP endpoint serving metrics and health; stops when dropped.
+#[derive(Debug)]
+pub struct MetricsServer {
+    address: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl MetricsServer {
+    /// Install the Prometheus recorder and serve `/metrics` and `/healthz` on
+    /// `address`, reporting the health of the agents of `controllers`.
+    ///
+    /// Fails if the address cannot be bound or another metrics recorder is
+    /// installed already; only one server runs per process.
+    pub async fn serve<A, I>(address: A, controllers: I) -> Result<Self>
+    where
+        A: ToSocketAddrs,
+        I: IntoIterator<Item = AgentController>,
+    {
+        let listener = TcpListener::bind(address).await?;
+        let address = listener.local_addr()?;
+        let handle =
+            PrometheusBuilder::new()
+                .install_recorder()
+                .map_err(|e| AgentError::Config {
+                    message: format!("Failed to install the metrics recorder: {}", e),
+                })?;
+        crate::metrics::describe();
+
+        let agents: Vec<_> = controllers
+            .into_iter()
+            .map(|controller| controller.subscribe())
+            .collect();
+        let task = tokio::spawn(async move {
+            let mut upkeep = tokio::time::interval(UPKEEP_INTERVAL);
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, _)) => {
+                            tokio::spawn(respond(stream, handle.clone(), agents.clone()));
+                        }
+                        Err(e) => {
+                            warn!("Metrics server stopped accepting requests: {}", e);
+                            break;
+                        }
+                    },
+                    _ = upkeep.tick() => handle.run_upkeep(),
+                }
+            }
+        });
+        debug!("Serving metrics on {}", address);
+        Ok(Self { address, task })
+    }
+
+    /// Address the server listens on, e.g. to find the port bound for `:0`.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Answer the request of one connection, then close it.
+async fn respond(
+    stream: TcpStream,
+    handle: PrometheusHandle,
+    agents: Vec<watch::Receiver<AgentExecutionState>>,
+) {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).await.is_err() {
+        return;
+    }
+    // The request's headers are not needed
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line).await {
+            Ok(read) if read > 0 && !line.trim().is_empty() => continue,
+            _ => break,
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", PROMETHEUS_CONTENT_TYPE, handle.render()),
+        ("GET", "/healthz") => {
+            let failed: Vec<String> = agents
+                .iter()
+                .enumerate()
+                .filter(|(_, state)| state.borrow().execution_state == PublicExecutionState::Error)
+                .map(|(i, _)| format!("agent {} failed", i))
+                .collect();
+            if failed.is_empty() {
+                ("200 OK", "text/plain", "ok\n".to_string())
+            } else {
+                let body = format!("{}\n", failed.join("\n"));
+                ("503 Service Unavailable", "text/plain", body)
+            }
+        }
+        ("GET", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_string(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    if let Err(e) = writer.write_all(response.as_bytes()).await {
+        debug!("Failed to answer a metrics request: {}", e);
+    }
+    let _ = writer.shutdown().await;
+}
@@ -0,0 +1,55 @@
+//! Hooks around tool calls.
+//!
+//! Middleware configured with `AgentConfigBuilder::tool_middleware` wraps
+//! every call dispatched through [`ToolRegistry::call`](crate::ToolRegistry::call).
+//! `before_call` hooks run in configuration order and may rewrite the
+//! arguments or reject the call; `after_call` hooks run in reverse order and
+//! may rewrite the result.
+//!
+//! Commands and patches run by Codex go through the same hooks, as calls of
+//! `exec_command` with the `command` argument and `apply_patch` with the
+//! `diff` argument. Their hooks run when Codex asks approval for the call,
+//! where a rejection denies it, or otherwise when the call starts, where a
+//! rejection interrupts the turn. Codex runs them with their original
+//! arguments. Rewritten results are reported in their `ToolComplete` output.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::tools::ToolExecutionResult;
+
+/// A tool invocation about to be executed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// Name of the tool
+    pub tool_name: String,
+
+    /// Arguments passed to the tool
+    pub arguments: serde_json::Value,
+
+    /// Turn the call belongs to
+    pub turn_id: u64,
+}
+
+/// Hooks run before and after each tool call, e.g. for logging, policy
+/// enforcement, or argument sanitization.
+///
+/// Hooks are called on the dispatching task, so they should return quickly.
+pub trait ToolMiddleware: Send + Sync {
+    /// Inspect or rewrite a call before it runs.
+    ///
+    /// Returning an error rejects the call; the error is reported to the
+    /// model as the tool's failed result and later hooks are skipped.
+    fn before_call(&self, _call: &mut ToolCall) -> Result<()> {
+        Ok(())
+    }
+
+    /// Inspect or rewrite the result of a call.
+    fn after_call(&self, _call: &ToolCall, _result: &mut ToolExecutionResult) {}
+}
+
+impl std::fmt::Debug for dyn ToolMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ToolMiddleware")
+    }
+}
@@ -0,0 +1,665 @@
+//! Loopback endpoint translating Codex's model requests for providers it
+//! cannot talk to directly.
+//!
+//! Codex only speaks the OpenAI Responses and Chat Completions APIs. For a
+//! [`ModelProvider`] with another API, such as Anthropic's Messages API or
+//! the Gemini API, or
+//! calling tools through the prompt, the agent serves Chat Completions on a
+//! loopback port instead, points Codex at it, and translates each request
+//! for the provider, streaming the provider's reply back as Chat Completions
+//! chunks. Like the tool server, the bridge only
+//! answers requests carrying the token it gave Codex.
+//!
+//! Codex handles a single tool call per model reply, so only the first call
+//! of a reply is passed on.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use codex_core::ModelProviderInfo;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::error::{AgentError, Result};
+use crate::provider::{ModelProvider, WireApi};
+
+/// Header Codex authenticates to the bridge with.
+const TOKEN_HEADER: &str = "x-agent-core-token";
+
+/// Largest request body accepted from Codex.
+const MAX_REQUEST_BYTES: usize = 64 << 20;
+
+/// Bridge serving one conversation's model requests; stops when dropped.
+#[derive(Debug)]
+pub(crate) struct ModelBridge {
+    address: SocketAddr,
+    token: String,
+    task: JoinHandle<()>,
+}
+
+impl ModelBridge {
+    /// Listen on a loopback port, translating requests for `provider`.
+    ///
+    /// Requests are authenticated with the key in the provider's `env_key`
+    /// variable, or else with `api_key`.
+    pub(crate) async fn listen(provider: ModelProvider, api_key: Option<String>) -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let address = listener.local_addr()?;
+        let token = uuid::Uuid::new_v4().to_string();
+
+        let api_key = provider
+            .env_key
+            .as_ref()
+            .and_then(|name| std::env::var(name).ok())
+            .or(api_key);
+        let upstream = Arc::new(Upstream {
+            provider,
+            api_key,
+            client: reqwest::Client::new(),
+        });
+        let expected: Arc<str> = token.clone().into();
+        let task = tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("Model bridge stopped accepting requests: {}", e);
+                        break;
+                    }
+                };
+                tokio::spawn(serve(stream, upstream.clone(), expected.clone()));
+            }
+        });
+        Ok(Self {
+            address,
+            token,
+            task,
+        })
+    }
+
+    /// Base URL of the bridge's Chat Completions API.
+    pub(crate) fn url(&self) -> String {
+        format!("http://{}/v1", self.address)
+    }
+
+    /// Token requests to the bridge must carry in [`TOKEN_HEADER`].
+    #[cfg(test)]
+    pub(crate) fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Codex provider sending requests through the bridge, named `name`.
+    pub(crate) fn codex_provider(&self, name: &str) -> Result<ModelProviderInfo> {
+        let info = json!({
+            "name": name,
+            "base_url": self.url(),
+            "wire_api": "chat",
+            "http_headers": { TOKEN_HEADER: self.token },
+        });
+        serde_json::from_value(info).map_err(|e| AgentError::Config {
+            message: format!("Invalid model bridge provider {}: {}", name, e),
+        })
+    }
+}
+
+impl Drop for ModelBridge {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Chat Completions request Codex sends.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ChatRequest {
+    pub model: String,
+    #[serde(default)]
+    pub messages: Vec<ChatMessage>,
+    #[serde(default)]
+    pub tools: Vec<ChatTool>,
+}
+
+impl ChatRequest {
+    /// Function tools offered to the model.
+    pub(crate) fn functions(&self) -> impl Iterator<Item = &ChatFunction> {
+        self.tools.iter().filter_map(|tool| tool.function.as_ref())
+    }
+
+    /// Name of the tool a call of an earlier reply was made to.
+    pub(crate) fn tool_name(&self, call_id: &str) -> Option<&str> {
+        self.messages
+            .iter()
+            .flat_map(|message| &message.tool_calls)
+            .find(|call| call.id == call_id)
+            .map(|call| call.function.name.as_str())
+    }
+}
+
+/// Message of a Chat Completions conversation.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct ChatMessage {
+    pub role: String,
+    /// Text, content parts, or null
+    #[serde(default)]
+    pub content: Value,
+    #[serde(default)]
+    pub tool_calls: Vec<ChatToolCall>,
+    #[serde(default)]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    /// Text of the message, with the text parts of multi-part content joined.
+    pub(crate) fn text(&self) -> String {
+        match &self.content {
+            Value::String(text) => text.clone(),
+            Value::Array(parts) => parts
+                .iter()
+                .filter_map(|part| part["text"].as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => String::new(),
+        }
+    }
+
+    /// URLs of the images of the message, data URLs included.
+    pub(crate) fn image_urls(&self) -> Vec<&str> {
+        match &self.content {
+            Value::Array(parts) => parts
+                .iter()
+                .filter_map(|part| part["image_url"]["url"].as_str())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Tool call of an earlier reply of the model.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ChatToolCall {
+    #[serde(default)]
+    pub id: String,
+    pub function: ChatFunctionCall,
+}
+
+impl ChatToolCall {
+    /// Arguments of the call as JSON, or an empty object if they don't parse.
+    pub(crate) fn input(&self) -> Value {
+        serde_json::from_str(&self.function.arguments).unwrap_or_else(|_| json!({}))
+    }
+}
+
+/// Function and arguments of a tool call.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ChatFunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: String,
+}
+
+/// Tool offered to the model.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ChatTool {
+    #[serde(default)]
+    pub function: Option<ChatFunction>,
+}
+
+/// Function tool offered to the model.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ChatFunction {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub parameters: Option<Value>,
+}
+
+impl ChatFunction {
+    /// JSON Schema of the function's arguments.
+    pub(crate) fn schema(&self) -> Value {
+        self.parameters
+            .clone()
+            .unwrap_or_else(|| json!({ "type": "object", "properties": {} }))
+    }
+}
+
+/// Media type and base64 data of a `data:` image URL.
+pub(crate) fn parse_data_url(url: &str) -> Option<(&str, &str)> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let media_type = header.strip_suffix(";base64")?;
+    Some((media_type, data))
+}
+
+/// Why the model stopped replying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FinishReason {
+    /// The reply is complete
+    Stop,
+    /// The reply ended with a tool call
+    ToolCalls,
+    /// The reply ran out of output tokens
+    Length,
+}
+
+impl FinishReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Stop => "stop",
+            Self::ToolCalls => "tool_calls",
+            Self::Length => "length",
+        }
+    }
+}
+
+/// Failure to answer a request, with the HTTP status passed back to Codex.
+///
+/// Codex retries rate-limited and server errors, so the provider's status
+/// is kept when it rejects a request.
+#[derive(Debug)]
+pub(crate) struct BridgeError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl BridgeError {
+    pub(crate) fn new<S: Into<String>>(status: u16, message: S) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<std::io::Error> for BridgeError {
+    fn from(e: std::io::Error) -> Self {
+        Self::new(502, format!("Failed to stream the reply: {}", e))
+    }
+}
+
+/// Provider the bridge translates requests for.
+pub(crate) struct Upstream {
+    pub provider: ModelProvider,
+    pub api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+impl Upstream {
+    /// URL of an endpoint of the provider, relative to its base URL.
+    pub(crate) fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.provider.base_url.trim_end_matches('/'), path)
+    }
+
+    /// Send a streamed request to the provider, with its headers, query
+    /// parameters, and the given authentication headers.
+    pub(crate) async fn post<I>(
+        &self,
+        url: &str,
+        auth: I,
+        body: &Value,
+    ) -> std::result::Result<SseEvents, BridgeError>
+    where
+        I: IntoIterator<Item = (&'static str, String)>,
+    {
+        let mut request = self
+            .client
+            .post(url)
+            .query(&self.provider.query_params)
+            .header("Accept", "text/event-stream")
+            .json(body);
+        for (name, value) in auth.into_iter() {
+            request = request.header(name, value);
+        }
+        for (name, value) in &self.provider.headers {
+            request = request.header(name, value);
+        }
+
+        let response = request.send().await.map_err(|e| {
+            BridgeError::new(
+                502,
+                format!("Request to {} failed: {}", self.provider.name, e),
+            )
+        })?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(BridgeError::new(status.as_u16(), message));
+        }
+        Ok(SseEvents::new(response))
+    }
+}
+
+/// Server-sent event of a streamed provider reply.
+///
+/// Providers repeat the event name in the data, so only the data is kept.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SseEvent {
+    pub data: String,
+}
+
+/// Server-sent events of a streamed provider reply.
+pub(crate) struct SseEvents {
+    response: reqwest::Response,
+    buffer: Vec<u8>,
+    done: bool,
+}
+
+impl SseEvents {
+    fn new(response: reqwest::Response) -> Self {
+        Self {
+            response,
+            buffer: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Next event of the reply, or `None` once it ends.
+    pub(crate) async fn next(&mut self) -> std::result::Result<Option<SseEvent>, BridgeError> {
+        loop {
+            if let Some(end) = self.buffer.windows(2).position(|pair| pair == b"\n\n") {
+                let block: Vec<u8> = self.buffer.drain(..end + 2).collect();
+                if let Some(event) = parse_event(&String::from_utf8_lossy(&block)) {
+                    return Ok(Some(event));
+                }
+                continue;
+            }
+            if self.done {
+                let block = std::mem::take(&mut self.buffer);
+                return Ok(parse_event(&String::from_utf8_lossy(&block)));
+            }
+            match self.response.chunk().await {
+                // Line ends may be CRLF; JSON payloads never hold a raw CR
+                Ok(Some(bytes)) => self
+                    .buffer
+                    .extend(bytes.iter().filter(|byte| **byte != b'\r')),
+                Ok(None) => self.done = true,
+                Err(e) => {
+                    return Err(BridgeError::new(
+                        502,
+                        format!("Reading the reply failed: {}", e),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Parse an event block, skipping blocks without data.
+fn parse_event(block: &str) -> Option<SseEvent> {
+    let data: Vec<&str> = block
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|value| value.strip_prefix(' ').unwrap_or(value))
+        .collect();
+    if data.is_empty() {
+        return None;
+    }
+    Some(SseEvent {
+        data: data.join("\n"),
+    })
+}
+
+/// Reply streamed back to Codex as Chat Completions chunks.
+pub(crate) struct ChatStream<'a> {
+    writer: &'a mut (dyn AsyncWrite + Send + Unpin),
+    model: String,
+    started: bool,
+    tool_called: bool,
+}
+
+impl<'a> ChatStream<'a> {
+    fn new(writer: &'a mut (dyn AsyncWrite + Send + Unpin), model: String) -> Self {
+        Self {
+            writer,
+            model,
+            started: false,
+            tool_called: false,
+        }
+    }
+
+    /// Stream text of the reply.
+    pub(crate) async fn text(&mut self, text: &str) -> std::result::Result<(), BridgeError> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.chunk(json!({ "content": text }), None).await
+    }
+
+    /// Pass on a tool call of the reply, unless one was already.
+    pub(crate) async fn tool_call(
+        &mut self,
+        id: &str,
+        name: &str,
+        arguments: &str,
+    ) -> std::result::Result<(), BridgeError> {
+        if self.tool_called {
+            debug!("Dropping call to {}, as Codex takes one per reply", name);
+            return Ok(());
+        }
+        self.tool_called = true;
+        let call = json!({
+            "index": 0,
+            "id": id,
+            "type": "function",
+            "function": { "name": name, "arguments": arguments },
+        });
+        self.chunk(json!({ "tool_calls": [call] }), None).await
+    }
+
+    /// End the reply.
+    pub(crate) async fn finish(
+        &mut self,
+        reason: FinishReason,
+    ) -> std::result::Result<(), BridgeError> {
+        let reason = if self.tool_called {
+            FinishReason::ToolCalls
+        } else {
+            reason
+        };
+        self.chunk(json!({}), Some(reason)).await?;
+        self.write("data: [DONE]\n\n").await?;
+        self.writer.write_all(b"0\r\n\r\n").await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    async fn chunk(
+        &mut self,
+        delta: Value,
+        finish_reason: Option<FinishReason>,
+    ) -> std::result::Result<(), BridgeError> {
+        let chunk = json!({
+            "object": "chat.completion.chunk",
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "delta": delta,
+                "finish_reason": finish_reason.map(FinishReason::as_str),
+            }],
+        });
+        self.write(&format!("data: {}\n\n", chunk)).await
+    }
+
+    /// Write a frame of the chunked response body, after the head if it is
+    /// the first.
+    async fn write(&mut self, data: &str) -> std::result::Result<(), BridgeError> {
+        if !self.started {
+            self.started = true;
+            self.writer
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                      Cache-Control: no-cache\r\nTransfer-Encoding: chunked\r\n\
+                      Connection: close\r\n\r\n",
+                )
+                .await?;
+        }
+        let frame = format!("{:x}\r\n{}\r\n", data.len(), data);
+        self.writer.write_all(frame.as_bytes()).await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Answer a request in the provider's API.
+async fn complete(
+    upstream: &Upstream,
+    request: ChatRequest,
+    stream: &mut ChatStream<'_>,
+) -> std::result::Result<(), BridgeError> {
+    #[cfg(feature = "provider-anthropic")]
+    if upstream.provider.wire_api == WireApi::Anthropic {
+        return crate::anthropic::complete(upstream, request, stream).await;
+    }
+    #[cfg(feature = "provider-gemini")]
+    if upstream.provider.wire_api == WireApi::Gemini {
+        return crate::gemini::complete(upstream, request, stream).await;
+    }
+    #[cfg(feature = "prompt-tools")]
+    if upstream.provider.prompt_tools {
+        return crate::prompt_tools::complete(upstream, request, stream).await;
+    }
+    Err(BridgeError::new(
+        500,
+        format!(
+            "Provider {} is not supported by the enabled features",
+            upstream.provider.name
+        ),
+    ))
+}
+
+/// HTTP request read from Codex.
+struct HttpRequest {
+    method: String,
+    path: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// Answer the request of one connection, then close it.
+async fn serve(stream: TcpStream, upstream: Arc<Upstream>, token: Arc<str>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let request = match read_request(&mut reader).await {
+        Ok(request) => request,
+        Err(message) => {
+            respond(&mut writer, 400, &message).await;
+            return;
+        }
+    };
+    // Any local process can connect; only Codex knows the token
+    if request.headers.get(TOKEN_HEADER).map(String::as_str) != Some(&*token) {
+        warn!("Rejected a model request without the bridge token");
+        respond(&mut writer, 401, "Missing or invalid bridge token").await;
+        return;
+    }
+    let path = request.path.split('?').next().unwrap_or_default();
+    if request.method != "POST" || !path.ends_with("/chat/completions") {
+        respond(&mut writer, 404, &format!("No endpoint at {}", path)).await;
+        return;
+    }
+    let chat: ChatRequest = match serde_json::from_slice(&request.body) {
+        Ok(chat) => chat,
+        Err(e) => {
+            respond(&mut writer, 400, &format!("Invalid request: {}", e)).await;
+            return;
+        }
+    };
+
+    debug!(
+        "Bridging a request for {} to {}",
+        chat.model, upstream.provider.name
+    );
+    let model = chat.model.clone();
+    let mut stream = ChatStream::new(&mut writer, model);
+    let result = complete(&upstream, chat, &mut stream).await;
+    // The status line is sent once streaming starts
+    let started = stream.started;
+    match result {
+        Ok(()) => {}
+        Err(e) if !started => {
+            warn!(
+                "Model request to {} failed ({}): {}",
+                upstream.provider.name, e.status, e.message
+            );
+            respond(&mut writer, e.status, &e.message).await;
+        }
+        Err(e) => warn!(
+            "Model reply from {} broke off: {}",
+            upstream.provider.name, e.message
+        ),
+    }
+    let _ = writer.shutdown().await;
+}
+
+/// Read a request with a `Content-Length` body.
+async fn read_request<R>(reader: &mut R) -> std::result::Result<HttpRequest, String>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut request_line = line.split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        line.clear();
+        let read = reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| e.to_string())?;
+        let header = line.trim_end();
+        if read == 0 || header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length: usize = headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    if length > MAX_REQUEST_BYTES {
+        return Err(format!("Request body over {} bytes", MAX_REQUEST_BYTES));
+    }
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(HttpRequest {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+/// Answer with an error status and an OpenAI-style error body.
+async fn respond<W>(writer: &mut W, status: u16, message: &str)
+where
+    W: AsyncWrite + Unpin,
+{
+    let body = json!({ "error": { "message": message } }).to_string();
+    let response = format!(
+        "HTTP/1.1 {} Error\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    if let Err(e) = writer.write_all(response.as_bytes()).await {
+        debug!("Failed to answer a model request: {}", e);
+    }
+}
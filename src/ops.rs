@@ -0,0 +1,155 @@
+//! Periodic operational summaries for monitoring long-running agents.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::messages::OutputData;
+use crate::plan::{PlanMessage, StepStatus};
+
+/// Number of tools listed in a summary.
+const TOP_TOOLS: usize = 5;
+
+/// Digest of agent activity over one reporting period.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpsSummary {
+    /// Length of the reporting period in seconds
+    pub period_secs: u64,
+
+    /// Turns completed during the period
+    pub turns_completed: u64,
+
+    /// Turns completed since execution started
+    pub total_turns: u64,
+
+    /// Input tokens consumed during the period
+    pub input_tokens: u64,
+
+    /// Output tokens produced during the period
+    pub output_tokens: u64,
+
+    /// Total tokens used during the period
+    pub total_tokens: u64,
+
+    /// Errors emitted during the period
+    pub errors: u64,
+
+    /// Warnings emitted during the period
+    pub warnings: u64,
+
+    /// Most frequently invoked tools during the period
+    pub top_tools: Vec<ToolUsage>,
+
+    /// Plan step in progress at the end of the period
+    pub current_step: Option<String>,
+}
+
+/// Token counts reported by the model.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// Input tokens consumed
+    pub input_tokens: u64,
+
+    /// Output tokens produced
+    pub output_tokens: u64,
+
+    /// Total tokens used
+    pub total_tokens: u64,
+}
+
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+/// Invocation count for a single tool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolUsage {
+    /// Tool name
+    pub name: String,
+
+    /// Number of invocations
+    pub count: u64,
+}
+
+/// Accumulates activity between summaries.
+#[derive(Debug, Default)]
+pub(crate) struct OpsAggregator {
+    turns_completed: u64,
+    total_turns: u64,
+    usage: TokenUsage,
+    errors: u64,
+    warnings: u64,
+    tools: HashMap<String, u64>,
+    current_step: Option<String>,
+}
+
+impl OpsAggregator {
+    /// Record an output message sent to the host.
+    pub(crate) fn record_output(&mut self, data: &OutputData) {
+        match data {
+            OutputData::ToolStart { tool_name, .. } => {
+                *self.tools.entry(tool_name.clone()).or_default() += 1;
+            }
+            OutputData::Error { .. } => self.errors += 1,
+            OutputData::Warning { .. } => self.warnings += 1,
+            _ => {}
+        }
+    }
+
+    /// Record a completed turn.
+    pub(crate) fn record_turn(&mut self) {
+        self.turns_completed += 1;
+        self.total_turns += 1;
+    }
+
+    /// Record token usage reported by the model.
+    pub(crate) fn record_tokens(&mut self, usage: TokenUsage) {
+        self.usage += usage;
+    }
+
+    /// Record a plan update, tracking the step in progress.
+    pub(crate) fn record_plan(&mut self, plan: &PlanMessage) {
+        self.current_step = plan
+            .todos
+            .iter()
+            .find(|todo| matches!(todo.status, StepStatus::InProgress))
+            .map(|todo| todo.content.clone());
+    }
+
+    /// Produce the summary for the period and start a new one.
+    ///
+    /// The turn total and current plan step carry over into the next period.
+    pub(crate) fn take(&mut self, period: Duration) -> OpsSummary {
+        let mut top_tools: Vec<ToolUsage> = self
+            .tools
+            .drain()
+            .map(|(name, count)| ToolUsage { name, count })
+            .collect();
+        top_tools.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        top_tools.truncate(TOP_TOOLS);
+
+        let summary = OpsSummary {
+            period_secs: period.as_secs(),
+            turns_completed: self.turns_completed,
+            total_turns: self.total_turns,
+            input_tokens: self.usage.input_tokens,
+            output_tokens: self.usage.output_tokens,
+            total_tokens: self.usage.total_tokens,
+            errors: self.errors,
+            warnings: self.warnings,
+            top_tools,
+            current_step: self.current_step.clone(),
+        };
+
+        *self = Self {
+            total_turns: self.total_turns,
+            current_step: self.current_step.take(),
+            ..Self::default()
+        };
+        summary
+    }
+}
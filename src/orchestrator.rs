@@ -0,0 +1,285 @@
+//! Supervisor/worker orchestration across multiple agents.
+//!
+//! A supervisor agent splits a task into subtasks for named workers, each
+//! worker agent runs with its own configuration and tools, and the supervisor
+//! synthesizes the final answer from their results. Output of every agent is
+//! multiplexed onto one channel, tagged with the agent id.
+
+use std::sync::Arc;
+
+use async_channel::Sender;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+use crate::agent::Agent;
+use crate::backend::LlmBackend;
+use crate::config::AgentConfig;
+use crate::error::{AgentError, Result};
+use crate::messages::OutputMessage;
+
+/// Agent id of the supervisor on the multiplexed output channel.
+pub const SUPERVISOR_ID: &str = "supervisor";
+
+/// A worker the supervisor can dispatch subtasks to.
+#[derive(Debug, Clone)]
+pub struct WorkerSpec {
+    /// Worker name, used by the supervisor to address it
+    pub name: String,
+
+    /// What the worker is good at, shown to the supervisor
+    pub description: String,
+
+    /// Configuration of the worker agent
+    pub config: AgentConfig,
+}
+
+/// A subtask assigned by the supervisor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subtask {
+    /// Name of the worker to run the subtask
+    pub worker: String,
+
+    /// Instructions for the worker
+    pub task: String,
+}
+
+/// Outcome of a subtask.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubtaskResult {
+    /// Agent id the worker's output was tagged with
+    pub agent_id: String,
+
+    /// The subtask
+    pub subtask: Subtask,
+
+    /// Worker response, or the error it failed with
+    pub result: std::result::Result<String, String>,
+}
+
+/// Output message of one of the orchestrated agents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaggedOutput {
+    /// `supervisor`, or `<worker>#<subtask index>`
+    pub agent_id: String,
+
+    /// The agent's output message
+    pub message: OutputMessage,
+}
+
+/// Final result of an orchestrated task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrchestrationResult {
+    /// Answer synthesized by the supervisor
+    pub answer: String,
+
+    /// Results of the individual subtasks, in plan order
+    pub subtasks: Vec<SubtaskResult>,
+}
+
+/// Runs tasks across a supervisor agent and a set of workers.
+#[derive(Debug, Clone)]
+pub struct Orchestrator {
+    supervisor: AgentConfig,
+    workers: Vec<WorkerSpec>,
+    concurrency: usize,
+    backend: Option<Arc<dyn LlmBackend>>,
+}
+
+impl Orchestrator {
+    /// Create an orchestrator with the given supervisor configuration.
+    pub fn new(supervisor: AgentConfig) -> Self {
+        Self {
+            supervisor,
+            workers: Vec::new(),
+            concurrency: 4,
+            backend: None,
+        }
+    }
+
+    /// Add a worker.
+    pub fn worker<S1, S2>(mut self, name: S1, description: S2, config: AgentConfig) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        self.workers.push(WorkerSpec {
+            name: name.into(),
+            description: description.into(),
+            config,
+        });
+        self
+    }
+
+    /// Set how many subtasks run at once (default 4).
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Run the supervisor and every worker on a mock backend replaying
+    /// scripted turns.
+    #[cfg(feature = "testing")]
+    pub fn with_mock_backend(mut self, backend: crate::testing::MockBackend) -> Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Decompose, dispatch, and synthesize a task.
+    ///
+    /// Output of the supervisor and every worker is sent to `output_tx`.
+    pub async fn run(
+        &self,
+        task: &str,
+        output_tx: Sender<TaggedOutput>,
+    ) -> Result<OrchestrationResult> {
+        if self.workers.is_empty() {
+            return Err(AgentError::Config {
+                message: "Orchestrator has no workers".to_string(),
+            });
+        }
+
+        let mut supervisor = self.agent(&self.supervisor)?;
+        forward(
+            SUPERVISOR_ID.to_string(),
+            supervisor.subscribe_output(),
+            output_tx.clone(),
+        );
+
+        let plan: Vec<Subtask> = supervisor
+            .query_json(self.decompose_prompt(task), subtask_schema())
+            .await?;
+        if let Some(unknown) = plan
+            .iter()
+            .find(|subtask| !self.workers.iter().any(|w| w.name == subtask.worker))
+        {
+            return Err(AgentError::Execution {
+                message: format!("Supervisor assigned unknown worker {}", unknown.worker),
+            });
+        }
+
+        let subtasks: Vec<SubtaskResult> = futures::stream::iter(plan.into_iter().enumerate())
+            .map(|(index, subtask)| self.dispatch(index, subtask, output_tx.clone()))
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+
+        let answer = supervisor.query(synthesis_prompt(task, &subtasks)).await?;
+        Ok(OrchestrationResult { answer, subtasks })
+    }
+
+    async fn dispatch(
+        &self,
+        index: usize,
+        subtask: Subtask,
+        output_tx: Sender<TaggedOutput>,
+    ) -> SubtaskResult {
+        let agent_id = format!("{}#{}", subtask.worker, index);
+        let result = async {
+            let spec = self
+                .workers
+                .iter()
+                .find(|worker| worker.name == subtask.worker)
+                .ok_or_else(|| AgentError::Execution {
+                    message: format!("Unknown worker {}", subtask.worker),
+                })?;
+            let mut worker = self.agent(&spec.config)?;
+            forward(agent_id.clone(), worker.subscribe_output(), output_tx);
+            worker.query(subtask.task.clone()).await
+        }
+        .await;
+
+        SubtaskResult {
+            agent_id,
+            subtask,
+            result: result.map_err(|e| e.to_string()),
+        }
+    }
+
+    fn agent(&self, config: &AgentConfig) -> Result<Agent> {
+        let agent = Agent::new(config.clone())?;
+        Ok(match &self.backend {
+            Some(backend) => agent.with_backend(backend.clone()),
+            None => agent,
+        })
+    }
+
+    fn decompose_prompt(&self, task: &str) -> String {
+        let mut prompt = String::from(
+            "You are a supervisor coordinating worker agents. Split the task below \
+             into independent subtasks, each assigned to one of these workers:\n\n",
+        );
+        for worker in &self.workers {
+            prompt.push_str(&format!("- {}: {}\n", worker.name, worker.description));
+        }
+        prompt.push_str(&format!(
+            "\nEach subtask must be self-contained; workers do not see the original \
+             task or each other's results.\n\nTask:\n{}",
+            task
+        ));
+        prompt
+    }
+}
+
+/// JSON Schema of the supervisor's plan.
+fn subtask_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "required": ["worker", "task"],
+            "properties": {
+                "worker": { "type": "string" },
+                "task": { "type": "string" }
+            }
+        }
+    })
+}
+
+fn synthesis_prompt(task: &str, results: &[SubtaskResult]) -> String {
+    let mut prompt = format!(
+        "Your workers have finished. Using their results, write the final answer \
+         to the original task.\n\nTask:\n{}\n",
+        task
+    );
+    for result in results {
+        prompt.push_str(&format!(
+            "\n## {} ({})\n{}\n",
+            result.agent_id,
+            result.subtask.task,
+            match &result.result {
+                Ok(response) => response.as_str(),
+                Err(e) => e.as_str(),
+            }
+        ));
+    }
+    prompt
+}
+
+/// Forward an agent's output to the multiplexed channel until either closes.
+pub(crate) fn forward(
+    agent_id: String,
+    mut output_rx: broadcast::Receiver<OutputMessage>,
+    output_tx: Sender<TaggedOutput>,
+) {
+    tokio::spawn(async move {
+        loop {
+            match output_rx.recv().await {
+                Ok(message) => {
+                    let tagged = TaggedOutput {
+                        agent_id: agent_id.clone(),
+                        message,
+                    };
+                    if output_tx.send(tagged).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Dropped {} output messages of {}", skipped, agent_id);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
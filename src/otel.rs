@@ -0,0 +1,121 @@
+//! OpenTelemetry export of agent spans.
+//!
+//! Agents trace each turn as an `agent.turn` span, with `agent.model_request`
+//! and `agent.tool` child spans carrying token counts and durations.
+//! [`OtlpExporter`] ships them to an OTLP collector over HTTP, so agent
+//! runs show up next to the host's other services in distributed traces.
+//!
+//! ```no_run
+//! use agent_core::otel::OtlpExporter;
+//!
+//! # fn main() -> agent_core::Result<()> {
+//! // Keep the guard alive; dropping it flushes the remaining spans
+//! let _guard = OtlpExporter::new()
+//!     .service_name("support-bot")
+//!     .endpoint("http://collector:4318/v1/traces")
+//!     .install()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Hosts with a `tracing` subscriber of their own add the layer of
+//! [`OtlpExporter::build`] to it instead of calling `install`.
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::{SdkTracerProvider, Tracer};
+use tracing::Subscriber;
+use tracing::warn;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::error::{AgentError, Result};
+
+/// Service name reported when none is set.
+const DEFAULT_SERVICE_NAME: &str = "agent-core";
+
+/// Exporter of spans to an OTLP collector.
+#[derive(Debug, Clone, Default)]
+pub struct OtlpExporter {
+    endpoint: Option<String>,
+    service_name: Option<String>,
+}
+
+impl OtlpExporter {
+    /// Create an exporter configured by the standard `OTEL_EXPORTER_OTLP_*`
+    /// environment variables, defaulting to a local collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send spans to the given OTLP/HTTP traces URL, e.g.
+    /// `http://localhost:4318/v1/traces`.
+    pub fn endpoint<S: Into<String>>(mut self, url: S) -> Self {
+        self.endpoint = Some(url.into());
+        self
+    }
+
+    /// Report spans as coming from the given service.
+    pub fn service_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.service_name = Some(name.into());
+        self
+    }
+
+    /// Build a layer exporting the spans of a `tracing` subscriber, and the
+    /// guard flushing them when dropped.
+    pub fn build<S>(self) -> Result<(OpenTelemetryLayer<S, Tracer>, OtelGuard)>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let mut exporter = SpanExporter::builder().with_http();
+        if let Some(endpoint) = self.endpoint {
+            exporter = exporter.with_endpoint(endpoint);
+        }
+        let exporter = exporter.build().map_err(|e| AgentError::Config {
+            message: format!("Failed to create OTLP exporter: {}", e),
+        })?;
+
+        let resource = Resource::builder()
+            .with_service_name(
+                self.service_name
+                    .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string()),
+            )
+            .build();
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(resource)
+            .build();
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("agent-core"));
+        Ok((layer, OtelGuard { provider }))
+    }
+
+    /// Install a global `tracing` subscriber exporting spans, failing if one
+    /// is installed already.
+    pub fn install(self) -> Result<OtelGuard> {
+        let (layer, guard) = self.build()?;
+        tracing_subscriber::registry()
+            .with(layer)
+            .try_init()
+            .map_err(|e| AgentError::Config {
+                message: format!("Failed to install tracing subscriber: {}", e),
+            })?;
+        Ok(guard)
+    }
+}
+
+/// Flushes and shuts down the export when dropped.
+#[derive(Debug)]
+pub struct OtelGuard {
+    provider: SdkTracerProvider,
+}
+
+impl Drop for OtelGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            warn!("Failed to shut down OTLP export: {}", e);
+        }
+    }
+}
@@ -0,0 +1,365 @@
+//! Inspection of patches before they are applied.
+//!
+//! Codex reports the changes of a patch it asks approval for. This module
+//! renders them as a unified diff for previews, checks the syntax of their
+//! hunks, and backs up the files they are about to change. Applied patches
+//! can be reverted from their hunks and backups.
+
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Directory backups are written to, relative to the working directory.
+const BACKUP_DIR: &str = ".agent-core/backups";
+
+/// Kind of change a patch makes to a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeKind {
+    /// The file is created
+    Add,
+
+    /// The file is deleted
+    Delete,
+
+    /// The file is modified, and possibly moved
+    Update,
+}
+
+/// Change a patch makes to one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PatchFile {
+    /// File the change applies to
+    pub path: PathBuf,
+
+    /// Kind of change
+    pub kind: FileChangeKind,
+
+    /// Unified diff of the change, with file headers
+    pub diff: String,
+
+    /// Path the file is moved to by an update
+    pub move_path: Option<PathBuf>,
+}
+
+impl PatchFile {
+    /// A file created with the given content.
+    pub(crate) fn add(path: &Path, content: &str) -> Self {
+        let mut diff = format!(
+            "--- /dev/null\n+++ b/{}\n@@ -0,0 +1,{} @@\n",
+            path.display(),
+            content.lines().count()
+        );
+        for line in content.lines() {
+            diff.push('+');
+            diff.push_str(line);
+            diff.push('\n');
+        }
+
+        Self {
+            path: path.to_path_buf(),
+            kind: FileChangeKind::Add,
+            diff,
+            move_path: None,
+        }
+    }
+
+    /// A deleted file.
+    pub(crate) fn delete(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            kind: FileChangeKind::Delete,
+            diff: format!("--- a/{}\n+++ /dev/null\n", path.display()),
+            move_path: None,
+        }
+    }
+
+    /// A file changed by the given hunks, and moved if `move_path` is set.
+    pub(crate) fn update(path: &Path, hunks: &str, move_path: Option<&Path>) -> Self {
+        let mut diff = format!(
+            "--- a/{}\n+++ b/{}\n",
+            path.display(),
+            move_path.unwrap_or(path).display()
+        );
+        diff.push_str(hunks);
+        if !diff.ends_with('\n') {
+            diff.push('\n');
+        }
+
+        Self {
+            path: path.to_path_buf(),
+            kind: FileChangeKind::Update,
+            diff,
+            move_path: move_path.map(Path::to_path_buf),
+        }
+    }
+}
+
+/// Unified diff of all the changes of a patch.
+pub(crate) fn render(files: &[PatchFile]) -> String {
+    files.iter().map(|file| file.diff.as_str()).collect()
+}
+
+/// Check that a patch fits the size limit and that its hunks are well formed.
+///
+/// Returns a description of the first problem found.
+pub(crate) fn validate(files: &[PatchFile], max_patch_size: usize) -> Result<(), String> {
+    let size: usize = files.iter().map(|file| file.diff.len()).sum();
+    if size > max_patch_size {
+        return Err(format!(
+            "patch is {size} bytes, over the limit of {max_patch_size} bytes"
+        ));
+    }
+
+    for file in files {
+        if file.kind == FileChangeKind::Update {
+            check_hunks(&file.diff).map_err(|e| format!("{}: {e}", file.path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Check that each hunk has a valid header and the line counts it declares.
+fn check_hunks(diff: &str) -> Result<(), String> {
+    // Old and new lines left in the current hunk
+    let mut remaining: Option<(usize, usize)> = None;
+    let mut hunks = 0;
+
+    for (index, line) in diff.lines().enumerate() {
+        let number = index + 1;
+        if let Some(header) = line.strip_prefix("@@") {
+            if remaining.is_some_and(|(old, new)| old > 0 || new > 0) {
+                return Err(format!("hunk ending at line {} is incomplete", number - 1));
+            }
+            let (old, new) = parse_hunk_header(header)
+                .ok_or_else(|| format!("invalid hunk header at line {number}"))?;
+            remaining = Some((old.1, new.1));
+            hunks += 1;
+            continue;
+        }
+
+        // "\ No newline at end of file"
+        if line.starts_with('\\') {
+            continue;
+        }
+
+        let Some((old, new)) = remaining.as_mut() else {
+            if line.starts_with("---") || line.starts_with("+++") {
+                continue;
+            }
+            return Err(format!("unexpected line {number} before the first hunk"));
+        };
+
+        let (old_lines, new_lines) = match line.chars().next() {
+            None | Some(' ') => (1, 1),
+            Some('-') => (1, 0),
+            Some('+') => (0, 1),
+            Some(_) => return Err(format!("invalid line {number} in hunk")),
+        };
+        if *old < old_lines || *new < new_lines {
+            return Err(format!(
+                "hunk has more lines than its header declares at line {number}"
+            ));
+        }
+        *old -= old_lines;
+        *new -= new_lines;
+    }
+
+    if hunks == 0 {
+        return Err("no hunks in diff".to_string());
+    }
+    if remaining.is_some_and(|(old, new)| old > 0 || new > 0) {
+        return Err("last hunk is incomplete".to_string());
+    }
+    Ok(())
+}
+
+/// Parse the old and new ranges of a hunk header, such as
+/// ` -1,3 +1,4 @@ fn main`, as start line and line count.
+fn parse_hunk_header(header: &str) -> Option<((usize, usize), (usize, usize))> {
+    let mut parts = header.split_whitespace();
+    let old = parts.next()?.strip_prefix('-')?;
+    let new = parts.next()?.strip_prefix('+')?;
+    if !parts.next()?.starts_with("@@") {
+        return None;
+    }
+    Some((parse_range(old)?, parse_range(new)?))
+}
+
+/// Start line and line count of a hunk range `start[,count]`, where the
+/// count defaults to 1.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// Hunk of a unified diff.
+struct Hunk<'a> {
+    /// First line of the new side, starting at 1
+    new_start: usize,
+
+    /// Lines of the old side
+    old_lines: Vec<&'a str>,
+
+    /// Lines of the new side
+    new_lines: Vec<&'a str>,
+}
+
+/// Parse the hunks of a unified diff, skipping file headers.
+fn parse_hunks(diff: &str) -> Result<Vec<Hunk<'_>>, String> {
+    let mut hunks = Vec::new();
+    for line in diff.lines() {
+        if let Some(header) = line.strip_prefix("@@") {
+            let (_, (new_start, _)) =
+                parse_hunk_header(header).ok_or_else(|| format!("invalid hunk header {line}"))?;
+            hunks.push(Hunk {
+                new_start,
+                old_lines: Vec::new(),
+                new_lines: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some(hunk) = hunks.last_mut() else {
+            continue;
+        };
+        match line.chars().next() {
+            None => {
+                hunk.old_lines.push("");
+                hunk.new_lines.push("");
+            }
+            Some(' ') => {
+                hunk.old_lines.push(&line[1..]);
+                hunk.new_lines.push(&line[1..]);
+            }
+            Some('-') => hunk.old_lines.push(&line[1..]),
+            Some('+') => hunk.new_lines.push(&line[1..]),
+            _ => {}
+        }
+    }
+    Ok(hunks)
+}
+
+/// Undo the hunks of a diff on the content it produced.
+///
+/// Each hunk's new side is looked up at its recorded line first, then at the
+/// closest line where it matches.
+fn reverse_hunks(content: &str, diff: &str) -> Result<String, String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut reverted: Vec<&str> = Vec::with_capacity(lines.len());
+    let mut cursor = 0;
+
+    for hunk in parse_hunks(diff)? {
+        let expected = if hunk.new_lines.is_empty() {
+            hunk.new_start
+        } else {
+            hunk.new_start.saturating_sub(1)
+        };
+        let start = (cursor..=lines.len().saturating_sub(hunk.new_lines.len()))
+            .filter(|&start| lines[start..].starts_with(&hunk.new_lines))
+            .min_by_key(|&start| start.abs_diff(expected))
+            .ok_or_else(|| format!("hunk at line {} no longer matches", hunk.new_start))?;
+
+        reverted.extend_from_slice(&lines[cursor..start]);
+        reverted.extend_from_slice(&hunk.old_lines);
+        cursor = start + hunk.new_lines.len();
+    }
+    reverted.extend_from_slice(&lines[cursor..]);
+
+    let mut original = reverted.join("\n");
+    if !original.is_empty() && (content.ends_with('\n') || content.is_empty()) {
+        original.push('\n');
+    }
+    Ok(original)
+}
+
+/// Undo the change a patch made to one file.
+///
+/// Created files are removed, updated files have their hunks reversed and
+/// their move undone, and deleted files are restored from `backup`.
+pub(crate) async fn revert(
+    file: &PatchFile,
+    working_directory: &Path,
+    backup: Option<&Path>,
+) -> Result<(), String> {
+    let path = working_directory.join(&file.path);
+    match file.kind {
+        FileChangeKind::Add => match tokio::fs::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        },
+        FileChangeKind::Delete => {
+            let backup = backup.ok_or_else(|| "no backup of the deleted file".to_string())?;
+            tokio::fs::copy(backup, &path)
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("failed to restore backup: {e}"))
+        }
+        FileChangeKind::Update => {
+            let current = working_directory.join(file.move_path.as_ref().unwrap_or(&file.path));
+            let content = tokio::fs::read_to_string(&current)
+                .await
+                .map_err(|e| e.to_string())?;
+            let original = reverse_hunks(&content, &file.diff)?;
+            tokio::fs::write(&path, original)
+                .await
+                .map_err(|e| e.to_string())?;
+            if current != path {
+                tokio::fs::remove_file(&current)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Back up the existing files a patch changes before it is applied.
+///
+/// Copies go under `.agent-core/backups/turn-<turn_id>` in the working
+/// directory, mirroring each file's path. Returns the backup paths written.
+pub(crate) async fn backup(
+    files: &[PatchFile],
+    working_directory: &Path,
+    turn_id: u64,
+) -> std::io::Result<Vec<PathBuf>> {
+    let mut written = Vec::new();
+
+    for file in files {
+        if file.kind == FileChangeKind::Add {
+            continue;
+        }
+
+        let source = working_directory.join(&file.path);
+        if !tokio::fs::try_exists(&source).await? {
+            continue;
+        }
+
+        let target = backup_path(working_directory, turn_id, &source);
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::copy(&source, &target).await?;
+        written.push(target);
+    }
+    Ok(written)
+}
+
+/// Path of a file's backup for a turn.
+///
+/// Files outside the working directory keep their full path without its root.
+pub(crate) fn backup_path(working_directory: &Path, turn_id: u64, path: &Path) -> PathBuf {
+    let path = working_directory.join(path);
+    let relative: PathBuf = path
+        .strip_prefix(working_directory)
+        .unwrap_or(&path)
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect();
+    working_directory
+        .join(BACKUP_DIR)
+        .join(format!("turn-{turn_id}"))
+        .join(relative)
+}
@@ -0,0 +1,466 @@
+//! Glob matching of paths accessed by file tools, and the paths shell
+//! commands name.
+//!
+//! Patterns use `/` separators: `*` and `?` match within one component, `**`
+//! matches any number of components, and a leading `~` is the home
+//! directory. Relative patterns are resolved against the tool's working
+//! directory.
+//!
+//! [`command_paths`] reads a command the way `sh` would, without running
+//! it: the paths it names as arguments and redirections are read, and those
+//! given to programs like `rm`, `mv`, or `sed -i` or redirected to are
+//! written. Commands with expansions (`$VAR`, `` `...` ``, `~user`) are
+//! rejected, as the paths they name are only known once they run. Paths a
+//! program computes itself, like a script opening a file, are not seen.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Programs writing every path they are given.
+const WRITERS: &[&str] = &[
+    "rm", "rmdir", "mv", "touch", "mkdir", "tee", "chmod", "chown", "truncate", "unlink", "shred",
+];
+
+/// Programs writing the last path they are given and reading the others.
+const COPIERS: &[&str] = &["cp", "ln", "install", "rsync"];
+
+/// Programs writing the files they are given with `-i`.
+const IN_PLACE_EDITORS: &[&str] = &["sed", "perl"];
+
+/// Programs running the command given after their options.
+const WRAPPERS: &[&str] = &[
+    "sudo", "env", "time", "nice", "nohup", "xargs", "exec", "command", "timeout", "stdbuf",
+];
+
+/// Devices commands may always read and write.
+const DEVICES: &[&str] = &[
+    "/dev/null",
+    "/dev/zero",
+    "/dev/random",
+    "/dev/urandom",
+    "/dev/stdin",
+    "/dev/stdout",
+    "/dev/stderr",
+    "/dev/tty",
+];
+
+/// How a command accesses a path it names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Access {
+    Read,
+    Write,
+}
+
+/// Paths a shell command named, relative ones resolved against
+/// `working_directory`, with how the command accesses them.
+///
+/// Fails for commands whose paths are only known once they run.
+pub(crate) fn command_paths(
+    command: &str,
+    working_directory: &Path,
+) -> Result<Vec<(PathBuf, Access)>, String> {
+    let mut paths = Vec::new();
+    // Directory relative paths resolve against, following `cd`
+    let mut directory = working_directory.to_path_buf();
+    let mut words = Vec::new();
+    let mut redirect = None;
+    for token in tokenize(command)? {
+        match token {
+            Token::Word(word) => match redirect.take() {
+                Some(Redirect::Path(access)) => {
+                    for path in word_paths(&word, &directory, true)? {
+                        paths.push((path, access));
+                    }
+                }
+                // Here-documents, here-strings, and duplicated descriptors
+                Some(Redirect::Data) => {}
+                None => words.push(word),
+            },
+            Token::Operator(operator) => {
+                redirect = match operator.as_str() {
+                    ">" | ">>" | ">|" | "&>" | "&>>" | "<>" => Some(Redirect::Path(Access::Write)),
+                    "<" => Some(Redirect::Path(Access::Read)),
+                    "<<" | "<<<" | ">&" | "<&" => Some(Redirect::Data),
+                    _ => {
+                        simple_command_paths(&words, &mut directory, &mut paths)?;
+                        words.clear();
+                        None
+                    }
+                };
+            }
+        }
+    }
+    simple_command_paths(&words, &mut directory, &mut paths)?;
+    Ok(paths)
+}
+
+/// Target of the redirection the next word belongs to.
+enum Redirect {
+    Path(Access),
+    Data,
+}
+
+enum Token {
+    Word(Word),
+    Operator(String),
+}
+
+#[derive(Default)]
+struct Word {
+    text: String,
+    /// Whether any part of the word was quoted
+    quoted: bool,
+    /// Whether the word has unquoted `*`, `?`, or `[`
+    wildcard: bool,
+    /// Whether the word starts with an unquoted `~`
+    tilde: bool,
+}
+
+/// Split a command into words, with quotes removed, and operators.
+fn tokenize(command: &str) -> Result<Vec<Token>, String> {
+    const EXPANSION: &str = "Commands with `$` or backtick expansions cannot be checked";
+    let unterminated = || "Command has an unterminated quote".to_string();
+
+    let mut tokens = Vec::new();
+    let mut word: Option<Word> = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => tokens.extend(word.take().map(Token::Word)),
+            '\n' => {
+                tokens.extend(word.take().map(Token::Word));
+                tokens.push(Token::Operator(";".to_string()));
+            }
+            '#' if word.is_none() => while chars.next_if(|&c| c != '\n').is_some() {},
+            '\'' => {
+                let word = word.get_or_insert_with(Word::default);
+                word.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.text.push(c),
+                        None => return Err(unterminated()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(Word::default);
+                word.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('$' | '`') => return Err(EXPANSION.to_string()),
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.text.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.text.push('\\');
+                                word.text.push(c);
+                            }
+                            None => return Err(unterminated()),
+                        },
+                        Some(c) => word.text.push(c),
+                        None => return Err(unterminated()),
+                    }
+                }
+            }
+            '\\' => {
+                if let Some(c) = chars.next().filter(|&c| c != '\n') {
+                    word.get_or_insert_with(Word::default).text.push(c);
+                }
+            }
+            '$' | '`' => return Err(EXPANSION.to_string()),
+            '|' | '&' | ';' | '<' | '>' | '(' | ')' => {
+                // A descriptor number before a redirection belongs to it
+                let descriptor = matches!(c, '<' | '>')
+                    && word.as_ref().is_some_and(|word| {
+                        !word.quoted && word.text.bytes().all(|byte| byte.is_ascii_digit())
+                    });
+                if descriptor {
+                    word = None;
+                } else {
+                    tokens.extend(word.take().map(Token::Word));
+                }
+                let mut operator = c.to_string();
+                while let Some(next) = chars.next_if(|&next| continues_operator(&operator, next)) {
+                    operator.push(next);
+                }
+                tokens.push(Token::Operator(operator));
+            }
+            c => {
+                let word = word.get_or_insert_with(Word::default);
+                if c == '~' && word.text.is_empty() && !word.quoted {
+                    word.tilde = true;
+                }
+                if matches!(c, '*' | '?' | '[') {
+                    word.wildcard = true;
+                }
+                word.text.push(c);
+            }
+        }
+    }
+    tokens.extend(word.take().map(Token::Word));
+    Ok(tokens)
+}
+
+fn continues_operator(operator: &str, next: char) -> bool {
+    matches!(
+        (operator, next),
+        ("|" | "&", '|' | '&')
+            | ("&" | "&>" | ">", '>')
+            | (">", '|' | '&')
+            | ("<" | "<<", '<')
+            | ("<", '>' | '&')
+    )
+}
+
+/// Collect the paths named by the words of one simple command.
+fn simple_command_paths(
+    words: &[Word],
+    directory: &mut PathBuf,
+    paths: &mut Vec<(PathBuf, Access)>,
+) -> Result<(), String> {
+    let mut program = None;
+    let mut arguments = Vec::new();
+    for word in words {
+        let runs_command = match program {
+            None => !is_assignment(word),
+            Some(name) => {
+                WRAPPERS.contains(&name)
+                    && !word
+                        .text
+                        .starts_with(|c: char| c == '-' || c.is_ascii_digit())
+                    && !is_assignment(word)
+            }
+        };
+        if !runs_command {
+            arguments.push(word);
+            continue;
+        }
+        program = Some(word.text.rsplit('/').next().unwrap_or_default());
+        // Programs run by path are read
+        if word.text.contains('/') {
+            for path in word_paths(word, directory, true)? {
+                paths.push((path, Access::Read));
+            }
+        }
+    }
+
+    let program = program.unwrap_or_default();
+    if matches!(program, "cd" | "pushd") {
+        let target = match arguments.first() {
+            Some(word) if word.text == "-" => {
+                return Err("Commands returning to a previous directory cannot be checked".into());
+            }
+            Some(word) => word_paths(word, directory, true)?.into_iter().next(),
+            None => expand_home("~", true),
+        };
+        if let Some(target) = target {
+            paths.push((target.clone(), Access::Read));
+            *directory = directory.join(target);
+        }
+        return Ok(());
+    }
+
+    let in_place = IN_PLACE_EDITORS.contains(&program)
+        && arguments.iter().any(|word| {
+            word.text.starts_with("--in-place")
+                || (word.text.starts_with('-')
+                    && !word.text.starts_with("--")
+                    && word.text.contains('i'))
+        });
+    let last = arguments
+        .iter()
+        .rposition(|word| !word.text.starts_with('-'));
+    for (index, word) in arguments.iter().enumerate() {
+        let writes =
+            WRITERS.contains(&program) || (COPIERS.contains(&program) && Some(index) == last);
+        for path in word_paths(word, directory, writes)? {
+            if in_place || !writes {
+                paths.push((path.clone(), Access::Read));
+            }
+            if in_place || writes {
+                paths.push((path, Access::Write));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Paths a word names, wildcards expanded against the filesystem.
+///
+/// Words are only taken as paths when they exist, are absolute, or have an
+/// existing parent directory, unless `named` tells they are paths.
+fn word_paths(word: &Word, working_directory: &Path, named: bool) -> Result<Vec<PathBuf>, String> {
+    let text = match word.text.split_once('=') {
+        // Options and assignments like --file=path or of=path
+        Some((_, value)) if word.text.starts_with('-') || is_assignment(word) => value,
+        _ if word.text.starts_with('-') => return Ok(Vec::new()),
+        _ => word.text.as_str(),
+    };
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Code given to interpreters names paths within it
+    if word.quoted && text.contains(|c: char| c.is_whitespace() || c == '(') {
+        let paths = text
+            .split(|c: char| c.is_whitespace() || "'\"`;|&<>(){}[],:=".contains(c))
+            .filter(|part| part.starts_with('/') || part.starts_with("~/"))
+            .filter_map(|part| expand_home(part, true))
+            .filter(|path| !is_device(path))
+            .collect();
+        return Ok(paths);
+    }
+
+    let path = match expand_home(text, word.tilde) {
+        Some(path) => working_directory.join(path),
+        None => return Err(format!("Commands naming {} cannot be checked", text)),
+    };
+    let paths = if word.wildcard {
+        expand_wildcards(&path)
+    } else {
+        vec![path]
+    };
+    Ok(paths
+        .into_iter()
+        .filter(|path| !is_device(path))
+        .filter(|path| {
+            named || word.tilde || Path::new(text).is_absolute() || names_path(text, path)
+        })
+        .collect())
+}
+
+/// Whether an argument names a path rather than other data: an existing
+/// one, or one with directories of which the parent exists.
+fn names_path(text: &str, path: &Path) -> bool {
+    path.symlink_metadata().is_ok()
+        || (text.contains('/') && path.parent().is_some_and(Path::is_dir))
+}
+
+fn is_assignment(word: &Word) -> bool {
+    word.text.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c == '_' || c.is_ascii_alphanumeric())
+    })
+}
+
+fn is_device(path: &Path) -> bool {
+    DEVICES.iter().any(|device| path == Path::new(device))
+}
+
+/// Replace a leading `~` with the home directory; `~user` is unsupported.
+fn expand_home(text: &str, tilde: bool) -> Option<PathBuf> {
+    let rest = match text.strip_prefix('~') {
+        Some(rest) if tilde => rest,
+        _ => return Some(PathBuf::from(text)),
+    };
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return None;
+    }
+    let home = std::env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(rest.trim_start_matches('/')))
+}
+
+/// Expand the `*` and `?` wildcards of an absolute path like the shell,
+/// keeping the path as given when nothing matches.
+fn expand_wildcards(pattern: &Path) -> Vec<PathBuf> {
+    let mut matches = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let part = component.as_os_str().to_string_lossy();
+        if !part.contains(['*', '?']) {
+            for path in &mut matches {
+                path.push(component);
+            }
+            continue;
+        }
+        matches = matches
+            .iter()
+            .filter_map(|directory| fs::read_dir(directory).ok())
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .is_some_and(|name| name_matches(&part, &name.to_string_lossy()))
+            })
+            .collect();
+    }
+    if matches.is_empty() {
+        matches.push(pattern.to_path_buf());
+    }
+    matches
+}
+
+/// Whether the resolved `path` matches `pattern`.
+pub(crate) fn glob_matches(pattern: &str, base: &Path, path: &Path) -> bool {
+    let pattern = absolute_pattern(pattern, base);
+    let pattern: Vec<&str> = pattern.split('/').filter(|part| !part.is_empty()).collect();
+    let path: Vec<String> = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    match_components(&pattern, &path)
+}
+
+/// Whether a file name matches a single-component pattern like `*.rs`.
+pub(crate) fn name_matches(pattern: &str, name: &str) -> bool {
+    match_segment(pattern.as_bytes(), name.as_bytes())
+}
+
+/// Anchor the pattern at the home or base directory.
+fn absolute_pattern(pattern: &str, base: &Path) -> String {
+    let (root, rest) = match pattern.strip_prefix('~') {
+        Some(rest) => match std::env::var_os("HOME") {
+            Some(home) => (canonical(Path::new(&home)), rest),
+            None => (PathBuf::new(), rest),
+        },
+        None if Path::new(pattern).is_absolute() => (PathBuf::new(), pattern),
+        None => (base.to_path_buf(), pattern),
+    };
+
+    let mut parts: Vec<String> = root
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    for part in rest.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part.to_string()),
+        }
+    }
+    parts.join("/")
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn match_components(pattern: &[&str], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_components(rest, &path[skip..])),
+        Some((part, rest)) => path.split_first().is_some_and(|(first, remaining)| {
+            match_segment(part.as_bytes(), first.as_bytes()) && match_components(rest, remaining)
+        }),
+    }
+}
+
+fn match_segment(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| match_segment(rest, &text[skip..])),
+        Some((b'?', rest)) => !text.is_empty() && match_segment(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && match_segment(rest, &text[1..]),
+    }
+}
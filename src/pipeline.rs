@@ -0,0 +1,241 @@
+//! Sequential pipelines of agents for ETL-style workflows.
+//!
+//! Each step runs on a fresh agent with its own configuration. The response
+//! of one step, optionally passed through a transform, becomes the input of
+//! the next. Output of every step is multiplexed onto one channel, tagged
+//! with the step name.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_channel::Sender;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::agent::Agent;
+use crate::backend::LlmBackend;
+use crate::config::AgentConfig;
+use crate::error::{AgentError, Result};
+use crate::orchestrator::{TaggedOutput, forward};
+
+/// Transform applied to a step's input before it is sent to the agent.
+type Transform = Arc<dyn Fn(String) -> Result<String> + Send + Sync>;
+
+/// How often a failed pipeline step is retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepRetryPolicy {
+    /// Total attempts, including the first
+    pub max_attempts: u32,
+
+    /// Delay before the first retry, doubled for every further retry
+    pub backoff: Duration,
+}
+
+impl StepRetryPolicy {
+    /// Run each step once.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    /// Make up to `max_attempts` attempts with exponential backoff.
+    pub fn new(max_attempts: u32, backoff: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            backoff,
+        }
+    }
+
+    /// Delay before the given retry (1 for the first).
+    fn delay(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+    }
+}
+
+impl Default for StepRetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+struct PipelineStep {
+    name: String,
+    config: AgentConfig,
+    transform: Option<Transform>,
+    retry: StepRetryPolicy,
+}
+
+/// Result of one pipeline step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepResult {
+    /// Step name
+    pub name: String,
+
+    /// Input sent to the step's agent, after the transform
+    pub input: String,
+
+    /// Response of the step's agent
+    pub output: String,
+
+    /// Attempts made, including the successful one
+    pub attempts: u32,
+}
+
+/// Final result of a pipeline run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineResult {
+    /// Response of the last step
+    pub output: String,
+
+    /// Results of every step, in order
+    pub steps: Vec<StepResult>,
+}
+
+/// Chain of agent steps, each feeding the next.
+#[derive(Default)]
+pub struct Pipeline {
+    steps: Vec<PipelineStep>,
+    backend: Option<Arc<dyn LlmBackend>>,
+}
+
+impl Pipeline {
+    /// Create an empty pipeline.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a step sending the previous output to an agent unchanged.
+    pub fn step<S: Into<String>>(mut self, name: S, config: AgentConfig) -> Self {
+        self.steps.push(PipelineStep {
+            name: name.into(),
+            config,
+            transform: None,
+            retry: StepRetryPolicy::default(),
+        });
+        self
+    }
+
+    /// Append a step passing the previous output through `transform` before
+    /// sending it to the agent, e.g. to wrap it in a prompt template.
+    pub fn step_with<S, F>(mut self, name: S, config: AgentConfig, transform: F) -> Self
+    where
+        S: Into<String>,
+        F: Fn(String) -> Result<String> + Send + Sync + 'static,
+    {
+        self.steps.push(PipelineStep {
+            name: name.into(),
+            config,
+            transform: Some(Arc::new(transform)),
+            retry: StepRetryPolicy::default(),
+        });
+        self
+    }
+
+    /// Set the retry policy of the last added step.
+    pub fn retry(mut self, policy: StepRetryPolicy) -> Self {
+        if let Some(step) = self.steps.last_mut() {
+            step.retry = policy;
+        }
+        self
+    }
+
+    /// Run the agents of every step on a mock backend replaying scripted
+    /// turns, one turn per attempt.
+    #[cfg(feature = "testing")]
+    pub fn with_mock_backend(mut self, backend: crate::testing::MockBackend) -> Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Run the pipeline on `input`.
+    ///
+    /// Output of every step is sent to `output_tx`, tagged with the step
+    /// name. Stops at the first step that fails all its attempts.
+    pub async fn run<S: Into<String>>(
+        &self,
+        input: S,
+        output_tx: Sender<TaggedOutput>,
+    ) -> Result<PipelineResult> {
+        if self.steps.is_empty() {
+            return Err(AgentError::Config {
+                message: "Pipeline has no steps".to_string(),
+            });
+        }
+
+        let mut output = input.into();
+        let mut steps = Vec::with_capacity(self.steps.len());
+        for step in &self.steps {
+            let input = match &step.transform {
+                Some(transform) => transform(output)?,
+                None => output,
+            };
+            let (response, attempts) =
+                run_step(step, self.backend.as_ref(), &input, &output_tx).await?;
+            output = response.clone();
+            steps.push(StepResult {
+                name: step.name.clone(),
+                input,
+                output: response,
+                attempts,
+            });
+        }
+
+        Ok(PipelineResult { output, steps })
+    }
+}
+
+impl std::fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pipeline")
+            .field(
+                "steps",
+                &self.steps.iter().map(|step| &step.name).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Run a step with its retry policy, returning the response and attempts.
+async fn run_step(
+    step: &PipelineStep,
+    backend: Option<&Arc<dyn LlmBackend>>,
+    input: &str,
+    output_tx: &Sender<TaggedOutput>,
+) -> Result<(String, u32)> {
+    let mut attempt = 1;
+    loop {
+        let mut agent = Agent::new(step.config.clone())?;
+        if let Some(backend) = backend {
+            agent = agent.with_backend(backend.clone());
+        }
+        forward(
+            step.name.clone(),
+            agent.subscribe_output(),
+            output_tx.clone(),
+        );
+
+        match agent.query(input).await {
+            Ok(response) => return Ok((response, attempt)),
+            Err(e) if attempt < step.retry.max_attempts => {
+                let delay = step.retry.delay(attempt);
+                warn!(
+                    "Pipeline step {} failed (attempt {}), retrying in {:?}: {}",
+                    step.name, attempt, delay, e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                return Err(AgentError::Execution {
+                    message: format!(
+                        "Pipeline step {} failed after {} attempts: {}",
+                        step.name, attempt, e
+                    ),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! A managed pool of agents for serving concurrent requests.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use codex_login::AuthManager;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::agent::Agent;
+use crate::auth;
+use crate::backend::LlmBackend;
+use crate::config::AgentConfig;
+use crate::error::{AgentError, Result};
+
+/// Pool of up to `size` agents sharing one configuration and auth manager.
+///
+/// Agents are created lazily, checked out for exclusive use, and returned
+/// to the pool when the [`PooledAgent`] is dropped. Returned agents drop
+/// their conversation and turn records, so every checkout starts a fresh
+/// conversation. An agent that has run the configured number of turns is
+/// discarded instead.
+#[derive(Clone)]
+pub struct AgentPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    config: AgentConfig,
+    auth_manager: Option<Arc<AuthManager>>,
+    /// Backend of the agents created, Codex unless replaced
+    backend: Mutex<Option<Arc<dyn LlmBackend>>>,
+    size: usize,
+    /// Turn limit per agent, 0 for none
+    recycle_after: AtomicU64,
+    permits: Arc<Semaphore>,
+    idle: Mutex<Vec<Agent>>,
+    created: AtomicU64,
+    recycled: AtomicU64,
+    checkouts: AtomicU64,
+}
+
+impl AgentPool {
+    /// Create a pool of at most `size` agents.
+    pub fn new(config: AgentConfig, size: usize) -> Result<Self> {
+        if size == 0 {
+            return Err(AgentError::Config {
+                message: "Agent pool size must be at least 1".to_string(),
+            });
+        }
+
+        // Agents with an API key authenticate with it directly
+        let auth_manager = config
+            .api_key()
+            .is_none()
+            .then(|| auth::auth_manager(config.auth_method()));
+
+        Ok(Self {
+            inner: Arc::new(PoolInner {
+                config,
+                auth_manager,
+                backend: Mutex::new(None),
+                size,
+                recycle_after: AtomicU64::new(0),
+                permits: Arc::new(Semaphore::new(size)),
+                idle: Mutex::new(Vec::new()),
+                created: AtomicU64::new(0),
+                recycled: AtomicU64::new(0),
+                checkouts: AtomicU64::new(0),
+            }),
+        })
+    }
+
+    /// Discard agents once they have run `turns` turns.
+    pub fn recycle_after(self, turns: u64) -> Self {
+        self.inner.recycle_after.store(turns, Ordering::Relaxed);
+        self
+    }
+
+    /// Run the agents created from now on on a mock backend replaying
+    /// scripted turns.
+    #[cfg(feature = "testing")]
+    pub fn with_mock_backend(self, backend: crate::testing::MockBackend) -> Self {
+        *self
+            .inner
+            .backend
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(backend));
+        self
+    }
+
+    /// Check out an agent, waiting until one is available.
+    pub async fn checkout(&self) -> Result<PooledAgent> {
+        let permit = self
+            .inner
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| AgentError::Generic {
+                message: "Agent pool is closed".to_string(),
+            })?;
+
+        let agent = match self.inner.lock_idle().pop() {
+            Some(agent) => agent,
+            None => self.inner.create_agent()?,
+        };
+        self.inner.checkouts.fetch_add(1, Ordering::Relaxed);
+
+        Ok(PooledAgent {
+            agent: Some(agent),
+            pool: self.inner.clone(),
+            _permit: permit,
+        })
+    }
+
+    /// Get a snapshot of the pool's metrics.
+    pub fn metrics(&self) -> PoolMetrics {
+        let available = self.inner.permits.available_permits();
+        PoolMetrics {
+            size: self.inner.size,
+            idle: self.inner.lock_idle().len(),
+            checked_out: self.inner.size - available,
+            created: self.inner.created.load(Ordering::Relaxed),
+            recycled: self.inner.recycled.load(Ordering::Relaxed),
+            checkouts: self.inner.checkouts.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl PoolInner {
+    fn create_agent(&self) -> Result<Agent> {
+        let mut agent = Agent::new(self.config.clone())?;
+        if let Some(auth_manager) = &self.auth_manager {
+            agent = agent.with_auth_manager(auth_manager.clone());
+        }
+        let backend = self
+            .backend
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        if let Some(backend) = backend {
+            agent = agent.with_backend(backend);
+        }
+        self.created.fetch_add(1, Ordering::Relaxed);
+        Ok(agent)
+    }
+
+    fn lock_idle(&self) -> std::sync::MutexGuard<'_, Vec<Agent>> {
+        // The list is always left consistent, so a poisoned lock is still usable
+        self.idle.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn release(&self, mut agent: Agent) {
+        let limit = self.recycle_after.load(Ordering::Relaxed);
+        let worn_out = limit > 0 && agent.controller().turn_count() >= limit;
+        if worn_out {
+            self.recycled.fetch_add(1, Ordering::Relaxed);
+        } else {
+            // The next checkout may serve another user
+            agent.reset_conversation();
+            self.lock_idle().push(agent);
+        }
+    }
+}
+
+/// An agent checked out of an [`AgentPool`], returned to it when dropped.
+pub struct PooledAgent {
+    agent: Option<Agent>,
+    pool: Arc<PoolInner>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledAgent {
+    /// Discard the agent instead of returning it to the pool.
+    pub fn discard(mut self) {
+        if self.agent.take().is_some() {
+            self.pool.recycled.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Deref for PooledAgent {
+    type Target = Agent;
+
+    // Only `discard` and `drop` take the agent, both consuming the guard
+    #[allow(clippy::expect_used)]
+    fn deref(&self) -> &Agent {
+        self.agent.as_ref().expect("pooled agent already released")
+    }
+}
+
+impl DerefMut for PooledAgent {
+    #[allow(clippy::expect_used)]
+    fn deref_mut(&mut self) -> &mut Agent {
+        self.agent.as_mut().expect("pooled agent already released")
+    }
+}
+
+impl Drop for PooledAgent {
+    fn drop(&mut self) {
+        if let Some(agent) = self.agent.take() {
+            self.pool.release(agent);
+        }
+    }
+}
+
+/// Point-in-time metrics of an [`AgentPool`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolMetrics {
+    /// Maximum number of agents
+    pub size: usize,
+
+    /// Agents created and waiting to be checked out
+    pub idle: usize,
+
+    /// Agents currently checked out
+    pub checked_out: usize,
+
+    /// Agents created since the pool started
+    pub created: u64,
+
+    /// Agents discarded after reaching the turn limit
+    pub recycled: u64,
+
+    /// Total checkouts served
+    pub checkouts: u64,
+}
@@ -0,0 +1,361 @@
+//! Preflight checks run before the first query.
+//!
+//! [`Agent::validate`](crate::Agent::validate) checks what would otherwise
+//! only fail mid-conversation: the configuration, credentials, the model,
+//! MCP server commands, the relay serving the host tools, and sandbox
+//! prerequisites. Checks are local and
+//! cheap: no model request is made and no MCP server is started.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use codex_login::AuthManager;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::auth::{self, AuthMethod};
+use crate::config::AgentConfig;
+use crate::diagnostics::Severity;
+use crate::error::AgentError;
+use crate::mcp::McpServerConfig;
+use crate::sandbox::SandboxBackend;
+use crate::tool_server;
+use crate::tools::ToolConfig;
+
+/// Area of the setup a check covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreflightArea {
+    /// Configuration values
+    Config,
+
+    /// API key or login
+    Credentials,
+
+    /// Model and provider
+    Model,
+
+    /// MCP server
+    McpServer,
+
+    /// Host tools and the relay Codex calls them through
+    Tools,
+
+    /// Working directory and sandbox backend
+    Sandbox,
+}
+
+/// A problem found by a preflight check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreflightIssue {
+    /// Area of the setup
+    pub area: PreflightArea,
+
+    /// What the issue is about, e.g. a config path or MCP server name
+    pub subject: String,
+
+    /// Error if queries will fail, warning if they may
+    pub severity: Severity,
+
+    /// Human-readable message
+    pub message: String,
+}
+
+impl std::fmt::Display for PreflightIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{:?}] {}: {}",
+            self.severity, self.subject, self.message
+        )
+    }
+}
+
+/// Outcome of the preflight checks.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreflightReport {
+    /// Problems found, in check order
+    pub issues: Vec<PreflightIssue>,
+}
+
+impl PreflightReport {
+    /// Whether no check found an error; warnings are allowed.
+    pub fn is_ok(&self) -> bool {
+        !self
+            .issues
+            .iter()
+            .any(|issue| issue.severity == Severity::Error)
+    }
+
+    /// Issues with the given severity.
+    pub fn with_severity(&self, severity: Severity) -> impl Iterator<Item = &PreflightIssue> {
+        self.issues
+            .iter()
+            .filter(move |issue| issue.severity == severity)
+    }
+
+    fn push<S1, S2>(&mut self, area: PreflightArea, severity: Severity, subject: S1, message: S2)
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        self.issues.push(PreflightIssue {
+            area,
+            subject: subject.into(),
+            severity,
+            message: message.into(),
+        });
+    }
+}
+
+impl std::fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.issues.is_empty() {
+            return write!(f, "All preflight checks passed");
+        }
+        for (i, issue) in self.issues.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every check against the configuration and the tools registered on
+/// the agent.
+pub(crate) async fn run(
+    config: &AgentConfig,
+    tools: &[ToolConfig],
+    auth_manager: Option<&AuthManager>,
+) -> PreflightReport {
+    let mut report = PreflightReport::default();
+    check_config(config, &mut report);
+    check_credentials(config, auth_manager, &mut report).await;
+    for server in config.mcp_servers() {
+        check_mcp_server(config, server, &mut report);
+    }
+    check_tool_bridge(config, tools, &mut report);
+    check_sandbox(config, &mut report).await;
+    report
+}
+
+fn check_config(config: &AgentConfig, report: &mut PreflightReport) {
+    let errors = match config.validate() {
+        Err(AgentError::InvalidConfig { issues }) => issues,
+        _ => Vec::new(),
+    };
+    let warnings = config.warnings();
+    let issues = errors
+        .into_iter()
+        .map(|issue| (Severity::Error, issue))
+        .chain(warnings.into_iter().map(|issue| (Severity::Warning, issue)));
+    for (severity, issue) in issues {
+        let area = match issue.path.as_str() {
+            "model" => PreflightArea::Model,
+            path if path.starts_with("model_provider") || path.starts_with("provider_routes") => {
+                PreflightArea::Model
+            }
+            "tool_bridge" => PreflightArea::Tools,
+            _ => PreflightArea::Config,
+        };
+        report.push(area, severity, issue.path, issue.message);
+    }
+}
+
+async fn check_credentials(
+    config: &AgentConfig,
+    auth_manager: Option<&AuthManager>,
+    report: &mut PreflightReport,
+) {
+    let (provider, model) = config.resolve_model(config.model());
+    if let Some(provider) = &provider {
+        // Providers with a key variable of their own authenticate with it
+        if let Some(env_key) = &provider.env_key {
+            if std::env::var(env_key).is_err() && config.api_key().is_none() {
+                report.push(
+                    PreflightArea::Credentials,
+                    Severity::Error,
+                    env_key.clone(),
+                    format!(
+                        "{} is not set, so requests for {} to {} are unauthenticated",
+                        env_key, model, provider.name
+                    ),
+                );
+            }
+            return;
+        }
+    }
+    if config.api_key().is_some() {
+        return;
+    }
+
+    let default_manager;
+    let auth_manager = match auth_manager {
+        Some(auth_manager) => auth_manager,
+        None => {
+            default_manager = auth::auth_manager(config.auth_method());
+            &default_manager
+        }
+    };
+    match auth::check_auth(auth_manager, config.auth_method()).await {
+        Ok(()) if auth_manager.auth().is_some() => {}
+        // Self-hosted providers may not need a key at all
+        Ok(()) if provider.is_some() => {}
+        Ok(()) => report.push(
+            PreflightArea::Credentials,
+            Severity::Error,
+            "api_key",
+            "no API key is set and no login was found; set an API key or sign in with \
+             `codex login`",
+        ),
+        Err(e) => {
+            let subject = match config.auth_method() {
+                AuthMethod::ApiKey => "api_key",
+                AuthMethod::ChatGpt => "auth_method",
+            };
+            report.push(
+                PreflightArea::Credentials,
+                Severity::Error,
+                subject,
+                e.to_string(),
+            );
+        }
+    }
+}
+
+fn check_mcp_server(config: &AgentConfig, server: &McpServerConfig, report: &mut PreflightReport) {
+    match server {
+        McpServerConfig::Command {
+            name,
+            command,
+            working_directory,
+            ..
+        } => {
+            let directory = match working_directory {
+                Some(directory) => config.working_directory().join(directory),
+                None => config.working_directory().clone(),
+            };
+            if !directory.is_dir() {
+                report.push(
+                    PreflightArea::McpServer,
+                    Severity::Error,
+                    name.clone(),
+                    format!("working directory {} does not exist", directory.display()),
+                );
+            }
+            if find_program(command, &directory).is_none() {
+                report.push(
+                    PreflightArea::McpServer,
+                    Severity::Error,
+                    name.clone(),
+                    format!("command {} was not found", command),
+                );
+            }
+        }
+        McpServerConfig::Http { name, url, .. } => {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                report.push(
+                    PreflightArea::McpServer,
+                    Severity::Error,
+                    name.clone(),
+                    format!("\"{}\" is not an http(s) URL", url),
+                );
+            }
+        }
+    }
+}
+
+fn check_tool_bridge(config: &AgentConfig, tools: &[ToolConfig], report: &mut PreflightReport) {
+    // Reported by the configuration check for the configured tools already
+    if report
+        .issues
+        .iter()
+        .any(|issue| issue.subject == "tool_bridge")
+        || !tool_server::needs_bridge(tools, config.sandbox_backend())
+    {
+        return;
+    }
+    if tool_server::resolve_bridge(config.tool_bridge()).is_none() {
+        let bridge = config
+            .tool_bridge()
+            .unwrap_or(Path::new(tool_server::DEFAULT_BRIDGE));
+        report.push(
+            PreflightArea::Tools,
+            Severity::Error,
+            "tool_bridge",
+            format!(
+                "relay {} was not found, so Codex cannot call the host tools",
+                bridge.display()
+            ),
+        );
+    }
+}
+
+async fn check_sandbox(config: &AgentConfig, report: &mut PreflightReport) {
+    let working_directory = config.working_directory();
+    if !working_directory.is_dir() {
+        report.push(
+            PreflightArea::Sandbox,
+            Severity::Error,
+            "working_directory",
+            format!("{} does not exist", working_directory.display()),
+        );
+        return;
+    }
+
+    if (config.git_checkpoints() || config.auto_commit().is_some())
+        && find_program("git", working_directory).is_none()
+    {
+        report.push(
+            PreflightArea::Sandbox,
+            Severity::Warning,
+            "git",
+            "git was not found, so turns will not be checkpointed or committed",
+        );
+    }
+
+    if let SandboxBackend::Container(container) = config.sandbox_backend() {
+        let program = container.runtime.program();
+        if find_program(program, working_directory).is_none() {
+            report.push(
+                PreflightArea::Sandbox,
+                Severity::Error,
+                "sandbox_backend",
+                format!("{} was not found", program),
+            );
+            return;
+        }
+        // `version` fails when the client cannot reach the engine
+        let reachable = Command::new(program)
+            .arg("version")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .is_ok_and(|status| status.success());
+        if !reachable {
+            report.push(
+                PreflightArea::Sandbox,
+                Severity::Error,
+                "sandbox_backend",
+                format!("{} cannot reach its container engine", program),
+            );
+        }
+    }
+}
+
+/// Resolve a program like the shell would: paths relative to `directory`,
+/// bare names on `PATH`.
+fn find_program(program: &str, directory: &Path) -> Option<PathBuf> {
+    if program.contains('/') {
+        let path = directory.join(program);
+        return path.is_file().then_some(path);
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
@@ -0,0 +1,311 @@
+//! Running commands with a timeout that cleans up their whole process group,
+//! and terminating commands other components spawned.
+
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::{Child, Command};
+
+use crate::blocking::CancellationToken;
+use crate::error::Result;
+use crate::limits::{KillPolicy, ReapedProcess};
+
+/// Output of a command run with [`run_with_timeout`].
+#[derive(Debug)]
+pub(crate) struct CommandOutput {
+    /// Exit status, if the command finished before the timeout or
+    /// cancellation
+    pub status: Option<ExitStatus>,
+
+    /// Captured standard output
+    pub stdout: String,
+
+    /// Captured standard error
+    pub stderr: String,
+
+    /// Whether the command was killed after exceeding the timeout
+    pub timed_out: bool,
+
+    /// Processes terminated while cleaning up the process group
+    pub reaped: Vec<ReapedProcess>,
+}
+
+/// Spawn a command in its own process group and wait for it with a timeout.
+///
+/// On timeout or cancellation the group is terminated following the kill
+/// policy. Processes the command left running in the background after
+/// exiting are terminated the same way, so nothing outlives the call.
+pub(crate) async fn run_with_timeout(
+    mut command: Command,
+    timeout: Option<Duration>,
+    policy: &KillPolicy,
+    cancellation: &CancellationToken,
+) -> Result<CommandOutput> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(unix)]
+    command.process_group(0);
+
+    let mut child = command.spawn()?;
+    let pgid = child.id();
+    let stdout = child.stdout.take().map(|out| tokio::spawn(read_all(out)));
+    let stderr = child.stderr.take().map(|err| tokio::spawn(read_all(err)));
+
+    let (status, timed_out) = {
+        let wait = async {
+            match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, child.wait()).await.ok(),
+                None => Some(child.wait().await),
+            }
+        };
+        tokio::select! {
+            status = wait => match status {
+                Some(status) => (Some(status?), false),
+                None => (None, true),
+            },
+            _ = cancellation.cancelled() => (None, false),
+        }
+    };
+
+    let reaped = match pgid {
+        Some(pgid) if status.is_none() || group_alive(pgid) => {
+            terminate_group(pgid, &mut child, policy).await
+        }
+        _ => Vec::new(),
+    };
+    if !reaped.is_empty() {
+        tracing::debug!("Reaped processes in group {:?}: {:?}", pgid, reaped);
+    }
+
+    Ok(CommandOutput {
+        status,
+        stdout: join_output(stdout).await,
+        stderr: join_output(stderr).await,
+        timed_out,
+        reaped,
+    })
+}
+
+/// Terminate every process in the group: SIGTERM, grace period, SIGKILL.
+#[cfg(unix)]
+async fn terminate_group(pgid: u32, child: &mut Child, policy: &KillPolicy) -> Vec<ReapedProcess> {
+    let members = group_members(pgid);
+    signal_group(pgid, libc::SIGTERM);
+
+    // Reap the leader so it doesn't linger as a zombie, then give the rest of
+    // the group the remainder of the grace period
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(policy.grace_period);
+    let _ = tokio::time::timeout_at(deadline, child.wait()).await;
+    while group_alive(pgid) && tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    signal_group(pgid, libc::SIGKILL);
+    let _ = child.wait().await;
+    members
+}
+
+/// Terminate the command; process groups are not available on this platform.
+#[cfg(not(unix))]
+async fn terminate_group(
+    _pgid: u32,
+    child: &mut Child,
+    _policy: &KillPolicy,
+) -> Vec<ReapedProcess> {
+    let _ = child.kill().await;
+    Vec::new()
+}
+
+#[cfg(unix)]
+fn signal_group(pgid: u32, signal: libc::c_int) {
+    // SAFETY: killpg has no memory safety preconditions
+    unsafe {
+        libc::killpg(pgid as libc::pid_t, signal);
+    }
+}
+
+/// Check whether any process in the group is still alive.
+#[cfg(unix)]
+fn group_alive(pgid: u32) -> bool {
+    // SAFETY: signal 0 only checks for existence and permission
+    unsafe { libc::killpg(pgid as libc::pid_t, 0) == 0 }
+}
+
+#[cfg(not(unix))]
+fn group_alive(_pgid: u32) -> bool {
+    false
+}
+
+/// List the processes in a process group.
+#[cfg(target_os = "linux")]
+fn group_members(pgid: u32) -> Vec<ReapedProcess> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    let mut members: Vec<ReapedProcess> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse::<u32>().ok()?;
+            let stat = std::fs::read_to_string(entry.path().join("stat")).ok()?;
+
+            // pid (comm) state ppid pgrp ...
+            let (head, tail) = stat.rsplit_once(')')?;
+            let command = head.split_once('(')?.1.to_string();
+            let pgrp = tail.split_whitespace().nth(2)?.parse::<u32>().ok()?;
+            (pgrp == pgid).then_some(ReapedProcess { pid, command })
+        })
+        .collect();
+    members.sort_by_key(|member| member.pid);
+    members
+}
+
+/// List the processes in a process group.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn group_members(_pgid: u32) -> Vec<ReapedProcess> {
+    Vec::new()
+}
+
+/// Terminate a command another component of the agent's process spawned,
+/// with everything it spawned: SIGTERM, grace period, SIGKILL.
+///
+/// Codex gives no handle on the commands it runs, so the command is found
+/// by its arguments among the descendants of the agent's process; those
+/// ending with `command` match, as sandbox wrappers go first. Returns the
+/// processes terminated, none if the command already exited.
+#[cfg(target_os = "linux")]
+pub(crate) async fn terminate_command(
+    command: &[String],
+    policy: &KillPolicy,
+) -> Vec<ReapedProcess> {
+    if command.is_empty() {
+        return Vec::new();
+    }
+    let processes = processes();
+    let parent = |pid: u32| {
+        processes
+            .iter()
+            .find(|process| process.pid == pid)
+            .map(|process| process.ppid)
+    };
+    let descends_from = |mut pid: u32, ancestor: u32| {
+        while let Some(ppid) = parent(pid) {
+            if ppid == ancestor {
+                return true;
+            }
+            pid = ppid;
+        }
+        false
+    };
+    let runs_command = |process: &Process| process.args.ends_with(command);
+
+    // The outermost processes running the command, and their descendants
+    let agent = std::process::id();
+    let roots: Vec<u32> = processes
+        .iter()
+        .filter(|process| runs_command(process) && descends_from(process.pid, agent))
+        .filter(|process| {
+            !processes
+                .iter()
+                .any(|other| runs_command(other) && descends_from(process.pid, other.pid))
+        })
+        .map(|process| process.pid)
+        .collect();
+    let targets: Vec<&Process> = processes
+        .iter()
+        .filter(|process| {
+            roots.contains(&process.pid)
+                || roots.iter().any(|root| descends_from(process.pid, *root))
+        })
+        .collect();
+
+    let signal = |signal: libc::c_int| {
+        for process in &targets {
+            // SAFETY: kill has no memory safety preconditions
+            unsafe {
+                libc::kill(process.pid as libc::pid_t, signal);
+            }
+        }
+    };
+    let alive = || {
+        targets
+            .iter()
+            .any(|process| std::path::Path::new(&format!("/proc/{}", process.pid)).exists())
+    };
+    signal(libc::SIGTERM);
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(policy.grace_period);
+    while alive() && tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    signal(libc::SIGKILL);
+
+    let mut reaped: Vec<ReapedProcess> = targets
+        .iter()
+        .map(|process| ReapedProcess {
+            pid: process.pid,
+            command: process.name.clone(),
+        })
+        .collect();
+    reaped.sort_by_key(|process| process.pid);
+    reaped
+}
+
+/// A running process, as listed in `/proc`.
+#[cfg(target_os = "linux")]
+struct Process {
+    pid: u32,
+    ppid: u32,
+    name: String,
+    args: Vec<String>,
+}
+
+#[cfg(target_os = "linux")]
+fn processes() -> Vec<Process> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse::<u32>().ok()?;
+            let stat = std::fs::read_to_string(entry.path().join("stat")).ok()?;
+            let cmdline = std::fs::read(entry.path().join("cmdline")).ok()?;
+
+            // pid (comm) state ppid ...
+            let (head, tail) = stat.rsplit_once(')')?;
+            let name = head.split_once('(')?.1.to_string();
+            let ppid = tail.split_whitespace().nth(1)?.parse::<u32>().ok()?;
+            let args = match cmdline.strip_suffix(&[0]) {
+                Some(cmdline) => cmdline
+                    .split(|byte| *byte == 0)
+                    .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                    .collect(),
+                // Kernel threads have no arguments
+                None => Vec::new(),
+            };
+            Some(Process {
+                pid,
+                ppid,
+                name,
+                args,
+            })
+        })
+        .collect()
+}
+
+async fn read_all<R: AsyncRead + Unpin>(mut reader: R) -> String {
+    let mut buffer = Vec::new();
+    let _ = reader.read_to_end(&mut buffer).await;
+    String::from_utf8_lossy(&buffer).to_string()
+}
+
+async fn join_output(task: Option<tokio::task::JoinHandle<String>>) -> String {
+    match task {
+        Some(task) => task.await.unwrap_or_default(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,162 @@
+//! Post-processing of the agent's final responses.
+//!
+//! Processors configured with `AgentConfigBuilder::output_processor` run in
+//! order on every `OutputData::Primary` message before it is emitted.
+//! Streaming fragments (`PrimaryDelta`) are passed through unchanged.
+
+/// A transformation applied to final response content.
+pub trait OutputProcessor: Send + Sync {
+    /// Transform the response content.
+    fn process(&self, content: String) -> String;
+}
+
+impl std::fmt::Debug for dyn OutputProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "OutputProcessor")
+    }
+}
+
+/// Whether emoji are allowed in responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmojiPolicy {
+    /// Leave emoji untouched
+    Keep,
+
+    /// Remove emoji, including modifiers and joiners
+    Strip,
+}
+
+impl OutputProcessor for EmojiPolicy {
+    fn process(&self, content: String) -> String {
+        match self {
+            EmojiPolicy::Keep => content,
+            EmojiPolicy::Strip => {
+                let mut result = String::with_capacity(content.len());
+                let mut after_emoji = false;
+                for c in content.chars() {
+                    if is_emoji(c) || (after_emoji && matches!(c, '\u{200D}' | '\u{FE0F}')) {
+                        after_emoji = true;
+                    } else {
+                        after_emoji = false;
+                        result.push(c);
+                    }
+                }
+                result
+            }
+        }
+    }
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0xFE0F | 0xE0020..=0xE007F
+    )
+}
+
+/// Rewrite relative markdown link and image targets against a base URL.
+#[derive(Debug, Clone)]
+pub struct LinkRewriter {
+    base_url: String,
+}
+
+impl LinkRewriter {
+    /// Resolve relative links against `base_url`.
+    pub fn new<S: Into<String>>(base_url: S) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn resolve(&self, url: &str) -> String {
+        let absolute = url.is_empty()
+            || url.starts_with('#')
+            || url.starts_with("//")
+            || url.contains("://")
+            || url.starts_with("mailto:")
+            || url.starts_with("data:");
+        if absolute {
+            return url.to_string();
+        }
+
+        match url.strip_prefix('/') {
+            Some(path) => format!("{}/{}", origin(&self.base_url), path),
+            None => format!("{}/{}", self.base_url, url.trim_start_matches("./")),
+        }
+    }
+}
+
+impl OutputProcessor for LinkRewriter {
+    fn process(&self, content: String) -> String {
+        let mut result = String::with_capacity(content.len());
+        let mut rest = content.as_str();
+
+        while let Some(start) = rest.find("](") {
+            let (before, after) = rest.split_at(start + 2);
+            result.push_str(before);
+            let end = after
+                .find(|c: char| c == ')' || c.is_whitespace())
+                .unwrap_or(after.len());
+            let (url, tail) = after.split_at(end);
+            result.push_str(&self.resolve(url));
+            rest = tail;
+        }
+
+        result.push_str(rest);
+        result
+    }
+}
+
+/// Scheme and host of a URL, e.g. `https://example.com`.
+fn origin(url: &str) -> &str {
+    let host_start = url.find("://").map_or(0, |i| i + 3);
+    match url[host_start..].find('/') {
+        Some(end) => &url[..host_start + end],
+        None => url,
+    }
+}
+
+/// Clamp markdown ATX heading levels into a range, e.g. so responses
+/// embedded under a page title never use `#`.
+#[derive(Debug, Clone, Copy)]
+pub struct HeadingLevels {
+    min: usize,
+    max: usize,
+}
+
+impl HeadingLevels {
+    /// Allow headings from level `min` to `max` (1 to 6).
+    pub fn new(min: u8, max: u8) -> Self {
+        let min = usize::from(min.clamp(1, 6));
+        Self {
+            min,
+            max: usize::from(max.clamp(1, 6)).max(min),
+        }
+    }
+}
+
+impl OutputProcessor for HeadingLevels {
+    fn process(&self, content: String) -> String {
+        let mut in_code = false;
+        let lines: Vec<String> = content
+            .split('\n')
+            .map(|line| {
+                if line.trim_start().starts_with("```") {
+                    in_code = !in_code;
+                    return line.to_string();
+                }
+
+                let level = line.chars().take_while(|c| *c == '#').count();
+                let is_heading = (1..=6).contains(&level)
+                    && line[level..].chars().next().is_none_or(|c| c == ' ');
+                if in_code || !is_heading {
+                    return line.to_string();
+                }
+
+                let clamped = level.clamp(self.min, self.max);
+                format!("{}{}", "#".repeat(clamped), &line[level..])
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,162 @@
+//! Named configuration presets.
+//!
+//! Applications define profiles such as "fast", "thorough", or "readonly" as
+//! overrides of a shared base configuration, in code or in a JSON file, and
+//! create agents by profile name. Each profile can switch the model, prompt,
+//! and policies, and replace or disable tools.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use codex_protocol::protocol::{AskForApproval, SandboxPolicy};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{AgentConfig, AgentConfigBuilder};
+use crate::error::{AgentError, Result};
+use crate::tools::ToolConfig;
+
+/// Overrides a profile applies to the base configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigProfile {
+    /// Model identifier
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    /// System prompt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+
+    /// Sandbox policy for tool execution
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sandbox_policy: Option<SandboxPolicy>,
+
+    /// Approval policy for command execution
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approval_policy: Option<AskForApproval>,
+
+    /// Maximum number of conversation turns
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_turns: Option<u32>,
+
+    /// Tools replacing those of the base configuration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ToolConfig>>,
+
+    /// Names of tools removed from the configuration
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub disabled_tools: Vec<String>,
+}
+
+impl ConfigProfile {
+    /// Create a profile keeping the base configuration unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use another model.
+    pub fn model<S: Into<String>>(mut self, model: S) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Use another system prompt.
+    pub fn system_prompt<S: Into<String>>(mut self, prompt: S) -> Self {
+        self.system_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Use another sandbox policy.
+    pub fn sandbox_policy(mut self, policy: SandboxPolicy) -> Self {
+        self.sandbox_policy = Some(policy);
+        self
+    }
+
+    /// Use another approval policy.
+    pub fn approval_policy(mut self, policy: AskForApproval) -> Self {
+        self.approval_policy = Some(policy);
+        self
+    }
+
+    /// Use another turn limit.
+    pub fn max_turns(mut self, max_turns: u32) -> Self {
+        self.max_turns = Some(max_turns);
+        self
+    }
+
+    /// Replace the tools of the base configuration.
+    pub fn tools<I>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = ToolConfig>,
+    {
+        self.tools = Some(tools.into_iter().collect());
+        self
+    }
+
+    /// Remove a tool by name.
+    pub fn disable_tool<S: Into<String>>(mut self, name: S) -> Self {
+        self.disabled_tools.push(name.into());
+        self
+    }
+}
+
+/// Base configuration plus the profiles derived from it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigProfiles {
+    base: AgentConfig,
+    profiles: BTreeMap<String, ConfigProfile>,
+}
+
+impl ConfigProfiles {
+    /// Create profiles over the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load profiles from a JSON file with a `base` configuration and a
+    /// `profiles` map.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|e| AgentError::Config {
+            message: format!("Invalid profiles file {}: {}", path.display(), e),
+        })
+    }
+
+    /// Set the configuration the profiles override.
+    pub fn base(mut self, config: AgentConfig) -> Self {
+        self.base = config;
+        self
+    }
+
+    /// Add or replace a profile.
+    pub fn profile<S: Into<String>>(mut self, name: S, profile: ConfigProfile) -> Self {
+        self.profiles.insert(name.into(), profile);
+        self
+    }
+
+    /// Names of the profiles, sorted.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    /// Get a profile by name.
+    pub fn get(&self, name: &str) -> Option<&ConfigProfile> {
+        self.profiles.get(name)
+    }
+
+    /// Configuration of the named profile.
+    pub fn config(&self, name: &str) -> Result<AgentConfig> {
+        let profile = self.profiles.get(name).ok_or_else(|| AgentError::Config {
+            message: format!("Unknown profile {}", name),
+        })?;
+        self.base.clone().with_profile(profile)
+    }
+
+    /// Builder starting from the named profile, e.g. to attach handlers that
+    /// cannot be stored in a profiles file.
+    pub fn builder(&self, name: &str) -> Result<AgentConfigBuilder> {
+        Ok(self.config(name)?.into_builder())
+    }
+}
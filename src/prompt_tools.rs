@@ -0,0 +1,279 @@
+//! Tool calling through the prompt, for models without native tool calling.
+//!
+//! Many local models served by Ollama or llama.cpp reject the `tools` field
+//! of a Chat Completions request, or accept it and ignore it. For a
+//! [`ModelProvider`](crate::ModelProvider) with
+//! [`prompt_tools`](crate::ModelProvider::prompt_tools) set, the model bridge
+//! drops the field and describes the tools in the system message instead,
+//! asking the model to reply with a `<tool_call>` block holding the call as
+//! JSON. Earlier calls and their results are rewritten into the same text
+//! form, and a block in the reply is passed back to Codex as a native tool
+//! call.
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::model_bridge::{BridgeError, ChatRequest, ChatStream, FinishReason, Upstream};
+
+/// Marker opening a tool call in a reply.
+const CALL_START: &str = "<tool_call>";
+
+/// Marker closing a tool call in a reply.
+const CALL_END: &str = "</tool_call>";
+
+/// Answer `request` through a Chat Completions provider, with the tools in
+/// the prompt.
+pub(crate) async fn complete(
+    upstream: &Upstream,
+    request: ChatRequest,
+    stream: &mut ChatStream<'_>,
+) -> Result<(), BridgeError> {
+    let body = json!({
+        "model": request.model,
+        "messages": messages(&request),
+        "stream": true,
+    });
+    let auth = upstream
+        .api_key
+        .as_ref()
+        .map(|key| ("Authorization", format!("Bearer {}", key)));
+    let mut events = upstream
+        .post(&upstream.url("chat/completions"), auth, &body)
+        .await?;
+
+    let mut parser = ReplyParser::default();
+    let mut reason = FinishReason::Stop;
+    while let Some(event) = events.next().await? {
+        if event.data.trim() == "[DONE]" {
+            break;
+        }
+        let chunk: Chunk = serde_json::from_str(&event.data)
+            .map_err(|e| BridgeError::new(502, format!("Invalid reply chunk: {}", e)))?;
+        for choice in chunk.choices {
+            if let Some(text) = choice.delta.content {
+                stream.text(&parser.push(&text)).await?;
+            }
+            if choice.finish_reason.as_deref() == Some("length") {
+                reason = FinishReason::Length;
+            }
+        }
+    }
+
+    match parser.finish() {
+        Reply::Text(text) => stream.text(&text).await?,
+        Reply::ToolCall { name, arguments } => {
+            let id = format!("call_{}", uuid::Uuid::new_v4().simple());
+            stream.tool_call(&id, &name, &arguments).await?;
+        }
+    }
+    stream.finish(reason).await
+}
+
+/// Messages of `request` with the tools described in the system message and
+/// tool calls and results written out as text.
+fn messages(request: &ChatRequest) -> Vec<Value> {
+    let mut messages: Vec<(String, String, Vec<String>)> = Vec::new();
+    let mut push = |role: &str, text: String, images: Vec<String>| {
+        // Consecutive messages of a role are merged, as many chat templates
+        // require the roles to alternate
+        match messages.last_mut() {
+            Some((last, content, last_images)) if last == role && role != "system" => {
+                content.push_str("\n\n");
+                content.push_str(&text);
+                last_images.extend(images);
+            }
+            _ => messages.push((role.to_string(), text, images)),
+        }
+    };
+
+    // The tools are described after the instructions, or on their own
+    let mut protocol = Some(protocol(request)).filter(|protocol| !protocol.is_empty());
+    if !request.messages.iter().any(|m| m.role == "system")
+        && let Some(protocol) = protocol.take()
+    {
+        push("system", protocol, Vec::new());
+    }
+    for message in &request.messages {
+        let images = message.image_urls().into_iter().map(String::from).collect();
+        match message.role.as_str() {
+            "system" => {
+                let mut text = message.text();
+                if let Some(protocol) = protocol.take() {
+                    text.push_str("\n\n");
+                    text.push_str(&protocol);
+                }
+                push("system", text, images);
+            }
+            "assistant" => {
+                let mut text = message.text();
+                for call in &message.tool_calls {
+                    let block = json!({ "name": call.function.name, "arguments": call.input() });
+                    if !text.is_empty() {
+                        text.push('\n');
+                    }
+                    text.push_str(&format!("{}\n{}\n{}", CALL_START, block, CALL_END));
+                }
+                push("assistant", text, images);
+            }
+            "tool" => {
+                let id = message.tool_call_id.as_deref().unwrap_or_default();
+                let name = request.tool_name(id).unwrap_or("tool");
+                let text = format!(
+                    "<tool_result name=\"{}\">\n{}\n</tool_result>",
+                    name,
+                    message.text()
+                );
+                push("user", text, images);
+            }
+            _ => push("user", message.text(), images),
+        }
+    }
+
+    messages
+        .into_iter()
+        .map(|(role, text, images)| {
+            if images.is_empty() {
+                return json!({ "role": role, "content": text });
+            }
+            let mut parts = vec![json!({ "type": "text", "text": text })];
+            parts.extend(
+                images
+                    .into_iter()
+                    .map(|url| json!({ "type": "image_url", "image_url": { "url": url } })),
+            );
+            json!({ "role": role, "content": parts })
+        })
+        .collect()
+}
+
+/// Instructions describing the tools of `request` and how to call them, or
+/// nothing if it offers none.
+fn protocol(request: &ChatRequest) -> String {
+    let tools: Vec<String> = request
+        .functions()
+        .map(|function| {
+            format!(
+                "## {}\n{}\nArguments (JSON Schema): {}",
+                function.name,
+                function.description,
+                function.schema()
+            )
+        })
+        .collect();
+    if tools.is_empty() {
+        return String::new();
+    }
+    format!(
+        "# Tools\n\n\
+         You can call the tools below. To call one, end your reply with a single \
+         block of this form and nothing after it:\n\n\
+         {CALL_START}\n{{\"name\": \"<tool name>\", \"arguments\": {{<arguments>}}}}\n{CALL_END}\n\n\
+         Call one tool at a time. Its result comes back in the next message as \
+         <tool_result name=\"<tool name>\">...</tool_result>. Reply without a \
+         {CALL_START} block once you are done.\n\n{}",
+        tools.join("\n\n")
+    )
+}
+
+/// Chunk of a streamed Chat Completions reply.
+#[derive(Debug, Deserialize)]
+struct Chunk {
+    #[serde(default)]
+    choices: Vec<Choice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Choice {
+    #[serde(default)]
+    delta: Delta,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Delta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// End of a reply after its streamed text.
+#[derive(Debug, PartialEq)]
+enum Reply {
+    /// Text held back, as it might have opened a tool call
+    Text(String),
+    /// Tool call the reply ended with
+    ToolCall { name: String, arguments: String },
+}
+
+/// Splits the streamed text of a reply from the tool call it ends with.
+#[derive(Debug, Default)]
+struct ReplyParser {
+    /// Text that may be the start of [`CALL_START`]
+    pending: String,
+    /// Tool call after [`CALL_START`], once seen
+    call: Option<String>,
+}
+
+impl ReplyParser {
+    /// Take a delta of the reply, returning the text safe to stream.
+    fn push(&mut self, delta: &str) -> String {
+        if let Some(call) = &mut self.call {
+            call.push_str(delta);
+            return String::new();
+        }
+        self.pending.push_str(delta);
+        if let Some(start) = self.pending.find(CALL_START) {
+            let text = self.pending[..start].to_string();
+            self.call = Some(self.pending[start + CALL_START.len()..].to_string());
+            self.pending.clear();
+            return text;
+        }
+        // Hold back a suffix that could still grow into the marker
+        let keep = (1..CALL_START.len())
+            .rev()
+            .find(|len| self.pending.ends_with(&CALL_START[..*len]))
+            .unwrap_or(0);
+        let text = self.pending[..self.pending.len() - keep].to_string();
+        self.pending.drain(..self.pending.len() - keep);
+        text
+    }
+
+    /// End the reply, returning the tool call, or the held back text if it
+    /// holds none that parses.
+    fn finish(self) -> Reply {
+        let Some(call) = self.call else {
+            return Reply::Text(self.pending);
+        };
+        let body = call.split(CALL_END).next().unwrap_or_default().trim();
+        let body = body
+            .strip_prefix("```json")
+            .or_else(|| body.strip_prefix("```"))
+            .unwrap_or(body)
+            .trim_end_matches("```")
+            .trim();
+
+        #[derive(Deserialize)]
+        struct Call {
+            name: String,
+            #[serde(default)]
+            arguments: Value,
+        }
+        match serde_json::from_str::<Call>(body) {
+            Ok(parsed) => {
+                let arguments = match parsed.arguments {
+                    Value::Null => json!({}),
+                    // Some models encode the arguments a second time
+                    Value::String(encoded) => {
+                        serde_json::from_str(&encoded).unwrap_or(Value::String(encoded))
+                    }
+                    arguments => arguments,
+                };
+                Reply::ToolCall {
+                    name: parsed.name,
+                    arguments: arguments.to_string(),
+                }
+            }
+            Err(_) => Reply::Text(format!("{}{}", CALL_START, call)),
+        }
+    }
+}
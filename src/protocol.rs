@@ -0,0 +1,695 @@
+//! Adapter between Codex protocol events and agent-core types.
+//!
+//! Every match on `codex_protocol` event shapes lives here, so the rest of the
+//! crate and the applications embedding it only see agent-core types. When
+//! upstream reshapes its event enums, this module is the only place that
+//! changes. `codex-core` and `codex-protocol` must come from the same release,
+//! so exactly one protocol version is pinned per build; it is reported by
+//! [`CODEX_PROTOCOL_VERSION`].
+//!
+//! Live events always come from the pinned version, but recorded events may
+//! not: [`decode_event`] reads events of other Codex versions, upgrading the
+//! shapes that changed between versions and skipping event types the pinned
+//! version does not know, so recordings survive Codex upgrades.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use codex_protocol::plan_tool::UpdatePlanArgs;
+#[cfg(feature = "testing")]
+use codex_protocol::protocol::{AskForApproval, SandboxPolicy};
+use codex_protocol::protocol::{Event, EventMsg, FileChange, Op, ReviewDecision};
+use mcp_types::ContentBlock;
+use tracing::debug;
+
+use crate::approval::{ApprovalDecision, ApprovalRequest};
+use crate::error::OutputError;
+use crate::messages::OutputData;
+use crate::ops::TokenUsage;
+use crate::patch::{self, PatchFile};
+use crate::tools::ToolExecutionResult;
+
+/// Version of the Codex protocol this build speaks.
+///
+/// Recordings made with another version are replayed through
+/// [`decode_event`].
+pub const CODEX_PROTOCOL_VERSION: &str = "0.24.0-alpha.5";
+
+/// Whether the event completes the task of the turn.
+pub(crate) fn is_task_complete(msg: &EventMsg) -> bool {
+    matches!(msg, EventMsg::TaskComplete(_))
+}
+
+/// Whether the event ends the turn, by completing or aborting it.
+pub(crate) fn ends_turn(msg: &EventMsg) -> bool {
+    matches!(msg, EventMsg::TaskComplete(_) | EventMsg::TurnAborted(_))
+}
+
+/// Last agent message reported when the task completes.
+pub(crate) fn final_message(msg: &EventMsg) -> Option<&str> {
+    match msg {
+        EventMsg::TaskComplete(complete) => complete.last_agent_message.as_deref(),
+        _ => None,
+    }
+}
+
+/// Token usage reported by the event.
+pub(crate) fn token_usage(msg: &EventMsg) -> Option<TokenUsage> {
+    match msg {
+        EventMsg::TokenCount(usage) => Some(TokenUsage {
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            total_tokens: usage.total_tokens,
+        }),
+        _ => None,
+    }
+}
+
+/// Plan carried by a plan update event.
+pub(crate) fn plan_update(msg: &EventMsg) -> Option<&UpdatePlanArgs> {
+    match msg {
+        EventMsg::PlanUpdate(update_args) => Some(update_args),
+        _ => None,
+    }
+}
+
+/// Message of a model error that failed the turn.
+pub(crate) fn model_error(msg: &EventMsg) -> Option<&str> {
+    match msg {
+        EventMsg::Error(error) => Some(&error.message),
+        _ => None,
+    }
+}
+
+/// Whether the event reports a successfully applied patch.
+pub(crate) fn patch_applied(msg: &EventMsg) -> bool {
+    matches!(msg, EventMsg::PatchApplyEnd(patch) if patch.success)
+}
+
+/// Whether the event ends a tool call that may have written files.
+pub(crate) fn may_write_files(msg: &EventMsg) -> bool {
+    matches!(
+        msg,
+        EventMsg::PatchApplyEnd(_) | EventMsg::ExecCommandEnd(_)
+    )
+}
+
+/// Changes of a patch Codex asks approval for, sorted by path.
+pub(crate) fn patch_approval_request(msg: &EventMsg) -> Option<Vec<PatchFile>> {
+    match msg {
+        EventMsg::ApplyPatchApprovalRequest(request) => Some(patch_files(&request.changes)),
+        _ => None,
+    }
+}
+
+/// Full agent message carried by the event.
+pub(crate) fn agent_message(msg: &EventMsg) -> Option<&str> {
+    match msg {
+        EventMsg::AgentMessage(message) => Some(&message.message),
+        _ => None,
+    }
+}
+
+/// Query of a web search the event starts.
+pub(crate) fn web_search_query(msg: &EventMsg) -> Option<&str> {
+    match msg {
+        EventMsg::WebSearchBegin(search) => Some(&search.query),
+        _ => None,
+    }
+}
+
+/// Command or patch Codex asks approval for.
+pub(crate) fn approval_request(msg: &EventMsg) -> Option<ApprovalRequest> {
+    match msg {
+        EventMsg::ExecApprovalRequest(request) => Some(ApprovalRequest::Exec {
+            command: request.command.clone(),
+            cwd: request.cwd.clone(),
+            reason: request.reason.clone(),
+        }),
+        EventMsg::ApplyPatchApprovalRequest(request) => Some(ApprovalRequest::Patch {
+            diff: patch::render(&patch_files(&request.changes)),
+            reason: request.reason.clone(),
+        }),
+        _ => None,
+    }
+}
+
+/// Op answering the approval request with the given id.
+pub(crate) fn approval_op(id: String, request: &ApprovalRequest, decision: ApprovalDecision) -> Op {
+    let decision = match decision {
+        ApprovalDecision::Approved => ReviewDecision::Approved,
+        ApprovalDecision::ApprovedForSession => ReviewDecision::ApprovedForSession,
+        ApprovalDecision::Denied => ReviewDecision::Denied,
+        ApprovalDecision::Abort => ReviewDecision::Abort,
+    };
+    match request {
+        ApprovalRequest::Exec { .. } => Op::ExecApproval { id, decision },
+        ApprovalRequest::Patch { .. } => Op::PatchApproval { id, decision },
+    }
+}
+
+/// Call id and changes of a patch Codex starts applying.
+pub(crate) fn patch_begin(msg: &EventMsg) -> Option<(&str, Vec<PatchFile>)> {
+    match msg {
+        EventMsg::PatchApplyBegin(patch) => Some((&patch.call_id, patch_files(&patch.changes))),
+        _ => None,
+    }
+}
+
+/// Call id of the patch a Codex event ends, and whether it applied.
+pub(crate) fn patch_end(msg: &EventMsg) -> Option<(&str, bool)> {
+    match msg {
+        EventMsg::PatchApplyEnd(patch) => Some((&patch.call_id, patch.success)),
+        _ => None,
+    }
+}
+
+/// Call id, tool name, and arguments of a command or patch Codex asks
+/// approval for or starts, as tool middleware sees it.
+pub(crate) fn codex_tool_call(msg: &EventMsg) -> Option<(&str, &'static str, serde_json::Value)> {
+    match msg {
+        EventMsg::ExecApprovalRequest(request) => Some((
+            &request.call_id,
+            "exec_command",
+            serde_json::json!({ "command": request.command }),
+        )),
+        EventMsg::ExecCommandBegin(exec) => Some((
+            &exec.call_id,
+            "exec_command",
+            serde_json::json!({ "command": exec.command }),
+        )),
+        EventMsg::ApplyPatchApprovalRequest(request) => Some((
+            &request.call_id,
+            "apply_patch",
+            serde_json::json!({ "diff": patch::render(&patch_files(&request.changes)) }),
+        )),
+        EventMsg::PatchApplyBegin(patch) => Some((
+            &patch.call_id,
+            "apply_patch",
+            serde_json::json!({ "diff": patch::render(&patch_files(&patch.changes)) }),
+        )),
+        _ => None,
+    }
+}
+
+/// Call id and result of a command or patch a Codex event ends, as tool
+/// middleware sees it.
+pub(crate) fn codex_tool_result(msg: &EventMsg) -> Option<(&str, ToolExecutionResult)> {
+    let (call_id, success, exit_code, stdout, stderr) = match msg {
+        EventMsg::ExecCommandEnd(exec) => (
+            &exec.call_id,
+            exec.exit_code == 0,
+            exec.exit_code,
+            &exec.stdout,
+            &exec.stderr,
+        ),
+        EventMsg::PatchApplyEnd(patch) => (
+            &patch.call_id,
+            patch.success,
+            i32::from(!patch.success),
+            &patch.stdout,
+            &patch.stderr,
+        ),
+        _ => return None,
+    };
+    let output = [stdout.as_str(), stderr.as_str()]
+        .into_iter()
+        .filter(|output| !output.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let result = if success {
+        ToolExecutionResult::success(output)
+    } else {
+        ToolExecutionResult::failure(output, exit_code)
+    };
+    Some((call_id, result))
+}
+
+/// Convert the changes of a patch, sorted by path.
+fn patch_files(changes: &HashMap<PathBuf, FileChange>) -> Vec<PatchFile> {
+    let mut files: Vec<PatchFile> = changes
+        .iter()
+        .map(|(path, change)| match change {
+            FileChange::Add { content } => PatchFile::add(path, content),
+            FileChange::Delete { .. } => PatchFile::delete(path),
+            FileChange::Update {
+                unified_diff,
+                move_path,
+            } => PatchFile::update(path, unified_diff, move_path.as_deref()),
+        })
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+/// Images in the result of an MCP tool call the event ends.
+pub(crate) fn mcp_images(msg: &EventMsg) -> Vec<OutputData> {
+    match msg {
+        EventMsg::McpToolCallEnd(mcp) => match &mcp.result {
+            Ok(result) => result
+                .content
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::ImageContent(image) => Some(OutputData::Image {
+                        data: image.data.clone(),
+                        mime_type: image.mime_type.clone(),
+                        caption: None,
+                    }),
+                    _ => None,
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Call id and chunk of a streamed command output event.
+pub(crate) fn exec_output(msg: &EventMsg) -> Option<(&str, &[u8])> {
+    match msg {
+        EventMsg::ExecCommandOutputDelta(output) => Some((&output.call_id, &output.chunk[..])),
+        _ => None,
+    }
+}
+
+/// Call id and arguments of the command a Codex event starts, if any.
+pub(crate) fn exec_begin(msg: &EventMsg) -> Option<(&str, &[String])> {
+    match msg {
+        EventMsg::ExecCommandBegin(exec) => Some((&exec.call_id, &exec.command)),
+        _ => None,
+    }
+}
+
+/// Call id of the command a Codex event ends, if any.
+pub(crate) fn exec_end(msg: &EventMsg) -> Option<&str> {
+    match msg {
+        EventMsg::ExecCommandEnd(exec) => Some(&exec.call_id),
+        _ => None,
+    }
+}
+
+/// Call id and tool name of a tool call a Codex event starts, if any.
+pub(crate) fn tool_begin(msg: &EventMsg) -> Option<(&str, &str)> {
+    match msg {
+        EventMsg::ExecCommandBegin(exec) => Some((&exec.call_id, "exec_command")),
+        EventMsg::PatchApplyBegin(patch) => Some((&patch.call_id, "apply_patch")),
+        EventMsg::McpToolCallBegin(mcp) => Some((&mcp.call_id, &mcp.invocation.tool)),
+        _ => None,
+    }
+}
+
+/// Call id of the tool call a Codex event ends, and whether it succeeded.
+pub(crate) fn tool_end(msg: &EventMsg) -> Option<(&str, bool)> {
+    match msg {
+        EventMsg::ExecCommandEnd(exec) => Some((&exec.call_id, exec.exit_code == 0)),
+        EventMsg::PatchApplyEnd(patch) => Some((&patch.call_id, patch.success)),
+        EventMsg::McpToolCallEnd(mcp) => Some((&mcp.call_id, mcp.is_success())),
+        _ => None,
+    }
+}
+
+/// Name of the tool a Codex event starts, if any.
+pub(crate) fn tool_start_name(msg: &EventMsg) -> Option<&str> {
+    match msg {
+        EventMsg::ExecCommandBegin(_) => Some("exec_command"),
+        EventMsg::PatchApplyBegin(_) => Some("apply_patch"),
+        EventMsg::WebSearchBegin(_) => Some("web_search"),
+        EventMsg::McpToolCallBegin(mcp) => Some(&mcp.invocation.tool),
+        _ => None,
+    }
+}
+
+/// Convert a Codex event to output data.
+pub(crate) fn to_output(msg: &EventMsg) -> Option<OutputData> {
+    match msg {
+        EventMsg::AgentMessage(msg) => Some(OutputData::Primary {
+            content: msg.message.clone(),
+        }),
+        EventMsg::AgentMessageDelta(delta) => Some(OutputData::PrimaryDelta {
+            content: delta.delta.clone(),
+        }),
+        EventMsg::AgentReasoning(reasoning) => Some(OutputData::Reasoning {
+            content: reasoning.text.clone(),
+        }),
+        EventMsg::AgentReasoningDelta(delta) => Some(OutputData::ReasoningDelta {
+            content: delta.delta.clone(),
+        }),
+        EventMsg::AgentReasoningRawContent(content) => Some(OutputData::Reasoning {
+            content: content.text.clone(),
+        }),
+        EventMsg::AgentReasoningRawContentDelta(delta) => Some(OutputData::ReasoningDelta {
+            content: delta.delta.clone(),
+        }),
+        EventMsg::TaskComplete(_) => Some(OutputData::Completed),
+        EventMsg::TaskStarted => Some(OutputData::Start),
+        EventMsg::Error(error) => Some(OutputData::Error {
+            error: OutputError::General {
+                message: error.message.clone(),
+            },
+        }),
+        EventMsg::ExecCommandBegin(exec) => Some(OutputData::ToolStart {
+            tool_name: "exec_command".to_string(),
+            arguments: serde_json::json!({ "command": exec.command }),
+        }),
+        EventMsg::ExecCommandEnd(exec) => {
+            let mut result = serde_json::json!({
+                "exit_code": exec.exit_code,
+                "call_id": exec.call_id
+            });
+
+            // Attach structured diagnostics recognized in the command output
+            let diagnostics =
+                crate::diagnostics::parse(&format!("{}\n{}", exec.stdout, exec.stderr));
+            if !diagnostics.is_empty() {
+                result["diagnostics"] = serde_json::to_value(diagnostics).unwrap_or_default();
+            }
+
+            Some(OutputData::ToolComplete {
+                tool_name: "exec_command".to_string(),
+                result,
+            })
+        }
+        EventMsg::McpToolCallBegin(mcp) => Some(OutputData::ToolStart {
+            tool_name: mcp.invocation.tool.clone(),
+            arguments: serde_json::json!({
+                "server": mcp.invocation.server,
+                "arguments": mcp.invocation.arguments
+            }),
+        }),
+        EventMsg::McpToolCallEnd(mcp) => Some(OutputData::ToolComplete {
+            tool_name: mcp.invocation.tool.clone(),
+            result: serde_json::json!({
+                "server": mcp.invocation.server,
+                "success": mcp.is_success(),
+                "result": mcp.result
+            }),
+        }),
+        EventMsg::WebSearchBegin(search) => Some(OutputData::ToolStart {
+            tool_name: "web_search".to_string(),
+            arguments: serde_json::json!({ "query": search.query }),
+        }),
+        EventMsg::PatchApplyBegin(patch) => Some(OutputData::ToolStart {
+            tool_name: "apply_patch".to_string(),
+            arguments: serde_json::json!({ "changes_count": patch.changes.len() }),
+        }),
+        EventMsg::PatchApplyEnd(patch) => Some(OutputData::ToolComplete {
+            tool_name: "apply_patch".to_string(),
+            result: serde_json::json!({
+                "success": patch.success,
+                "message": "Patch application finished"
+            }),
+        }),
+        EventMsg::ExecCommandOutputDelta(output) => Some(OutputData::ToolOutput {
+            tool_name: "exec_command".to_string(),
+            output: String::from_utf8_lossy(&output.chunk).to_string(),
+        }),
+        EventMsg::StreamError(error) => Some(OutputData::Error {
+            error: OutputError::General {
+                message: error.message.clone(),
+            },
+        }),
+        EventMsg::TokenCount(_) => None, // Token count events don't need to be converted to output
+        EventMsg::SessionConfigured(_) => None, // Session configured events are internal
+        EventMsg::ConversationHistory(_) => None, // History events are internal
+        EventMsg::McpListToolsResponse(_) => None, // Tool list responses are internal
+        EventMsg::GetHistoryEntryResponse(_) => None, // History entry responses are internal
+        EventMsg::TurnAborted(_) => Some(OutputData::Error {
+            error: OutputError::General {
+                message: "Turn was aborted".to_string(),
+            },
+        }),
+        EventMsg::ShutdownComplete => Some(OutputData::Completed),
+        _ => None, // Handle any remaining event types
+    }
+}
+
+/// Kind of an operation, e.g. `user_input`, as tagged in its JSON form.
+pub(crate) fn op_kind(op: &Op) -> String {
+    serde_json::to_value(op)
+        .ok()
+        .and_then(|op| op.get("type")?.as_str().map(ToString::to_string))
+        .unwrap_or_default()
+}
+
+/// Upgrade of an event shape recorded by other Codex versions to the shape of
+/// [`CODEX_PROTOCOL_VERSION`].
+struct EventMigration {
+    /// Type of the events upgraded
+    event: &'static str,
+
+    /// Rewrite the event message in place, returning `false` if it carries
+    /// nothing this version can represent
+    upgrade: fn(&mut serde_json::Value) -> bool,
+}
+
+/// Event shapes that changed between Codex versions.
+const EVENT_MIGRATIONS: &[EventMigration] = &[EventMigration {
+    event: "token_count",
+    upgrade: token_count_info,
+}];
+
+/// Later Codex versions wrap the token usage in an `info` object with the
+/// running total, absent until the first response.
+fn token_count_info(msg: &mut serde_json::Value) -> bool {
+    let Some(info) = msg.get("info") else {
+        return true;
+    };
+    let usage = info
+        .get("last_token_usage")
+        .or_else(|| info.get("total_token_usage"))
+        .cloned();
+    let Some(serde_json::Value::Object(mut usage)) = usage else {
+        return false;
+    };
+    usage.insert("type".to_string(), serde_json::json!("token_count"));
+    *msg = serde_json::Value::Object(usage);
+    true
+}
+
+/// Decode an event recorded by any Codex version.
+///
+/// Events of a shape that changed since are upgraded first. Events of types
+/// this version does not know are skipped with `None`, so a recording of a
+/// later version stays readable; an event of a known type that fails to
+/// decode is an error, as it means the recording is corrupt.
+pub(crate) fn decode_event(mut event: serde_json::Value) -> Result<Option<Event>, String> {
+    let kind = event["msg"]["type"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    for migration in EVENT_MIGRATIONS.iter().filter(|m| m.event == kind) {
+        if !(migration.upgrade)(&mut event["msg"]) {
+            debug!("Skipping a {} event without data", kind);
+            return Ok(None);
+        }
+    }
+    match serde_json::from_value(event) {
+        Ok(event) => Ok(Some(event)),
+        // Event types added by later versions
+        Err(e) if e.to_string().starts_with("unknown variant") => {
+            debug!(
+                "Skipping a {} event unknown to Codex {}",
+                kind, CODEX_PROTOCOL_VERSION
+            );
+            Ok(None)
+        }
+        Err(e) => Err(format!("{} event failed to decode: {}", kind, e)),
+    }
+}
+
+/// Text of the user input an operation submits, if it starts a turn.
+#[cfg(feature = "testing")]
+pub(crate) fn input_text(op: &Op) -> Option<String> {
+    use codex_protocol::protocol::InputItem;
+
+    let items = match op {
+        Op::UserInput { items } | Op::UserTurn { items, .. } => items,
+        _ => return None,
+    };
+    let text: Vec<&str> = items
+        .iter()
+        .filter_map(|item| match item {
+            InputItem::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    Some(text.join("\n"))
+}
+
+/// Approval and sandbox policy an operation runs its turn and the
+/// following ones under, if it sets them.
+#[cfg(feature = "testing")]
+pub(crate) fn turn_policies(op: &Op) -> Option<(AskForApproval, SandboxPolicy)> {
+    match op {
+        Op::UserTurn {
+            approval_policy,
+            sandbox_policy,
+            ..
+        } => Some((*approval_policy, sandbox_policy.clone())),
+        _ => None,
+    }
+}
+
+/// Whether an operation interrupts the running turn.
+#[cfg(feature = "testing")]
+pub(crate) fn is_interrupt(op: &Op) -> bool {
+    matches!(op, Op::Interrupt)
+}
+
+/// Codex event producing the given output data, for the kinds of output
+/// Codex reports with an event of its own.
+#[cfg(feature = "testing")]
+pub(crate) fn output_event(data: &OutputData) -> Option<EventMsg> {
+    let msg = match data {
+        OutputData::Primary { content } => {
+            serde_json::json!({ "type": "agent_message", "message": content })
+        }
+        OutputData::PrimaryDelta { content } => {
+            serde_json::json!({ "type": "agent_message_delta", "delta": content })
+        }
+        OutputData::Reasoning { content } => {
+            serde_json::json!({ "type": "agent_reasoning", "text": content })
+        }
+        OutputData::ReasoningDelta { content } => {
+            serde_json::json!({ "type": "agent_reasoning_delta", "delta": content })
+        }
+        OutputData::Error { error } => {
+            serde_json::json!({ "type": "error", "message": error_message(error) })
+        }
+        OutputData::ApprovalRequired {
+            approval_id,
+            request:
+                ApprovalRequest::Exec {
+                    command,
+                    cwd,
+                    reason,
+                },
+        } => serde_json::json!({
+            "type": "exec_approval_request",
+            "call_id": approval_id,
+            "command": command,
+            "cwd": cwd,
+            "reason": reason,
+        }),
+        _ => return None,
+    };
+    serde_json::from_value(msg).ok()
+}
+
+/// Message of an output error, as Codex would report it.
+#[cfg(feature = "testing")]
+fn error_message(error: &OutputError) -> String {
+    match error {
+        OutputError::General { message } => message.clone(),
+        OutputError::ModelRequestFailed { error } | OutputError::ConfigurationError { error } => {
+            error.clone()
+        }
+        OutputError::ToolExecutionFailed { tool_name, error } => {
+            format!("{} failed: {}", tool_name, error)
+        }
+        OutputError::SandboxViolation { command, reason } => format!("{}: {}", command, reason),
+        OutputError::PermissionDenied { operation, reason } => {
+            format!("{}: {}", operation, reason)
+        }
+        OutputError::ResourceLimitExceeded { resource, limit } => {
+            format!("{} limit of {} exceeded", resource, limit)
+        }
+        OutputError::ResponseSchemaMismatch { errors } => errors.join("; "),
+    }
+}
+
+/// Event starting a turn.
+#[cfg(feature = "testing")]
+pub(crate) fn task_started() -> Option<EventMsg> {
+    serde_json::from_value(serde_json::json!({ "type": "task_started" })).ok()
+}
+
+/// Event completing a turn with its last agent message.
+#[cfg(feature = "testing")]
+pub(crate) fn task_complete(last_agent_message: Option<String>) -> Option<EventMsg> {
+    serde_json::from_value(serde_json::json!({
+        "type": "task_complete",
+        "last_agent_message": last_agent_message
+    }))
+    .ok()
+}
+
+/// Event of a turn aborted by an interrupt.
+#[cfg(feature = "testing")]
+pub(crate) fn turn_aborted() -> Option<EventMsg> {
+    serde_json::from_value(serde_json::json!({ "type": "turn_aborted", "reason": "interrupted" }))
+        .ok()
+}
+
+/// Event starting a call to a tool of an MCP server.
+#[cfg(feature = "testing")]
+pub(crate) fn mcp_tool_begin(
+    call_id: &str,
+    server: &str,
+    tool: &str,
+    arguments: &serde_json::Value,
+) -> Option<EventMsg> {
+    serde_json::from_value(serde_json::json!({
+        "type": "mcp_tool_call_begin",
+        "call_id": call_id,
+        "invocation": { "server": server, "tool": tool, "arguments": arguments }
+    }))
+    .ok()
+}
+
+/// Event starting a command Codex runs with its own shell.
+#[cfg(feature = "testing")]
+pub(crate) fn exec_command_begin(
+    call_id: &str,
+    command: &[String],
+    cwd: &std::path::Path,
+) -> Option<EventMsg> {
+    serde_json::from_value(serde_json::json!({
+        "type": "exec_command_begin",
+        "call_id": call_id,
+        "command": command,
+        "cwd": cwd,
+        "parsed_cmd": []
+    }))
+    .ok()
+}
+
+/// Event ending a command Codex runs with its own shell.
+#[cfg(feature = "testing")]
+pub(crate) fn exec_command_end(
+    call_id: &str,
+    stdout: &str,
+    stderr: &str,
+    exit_code: i32,
+) -> Option<EventMsg> {
+    serde_json::from_value(serde_json::json!({
+        "type": "exec_command_end",
+        "call_id": call_id,
+        "stdout": stdout,
+        "stderr": stderr,
+        "aggregated_output": format!("{}{}", stdout, stderr),
+        "exit_code": exit_code,
+        "duration": { "secs": 0, "nanos": 0 },
+        "formatted_output": format!("{}{}", stdout, stderr)
+    }))
+    .ok()
+}
+
+/// Event ending a call to a tool of an MCP server with its MCP result.
+#[cfg(feature = "testing")]
+pub(crate) fn mcp_tool_end(
+    call_id: &str,
+    server: &str,
+    tool: &str,
+    arguments: &serde_json::Value,
+    result: serde_json::Value,
+) -> Option<EventMsg> {
+    serde_json::from_value(serde_json::json!({
+        "type": "mcp_tool_call_end",
+        "call_id": call_id,
+        "invocation": { "server": server, "tool": tool, "arguments": arguments },
+        "duration": { "secs": 0, "nanos": 0 },
+        "result": { "Ok": result }
+    }))
+    .ok()
+}
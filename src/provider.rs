@@ -0,0 +1,323 @@
+//! Model provider settings for self-hosted and OpenAI-compatible endpoints.
+//!
+//! By default the agent talks to OpenAI. A [`ModelProvider`] points it at
+//! another base URL instead, such as a vLLM server or a LiteLLM or OpenRouter
+//! gateway, speaking either the Responses or the Chat Completions API, with
+//! extra headers and query parameters sent on every request.
+//!
+//! Presets cover hosted providers, so switching models is a config change:
+//! e.g. [`ModelProvider::anthropic`] runs Claude models on Anthropic's native
+//! Messages API, and [`ModelProvider::ollama`] runs local models fully
+//! offline. Codex only speaks OpenAI's APIs, so native APIs are translated by
+//! a model bridge the agent serves on a loopback port, each behind a feature
+//! (`provider-anthropic`, `provider-gemini`). The `_compat` presets go through a provider's
+//! OpenAI compatibility layer instead, which only covers what the OpenAI API
+//! can express.
+//!
+//! Claude and Gemini models select their provider from the model name alone,
+//! see [`ModelProvider::for_model`].
+//!
+//! Codex sends its shell, patch, and plan tools as tool definitions on each
+//! request. For models without native tool calling, such as many local
+//! models, [`ModelProvider::prompt_tools`] describes the tools in the prompt
+//! instead and parses the calls out of the model's replies (requires the
+//! `prompt-tools` feature).
+//!
+//! A routing table of [`ProviderRoute`]s maps model-name prefixes to
+//! providers, so one configuration serves models of several providers, e.g.
+//! `claude-` to Anthropic and `ollama/` to a local server.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Base URL of the OpenAI API.
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Base URL of Anthropic's API, native and OpenAI-compatible.
+const ANTHROPIC_BASE_URL: &str = "https://api.anthropic.com/v1";
+
+/// Base URL of the Gemini API.
+const GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// Base URL of the OpenAI-compatible Gemini API.
+const GEMINI_COMPAT_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta/openai";
+
+/// Base URL of the OpenAI-compatible API of a local Ollama server.
+const OLLAMA_BASE_URL: &str = "http://localhost:11434/v1";
+
+/// API style a provider speaks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WireApi {
+    /// OpenAI Responses API (`/responses`)
+    #[default]
+    Responses,
+
+    /// Chat Completions API (`/chat/completions`), served by most
+    /// OpenAI-compatible gateways
+    Chat,
+
+    /// Anthropic Messages API (`/messages`), translated by the model bridge;
+    /// needs the `provider-anthropic` feature
+    Anthropic,
+
+    /// Gemini API (`:streamGenerateContent`), translated by the model bridge;
+    /// needs the `provider-gemini` feature
+    Gemini,
+}
+
+/// Endpoint the agent sends model requests to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelProvider {
+    /// Provider name, used in logs and errors
+    pub name: String,
+
+    /// Base URL requests are sent to, e.g. `http://localhost:8000/v1`
+    pub base_url: String,
+
+    /// API style of the endpoint
+    #[serde(default)]
+    pub wire_api: WireApi,
+
+    /// Environment variable holding the API key sent as a bearer token;
+    /// when unset, the agent's API key is sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_key: Option<String>,
+
+    /// Headers added to every request
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+
+    /// Query parameters added to every request, e.g. an `api-version`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub query_params: HashMap<String, String>,
+
+    /// Call tools through the prompt rather than the API's tool definitions,
+    /// for models without native tool calling; needs the Chat Completions
+    /// API and the `prompt-tools` feature
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub prompt_tools: bool,
+}
+
+impl ModelProvider {
+    /// Create a provider speaking the Responses API at `base_url`.
+    pub fn new<S1, S2>(name: S1, base_url: S2) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            base_url: base_url.into(),
+            wire_api: WireApi::default(),
+            env_key: None,
+            headers: HashMap::new(),
+            query_params: HashMap::new(),
+            prompt_tools: false,
+        }
+    }
+
+    /// Anthropic's native Messages API, serving Claude models (e.g.
+    /// `claude-sonnet-4-5`), with the API key read from `ANTHROPIC_API_KEY`.
+    ///
+    /// Needs the `provider-anthropic` feature: the agent translates Codex's
+    /// requests on a loopback port, passing tool calls, their results, and
+    /// images as Messages content blocks.
+    pub fn anthropic() -> Self {
+        Self::new("anthropic", ANTHROPIC_BASE_URL)
+            .wire_api(WireApi::Anthropic)
+            .env_key("ANTHROPIC_API_KEY")
+    }
+
+    /// Anthropic's OpenAI compatibility layer, serving Claude models, with
+    /// the API key read from `ANTHROPIC_API_KEY`.
+    ///
+    /// Requests go through Chat Completions rather than the native Messages
+    /// API: tool use and streaming work, while Messages-only features such as
+    /// prompt caching and extended thinking output are not available. Prefer
+    /// [`anthropic`](Self::anthropic) where the `provider-anthropic` feature
+    /// is enabled.
+    pub fn anthropic_compat() -> Self {
+        Self::new("anthropic", ANTHROPIC_BASE_URL)
+            .wire_api(WireApi::Chat)
+            .env_key("ANTHROPIC_API_KEY")
+    }
+
+    /// Google's native Gemini API, serving Gemini models (e.g.
+    /// `gemini-2.5-pro`), with the API key read from `GEMINI_API_KEY`.
+    ///
+    /// Needs the `provider-gemini` feature: the agent translates Codex's
+    /// requests on a loopback port, passing tool calls and their results as
+    /// function call and response parts, with their thought signatures.
+    pub fn gemini() -> Self {
+        Self::new("gemini", GEMINI_BASE_URL)
+            .wire_api(WireApi::Gemini)
+            .env_key("GEMINI_API_KEY")
+    }
+
+    /// Google's OpenAI compatibility layer for Gemini models, with the API
+    /// key read from `GEMINI_API_KEY`.
+    ///
+    /// Requests go through Chat Completions rather than the native Gemini
+    /// API: streaming and function calling work, while native-only features
+    /// such as context caching and grounding are not available. Prefer
+    /// [`gemini`](Self::gemini) where the `provider-gemini` feature is
+    /// enabled.
+    pub fn gemini_compat() -> Self {
+        Self::new("gemini", GEMINI_COMPAT_BASE_URL)
+            .wire_api(WireApi::Chat)
+            .env_key("GEMINI_API_KEY")
+    }
+
+    /// Hosted provider serving a model, recognized from its name: `claude-*`
+    /// models use [`anthropic`](Self::anthropic) with the
+    /// `provider-anthropic` feature and
+    /// [`anthropic_compat`](Self::anthropic_compat) without it; `gemini-*`
+    /// models likewise use [`gemini`](Self::gemini) or
+    /// [`gemini_compat`](Self::gemini_compat) by the `provider-gemini`
+    /// feature. Returns `None` for OpenAI and unknown models.
+    pub fn for_model(model: &str) -> Option<Self> {
+        if model.starts_with("claude-") {
+            if cfg!(feature = "provider-anthropic") {
+                Some(Self::anthropic())
+            } else {
+                Some(Self::anthropic_compat())
+            }
+        } else if model.starts_with("gemini-") {
+            if cfg!(feature = "provider-gemini") {
+                Some(Self::gemini())
+            } else {
+                Some(Self::gemini_compat())
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Ollama on its default local port, serving models pulled with
+    /// `ollama pull` (e.g. `qwen2.5-coder:14b`).
+    ///
+    /// Models with tool calling (e.g. Qwen 2.5, Llama 3.1, or Mistral models)
+    /// work as is; Ollama fails every turn of models without it with a "does
+    /// not support tools" error unless [`prompt_tools`](Self::prompt_tools)
+    /// is set.
+    pub fn ollama() -> Self {
+        Self::new("ollama", OLLAMA_BASE_URL).wire_api(WireApi::Chat)
+    }
+
+    /// A llama.cpp server (`llama-server --jinja`) or another local server
+    /// with an OpenAI-compatible Chat Completions API at `base_url`.
+    ///
+    /// As with [`ollama`](Self::ollama), the model's chat template must
+    /// support tool calling, which `--jinja` enables in llama.cpp, unless
+    /// [`prompt_tools`](Self::prompt_tools) is set.
+    pub fn llama_cpp<S: Into<String>>(base_url: S) -> Self {
+        Self::new("llama_cpp", base_url).wire_api(WireApi::Chat)
+    }
+
+    /// Set the API style.
+    pub fn wire_api(mut self, wire_api: WireApi) -> Self {
+        self.wire_api = wire_api;
+        self
+    }
+
+    /// Call tools through the prompt, for models without native tool calling.
+    ///
+    /// The agent serves the model's requests on a loopback port, describing
+    /// the tools in the system message and turning `<tool_call>` blocks of
+    /// the replies into tool calls. Needs [`WireApi::Chat`] and the
+    /// `prompt-tools` feature.
+    pub fn prompt_tools(mut self) -> Self {
+        self.prompt_tools = true;
+        self
+    }
+
+    /// Whether Codex reaches the provider through the agent's model bridge
+    /// rather than directly.
+    pub(crate) fn needs_bridge(&self) -> bool {
+        self.prompt_tools || matches!(self.wire_api, WireApi::Anthropic | WireApi::Gemini)
+    }
+
+    /// Read the API key from an environment variable.
+    pub fn env_key<S: Into<String>>(mut self, env_key: S) -> Self {
+        self.env_key = Some(env_key.into());
+        self
+    }
+
+    /// Add a header sent with every request.
+    pub fn header<S1, S2>(mut self, name: S1, value: S2) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Add a query parameter sent with every request.
+    pub fn query_param<S1, S2>(mut self, name: S1, value: S2) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        self.query_params.insert(name.into(), value.into());
+        self
+    }
+}
+
+impl Default for ModelProvider {
+    /// The OpenAI API, to be customized.
+    fn default() -> Self {
+        Self::new("custom", OPENAI_BASE_URL)
+    }
+}
+
+/// Provider serving the models whose name starts with a prefix.
+///
+/// A prefix ending in `/`, such as `ollama/`, names a namespace and is removed
+/// from the model name sent to the provider: `ollama/qwen2.5-coder` requests
+/// `qwen2.5-coder`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderRoute {
+    /// Model-name prefix, e.g. `claude-` or `ollama/`
+    pub prefix: String,
+
+    /// Provider serving the matching models
+    pub provider: ModelProvider,
+}
+
+impl ProviderRoute {
+    /// Route models starting with `prefix` to `provider`.
+    pub fn new<S: Into<String>>(prefix: S, provider: ModelProvider) -> Self {
+        Self {
+            prefix: prefix.into(),
+            provider,
+        }
+    }
+
+    /// Name the provider knows `model` by, if the route matches it.
+    pub fn provider_model<'a>(&self, model: &'a str) -> Option<&'a str> {
+        if !model.starts_with(&self.prefix) {
+            return None;
+        }
+        if self.prefix.ends_with('/') {
+            Some(&model[self.prefix.len()..])
+        } else {
+            Some(model)
+        }
+    }
+}
+
+/// Route `model` through the route with the longest matching prefix,
+/// returning its provider and the model name to request.
+pub(crate) fn route<'a>(
+    routes: &'a [ProviderRoute],
+    model: &'a str,
+) -> Option<(&'a ModelProvider, &'a str)> {
+    routes
+        .iter()
+        .filter_map(|route| Some((route, route.provider_model(model)?)))
+        .max_by_key(|(route, _)| route.prefix.len())
+        .map(|(route, model)| (&route.provider, model))
+}
@@ -0,0 +1,106 @@
+//! Queue of input messages waiting for the agent to start their turn.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use async_channel::Receiver;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, Notify};
+
+use crate::messages::InputMessage;
+
+/// An input message queued behind the running turn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingInput {
+    /// Identifier used to cancel the input before it runs, the same as the
+    /// message id
+    pub request_id: String,
+
+    /// The queued message
+    pub message: InputMessage,
+
+    /// When the message was received
+    pub queued_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Shared queue between the input channel and the execution loop.
+///
+/// Inputs are moved off the caller's channel as soon as they arrive so they
+/// can be listed and cancelled while earlier turns run.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InputQueue {
+    pending: Arc<Mutex<VecDeque<PendingInput>>>,
+    notify: Arc<Notify>,
+    closed: Arc<AtomicBool>,
+}
+
+impl InputQueue {
+    /// Move messages from the input channel into the queue until it closes.
+    pub(crate) async fn fill_from(self, input_rx: Receiver<InputMessage>) {
+        while let Ok(message) = input_rx.recv().await {
+            self.push(message).await;
+        }
+        self.closed.store(true, Ordering::Release);
+        self.notify.notify_waiters();
+    }
+
+    /// Queue a message.
+    pub(crate) async fn push(&self, mut message: InputMessage) {
+        let request_id = message
+            .id
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+            .clone();
+        let pending = PendingInput {
+            request_id,
+            message,
+            queued_at: chrono::Utc::now(),
+        };
+        self.pending.lock().await.push_back(pending);
+        #[cfg(feature = "metrics")]
+        crate::metrics::input_queued();
+        self.notify.notify_one();
+    }
+
+    /// Wait for the next queued message.
+    ///
+    /// Returns `None` once the input channel is closed and the queue drained.
+    pub(crate) async fn pop(&self) -> Option<PendingInput> {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if let Some(pending) = self.pending.lock().await.pop_front() {
+                #[cfg(feature = "metrics")]
+                crate::metrics::input_dequeued(
+                    (chrono::Utc::now() - pending.queued_at).to_std().ok(),
+                );
+                return Some(pending);
+            }
+            if self.closed.load(Ordering::Acquire) {
+                return None;
+            }
+
+            notified.await;
+        }
+    }
+
+    /// List the queued messages in order.
+    pub(crate) async fn snapshot(&self) -> Vec<PendingInput> {
+        self.pending.lock().await.iter().cloned().collect()
+    }
+
+    /// Remove a queued message, returning whether it was found.
+    pub(crate) async fn cancel(&self, request_id: &str) -> bool {
+        let mut pending = self.pending.lock().await;
+        let len = pending.len();
+        pending.retain(|input| input.request_id != request_id);
+        let found = pending.len() != len;
+        #[cfg(feature = "metrics")]
+        if found {
+            crate::metrics::input_dequeued(None);
+        }
+        found
+    }
+}
@@ -0,0 +1,187 @@
+//! Client-side rate limiting of model requests.
+//!
+//! Limits are token buckets per provider and credential, shared by every
+//! agent of the process that uses the provider with the same API key or
+//! login, so pools of agents sharing one API key stay under the provider's
+//! limits instead of failing once they trip them, while agents with keys of
+//! their own keep limits of their own. The first agent of a provider and
+//! credential sets the limit; a later agent configuring another one is
+//! warned about and shares the first.
+//! Each turn waits for the buckets before it is submitted. The requests Codex
+//! makes and the tokens they use are counted as their usage is reported, so a
+//! burst of requests delays the next turn.
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// Name rate limits of the default OpenAI provider are configured under.
+pub const OPENAI_PROVIDER: &str = "openai";
+
+/// Limiters by provider name and credential hash, shared by all agents of
+/// the process.
+static LIMITERS: LazyLock<Mutex<HashMap<(String, u64), Arc<RateLimiter>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Request and token limits of a provider.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimit {
+    /// Model requests per minute
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+
+    /// Input and output tokens per minute
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<u64>,
+}
+
+impl RateLimit {
+    /// Create a limit that allows everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the model requests per minute.
+    pub fn requests_per_minute(mut self, requests: u32) -> Self {
+        self.requests_per_minute = Some(requests);
+        self
+    }
+
+    /// Limit the input and output tokens per minute.
+    pub fn tokens_per_minute(mut self, tokens: u64) -> Self {
+        self.tokens_per_minute = Some(tokens);
+        self
+    }
+}
+
+/// Bucket refilled continuously up to its per-minute capacity. It goes into
+/// debt when usage is reported after the fact.
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    available: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(per_minute: f64, now: Instant) -> Self {
+        Self {
+            capacity: per_minute,
+            available: per_minute,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.capacity / 60.0).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Time until `amount` is available.
+    fn wait(&self, amount: f64) -> Duration {
+        let missing = amount - self.available;
+        if missing <= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(missing * 60.0 / self.capacity)
+    }
+}
+
+/// Request and token buckets of one provider.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    buckets: Mutex<(Option<Bucket>, Option<Bucket>)>,
+}
+
+impl RateLimiter {
+    fn new(limit: &RateLimit) -> Self {
+        let now = Instant::now();
+        let requests = limit
+            .requests_per_minute
+            .filter(|requests| *requests > 0)
+            .map(|requests| Bucket::new(f64::from(requests), now));
+        let tokens = limit
+            .tokens_per_minute
+            .filter(|tokens| *tokens > 0)
+            .map(|tokens| Bucket::new(tokens as f64, now));
+        Self {
+            limit: *limit,
+            buckets: Mutex::new((requests, tokens)),
+        }
+    }
+
+    /// Wait until a request and some tokens are available, then take the
+    /// request.
+    pub(crate) async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+                let (requests, tokens) = &mut *buckets;
+                let now = Instant::now();
+                let mut wait = Duration::ZERO;
+                if let Some(requests) = requests.as_mut() {
+                    requests.refill(now);
+                    wait = wait.max(requests.wait(1.0));
+                }
+                if let Some(tokens) = tokens.as_mut() {
+                    tokens.refill(now);
+                    wait = wait.max(tokens.wait(f64::MIN_POSITIVE));
+                }
+                if wait.is_zero() {
+                    if let Some(requests) = requests.as_mut() {
+                        requests.available -= 1.0;
+                    }
+                    return;
+                }
+                wait
+            };
+            debug!("Rate limited, waiting {:?}", wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Count a request that completed, using `tokens`. `reserved` requests
+    /// were taken by [`acquire`](Self::acquire) already.
+    pub(crate) fn record(&self, tokens: u64, reserved: bool) {
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        let (requests, token_bucket) = &mut *buckets;
+        let now = Instant::now();
+        if !reserved && let Some(requests) = requests.as_mut() {
+            requests.refill(now);
+            requests.available -= 1.0;
+        }
+        if let Some(token_bucket) = token_bucket.as_mut() {
+            token_bucket.refill(now);
+            token_bucket.available -= tokens as f64;
+        }
+    }
+}
+
+/// Limiter of `provider` used with `credential`, created with `limit` by the
+/// first agent using them. Only a hash of the credential is kept.
+pub(crate) fn limiter(provider: &str, credential: &str, limit: &RateLimit) -> Arc<RateLimiter> {
+    let mut hasher = DefaultHasher::new();
+    credential.hash(&mut hasher);
+    let limiter = LIMITERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry((provider.to_string(), hasher.finish()))
+        .or_insert_with(|| Arc::new(RateLimiter::new(limit)))
+        .clone();
+    if limiter.limit != *limit {
+        warn!(
+            "Rate limit {:?} of provider {} is ignored, as agents with the same credential \
+             share the limit {:?} set first",
+            limit, provider, limiter.limit
+        );
+    }
+    limiter
+}
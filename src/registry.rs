@@ -0,0 +1,434 @@
+//! Central registry of versioned agent definitions.
+//!
+//! A registry is a tree of JSON documents, served over HTTP or stored in a
+//! git repository, laid out as:
+//!
+//! ```text
+//! <name>/index.json        list of published versions and their checksums
+//! <name>/<version>.json    the agent definition
+//! ```
+//!
+//! Definitions are resolved against a semver requirement and verified against
+//! the SHA-256 checksum recorded in the index before use.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::AgentConfigBuilder;
+use crate::error::{AgentError, Result};
+use crate::git;
+use crate::mcp::McpServerConfig;
+use crate::tools::ToolConfig;
+
+/// Name and email of commits published to git registries.
+const PUBLISH_AUTHOR: (&str, &str) = ("agent-core", "agent-core@localhost");
+
+/// A vetted, shareable agent configuration.
+///
+/// Custom tool handlers cannot be serialized; custom tools in a definition
+/// must be given handlers by the embedding application.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentDefinition {
+    /// Registry name of the agent
+    pub name: String,
+
+    /// Version of this definition
+    pub version: Version,
+
+    /// What the agent is for
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// Model to use
+    pub model: String,
+
+    /// System prompt
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+
+    /// Maximum number of turns
+    #[serde(default)]
+    pub max_turns: Option<u32>,
+
+    /// Tools available to the agent
+    #[serde(default)]
+    pub tools: Vec<ToolConfig>,
+
+    /// MCP servers to connect to
+    #[serde(default)]
+    pub mcp_servers: Vec<McpServerConfig>,
+
+    /// Environment variables set for tools
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+
+    /// Host environment variables passed through to tools
+    #[serde(default)]
+    pub env_passthrough: Vec<String>,
+}
+
+impl AgentDefinition {
+    /// Create a definition for the given name, version, and model.
+    pub fn new<S1, S2>(name: S1, version: Version, model: S2) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            version,
+            description: None,
+            model: model.into(),
+            system_prompt: None,
+            max_turns: None,
+            tools: Vec::new(),
+            mcp_servers: Vec::new(),
+            environment: HashMap::new(),
+            env_passthrough: Vec::new(),
+        }
+    }
+
+    /// Start an agent configuration from this definition.
+    ///
+    /// Host-specific settings such as the working directory and API key are
+    /// left to the caller.
+    pub fn to_builder(&self) -> AgentConfigBuilder {
+        let mut builder = crate::config::AgentConfig::builder()
+            .model(self.model.clone())
+            .tools(self.tools.iter().cloned())
+            .mcp_servers(self.mcp_servers.iter().cloned())
+            .envs(self.environment.clone())
+            .env_passthroughs(self.env_passthrough.iter().cloned());
+        if let Some(prompt) = &self.system_prompt {
+            builder = builder.system_prompt(prompt.clone());
+        }
+        if let Some(max_turns) = self.max_turns {
+            builder = builder.max_turns(max_turns);
+        }
+        builder
+    }
+}
+
+/// A published version listed in a registry index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegistryEntry {
+    /// Published version
+    pub version: Version,
+
+    /// Hex-encoded SHA-256 of the definition document
+    pub checksum: String,
+}
+
+/// Where a registry is hosted.
+#[derive(Debug, Clone)]
+pub enum RegistrySource {
+    /// Documents served under a base URL; publishing uses `PUT`
+    Http {
+        base_url: String,
+        token: Option<String>,
+    },
+
+    /// Documents committed to a git repository
+    Git {
+        url: String,
+        reference: Option<String>,
+    },
+}
+
+/// Client for fetching and publishing agent definitions.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    source: RegistrySource,
+    client: reqwest::Client,
+}
+
+impl Registry {
+    /// Create a client for an HTTP registry.
+    pub fn http<S: Into<String>>(base_url: S) -> Result<Self> {
+        Self::new(RegistrySource::Http {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+        })
+    }
+
+    /// Create a client for a git registry.
+    pub fn git<S: Into<String>>(url: S) -> Result<Self> {
+        Self::new(RegistrySource::Git {
+            url: url.into(),
+            reference: None,
+        })
+    }
+
+    fn new(source: RegistrySource) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .user_agent("agent-core")
+            .build()
+            .map_err(registry_error)?;
+        Ok(Self { source, client })
+    }
+
+    /// Authenticate HTTP requests with a bearer token.
+    pub fn token<S: Into<String>>(mut self, value: S) -> Self {
+        if let RegistrySource::Http { token, .. } = &mut self.source {
+            *token = Some(value.into());
+        }
+        self
+    }
+
+    /// Read a git registry at a branch or tag instead of the default branch.
+    pub fn reference<S: Into<String>>(mut self, value: S) -> Self {
+        if let RegistrySource::Git { reference, .. } = &mut self.source {
+            *reference = Some(value.into());
+        }
+        self
+    }
+
+    /// List the published versions of an agent.
+    pub async fn versions(&self, name: &str) -> Result<Vec<RegistryEntry>> {
+        validate_name(name)?;
+        let checkout = self.checkout().await?;
+        let index = self.read_index(checkout.as_deref(), name).await;
+        remove_checkout(checkout).await;
+        index
+    }
+
+    /// Fetch the newest definition matching a semver requirement such as
+    /// `^1.2` or `=1.0.3`, verifying its checksum.
+    pub async fn fetch(&self, name: &str, requirement: &str) -> Result<AgentDefinition> {
+        validate_name(name)?;
+        let requirement = VersionReq::parse(requirement).map_err(|e| AgentError::Config {
+            message: format!("Invalid version requirement \"{}\": {}", requirement, e),
+        })?;
+
+        let checkout = self.checkout().await?;
+        let result = async {
+            let entry = self
+                .read_index(checkout.as_deref(), name)
+                .await?
+                .into_iter()
+                .filter(|entry| requirement.matches(&entry.version))
+                .max_by(|a, b| a.version.cmp(&b.version))
+                .ok_or_else(|| AgentError::Config {
+                    message: format!("No version of {} matches {}", name, requirement),
+                })?;
+
+            let document = self
+                .read(checkout.as_deref(), &definition_path(name, &entry.version))
+                .await?;
+            let checksum = sha256_hex(&document);
+            if !checksum.eq_ignore_ascii_case(&entry.checksum) {
+                return Err(AgentError::Config {
+                    message: format!(
+                        "Checksum mismatch for {} {}: expected {}, got {}",
+                        name, entry.version, entry.checksum, checksum
+                    ),
+                });
+            }
+
+            let definition: AgentDefinition = serde_json::from_slice(&document)?;
+            if definition.name != name || definition.version != entry.version {
+                return Err(AgentError::Config {
+                    message: format!(
+                        "Registry document for {} {} describes {} {}",
+                        name, entry.version, definition.name, definition.version
+                    ),
+                });
+            }
+            Ok(definition)
+        }
+        .await;
+        remove_checkout(checkout).await;
+        result
+    }
+
+    /// Publish a definition, returning its index entry.
+    ///
+    /// Published versions are immutable; publishing an existing version fails.
+    pub async fn publish(&self, definition: &AgentDefinition) -> Result<RegistryEntry> {
+        validate_name(&definition.name)?;
+        let document = serde_json::to_vec_pretty(definition)?;
+        let entry = RegistryEntry {
+            version: definition.version.clone(),
+            checksum: sha256_hex(&document),
+        };
+
+        match &self.source {
+            RegistrySource::Http { base_url, token } => {
+                let url = format!(
+                    "{}/{}",
+                    base_url,
+                    definition_path(&definition.name, &definition.version)
+                );
+                let mut request = self
+                    .client
+                    .put(url)
+                    .header("Content-Type", "application/json")
+                    .header("X-Checksum-Sha256", &entry.checksum)
+                    .body(document);
+                if let Some(token) = token {
+                    request = request.bearer_auth(token);
+                }
+                request
+                    .send()
+                    .await
+                    .and_then(reqwest::Response::error_for_status)
+                    .map_err(registry_error)?;
+            }
+            RegistrySource::Git { .. } => {
+                let checkout = self.checkout().await?;
+                let result = self
+                    .publish_to_git(checkout.as_deref(), definition, &document, &entry)
+                    .await;
+                remove_checkout(checkout).await;
+                result?;
+            }
+        }
+
+        Ok(entry)
+    }
+
+    async fn publish_to_git(
+        &self,
+        checkout: Option<&Path>,
+        definition: &AgentDefinition,
+        document: &[u8],
+        entry: &RegistryEntry,
+    ) -> Result<()> {
+        let root = checkout.ok_or_else(|| AgentError::Generic {
+            message: "Git registry checkout missing".to_string(),
+        })?;
+
+        let mut index = match self.read_index(Some(root), &definition.name).await {
+            Ok(index) => index,
+            Err(AgentError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        if index.iter().any(|e| e.version == entry.version) {
+            return Err(AgentError::Config {
+                message: format!("{} {} is already published", definition.name, entry.version),
+            });
+        }
+        index.push(entry.clone());
+        index.sort_by(|a, b| a.version.cmp(&b.version));
+
+        let definition_file = definition_path(&definition.name, &definition.version);
+        let index_file = index_path(&definition.name);
+        tokio::fs::create_dir_all(root.join(&definition.name)).await?;
+        tokio::fs::write(root.join(&definition_file), document).await?;
+        tokio::fs::write(root.join(&index_file), serde_json::to_vec_pretty(&index)?).await?;
+
+        let (name, email) = PUBLISH_AUTHOR;
+        let message = format!("Publish {} {}", definition.name, definition.version);
+        git::run_git(root, ["add", definition_file.as_str(), index_file.as_str()]).await?;
+        git::run_git_with_env(
+            root,
+            ["commit", "-m", message.as_str()],
+            [
+                ("GIT_AUTHOR_NAME", name),
+                ("GIT_AUTHOR_EMAIL", email),
+                ("GIT_COMMITTER_NAME", name),
+                ("GIT_COMMITTER_EMAIL", email),
+            ],
+        )
+        .await?;
+        git::run_git(root, ["push", "origin", "HEAD"]).await?;
+        Ok(())
+    }
+
+    /// Clone a git registry into a temporary directory.
+    async fn checkout(&self) -> Result<Option<PathBuf>> {
+        let RegistrySource::Git { url, reference } = &self.source else {
+            return Ok(None);
+        };
+
+        let dir =
+            std::env::temp_dir().join(format!("agent-core-registry-{}", uuid::Uuid::new_v4()));
+        let target = dir.to_string_lossy().to_string();
+        let mut args = vec!["clone", "--depth", "1"];
+        if let Some(reference) = reference {
+            args.extend(["--branch", reference.as_str()]);
+        }
+        args.extend([url.as_str(), target.as_str()]);
+        git::run_git(&std::env::temp_dir(), args).await?;
+        Ok(Some(dir))
+    }
+
+    async fn read_index(&self, checkout: Option<&Path>, name: &str) -> Result<Vec<RegistryEntry>> {
+        let document = self.read(checkout, &index_path(name)).await?;
+        Ok(serde_json::from_slice(&document)?)
+    }
+
+    async fn read(&self, checkout: Option<&Path>, path: &str) -> Result<Vec<u8>> {
+        match (&self.source, checkout) {
+            (_, Some(root)) => Ok(tokio::fs::read(root.join(path)).await?),
+            (RegistrySource::Http { base_url, token }, None) => {
+                let mut request = self.client.get(format!("{}/{}", base_url, path));
+                if let Some(token) = token {
+                    request = request.bearer_auth(token);
+                }
+                let response = request
+                    .send()
+                    .await
+                    .and_then(reqwest::Response::error_for_status)
+                    .map_err(registry_error)?;
+                Ok(response.bytes().await.map_err(registry_error)?.to_vec())
+            }
+            (RegistrySource::Git { .. }, None) => Err(AgentError::Generic {
+                message: "Git registry checkout missing".to_string(),
+            }),
+        }
+    }
+}
+
+async fn remove_checkout(checkout: Option<PathBuf>) {
+    if let Some(dir) = checkout
+        && let Err(e) = tokio::fs::remove_dir_all(&dir).await
+    {
+        tracing::warn!(
+            "Failed to remove registry checkout {}: {}",
+            dir.display(),
+            e
+        );
+    }
+}
+
+/// Agent names become path segments, so only allow a safe character set.
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(AgentError::Config {
+            message: format!("Invalid agent name \"{}\"", name),
+        })
+    }
+}
+
+fn index_path(name: &str) -> String {
+    format!("{}/index.json", name)
+}
+
+fn definition_path(name: &str, version: &Version) -> String {
+    format!("{}/{}.json", name, version)
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn registry_error(e: reqwest::Error) -> AgentError {
+    AgentError::Execution {
+        message: format!("Registry request failed: {}", e),
+    }
+}
@@ -0,0 +1,376 @@
+//! Recording and replay of conversations.
+//!
+//! With `AgentConfigBuilder::record_to`, every submission the execution loop
+//! makes and every event Codex answers with is appended to a JSONL file. A
+//! [`Replay`] of the file plays the events back through the execution loop
+//! without a model: the events recorded after each submission are released
+//! when the loop makes the same submission again. A run that submits
+//! something other than the recording fails, so recordings of production runs
+//! double as regression tests and bug reproductions. Submissions are compared
+//! field by field, except for the working directory, so a recording replays
+//! from another checkout.
+//!
+//! The first line of a recording names the Codex protocol version it was
+//! made with. Recordings of other versions are read through the protocol
+//! adapter: events of changed shapes are upgraded and events this version
+//! does not know are skipped, but an event of a known type that fails to
+//! decode fails the whole recording. As operations may change shape too,
+//! submissions of other versions are only matched by kind.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use codex_protocol::protocol::{Event, Op, Submission};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use tokio::sync::Notify;
+use tracing::warn;
+
+use crate::backend::{ConversationBackend, LlmBackend};
+use crate::error::{AgentError, Result};
+use crate::protocol::{self, CODEX_PROTOCOL_VERSION};
+use crate::tool_server::ToolServer;
+
+/// Line of a recording.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Entry {
+    /// First line, naming the protocol version
+    Header { protocol_version: String },
+
+    /// Operation submitted by the execution loop
+    Submission { submission: Submission },
+
+    /// Event received from Codex
+    Event { event: Event },
+}
+
+/// Conversation recording everything passing through it.
+struct RecordingConversation {
+    inner: Arc<dyn ConversationBackend>,
+    file: Mutex<BufWriter<File>>,
+}
+
+impl RecordingConversation {
+    /// Append an entry. Failures are logged, as a broken recording should
+    /// not break the run.
+    fn write(&self, entry: &Entry) {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        let result = serde_json::to_string(entry)
+            .map_err(std::io::Error::from)
+            .and_then(|line| writeln!(file, "{}", line))
+            .and_then(|()| file.flush());
+        if let Err(e) = result {
+            warn!("Failed to write recording: {}", e);
+        }
+    }
+}
+
+impl ConversationBackend for RecordingConversation {
+    fn submit(&self, op: Op) -> BoxFuture<'_, Result<String>> {
+        // Choose the id up front so the submission is recorded before any
+        // of its events
+        let id = uuid::Uuid::new_v4().to_string();
+        Box::pin(async move {
+            self.submit_with_id(Submission { id: id.clone(), op })
+                .await?;
+            Ok(id)
+        })
+    }
+
+    fn submit_with_id(&self, submission: Submission) -> BoxFuture<'_, Result<()>> {
+        self.write(&Entry::Submission {
+            submission: submission.clone(),
+        });
+        self.inner.submit_with_id(submission)
+    }
+
+    fn next_event(&self) -> BoxFuture<'_, Result<Event>> {
+        Box::pin(async move {
+            let event = self.inner.next_event().await?;
+            self.write(&Entry::Event {
+                event: event.clone(),
+            });
+            Ok(event)
+        })
+    }
+}
+
+/// Record a conversation to the file at `path`, replacing it.
+pub(crate) fn record(
+    conversation: Arc<dyn ConversationBackend>,
+    path: &Path,
+) -> Result<Arc<dyn ConversationBackend>> {
+    let recording = RecordingConversation {
+        inner: conversation,
+        file: Mutex::new(BufWriter::new(File::create(path)?)),
+    };
+    recording.write(&Entry::Header {
+        protocol_version: CODEX_PROTOCOL_VERSION.to_string(),
+    });
+    Ok(Arc::new(recording))
+}
+
+/// Operation fields that may differ between a recording and its replay.
+const UNCOMPARED_FIELDS: &[&str] = &["cwd"];
+
+/// Line of a recording read back.
+#[derive(Debug, Clone)]
+enum Recorded {
+    /// Submission, with its recorded id and operation and the line it is on
+    Submission {
+        id: String,
+        op: serde_json::Value,
+        line: usize,
+    },
+
+    /// Event, in the shape of this version
+    Event(Event),
+}
+
+/// Recorded conversation, played back by running an agent on it with
+/// `Agent::with_replay`.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    entries: Arc<Vec<Recorded>>,
+    /// Whether the recording was made with this protocol version, so its
+    /// submissions are compared in full
+    same_version: bool,
+}
+
+impl Replay {
+    /// Load a recording from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parse a recording from its JSONL text.
+    pub fn parse(jsonl: &str) -> Result<Self> {
+        let mut lines = jsonl
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        let header = lines
+            .next()
+            .map(|(_, line)| serde_json::from_str::<serde_json::Value>(line))
+            .transpose()?
+            .unwrap_or_default();
+        let Some(protocol_version) = header["protocol_version"]
+            .as_str()
+            .filter(|_| header["kind"] == "header")
+        else {
+            return Err(AgentError::Config {
+                message: "Recording does not start with a header".to_string(),
+            });
+        };
+        if protocol_version != CODEX_PROTOCOL_VERSION {
+            warn!(
+                "Replaying a recording of Codex protocol {} with {}",
+                protocol_version, CODEX_PROTOCOL_VERSION
+            );
+        }
+
+        let mut entries = Vec::new();
+        for (index, line) in lines {
+            let invalid = |reason: String| AgentError::Config {
+                message: format!("Invalid recording entry at line {}: {}", index + 1, reason),
+            };
+            let mut entry: serde_json::Value =
+                serde_json::from_str(line).map_err(|e| invalid(e.to_string()))?;
+            match entry["kind"].as_str() {
+                Some("submission") => {
+                    let submission = &mut entry["submission"];
+                    entries.push(Recorded::Submission {
+                        id: submission["id"].as_str().unwrap_or_default().to_string(),
+                        op: submission["op"].take(),
+                        line: index + 1,
+                    });
+                }
+                Some("event") => {
+                    if let Some(event) =
+                        protocol::decode_event(entry["event"].take()).map_err(invalid)?
+                    {
+                        entries.push(Recorded::Event(event));
+                    }
+                }
+                _ => return Err(invalid("unknown kind".to_string())),
+            }
+        }
+        Ok(Self {
+            entries: Arc::new(entries),
+            same_version: protocol_version == CODEX_PROTOCOL_VERSION,
+        })
+    }
+
+    /// Number of submissions recorded.
+    pub fn submissions(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry, Recorded::Submission { .. }))
+            .count()
+    }
+}
+
+impl LlmBackend for Replay {
+    // Tool calls are replayed from the recording, not run
+    fn start_conversation(
+        &self,
+        _tools: ToolServer,
+    ) -> BoxFuture<'_, Result<Arc<dyn ConversationBackend>>> {
+        let mut state = ReplayState {
+            cursor: 0,
+            events: VecDeque::new(),
+            ids: HashMap::new(),
+        };
+        // Events preceding the first submission, like the session setup
+        state.release(&self.entries);
+        let conversation: Arc<dyn ConversationBackend> = Arc::new(ReplayConversation {
+            entries: self.entries.clone(),
+            same_version: self.same_version,
+            state: Mutex::new(state),
+            notify: Notify::new(),
+        });
+        Box::pin(async move { Ok(conversation) })
+    }
+}
+
+/// Conversation releasing recorded events as the loop repeats the recorded
+/// submissions.
+struct ReplayConversation {
+    entries: Arc<Vec<Recorded>>,
+    same_version: bool,
+    state: Mutex<ReplayState>,
+    notify: Notify,
+}
+
+struct ReplayState {
+    /// Index of the next entry not released
+    cursor: usize,
+
+    /// Released events not read yet
+    events: VecDeque<Event>,
+
+    /// Ids of this run's submissions by their recorded ids
+    ids: HashMap<String, String>,
+}
+
+impl ReplayState {
+    /// Release the events up to the next submission.
+    fn release(&mut self, entries: &[Recorded]) {
+        while let Some(Recorded::Event(event)) = entries.get(self.cursor) {
+            let mut event = event.clone();
+            if let Some(id) = self.ids.get(&event.id) {
+                event.id = id.clone();
+            }
+            self.events.push_back(event);
+            self.cursor += 1;
+        }
+    }
+
+    /// Recorded id of a submission of this run.
+    fn recorded_id(&self, id: &str) -> Option<&String> {
+        self.ids
+            .iter()
+            .find(|(_, current)| *current == id)
+            .map(|(recorded, _)| recorded)
+    }
+}
+
+impl ReplayConversation {
+    fn handle(&self, submission: Submission) -> Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let kind = protocol::op_kind(&submission.op);
+        let (recorded_id, recorded_op, line) = match self.entries.get(state.cursor) {
+            Some(Recorded::Submission { id, op, line }) => (id, op, line),
+            _ => {
+                return Err(AgentError::Execution {
+                    message: format!("Replay diverged: {} submitted after the recording", kind),
+                });
+            }
+        };
+        let recorded_kind = recorded_op["type"].as_str().unwrap_or_default();
+        if recorded_kind != kind {
+            return Err(AgentError::Execution {
+                message: format!(
+                    "Replay diverged at line {}: {} submitted where the recording has {}",
+                    line, kind, recorded_kind
+                ),
+            });
+        }
+        if self.same_version {
+            let mut op = serde_json::to_value(&submission.op)?;
+            // Approvals answer events by this run's submission ids
+            if let Some(id) = op["id"].as_str().and_then(|id| state.recorded_id(id)) {
+                op["id"] = serde_json::Value::String(id.clone());
+            }
+            let fields = differing_fields(recorded_op, &op);
+            if !fields.is_empty() {
+                return Err(AgentError::Execution {
+                    message: format!(
+                        "Replay diverged at line {}: {} submitted with another {} than the recording",
+                        line,
+                        kind,
+                        fields.join(", ")
+                    ),
+                });
+            }
+        }
+        state.ids.insert(recorded_id.clone(), submission.id);
+        state.cursor += 1;
+        state.release(&self.entries);
+        self.notify.notify_one();
+        Ok(())
+    }
+}
+
+/// Fields of two operations that differ, besides [`UNCOMPARED_FIELDS`].
+fn differing_fields(recorded: &serde_json::Value, submitted: &serde_json::Value) -> Vec<String> {
+    let empty = serde_json::Map::new();
+    let recorded = recorded.as_object().unwrap_or(&empty);
+    let submitted = submitted.as_object().unwrap_or(&empty);
+    let mut fields: Vec<String> = recorded
+        .keys()
+        .chain(submitted.keys())
+        .filter(|field| !UNCOMPARED_FIELDS.contains(&field.as_str()))
+        .filter(|field| recorded.get(*field) != submitted.get(*field))
+        .cloned()
+        .collect();
+    fields.sort();
+    fields.dedup();
+    fields
+}
+
+impl ConversationBackend for ReplayConversation {
+    fn submit(&self, op: Op) -> BoxFuture<'_, Result<String>> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let result = self.handle(Submission { id: id.clone(), op });
+        Box::pin(async move { result.map(|()| id) })
+    }
+
+    fn submit_with_id(&self, submission: Submission) -> BoxFuture<'_, Result<()>> {
+        let result = self.handle(submission);
+        Box::pin(async move { result })
+    }
+
+    fn next_event(&self) -> BoxFuture<'_, Result<Event>> {
+        Box::pin(async move {
+            loop {
+                {
+                    let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+                    if let Some(event) = state.events.pop_front() {
+                        return Ok(event);
+                    }
+                    if state.cursor >= self.entries.len() {
+                        return Err(AgentError::Execution {
+                            message: "Replay reached the end of the recording".to_string(),
+                        });
+                    }
+                }
+                self.notify.notified().await;
+            }
+        })
+    }
+}
@@ -0,0 +1,152 @@
+//! Retries of turns failed by transient model errors.
+//!
+//! Codex retries dropped streams a few times on its own; when it gives up, or
+//! the API answers with a 5xx or 429 status, the turn fails with a model
+//! error. With a [`RetryPolicy`], the execution loop holds such errors back,
+//! waits with exponential backoff, and asks the model to continue, emitting
+//! `OutputData::Retrying` before each attempt.
+
+use std::time::Duration;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Status codes worth retrying: rate limits and server errors.
+const TRANSIENT_STATUSES: &[&str] = &["429", "500", "502", "503", "504", "529"];
+
+/// Error messages of dropped connections and overloaded servers.
+const TRANSIENT_MESSAGES: &[&str] = &[
+    "stream disconnected",
+    "stream error",
+    "connection reset",
+    "connection closed",
+    "timed out",
+    "timeout",
+    "overloaded",
+    "rate limit",
+    "too many requests",
+    "service unavailable",
+    "bad gateway",
+];
+
+/// Prompt continuing a turn after its model request failed.
+const RESUME_PROMPT: &str = "The previous model request failed with a transient error before \
+     the task was finished. Continue the task where you left off.";
+
+/// How turns failed by transient model errors are retried.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Maximum retries per turn
+    pub max_attempts: u32,
+
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+
+    /// Upper bound of the delay between retries
+    pub max_backoff: Duration,
+
+    /// Factor the delay grows by after each retry
+    pub multiplier: f64,
+
+    /// Randomize each delay between half and all of its value, so agents
+    /// failing together do not retry together
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    /// Three retries, starting at one second and doubling up to 30 seconds,
+    /// with jitter.
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create the default policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum retries per turn.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// Set the delays: the first retry waits `initial`, later ones grow by
+    /// `multiplier` up to `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration, multiplier: f64) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Enable or disable jitter.
+    pub fn jitter(mut self, enable: bool) -> Self {
+        self.jitter = enable;
+        self
+    }
+
+    /// Delay before the given retry (1-based). A delay the server asked for
+    /// in the error is honored if longer, up to `max_backoff`.
+    pub(crate) fn delay(&self, attempt: u32, error: &str) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let seconds = self.initial_backoff.as_secs_f64() * self.multiplier.max(1.0).powi(exponent);
+        let backoff = Duration::try_from_secs_f64(seconds)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        let backoff = if self.jitter {
+            backoff.mul_f64(0.5 + random_fraction() / 2.0)
+        } else {
+            backoff
+        };
+        match retry_after(error) {
+            Some(requested) => backoff.max(requested.min(self.max_backoff)),
+            None => backoff,
+        }
+    }
+}
+
+/// Whether a model error is worth retrying.
+pub(crate) fn is_transient(error: &str) -> bool {
+    let error = error.to_lowercase();
+    TRANSIENT_STATUSES
+        .iter()
+        .any(|status| error.contains(&format!("status {}", status)))
+        || TRANSIENT_MESSAGES
+            .iter()
+            .any(|message| error.contains(message))
+}
+
+/// Prompt continuing the failed turn.
+pub(crate) fn resume_prompt() -> &'static str {
+    RESUME_PROMPT
+}
+
+/// Delay the server asked for, from a `retry-after` value or a "try again
+/// in" hint in the error.
+fn retry_after(error: &str) -> Option<Duration> {
+    let pattern =
+        Regex::new(r"(?i)(?:retry[- ]after:?|try again in)\s*(\d+(?:\.\d+)?)\s*(ms|s|seconds?)?")
+            .ok()?;
+    let captures = pattern.captures(error)?;
+    let value: f64 = captures.get(1)?.as_str().parse().ok()?;
+    let seconds = match captures.get(2) {
+        Some(unit) if unit.as_str().eq_ignore_ascii_case("ms") => value / 1000.0,
+        _ => value,
+    };
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+/// Random number in [0, 1), from the random bits of a v4 UUID.
+fn random_fraction() -> f64 {
+    let bits = uuid::Uuid::new_v4().as_u128() as u64 >> 11;
+    bits as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,297 @@
+//! Backends running commands spawned by agent-core.
+//!
+//! The native backend runs commands on the host, capped by the bash tool's
+//! [`ResourceLimits`]. The container backend runs each command in a fresh,
+//! named Docker or Podman container with the working directory mounted at
+//! `/workspace`, and removes the container when the command is terminated on
+//! timeout or cancellation. Under the container backend every command of the
+//! bash tool runs through the backend, and Codex's own shell is refused;
+//! under the native backend only a bash tool with resource limits or a kill
+//! policy does, and Codex runs the other commands under its sandbox policy.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+use crate::blocking::CancellationToken;
+use crate::error::Result;
+use crate::limits::{KillPolicy, ResourceLimits};
+use crate::process::{CommandOutput, run_with_timeout};
+
+/// Mount point of the working directory inside containers.
+const CONTAINER_WORKDIR: &str = "/workspace";
+
+/// Host variables not passed into containers, since they describe the host.
+const HOST_ONLY_VARIABLES: &[&str] = &["PATH", "HOME", "TMPDIR"];
+
+/// Where commands spawned by agent-core run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SandboxBackend {
+    /// On the host, under rlimits
+    #[default]
+    Native,
+
+    /// In a container
+    Container(ContainerSandbox),
+}
+
+/// Container engine used by the container backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContainerRuntime {
+    /// Docker
+    #[default]
+    Docker,
+
+    /// Podman
+    Podman,
+}
+
+impl ContainerRuntime {
+    pub(crate) fn program(&self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::Podman => "podman",
+        }
+    }
+}
+
+/// A host path mounted into the container.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContainerMount {
+    /// Path on the host
+    pub source: PathBuf,
+
+    /// Path inside the container
+    pub target: String,
+
+    /// Whether the container may only read the mount
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// Configuration of the container backend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContainerSandbox {
+    /// Container engine
+    #[serde(default)]
+    pub runtime: ContainerRuntime,
+
+    /// Image commands run in (e.g. "rust:1.85")
+    pub image: String,
+
+    /// Additional mounts besides the working directory
+    #[serde(default)]
+    pub mounts: Vec<ContainerMount>,
+
+    /// Network mode passed to `--network` (e.g. "none", "bridge", "host")
+    #[serde(default = "default_network")]
+    pub network: String,
+}
+
+impl ContainerSandbox {
+    /// Run commands in the given image with Docker, without network access.
+    pub fn docker<S: Into<String>>(image: S) -> Self {
+        Self {
+            runtime: ContainerRuntime::Docker,
+            image: image.into(),
+            mounts: Vec::new(),
+            network: default_network(),
+        }
+    }
+
+    /// Run commands in the given image with Podman, without network access.
+    pub fn podman<S: Into<String>>(image: S) -> Self {
+        Self {
+            runtime: ContainerRuntime::Podman,
+            ..Self::docker(image)
+        }
+    }
+
+    /// Mount a host path into the container.
+    pub fn mount<P: Into<PathBuf>, S: Into<String>>(mut self, source: P, target: S) -> Self {
+        self.mounts.push(ContainerMount {
+            source: source.into(),
+            target: target.into(),
+            read_only: false,
+        });
+        self
+    }
+
+    /// Mount a host path into the container read-only.
+    pub fn mount_read_only<P: Into<PathBuf>, S: Into<String>>(
+        mut self,
+        source: P,
+        target: S,
+    ) -> Self {
+        self.mounts.push(ContainerMount {
+            source: source.into(),
+            target: target.into(),
+            read_only: true,
+        });
+        self
+    }
+
+    /// Set the network mode (e.g. "bridge" to allow network access).
+    pub fn network<S: Into<String>>(mut self, mode: S) -> Self {
+        self.network = mode.into();
+        self
+    }
+
+    /// Build the command running `shell_command` in a fresh container named
+    /// `container_name`.
+    ///
+    /// Resource limits map to the engine's `--ulimit`, `--memory`, and
+    /// `--pids-limit` options.
+    fn command(
+        &self,
+        container_name: &str,
+        shell_command: &str,
+        working_directory: &Path,
+        environment: &HashMap<String, String>,
+        limits: &ResourceLimits,
+    ) -> Command {
+        let mut command = Command::new(self.runtime.program());
+        command
+            .args(["run", "--rm", "--init", "--name", container_name])
+            .args(["--network", &self.network])
+            .arg("--volume")
+            .arg(volume(working_directory, CONTAINER_WORKDIR, false))
+            .args(["--workdir", CONTAINER_WORKDIR]);
+        for mount in &self.mounts {
+            command
+                .arg("--volume")
+                .arg(volume(&mount.source, &mount.target, mount.read_only));
+        }
+        for (name, value) in environment {
+            if !HOST_ONLY_VARIABLES.contains(&name.as_str()) {
+                command.arg("--env").arg(format!("{name}={value}"));
+            }
+        }
+        if let Some(seconds) = limits.cpu_seconds {
+            command.arg("--ulimit").arg(format!("cpu={seconds}"));
+        }
+        if let Some(bytes) = limits.max_memory_bytes {
+            command.arg("--memory").arg(bytes.to_string());
+        }
+        if let Some(bytes) = limits.max_file_size_bytes {
+            command.arg("--ulimit").arg(format!("fsize={bytes}"));
+        }
+        if let Some(processes) = limits.max_processes {
+            command.arg("--pids-limit").arg(processes.to_string());
+        }
+        command.arg(&self.image).args(["sh", "-c", shell_command]);
+        command
+    }
+}
+
+impl SandboxBackend {
+    /// Build the command running `shell_command` in the working directory
+    /// with the given environment and resource limits.
+    pub(crate) fn command(
+        &self,
+        shell_command: &str,
+        working_directory: &Path,
+        environment: &HashMap<String, String>,
+        limits: &ResourceLimits,
+    ) -> SandboxCommand {
+        match self {
+            SandboxBackend::Native => {
+                let mut command = Command::new("sh");
+                command
+                    .arg("-c")
+                    .arg(shell_command)
+                    .current_dir(working_directory)
+                    .env_clear()
+                    .envs(environment);
+                limits.apply(&mut command);
+                SandboxCommand {
+                    command,
+                    container: None,
+                }
+            }
+            SandboxBackend::Container(container) => {
+                let name = format!("agent-core-{}", uuid::Uuid::new_v4().simple());
+                // The engine client needs the host environment to reach its daemon
+                let mut command =
+                    container.command(&name, shell_command, working_directory, environment, limits);
+                command.current_dir(working_directory);
+                SandboxCommand {
+                    command,
+                    container: Some((container.runtime, name)),
+                }
+            }
+        }
+    }
+}
+
+/// Command built by a [`SandboxBackend`].
+#[derive(Debug)]
+pub(crate) struct SandboxCommand {
+    command: Command,
+    /// Engine and name of the container the command runs in
+    container: Option<(ContainerRuntime, String)>,
+}
+
+impl SandboxCommand {
+    /// Arguments the program is run with.
+    #[cfg(test)]
+    pub(crate) fn args(&self) -> Vec<String> {
+        self.command
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// Run the command with [`run_with_timeout`].
+    ///
+    /// Terminating the engine client leaves its container running, so the
+    /// container of a command terminated on timeout or cancellation is
+    /// removed.
+    pub(crate) async fn run(
+        self,
+        timeout: Option<Duration>,
+        policy: &KillPolicy,
+        cancellation: &CancellationToken,
+    ) -> Result<CommandOutput> {
+        let output = run_with_timeout(self.command, timeout, policy, cancellation).await?;
+        if output.status.is_none()
+            && let Some((runtime, name)) = &self.container
+        {
+            remove_container(*runtime, name).await;
+        }
+        Ok(output)
+    }
+}
+
+/// Force the removal of a container, stopping it if running.
+async fn remove_container(runtime: ContainerRuntime, name: &str) {
+    let status = Command::new(runtime.program())
+        .args(["rm", "--force", name])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await;
+    match status {
+        Ok(status) if status.success() => debug!("Removed container {}", name),
+        Ok(status) => warn!("Failed to remove container {}: {}", name, status),
+        Err(e) => warn!("Failed to remove container {}: {}", name, e),
+    }
+}
+
+fn volume(source: &Path, target: &str, read_only: bool) -> String {
+    let mode = if read_only { ":ro" } else { "" };
+    format!("{}:{}{}", source.display(), target, mode)
+}
+
+fn default_network() -> String {
+    "none".to_string()
+}
@@ -0,0 +1,180 @@
+//! Built-in key/value notes the model keeps across turns.
+//!
+//! Each agent owns one [`Scratchpad`], shared by its tool registry and
+//! handles and saved with session snapshots. The model reads and writes it
+//! through the scratchpad tool instead of restating notes in the context.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::error::Result;
+use crate::tools::{CustomToolHandler, ToolExecutionContext, ToolExecutionResult};
+
+/// Notes of one conversation; clones share the same notes.
+#[derive(Debug, Clone, Default)]
+pub struct Scratchpad {
+    notes: Arc<RwLock<BTreeMap<String, String>>>,
+}
+
+impl Scratchpad {
+    /// Create an empty scratchpad.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a note.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.read().get(key).cloned()
+    }
+
+    /// Set a note, returning the previous value.
+    pub fn set<K: Into<String>, V: Into<String>>(&self, key: K, value: V) -> Option<String> {
+        self.write().insert(key.into(), value.into())
+    }
+
+    /// Remove a note, returning its value.
+    pub fn remove(&self, key: &str) -> Option<String> {
+        self.write().remove(key)
+    }
+
+    /// Get all notes, ordered by key.
+    pub fn entries(&self) -> BTreeMap<String, String> {
+        self.read().clone()
+    }
+
+    /// Replace all notes, e.g. with ones saved in a session snapshot.
+    pub fn restore(&self, entries: BTreeMap<String, String>) {
+        *self.write() = entries;
+    }
+
+    /// Get the number of notes.
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Check if there are no notes.
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    // Every write leaves the map consistent, so a poisoned lock is still usable
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, BTreeMap<String, String>> {
+        self.notes.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, BTreeMap<String, String>> {
+        self.notes.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+enum ScratchpadOperation {
+    Read { key: String },
+    Write { key: String, value: String },
+    Delete { key: String },
+    List,
+}
+
+/// Tool handler reading and writing an agent's scratchpad.
+#[derive(Debug, Clone)]
+pub struct ScratchpadTool {
+    scratchpad: Scratchpad,
+    max_entries: usize,
+    max_value_bytes: usize,
+}
+
+impl ScratchpadTool {
+    /// Create a handler over `scratchpad` holding at most `max_entries`
+    /// notes of at most `max_value_bytes` each.
+    pub fn new(scratchpad: Scratchpad, max_entries: usize, max_value_bytes: usize) -> Self {
+        Self {
+            scratchpad,
+            max_entries,
+            max_value_bytes,
+        }
+    }
+}
+
+impl CustomToolHandler for ScratchpadTool {
+    fn execute(
+        &self,
+        parameters: Value,
+        _context: &ToolExecutionContext,
+    ) -> Result<ToolExecutionResult> {
+        let result = match serde_json::from_value(parameters)? {
+            ScratchpadOperation::Read { key } => match self.scratchpad.get(&key) {
+                Some(value) => ToolExecutionResult::success(value),
+                None => ToolExecutionResult::failure(format!("No note named {key}"), 1),
+            },
+            ScratchpadOperation::Write { value, .. } if value.len() > self.max_value_bytes => {
+                ToolExecutionResult::error(format!(
+                    "Note is {} bytes, the limit is {}",
+                    value.len(),
+                    self.max_value_bytes
+                ))
+            }
+            ScratchpadOperation::Write { key, .. }
+                if self.scratchpad.get(&key).is_none()
+                    && self.scratchpad.len() >= self.max_entries =>
+            {
+                ToolExecutionResult::error(format!(
+                    "The scratchpad is full ({} notes); delete a note first",
+                    self.max_entries
+                ))
+            }
+            ScratchpadOperation::Write { key, value } => {
+                self.scratchpad.set(key.clone(), value);
+                ToolExecutionResult::success(format!("Saved note {key}"))
+            }
+            ScratchpadOperation::Delete { key } => match self.scratchpad.remove(&key) {
+                Some(_) => ToolExecutionResult::success(format!("Deleted note {key}")),
+                None => ToolExecutionResult::failure(format!("No note named {key}"), 1),
+            },
+            ScratchpadOperation::List => {
+                let entries = self.scratchpad.entries();
+                let output = if entries.is_empty() {
+                    "The scratchpad is empty".to_string()
+                } else {
+                    entries
+                        .iter()
+                        .map(|(key, value)| format!("{key}: {value}"))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                };
+                ToolExecutionResult::success_with_data(output, json!(entries))
+            }
+        };
+        Ok(result)
+    }
+
+    fn parameter_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "operation": {
+                    "type": "string",
+                    "enum": ["read", "write", "delete", "list"]
+                },
+                "key": {
+                    "type": "string",
+                    "description": "Name of the note (read, write, delete)"
+                },
+                "value": {
+                    "type": "string",
+                    "description": "Content of the note (write)"
+                }
+            },
+            "required": ["operation"]
+        })
+    }
+
+    fn description(&self) -> String {
+        "Keep short named notes that persist across turns: read, write, delete, or list \
+         them instead of repeating them in messages"
+            .to_string()
+    }
+}
@@ -0,0 +1,266 @@
+//! Built-in regex content search over the working directory.
+//!
+//! Files denied to the file read tool by its path globs are not searched.
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::config::AgentConfig;
+use crate::error::{AgentError, Result};
+use crate::paths::{glob_matches, name_matches};
+use crate::tools::{CustomToolHandler, ToolExecutionContext, ToolExecutionResult};
+
+/// Files larger than this are skipped.
+const MAX_SEARCH_FILE_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Matched lines longer than this are cut.
+const MAX_LINE_CHARS: usize = 300;
+
+/// Directories never descended into.
+const SKIPPED_DIRECTORIES: &[&str] = &[".git", "node_modules", "target"];
+
+/// A line matching the search pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchMatch {
+    /// Path relative to the search root
+    pub path: String,
+
+    /// Line number, starting at 1
+    pub line: usize,
+
+    /// Text of the line
+    pub text: String,
+}
+
+/// Tool handler searching file contents with a regular expression.
+///
+/// Hidden files, binary files, and files over 2 MB are skipped. Globs
+/// without a `/` match file names at any depth; others match paths relative
+/// to the search root.
+#[derive(Debug, Clone)]
+pub struct SearchTool {
+    max_matches: usize,
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    pattern: String,
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    case_insensitive: bool,
+}
+
+impl SearchTool {
+    /// Create a handler returning at most `max_matches` matches per call,
+    /// restricted to files matching `include` (if any) and not `exclude`.
+    pub fn new(max_matches: usize, include: Vec<String>, exclude: Vec<String>) -> Self {
+        Self {
+            max_matches,
+            include,
+            exclude,
+        }
+    }
+
+    /// Search the files under `root` the file read tool may read, returning
+    /// the matches and whether the match cap was hit.
+    fn search(
+        &self,
+        root: &Path,
+        pattern: &Regex,
+        include: &[String],
+        config: &AgentConfig,
+    ) -> Result<(Vec<SearchMatch>, bool)> {
+        let mut matches = Vec::new();
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(directory) = pending.pop() {
+            let mut entries: Vec<PathBuf> = fs::read_dir(&directory)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect();
+            entries.sort();
+            // Visit subdirectories in order after this directory's files
+            for path in entries.iter().rev() {
+                let name = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or("");
+                if name.starts_with('.') || SKIPPED_DIRECTORIES.contains(&name) {
+                    continue;
+                }
+                let Ok(metadata) = fs::symlink_metadata(path) else {
+                    continue;
+                };
+                if metadata.is_dir() {
+                    pending.push(path.clone());
+                }
+            }
+            for path in &entries {
+                let Ok(metadata) = fs::symlink_metadata(path) else {
+                    continue;
+                };
+                if !metadata.is_file()
+                    || metadata.len() > MAX_SEARCH_FILE_BYTES
+                    || !self.selected(root, path, include)
+                    || config.check_tool_path("file_read", path).is_err()
+                {
+                    continue;
+                }
+                if search_file(root, path, pattern, &mut matches, self.max_matches) {
+                    return Ok((matches, true));
+                }
+            }
+        }
+        Ok((matches, false))
+    }
+
+    fn selected(&self, root: &Path, path: &Path, include: &[String]) -> bool {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+        if name.starts_with('.') {
+            return false;
+        }
+        let matches = |glob: &String| {
+            if glob.contains('/') {
+                glob_matches(glob, root, path)
+            } else {
+                name_matches(glob, name)
+            }
+        };
+        if self.exclude.iter().any(matches) {
+            return false;
+        }
+        [&self.include[..], include]
+            .iter()
+            .all(|globs| globs.is_empty() || globs.iter().any(matches))
+    }
+}
+
+/// Search one file, returning whether the match cap was hit.
+fn search_file(
+    root: &Path,
+    path: &Path,
+    pattern: &Regex,
+    matches: &mut Vec<SearchMatch>,
+    max_matches: usize,
+) -> bool {
+    let mut contents = Vec::new();
+    if fs::File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut contents))
+        .is_err()
+        || contents.iter().take(8192).any(|byte| *byte == 0)
+    {
+        return false;
+    }
+    let contents = String::from_utf8_lossy(&contents);
+    let relative = path
+        .strip_prefix(root)
+        .unwrap_or(path)
+        .display()
+        .to_string();
+
+    for (index, line) in contents.lines().enumerate() {
+        if !pattern.is_match(line) {
+            continue;
+        }
+        if matches.len() >= max_matches {
+            return true;
+        }
+        matches.push(SearchMatch {
+            path: relative.clone(),
+            line: index + 1,
+            text: line.chars().take(MAX_LINE_CHARS).collect(),
+        });
+    }
+    false
+}
+
+impl CustomToolHandler for SearchTool {
+    fn execute(
+        &self,
+        parameters: Value,
+        context: &ToolExecutionContext,
+    ) -> Result<ToolExecutionResult> {
+        let params: SearchParams = serde_json::from_value(parameters)?;
+        let pattern = match RegexBuilder::new(&params.pattern)
+            .case_insensitive(params.case_insensitive)
+            .build()
+        {
+            Ok(pattern) => pattern,
+            Err(e) => return Ok(ToolExecutionResult::error(format!("Invalid pattern: {e}"))),
+        };
+
+        let base = fs::canonicalize(&context.working_directory)?;
+        let root = match &params.path {
+            Some(path) => fs::canonicalize(base.join(path))?,
+            None => base.clone(),
+        };
+        if !root.starts_with(&base) {
+            return Err(AgentError::Tool {
+                message: format!(
+                    "Search path {} escapes the working directory",
+                    root.display()
+                ),
+            });
+        }
+
+        let (matches, truncated) =
+            self.search(&root, &pattern, &params.include, &context.agent_config)?;
+        let mut output: String = matches
+            .iter()
+            .map(|m| format!("{}:{}:{}\n", m.path, m.line, m.text))
+            .collect();
+        if matches.is_empty() {
+            output.push_str("No matches found\n");
+        }
+        if truncated {
+            output.push_str(&format!("[stopped after {} matches]\n", self.max_matches));
+        }
+        Ok(ToolExecutionResult::success_with_data(
+            output,
+            json!({ "matches": matches, "truncated": truncated }),
+        ))
+    }
+
+    fn parameter_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "pattern": {
+                    "type": "string",
+                    "description": "Regular expression matched against each line"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "Directory to search, relative to the working directory"
+                },
+                "include": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Globs of files to search, e.g. \"*.rs\" or \"src/**\""
+                },
+                "case_insensitive": {
+                    "type": "boolean",
+                    "description": "Ignore case when matching"
+                }
+            },
+            "required": ["pattern"]
+        })
+    }
+
+    fn description(&self) -> String {
+        "Search file contents with a regular expression, returning matching lines as \
+         path:line:text"
+            .to_string()
+    }
+}
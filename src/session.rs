@@ -1,17 +1,50 @@
 //! Session management for persistent agent state (optional feature).
 
-use crate::agent::Agent;
-use crate::error::Result;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::agent::{Agent, AgentHandle};
+use crate::error::{AgentError, Result};
+use crate::messages::{InputMessage, OutputData};
+use crate::plan::TodoItem;
+use crate::queue::PendingInput;
+use crate::workspace::TurnRecord;
+
+/// Directory snapshots are stored in unless configured otherwise.
+const DEFAULT_SESSION_DIR: &str = ".agent-core/sessions";
+
+/// Characters of context kept on each side of a match in search snippets.
+const SNIPPET_CONTEXT_CHARS: usize = 80;
 
 /// Session manager for persisting and restoring agent state across sessions.
 pub struct SessionManager {
-    // Placeholder for session storage implementation
+    /// Directory holding one JSON snapshot per session
+    directory: PathBuf,
 }
 
 impl SessionManager {
     /// Create a new session manager.
     pub fn new() -> Self {
-        Self {}
+        Self {
+            directory: PathBuf::from(DEFAULT_SESSION_DIR),
+        }
+    }
+
+    /// Create a session manager storing snapshots in `directory`.
+    pub fn with_directory<P: Into<PathBuf>>(directory: P) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// Get the snapshot directory.
+    pub fn directory(&self) -> &Path {
+        &self.directory
     }
 
     /// Save agent state to persistent storage.
@@ -37,16 +70,241 @@ impl SessionManager {
         })
     }
 
+    /// Write a session snapshot, returning its path.
+    pub async fn save_snapshot(&self, snapshot: &SessionSnapshot) -> Result<PathBuf> {
+        let path = self.snapshot_path(&snapshot.session_id)?;
+        tokio::fs::create_dir_all(&self.directory).await?;
+        tokio::fs::write(&path, serde_json::to_vec_pretty(snapshot)?).await?;
+        Ok(path)
+    }
+
+    /// Read a session snapshot.
+    pub async fn load_snapshot(&self, session_id: &str) -> Result<SessionSnapshot> {
+        let path = self.snapshot_path(session_id)?;
+        Ok(serde_json::from_slice(&tokio::fs::read(path).await?)?)
+    }
+
     /// List available saved sessions.
     pub async fn list_sessions(&self) -> Result<Vec<SessionInfo>> {
-        // TODO: Implement session listing
-        Ok(Vec::new())
+        let mut entries = match tokio::fs::read_dir(&self.directory).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut sessions = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let Some(id) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+                continue;
+            };
+
+            let metadata = entry.metadata().await?;
+            let modified_at = metadata
+                .modified()
+                .map(chrono::DateTime::<chrono::Utc>::from)
+                .unwrap_or_else(|_| chrono::Utc::now());
+            let created_at = metadata
+                .created()
+                .map(chrono::DateTime::<chrono::Utc>::from)
+                .unwrap_or(modified_at);
+            sessions.push(SessionInfo {
+                name: id.clone(),
+                id,
+                created_at,
+                modified_at,
+                size_bytes: metadata.len(),
+                metadata: std::collections::HashMap::new(),
+            });
+        }
+
+        sessions.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(sessions)
     }
 
     /// Delete a saved session.
-    pub async fn delete_session(&self, _session_id: &str) -> Result<()> {
-        // TODO: Implement session deletion
-        Ok(())
+    pub async fn delete_session(&self, session_id: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.snapshot_path(session_id)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Full-text search over the inputs and responses of stored sessions.
+    ///
+    /// A turn matches when it contains every word of `query`, ignoring case.
+    /// Hits are ordered by the number of occurrences, most first.
+    pub async fn search(&self, query: &str) -> Result<Vec<SearchHit>> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut hits = Vec::new();
+        for (session_id, turn_id, text) in self.transcripts().await? {
+            let lower = text.to_lowercase();
+            if !terms.iter().all(|term| lower.contains(term.as_str())) {
+                continue;
+            }
+            let occurrences: usize = terms
+                .iter()
+                .map(|term| lower.matches(term.as_str()).count())
+                .sum();
+            let position = lower.find(terms[0].as_str()).unwrap_or(0);
+            hits.push(SearchHit {
+                session_id,
+                turn_id,
+                snippet: snippet(&lower, &text, position),
+                score: occurrences as f32,
+            });
+        }
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(hits)
+    }
+
+    /// Semantic search over stored sessions using embeddings.
+    ///
+    /// Every stored turn is embedded on each call, so this suits modest
+    /// session stores; returns the `limit` most similar turns.
+    pub async fn search_semantic(
+        &self,
+        query: &str,
+        embedder: &dyn Embedder,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>> {
+        let transcripts = self.transcripts().await?;
+        if transcripts.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut texts = vec![query.to_string()];
+        texts.extend(transcripts.iter().map(|(_, _, text)| text.clone()));
+        let embeddings = embedder.embed(&texts).await?;
+        if embeddings.len() != texts.len() {
+            return Err(AgentError::Generic {
+                message: format!(
+                    "Embedder returned {} embeddings for {} texts",
+                    embeddings.len(),
+                    texts.len()
+                ),
+            });
+        }
+
+        let mut hits: Vec<SearchHit> = transcripts
+            .into_iter()
+            .zip(&embeddings[1..])
+            .map(|((session_id, turn_id, text), embedding)| SearchHit {
+                session_id,
+                turn_id,
+                snippet: snippet(&text, &text, 0),
+                score: cosine_similarity(&embeddings[0], embedding),
+            })
+            .collect();
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(limit);
+        Ok(hits)
+    }
+
+    /// Searchable text of every stored turn, as (session id, turn id, text).
+    async fn transcripts(&self) -> Result<Vec<(String, u64, String)>> {
+        let mut transcripts = Vec::new();
+        for session in self.list_sessions().await? {
+            let snapshot = match self.load_snapshot(&session.id).await {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    warn!("Skipping unreadable session {}: {}", session.id, e);
+                    continue;
+                }
+            };
+            for record in &snapshot.turn_records {
+                let text = turn_text(record);
+                if !text.is_empty() {
+                    transcripts.push((snapshot.session_id.clone(), record.turn_id, text));
+                }
+            }
+        }
+        Ok(transcripts)
+    }
+
+    /// Pause every agent at its next safe point and persist its state.
+    ///
+    /// Agents are paused between events, so no tool call is cut off midway.
+    /// An agent that does not reach a safe point within `timeout` is saved
+    /// as-is and marked as not paused in the manifest.
+    pub async fn freeze(
+        &self,
+        agents: &[(String, AgentHandle)],
+        timeout: Duration,
+    ) -> Result<ResumeManifest> {
+        let mut manifest = ResumeManifest {
+            created_at: chrono::Utc::now(),
+            sessions: Vec::with_capacity(agents.len()),
+        };
+
+        for (session_id, handle) in agents {
+            let paused = if handle.is_finished() {
+                false
+            } else {
+                match tokio::time::timeout(timeout, handle.controller().pause()).await {
+                    Ok(Ok(())) => true,
+                    Ok(Err(e)) => {
+                        warn!("Failed to pause session {}: {}", session_id, e);
+                        false
+                    }
+                    Err(_) => {
+                        warn!("Session {} did not reach a safe point in time", session_id);
+                        false
+                    }
+                }
+            };
+
+            let snapshot = SessionSnapshot::capture(session_id, handle, paused).await;
+            let path = self.save_snapshot(&snapshot).await?;
+            manifest.sessions.push(ManifestEntry {
+                session_id: session_id.clone(),
+                snapshot: path,
+                paused,
+                turn_count: snapshot.turn_count,
+                pending_inputs: snapshot.pending_inputs.len(),
+            });
+        }
+
+        Ok(manifest)
+    }
+
+    /// Wait for a shutdown signal (SIGTERM or Ctrl-C), then freeze all agents
+    /// and write the resume manifest to `manifest_path`.
+    pub async fn freeze_on_shutdown<P: AsRef<Path>>(
+        &self,
+        agents: &[(String, AgentHandle)],
+        manifest_path: P,
+        timeout: Duration,
+    ) -> Result<ResumeManifest> {
+        shutdown_signal().await?;
+        info!("Shutdown requested, freezing {} sessions", agents.len());
+
+        let manifest = self.freeze(agents, timeout).await?;
+        manifest.save(manifest_path).await?;
+        Ok(manifest)
+    }
+
+    fn snapshot_path(&self, session_id: &str) -> Result<PathBuf> {
+        let valid = !session_id.is_empty()
+            && !session_id.starts_with('.')
+            && session_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(AgentError::Config {
+                message: format!("Invalid session id \"{}\"", session_id),
+            });
+        }
+        Ok(self.directory.join(format!("{}.json", session_id)))
     }
 }
 
@@ -56,6 +314,29 @@ impl Default for SessionManager {
     }
 }
 
+/// A stored turn matching a search.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    /// Session containing the turn
+    pub session_id: String,
+
+    /// Matching turn
+    pub turn_id: u64,
+
+    /// Excerpt of the turn around the match
+    pub snippet: String,
+
+    /// Relevance: occurrences for full-text search, cosine similarity for
+    /// semantic search
+    pub score: f32,
+}
+
+/// Computes embeddings for semantic session search.
+pub trait Embedder: Send + Sync {
+    /// Embed each text, returning one vector per input in order.
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>>>;
+}
+
 /// Information about a saved session.
 #[derive(Debug, Clone)]
 pub struct SessionInfo {
@@ -77,3 +358,196 @@ pub struct SessionInfo {
     /// Session metadata
     pub metadata: std::collections::HashMap<String, String>,
 }
+
+/// Persisted state of an in-flight conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    /// Session identifier
+    pub session_id: String,
+
+    /// Turns started when the snapshot was taken
+    pub turn_count: u64,
+
+    /// Whether the agent was paused at a safe point
+    pub paused: bool,
+
+    /// Records of completed turns
+    pub turn_records: Vec<TurnRecord>,
+
+    /// Latest plan recorded for the session
+    pub plan: Option<Vec<TodoItem>>,
+
+    /// Inputs queued but not yet started
+    pub pending_inputs: Vec<PendingInput>,
+
+    /// Notes kept by the scratchpad tool
+    #[serde(default)]
+    pub scratchpad: BTreeMap<String, String>,
+
+    /// When the snapshot was taken
+    pub saved_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl SessionSnapshot {
+    /// Capture the state of a running agent.
+    pub async fn capture(session_id: &str, handle: &AgentHandle, paused: bool) -> Self {
+        let turn_records = handle.turn_records().await;
+        let plan = turn_records
+            .iter()
+            .rev()
+            .flat_map(|record| record.events.iter().rev())
+            .find_map(|message| match &message.data {
+                OutputData::TodoUpdate { todos } => Some(todos.clone()),
+                _ => None,
+            });
+
+        Self {
+            session_id: session_id.to_string(),
+            turn_count: handle.controller().turn_count(),
+            paused,
+            turn_records,
+            plan,
+            pending_inputs: handle.pending_inputs().await,
+            scratchpad: handle.tool_registry().scratchpad().entries(),
+            saved_at: chrono::Utc::now(),
+        }
+    }
+
+    /// Inputs to replay on the restored agent, in order.
+    pub fn pending_messages(&self) -> Vec<InputMessage> {
+        self.pending_inputs
+            .iter()
+            .map(|pending| pending.message.clone())
+            .collect()
+    }
+
+    /// Restore the scratchpad notes into a restored agent.
+    pub fn restore_scratchpad(&self, handle: &AgentHandle) {
+        handle
+            .tool_registry()
+            .scratchpad()
+            .restore(self.scratchpad.clone());
+    }
+}
+
+/// Machine-readable list of frozen sessions for a redeployed service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeManifest {
+    /// When the sessions were frozen
+    pub created_at: chrono::DateTime<chrono::Utc>,
+
+    /// Frozen sessions
+    pub sessions: Vec<ManifestEntry>,
+}
+
+impl ResumeManifest {
+    /// Write the manifest as JSON.
+    pub async fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        tokio::fs::write(path, serde_json::to_vec_pretty(self)?).await?;
+        Ok(())
+    }
+
+    /// Read a manifest written by [`save`](Self::save).
+    pub async fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_json::from_slice(&tokio::fs::read(path).await?)?)
+    }
+}
+
+/// A frozen session listed in a resume manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Session identifier
+    pub session_id: String,
+
+    /// Path of the session snapshot
+    pub snapshot: PathBuf,
+
+    /// Whether the agent was paused at a safe point
+    pub paused: bool,
+
+    /// Turns started when the session was frozen
+    pub turn_count: u64,
+
+    /// Inputs queued but not yet started
+    pub pending_inputs: usize,
+}
+
+/// Input and responses of a turn, one per line.
+fn turn_text(record: &TurnRecord) -> String {
+    let responses = record
+        .events
+        .iter()
+        .filter_map(|message| match &message.data {
+            OutputData::Primary { content } => Some(content.as_str()),
+            _ => None,
+        });
+    let mut parts: Vec<&str> = record
+        .input
+        .as_deref()
+        .into_iter()
+        .chain(responses)
+        .collect();
+    if let Some(summary) = record.summary.as_deref()
+        && !parts.contains(&summary)
+    {
+        parts.push(summary);
+    }
+    parts.join("\n")
+}
+
+/// Excerpt of `text` around byte `position` of `lower`, its lowercase form.
+///
+/// Falls back to the start of `text` when lowercasing changed byte offsets.
+fn snippet(lower: &str, text: &str, position: usize) -> String {
+    let position = if lower.len() == text.len() {
+        position
+    } else {
+        0
+    };
+    let mut start = position.saturating_sub(SNIPPET_CONTEXT_CHARS);
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = (position + SNIPPET_CONTEXT_CHARS).min(text.len());
+    while !text.is_char_boundary(end) {
+        end += 1;
+    }
+
+    let mut snippet = text[start..end].replace('\n', " ");
+    if start > 0 {
+        snippet.insert_str(0, "...");
+    }
+    if end < text.len() {
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 {
+        0.0
+    } else {
+        dot / denominator
+    }
+}
+
+/// Wait for SIGTERM or Ctrl-C.
+async fn shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            _ = terminate.recv() => Ok(()),
+            result = tokio::signal::ctrl_c() => Ok(result?),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        Ok(tokio::signal::ctrl_c().await?)
+    }
+}
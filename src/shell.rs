@@ -0,0 +1,164 @@
+//! Built-in shell tool running commands on the host under resource limits.
+//!
+//! Codex spawns the commands it runs itself and offers no hook to cap them,
+//! contain them, or clean up after them, so a bash tool with
+//! [`ResourceLimits`] or a [`KillPolicy`], or any bash tool under a container
+//! [`SandboxBackend`](crate::SandboxBackend), is served as a host tool
+//! instead. Each command runs with
+//! `sh -c` through the agent's sandbox backend under the limits, in a
+//! process group of its own that is terminated following the kill policy on
+//! timeout or cancellation. The processes terminated are listed in the
+//! result.
+//!
+//! Under the path globs of a file read or file write tool, the bash tool
+//! runs on the host as well, and commands naming paths denied to those tools
+//! are rejected before they run. The paths a command names as arguments and
+//! redirections are checked, and commands with `$` or backtick expansions
+//! rejected; paths programs compute themselves, like a script opening a
+//! file, are not seen.
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::error::{AgentError, Result};
+use crate::limits::{KillPolicy, ResourceLimits};
+use crate::paths::{Access, command_paths};
+use crate::tools::{CustomToolHandler, ToolExecutionContext, ToolExecutionResult};
+
+#[derive(Deserialize)]
+struct ShellParams {
+    command: String,
+}
+
+/// Tool handler running shell commands through the sandbox backend.
+///
+/// `execute` blocks on the command, so call it through
+/// [`crate::blocking::run_tool`].
+#[derive(Debug, Clone)]
+pub struct ShellTool {
+    limits: ResourceLimits,
+    kill_policy: KillPolicy,
+    timeout: Option<Duration>,
+}
+
+impl ShellTool {
+    /// Create a handler running commands under `limits`, terminated
+    /// following `kill_policy` once they outlive `timeout`.
+    pub fn new(limits: ResourceLimits, kill_policy: KillPolicy, timeout: Option<Duration>) -> Self {
+        Self {
+            limits,
+            kill_policy,
+            timeout,
+        }
+    }
+}
+
+impl CustomToolHandler for ShellTool {
+    fn execute(
+        &self,
+        parameters: Value,
+        context: &ToolExecutionContext,
+    ) -> Result<ToolExecutionResult> {
+        let params: ShellParams = serde_json::from_value(parameters)?;
+        let runtime = tokio::runtime::Handle::try_current().map_err(|_| AgentError::Tool {
+            message: "The bash tool must run inside a Tokio runtime".to_string(),
+        })?;
+
+        if context.agent_config.checks_tool_paths()
+            && let Err(reason) = check_paths(&params.command, context)
+        {
+            return Ok(ToolExecutionResult::error(reason));
+        }
+
+        let command = context.agent_config.sandbox_backend().command(
+            &params.command,
+            &context.working_directory,
+            &context.environment,
+            &self.limits,
+        );
+        let output = runtime.block_on(command.run(
+            self.timeout,
+            &self.kill_policy,
+            &context.cancellation,
+        ))?;
+
+        let mut combined = output.stdout;
+        combined.push_str(&output.stderr);
+        let mut result = match output.status {
+            Some(status) if status.success() => ToolExecutionResult::success(combined),
+            Some(status) => {
+                // Commands over a CPU or file size limit are killed by a signal
+                #[cfg(unix)]
+                {
+                    use std::os::unix::process::ExitStatusExt;
+                    if let Some(signal) = status.signal() {
+                        combined.push_str(&format!("\n[terminated by signal {}]", signal));
+                    }
+                }
+                ToolExecutionResult::failure(combined, status.code().unwrap_or(-1))
+            }
+            None if output.timed_out => match self.timeout {
+                Some(timeout) => ToolExecutionResult::timed_out("bash", timeout),
+                None => ToolExecutionResult::error("Command timed out"),
+            },
+            None => ToolExecutionResult::error("Command cancelled"),
+        };
+        if !output.reaped.is_empty() {
+            let reaped: Vec<String> = output
+                .reaped
+                .iter()
+                .map(|process| format!("{} ({})", process.command, process.pid))
+                .collect();
+            result
+                .output
+                .push_str(&format!("\n[terminated processes: {}]", reaped.join(", ")));
+            result = result.with_metadata("reaped", &output.reaped)?;
+        }
+        Ok(result)
+    }
+
+    fn parameter_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "command": {
+                    "type": "string",
+                    "description": "Shell command to run with `sh -c`"
+                }
+            },
+            "required": ["command"]
+        })
+    }
+
+    fn description(&self) -> String {
+        "Run a shell command in the working directory".to_string()
+    }
+}
+
+/// Check the paths a command names against the path globs of the file read
+/// and file write tools, following symlinks, and the paths it writes against
+/// the sandbox's writable roots, which do not bound commands run on the host.
+fn check_paths(command: &str, context: &ToolExecutionContext) -> std::result::Result<(), String> {
+    let config = &context.agent_config;
+    for (path, access) in command_paths(command, &context.working_directory)? {
+        let tool_name = match access {
+            Access::Read => "file_read",
+            Access::Write => "file_write",
+        };
+        let resolved = config
+            .check_tool_path(tool_name, &path)
+            .map_err(|e| match e {
+                AgentError::Tool { message } => message,
+                e => e.to_string(),
+            })?;
+        if access == Access::Write && !config.sandbox_allows_write(&resolved) {
+            return Err(format!(
+                "Path {} is outside the sandbox's writable roots",
+                resolved.display()
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,302 @@
+//! Stable text transcripts of agent output for snapshot tests.
+//!
+//! A [`Transcript`] renders a conversation's output messages one event per
+//! line, grouped by turn, leaving out what differs between equal runs:
+//! timestamps, sequence numbers, ids, retry delays, and periodic heartbeats
+//! and summaries. Streamed deltas are collapsed into the message they form,
+//! so the rendering does not depend on how the model chunked its output.
+//! The result suits `insta`-style snapshot assertions, turning changes of
+//! agent behavior into reviewable diffs.
+//!
+//! ```
+//! use agent_core::snapshot::Transcript;
+//! use agent_core::{OutputData, OutputMessage};
+//!
+//! let messages = vec![
+//!     OutputMessage::new(7, OutputData::Start),
+//!     OutputMessage::new(7, OutputData::primary_delta("Hello, ")),
+//!     OutputMessage::new(7, OutputData::primary_delta("world")),
+//!     OutputMessage::new(7, OutputData::Completed),
+//! ];
+//! let transcript = Transcript::new().render(&messages);
+//! assert_eq!(
+//!     transcript,
+//!     "=== Turn 1 ===\n[Start]\n[Assistant] Hello, world\n[Completed]\n"
+//! );
+//! ```
+
+use regex::Regex;
+
+use crate::approval::ApprovalRequest;
+use crate::messages::{OutputData, OutputMessage};
+
+/// UUIDs, like submission and approval ids.
+const UUID_PATTERN: &str = r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b";
+
+/// RFC 3339 timestamps.
+const TIMESTAMP_PATTERN: &str =
+    r"\b\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:\.\d+)?(?:Z|[+-]\d{2}:?\d{2})?\b";
+
+/// Renders output messages as a stable transcript.
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    reasoning: bool,
+    redactions: Vec<(String, String)>,
+}
+
+impl Default for Transcript {
+    fn default() -> Self {
+        Self {
+            reasoning: true,
+            redactions: Vec::new(),
+        }
+    }
+}
+
+impl Transcript {
+    /// Create a renderer including reasoning.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Include or leave out reasoning, which varies more between runs than
+    /// answers do.
+    pub fn reasoning(mut self, include: bool) -> Self {
+        self.reasoning = include;
+        self
+    }
+
+    /// Replace every occurrence of `value`, e.g. a temporary working
+    /// directory, with `replacement`.
+    pub fn redact<S1, S2>(mut self, value: S1, replacement: S2) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        self.redactions.push((value.into(), replacement.into()));
+        self
+    }
+
+    /// Render the messages, in the order they were received.
+    pub fn render(&self, messages: &[OutputMessage]) -> String {
+        let mut lines = Lines::default();
+        let mut turns: Vec<u64> = Vec::new();
+        for message in messages {
+            if turns.last() != Some(&message.turn_id) {
+                lines.flush();
+                let number = match turns.iter().position(|turn| *turn == message.turn_id) {
+                    Some(index) => index + 1,
+                    None => {
+                        turns.push(message.turn_id);
+                        turns.len()
+                    }
+                };
+                lines.push(format!("=== Turn {} ===", number));
+            }
+            self.render_data(&message.data, &mut lines);
+        }
+        lines.flush();
+
+        let mut text = lines.text;
+        for (value, replacement) in &self.redactions {
+            if !value.is_empty() {
+                text = text.replace(value, replacement);
+            }
+        }
+        for (pattern, replacement) in [(UUID_PATTERN, "[uuid]"), (TIMESTAMP_PATTERN, "[timestamp]")]
+        {
+            if let Ok(pattern) = Regex::new(pattern) {
+                text = pattern.replace_all(&text, replacement).into_owned();
+            }
+        }
+        text
+    }
+
+    fn render_data(&self, data: &OutputData, lines: &mut Lines) {
+        match data {
+            OutputData::Start => lines.push("[Start]"),
+            OutputData::Primary { content } => lines.message(Stream::Primary, content),
+            OutputData::PrimaryDelta { content } => lines.delta(Stream::Primary, content),
+            OutputData::Reasoning { content } if self.reasoning => {
+                lines.message(Stream::Reasoning, content)
+            }
+            OutputData::ReasoningDelta { content } if self.reasoning => {
+                lines.delta(Stream::Reasoning, content)
+            }
+            OutputData::Reasoning { .. } | OutputData::ReasoningDelta { .. } => {}
+            OutputData::ToolStart {
+                tool_name,
+                arguments,
+            } => lines.push(format!("[Tool start] {} {}", tool_name, arguments)),
+            OutputData::ToolComplete { tool_name, result } => {
+                lines.push(format!("[Tool complete] {} {}", tool_name, result))
+            }
+            OutputData::ToolOutput { tool_name, output } => {
+                lines.delta(Stream::Tool(tool_name.clone()), output)
+            }
+            OutputData::ToolOutputTruncated {
+                tool_name,
+                omitted_bytes,
+                ..
+            } => lines.push(format!(
+                "[{}] {} bytes of output truncated",
+                tool_name, omitted_bytes
+            )),
+            OutputData::ApprovalRequired { request, .. } => match request {
+                ApprovalRequest::Exec { command, .. } => {
+                    lines.push(format!("[Approval] run {}", command.join(" ")))
+                }
+                ApprovalRequest::Patch { diff, .. } => {
+                    lines.push(format!("[Approval] apply patch\n{}", diff))
+                }
+            },
+            OutputData::WebSearchResults { queries, results } => {
+                lines.push(format!("[Web search] {}", queries.join(", ")));
+                for result in results {
+                    lines.push(format!("- {} <{}>", result.title, result.url));
+                }
+            }
+            OutputData::PatchPreview { diff } => lines.push(format!("[Patch preview]\n{}", diff)),
+            OutputData::FileChange { path, kind, diff } => {
+                lines.push(format!("[File] {:?} {}\n{}", kind, path.display(), diff))
+            }
+            OutputData::Image {
+                data,
+                mime_type,
+                caption,
+            } => lines.push(format!(
+                "[Image] {}, {} bytes of base64{}",
+                mime_type,
+                data.len(),
+                caption
+                    .as_ref()
+                    .map(|caption| format!(": {}", caption))
+                    .unwrap_or_default()
+            )),
+            OutputData::TodoUpdate { todos } => {
+                lines.push("[Plan]");
+                for todo in todos {
+                    lines.push(format!("- [{:?}] {}", todo.status, todo.content));
+                }
+            }
+            OutputData::WorkspaceDelta {
+                created,
+                modified,
+                deleted,
+            } => {
+                lines.push("[Workspace]");
+                for (change, paths) in [
+                    ("created", created),
+                    ("modified", modified),
+                    ("deleted", deleted),
+                ] {
+                    let mut paths: Vec<_> = paths.iter().map(|path| path.display()).collect();
+                    paths.sort_by_key(ToString::to_string);
+                    for path in paths {
+                        lines.push(format!("- {} {}", change, path));
+                    }
+                }
+            }
+            OutputData::Warning { message } => lines.push(format!("[Warning] {}", message)),
+            // The delay is jittered
+            OutputData::Retrying {
+                attempt,
+                max_attempts,
+                reason,
+                ..
+            } => lines.push(format!("[Retry {}/{}] {}", attempt, max_attempts, reason)),
+            // Emitted on timers rather than by the conversation
+            OutputData::Heartbeat | OutputData::OpsSummary { .. } => {}
+            OutputData::Suggestions { prompts } => {
+                lines.push("[Suggestions]");
+                for prompt in prompts {
+                    lines.push(format!("- {}", prompt));
+                }
+            }
+            OutputData::CheckIn { check_in } => lines.push(format!(
+                "[Check-in {}] {}/{} steps, {} tool calls",
+                check_in.number,
+                check_in.completed_steps,
+                check_in.total_steps,
+                check_in.tool_calls
+            )),
+            OutputData::Completed => lines.push("[Completed]"),
+            OutputData::Error { error } => lines.push(format!("[Error] {:?}", error)),
+        }
+    }
+}
+
+/// Render output messages with the default settings.
+pub fn transcript(messages: &[OutputMessage]) -> String {
+    Transcript::new().render(messages)
+}
+
+/// Stream of deltas collapsed into one line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Stream {
+    Primary,
+    Reasoning,
+    Tool(String),
+}
+
+impl Stream {
+    fn label(&self) -> &str {
+        match self {
+            Stream::Primary => "Assistant",
+            Stream::Reasoning => "Reasoning",
+            Stream::Tool(tool_name) => tool_name,
+        }
+    }
+}
+
+/// Transcript text with the deltas of the current stream pending.
+#[derive(Debug, Default)]
+struct Lines {
+    text: String,
+    pending: Option<(Stream, String)>,
+}
+
+impl Lines {
+    fn push<S: AsRef<str>>(&mut self, line: S) {
+        self.flush();
+        self.write(line.as_ref());
+    }
+
+    fn delta(&mut self, stream: Stream, content: &str) {
+        match &mut self.pending {
+            Some((pending, text)) if *pending == stream => text.push_str(content),
+            _ => {
+                self.flush();
+                self.pending = Some((stream, content.to_string()));
+            }
+        }
+    }
+
+    /// A complete message, replacing the deltas it was streamed as.
+    fn message(&mut self, stream: Stream, content: &str) {
+        if let Some((pending, text)) = &self.pending
+            && *pending == stream
+            && text == content
+        {
+            self.pending = None;
+        }
+        self.push(format!("[{}] {}", stream.label(), content));
+    }
+
+    fn flush(&mut self) {
+        if let Some((stream, text)) = self.pending.take() {
+            self.write(&format!("[{}] {}", stream.label(), text));
+        }
+    }
+
+    fn write(&mut self, line: &str) {
+        // Continuation lines are indented so each entry stays recognizable
+        for (i, part) in line.trim_end().lines().enumerate() {
+            if i > 0 {
+                self.text.push_str("  ");
+            }
+            self.text.push_str(part);
+            self.text.push('\n');
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Tracing spans of turns, model requests, and tool executions.
+//!
+//! Each turn gets an `agent.turn` span with child spans for the model
+//! requests Codex makes and the tools it runs, carrying token counts and
+//! durations as fields. Spans go to whatever `tracing` subscriber the host
+//! installs; with the `otel` feature, `otel::OtlpExporter` exports them over
+//! OTLP. With the `metrics` feature, the same measurements are reported as
+//! metrics.
+//!
+//! Codex reports a request's usage once the request is done, so a request
+//! span runs from the previous request's usage report, or the turn's
+//! submission, to its own.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use codex_protocol::protocol::EventMsg;
+use tracing::{Span, debug, field, info_span};
+
+use crate::ops::TokenUsage;
+use crate::protocol;
+
+/// How a turn ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TurnStatus {
+    Completed,
+    Aborted,
+    Failed,
+}
+
+impl TurnStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            TurnStatus::Completed => "completed",
+            TurnStatus::Aborted => "aborted",
+            TurnStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Spans of a running turn.
+#[derive(Debug)]
+pub(crate) struct TurnSpans {
+    turn: Span,
+    started: Instant,
+    model: String,
+    /// Request in flight and when it started
+    request: Option<(Span, Instant)>,
+    /// Tools running, their names, and when they started, by call id
+    tools: HashMap<String, (Span, String, Instant)>,
+    usage: TokenUsage,
+    requests: u32,
+    tool_calls: u32,
+    errors: u32,
+}
+
+impl TurnSpans {
+    /// Open the spans of a turn about to be submitted.
+    pub(crate) fn start(turn_id: u64, model: &str) -> Self {
+        let turn = info_span!(
+            "agent.turn",
+            turn_id,
+            model,
+            status = field::Empty,
+            input_tokens = field::Empty,
+            output_tokens = field::Empty,
+            total_tokens = field::Empty,
+            model_requests = field::Empty,
+            tool_calls = field::Empty,
+            errors = field::Empty,
+            duration_ms = field::Empty,
+            otel.status_code = field::Empty,
+        );
+        let mut spans = Self {
+            turn,
+            started: Instant::now(),
+            model: model.to_string(),
+            request: None,
+            tools: HashMap::new(),
+            usage: TokenUsage::default(),
+            requests: 0,
+            tool_calls: 0,
+            errors: 0,
+        };
+        spans.open_request();
+        spans
+    }
+
+    /// Update the spans with an event of the turn.
+    pub(crate) fn record(&mut self, msg: &EventMsg) {
+        if let Some(usage) = protocol::token_usage(msg) {
+            self.usage += usage;
+            #[cfg(feature = "metrics")]
+            crate::metrics::tokens(&self.model, usage);
+            if let Some((span, started)) = self.request.take() {
+                span.record("input_tokens", usage.input_tokens);
+                span.record("output_tokens", usage.output_tokens);
+                span.record("total_tokens", usage.total_tokens);
+                span.record("duration_ms", elapsed_ms(started));
+            }
+            return;
+        }
+        if protocol::ends_turn(msg) {
+            return;
+        }
+        if self.request.is_none() {
+            self.open_request();
+        }
+
+        if protocol::model_error(msg).is_some() {
+            self.errors += 1;
+            if let Some((span, _)) = &self.request {
+                span.record("otel.status_code", "error");
+            }
+        }
+        if let Some((call_id, tool_name)) = protocol::tool_begin(msg) {
+            self.tool_calls += 1;
+            let span = info_span!(
+                parent: &self.turn,
+                "agent.tool",
+                tool_name,
+                call_id,
+                success = field::Empty,
+                duration_ms = field::Empty,
+                otel.status_code = field::Empty,
+            );
+            self.tools.insert(
+                call_id.to_string(),
+                (span, tool_name.to_string(), Instant::now()),
+            );
+        }
+        if let Some((call_id, success)) = protocol::tool_end(msg)
+            && let Some((span, tool_name, started)) = self.tools.remove(call_id)
+        {
+            close_tool(span, &tool_name, started, success);
+        }
+    }
+
+    /// Close the spans, recording the turn's totals.
+    pub(crate) fn finish(mut self, status: TurnStatus) {
+        for (_, (span, tool_name, started)) in self.tools.drain() {
+            close_tool(span, &tool_name, started, false);
+        }
+        if let Some((span, started)) = self.request.take() {
+            span.record("duration_ms", elapsed_ms(started));
+        }
+
+        let turn = &self.turn;
+        turn.record("status", status.as_str());
+        turn.record("input_tokens", self.usage.input_tokens);
+        turn.record("output_tokens", self.usage.output_tokens);
+        turn.record("total_tokens", self.usage.total_tokens);
+        turn.record("model_requests", self.requests);
+        turn.record("tool_calls", self.tool_calls);
+        turn.record("errors", self.errors);
+        turn.record("duration_ms", elapsed_ms(self.started));
+        if status != TurnStatus::Completed || self.errors > 0 {
+            turn.record("otel.status_code", "error");
+        }
+        #[cfg(feature = "metrics")]
+        crate::metrics::turn(&self.model, status.as_str(), self.started.elapsed());
+    }
+
+    fn open_request(&mut self) {
+        self.requests += 1;
+        #[cfg(feature = "metrics")]
+        crate::metrics::model_request(&self.model);
+        let span = info_span!(
+            parent: &self.turn,
+            "agent.model_request",
+            model = self.model.as_str(),
+            request = self.requests,
+            input_tokens = field::Empty,
+            output_tokens = field::Empty,
+            total_tokens = field::Empty,
+            duration_ms = field::Empty,
+            otel.status_code = field::Empty,
+        );
+        self.request = Some((span, Instant::now()));
+    }
+}
+
+fn close_tool(span: Span, tool_name: &str, started: Instant, success: bool) {
+    debug!(
+        "Tool {} {} after {:?}",
+        tool_name,
+        if success { "succeeded" } else { "failed" },
+        started.elapsed()
+    );
+    #[cfg(feature = "metrics")]
+    crate::metrics::tool(tool_name, success, started.elapsed());
+    span.record("success", success);
+    span.record("duration_ms", elapsed_ms(started));
+    if !success {
+        span.record("otel.status_code", "error");
+    }
+}
+
+fn elapsed_ms(started: Instant) -> u64 {
+    u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX)
+}
@@ -0,0 +1,62 @@
+//! Structured output support: prompting for schema-conforming JSON and
+//! extracting it from model responses.
+
+use crate::markdown::fenced_blocks;
+
+/// Build a prompt asking the model to answer with JSON matching the schema.
+pub(crate) fn structured_prompt(message: &str, schema: &serde_json::Value) -> String {
+    format!(
+        "{}\n\nRespond with a single JSON value that conforms to this JSON Schema. \
+         Output only the JSON, without explanations or code fences.\n\n{}",
+        message, schema
+    )
+}
+
+/// Build a prompt asking the model to fix a response that failed to parse.
+pub(crate) fn retry_prompt(error: &serde_json::Error, schema: &serde_json::Value) -> String {
+    format!(
+        "Your previous response was not valid JSON for the required schema: {}. \
+         Respond again with only a single JSON value conforming to this JSON Schema.\n\n{}",
+        error, schema
+    )
+}
+
+/// Check a response against a JSON Schema.
+///
+/// Returns the reasons the response does not conform, empty if it does.
+pub(crate) fn schema_errors(response: &str, schema: &serde_json::Value) -> Vec<String> {
+    let value: serde_json::Value = match serde_json::from_str(extract_json(response)) {
+        Ok(value) => value,
+        Err(e) => return vec![format!("Response is not valid JSON: {}", e)],
+    };
+    match jsonschema::validator_for(schema) {
+        Ok(validator) => validator
+            .iter_errors(&value)
+            .map(|error| format!("{} at {}", error, error.instance_path))
+            .collect(),
+        Err(e) => vec![format!("Invalid response schema: {}", e)],
+    }
+}
+
+/// Extract the JSON payload from a model response.
+///
+/// Handles responses wrapped in a fenced code block, containing a `json`
+/// fenced block amid prose, or surrounded by prose, in which case the
+/// outermost object or array is taken.
+pub fn extract_json(response: &str) -> &str {
+    let trimmed = response.trim();
+
+    if let Some(block) = fenced_blocks(trimmed)
+        .into_iter()
+        .find(|block| block.span.start == 0 || block.language.eq_ignore_ascii_case("json"))
+    {
+        return block.code.trim();
+    }
+
+    let start = trimmed.find(['{', '[']);
+    let end = trimmed.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => &trimmed[start..=end],
+        _ => trimmed,
+    }
+}
@@ -0,0 +1,81 @@
+//! Nested agents exposed as tools, so the primary model can delegate bounded
+//! subtasks with an isolated context.
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::agent::Agent;
+use crate::config::AgentConfig;
+use crate::error::{AgentError, Result};
+use crate::tools::{CustomToolHandler, ToolExecutionContext, ToolExecutionResult};
+
+/// Tool handler running each call on a fresh agent.
+///
+/// The nested agent starts from an empty conversation every call and only
+/// sees the task it is given. `execute` blocks on the agent, so call it
+/// through [`crate::blocking::run_tool`].
+#[derive(Debug, Clone)]
+pub struct SubAgentTool {
+    description: String,
+    config: AgentConfig,
+}
+
+#[derive(Deserialize)]
+struct SubAgentParams {
+    task: String,
+}
+
+impl SubAgentTool {
+    /// Create a handler running `config` for every call.
+    pub fn new<S: Into<String>>(description: S, config: AgentConfig) -> Self {
+        Self {
+            description: description.into(),
+            config,
+        }
+    }
+
+    /// Get the nested agent's configuration.
+    pub fn config(&self) -> &AgentConfig {
+        &self.config
+    }
+
+    /// Run a task on a fresh nested agent.
+    pub async fn run(&self, task: String) -> Result<String> {
+        Agent::new(self.config.clone())?.query(task).await
+    }
+}
+
+impl CustomToolHandler for SubAgentTool {
+    fn execute(
+        &self,
+        parameters: Value,
+        _context: &ToolExecutionContext,
+    ) -> Result<ToolExecutionResult> {
+        let params: SubAgentParams = serde_json::from_value(parameters)?;
+        let runtime = tokio::runtime::Handle::try_current().map_err(|_| AgentError::Tool {
+            message: "Sub-agent tools must run inside a Tokio runtime".to_string(),
+        })?;
+
+        match runtime.block_on(self.run(params.task)) {
+            Ok(response) => Ok(ToolExecutionResult::success(response)),
+            Err(e) => Ok(ToolExecutionResult::error(e.to_string())),
+        }
+    }
+
+    fn parameter_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "task": {
+                    "type": "string",
+                    "description": "Self-contained instructions; the sub-agent does not see this conversation"
+                }
+            },
+            "required": ["task"]
+        })
+    }
+
+    fn description(&self) -> String {
+        self.description.clone()
+    }
+}
@@ -0,0 +1,152 @@
+//! Follow-up prompt suggestions generated after each completed turn.
+//!
+//! Suggestions are generated in the background once a turn ends, and
+//! emitted as a separate `OutputData::Suggestions` when ready.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use codex_protocol::protocol::SandboxPolicy;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::Agent;
+use crate::backend::LlmBackend;
+use crate::config::AgentConfig;
+use crate::error::{AgentError, Result};
+
+/// Maximum characters of the user input and of the response included in the
+/// prompt, keeping the cost of each suggestion call bounded.
+const DEFAULT_MAX_CONTEXT_CHARS: usize = 4000;
+
+/// Settings for follow-up suggestions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuggestionsConfig {
+    /// Model used to generate suggestions, typically a small one
+    pub model: String,
+
+    /// Number of suggestions to ask for (2 or 3)
+    pub count: usize,
+
+    /// Characters of the input and response given to the model
+    pub max_context_chars: usize,
+
+    /// Give up on suggestions after this long
+    pub timeout: Duration,
+}
+
+impl SuggestionsConfig {
+    /// Generate three suggestions with `model`.
+    pub fn new<S: Into<String>>(model: S) -> Self {
+        Self {
+            model: model.into(),
+            count: 3,
+            max_context_chars: DEFAULT_MAX_CONTEXT_CHARS,
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Set the number of suggestions, clamped to 2 or 3.
+    pub fn count(mut self, count: usize) -> Self {
+        self.count = count.clamp(2, 3);
+        self
+    }
+
+    /// Set how many characters of the input and response the model sees.
+    pub fn max_context_chars(mut self, chars: usize) -> Self {
+        self.max_context_chars = chars;
+        self
+    }
+
+    /// Set how long to wait for suggestions.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct Suggestions {
+    prompts: Vec<String>,
+}
+
+/// Ask a single-turn, tool-less agent for follow-up prompts, on the model
+/// of `suggestions` and otherwise the agent's provider and backend.
+///
+/// Boxed and owning its arguments, as it is spawned from an execution loop
+/// and starts another one.
+pub(crate) fn generate_suggestions(
+    config: AgentConfig,
+    backend: Option<Arc<dyn LlmBackend>>,
+    suggestions: SuggestionsConfig,
+    input: String,
+    response: String,
+) -> BoxFuture<'static, Result<Vec<String>>> {
+    Box::pin(async move {
+        let config = config
+            .for_side_query()
+            .into_builder()
+            .model(suggestions.model.clone())
+            .sandbox_policy(SandboxPolicy::ReadOnly)
+            .max_turns(1)
+            .json_retries(0)
+            .build()?;
+
+        let mut agent = Agent::new(config)?;
+        if let Some(backend) = backend {
+            agent = agent.with_backend(backend);
+        }
+        let query = agent.query_json::<Suggestions, _>(
+            suggestions_prompt(&suggestions, &input, &response),
+            suggestions_schema(suggestions.count),
+        );
+        let mut prompts = tokio::time::timeout(suggestions.timeout, query)
+            .await
+            .map_err(|_| AgentError::Execution {
+                message: "Timed out generating suggestions".to_string(),
+            })??
+            .prompts;
+
+        prompts.retain(|prompt| !prompt.trim().is_empty());
+        prompts.truncate(suggestions.count);
+        Ok(prompts)
+    })
+}
+
+fn suggestions_schema(count: usize) -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "required": ["prompts"],
+        "properties": {
+            "prompts": {
+                "type": "array",
+                "minItems": count,
+                "maxItems": count,
+                "items": { "type": "string" }
+            }
+        }
+    })
+}
+
+fn suggestions_prompt(suggestions: &SuggestionsConfig, input: &str, response: &str) -> String {
+    format!(
+        "Suggest {} short follow-up prompts the user might send next in this \
+         conversation, written from the user's point of view. Do not run any tools.\n\n\
+         User:\n{}\n\nAssistant:\n{}",
+        suggestions.count,
+        truncate(input, suggestions.max_context_chars),
+        truncate(response, suggestions.max_context_chars)
+    )
+}
+
+/// Keep the end of long text, where the most relevant context usually is.
+fn truncate(text: &str, max_chars: usize) -> &str {
+    if text.len() <= max_chars {
+        return text;
+    }
+    let mut start = text.len() - max_chars;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
@@ -0,0 +1,142 @@
+//! Opt-in, anonymized usage reporting.
+//!
+//! Nothing is reported unless a [`TelemetrySink`] is configured with
+//! `AgentConfigBuilder::telemetry`. Events carry counts and categories only:
+//! never message content, file paths, command lines, or custom tool names.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::AgentConfig;
+use crate::error::OutputError;
+use crate::messages::OutputData;
+use crate::sandbox::SandboxBackend;
+
+/// An anonymized usage event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TelemetryEvent {
+    /// An execution started
+    ExecutionStarted,
+
+    /// A configured feature is in use for the execution
+    FeatureUsed { feature: String },
+
+    /// A turn finished
+    TurnCompleted,
+
+    /// An error of the given category was reported
+    Error { category: String },
+
+    /// A tool was invoked; custom and MCP tools are reported as `custom`
+    ToolUsed { tool: String },
+}
+
+/// Receiver of telemetry events, forwarding them to the host's pipeline.
+///
+/// `record` is called inline on the agent's execution task, so it should
+/// only buffer or enqueue the event and return quickly.
+pub trait TelemetrySink: Send + Sync {
+    /// Record a single event.
+    fn record(&self, event: &TelemetryEvent);
+}
+
+impl std::fmt::Debug for dyn TelemetrySink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TelemetrySink")
+    }
+}
+
+/// Events describing which features an execution uses.
+pub(crate) fn feature_events(config: &AgentConfig) -> Vec<TelemetryEvent> {
+    let features = [
+        ("verify", config.verify().is_some()),
+        ("retry_policy", config.retry_policy().is_some()),
+        ("auto_commit", config.auto_commit().is_some()),
+        ("git_checkpoints", config.git_checkpoints()),
+        ("workspace_summary", config.workspace_summary()),
+        ("model_provider", config.model_provider().is_some()),
+        ("provider_routes", !config.provider_routes().is_empty()),
+        ("show_raw_reasoning", config.show_raw_reasoning()),
+        ("coalesce_deltas", config.coalesce_deltas()),
+        ("debounce_deltas", config.debounce_deltas().is_some()),
+        ("disk_quota", config.disk_quota().is_some()),
+        ("rate_limits", !config.rate_limits().is_empty()),
+        ("response_cache", config.response_cache().is_some()),
+        ("recording", config.recording().is_some()),
+        ("tool_bridge", config.tool_bridge().is_some()),
+        (
+            "container_sandbox",
+            matches!(config.sandbox_backend(), SandboxBackend::Container(_)),
+        ),
+        ("heartbeat", config.heartbeat().is_some()),
+        ("ops_summary", config.ops_summary().is_some()),
+        ("lexicon_filter", config.lexicon_filter().is_some()),
+        ("event_converter", config.event_converter().is_some()),
+        ("tool_middleware", !config.tool_middleware().is_empty()),
+        ("mcp", !config.mcp_servers().is_empty()),
+    ];
+
+    let tools = config
+        .tools()
+        .iter()
+        .map(|tool| format!("tool:{}", builtin_tool_name(tool.name())));
+
+    std::iter::once(TelemetryEvent::ExecutionStarted)
+        .chain(
+            features
+                .into_iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(feature, _)| feature.to_string())
+                .chain(tools)
+                .map(|feature| TelemetryEvent::FeatureUsed { feature }),
+        )
+        .collect()
+}
+
+/// The anonymized event for an output message, if any.
+pub(crate) fn output_event(data: &OutputData) -> Option<TelemetryEvent> {
+    match data {
+        OutputData::ToolStart { tool_name, .. } => Some(TelemetryEvent::ToolUsed {
+            tool: builtin_tool_name(tool_name).to_string(),
+        }),
+        OutputData::Error { error } => Some(TelemetryEvent::Error {
+            category: error_category(error).to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// Keep names of built-in tools, collapsing everything else to `custom`.
+fn builtin_tool_name(name: &str) -> &str {
+    const BUILTIN: &[&str] = &[
+        "bash",
+        "exec_command",
+        "web_search",
+        "file_read",
+        "file_write",
+        "apply_patch",
+        "search",
+        "git",
+        "scratchpad",
+        "verify",
+    ];
+
+    if BUILTIN.contains(&name) {
+        name
+    } else {
+        "custom"
+    }
+}
+
+fn error_category(error: &OutputError) -> &'static str {
+    match error {
+        OutputError::ToolExecutionFailed { .. } => "tool_execution_failed",
+        OutputError::ModelRequestFailed { .. } => "model_request_failed",
+        OutputError::ConfigurationError { .. } => "configuration_error",
+        OutputError::SandboxViolation { .. } => "sandbox_violation",
+        OutputError::PermissionDenied { .. } => "permission_denied",
+        OutputError::ResourceLimitExceeded { .. } => "resource_limit_exceeded",
+        OutputError::ResponseSchemaMismatch { .. } => "response_schema_mismatch",
+        OutputError::General { .. } => "general",
+    }
+}
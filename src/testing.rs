@@ -0,0 +1,351 @@
+//! Deterministic test double of the model backend.
+//!
+//! [`MockBackend`] answers each turn with the next scripted sequence of
+//! output and records the input of every turn, so applications can test
+//! their agent integration without network access or a Codex install. The
+//! agent runs its regular execution loop on the scripted events, so hooks,
+//! processors, and output filters behave as they would against a model.
+//!
+//! ```
+//! use agent_core::testing::MockBackend;
+//! use agent_core::{Agent, AgentConfig, OutputData};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> agent_core::Result<()> {
+//! let backend = MockBackend::new()
+//!     .turn([
+//!         OutputData::Reasoning {
+//!             content: "Adding numbers".to_string(),
+//!         },
+//!         OutputData::Primary {
+//!             content: "4".to_string(),
+//!         },
+//!     ])
+//!     .respond("6");
+//!
+//! let config = AgentConfig::builder().model("gpt-5-mini").build()?;
+//! let mut agent = Agent::new(config)?.with_mock_backend(backend.clone());
+//!
+//! assert_eq!(agent.query("2 + 2?").await?, "4");
+//! assert_eq!(agent.query("3 + 3?").await?, "6");
+//! backend.assert_inputs(&["2 + 2?", "3 + 3?"]);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Scripts replay the output Codex reports with events of its own: primary
+//! and reasoning messages, their deltas, and errors. Turn boundaries are
+//! implied. A scripted [`OutputData::ToolStart`] is a call of the model to
+//! a host tool, like a custom tool or search: it runs on the agent's tools
+//! the way a call from Codex would, and its result is reported with
+//! [`OutputData::ToolComplete`]. A scripted `ToolStart` of `exec_command`
+//! with a `command` argument list is Codex running the command with its own
+//! shell, as a child of the agent's process. A scripted [`OutputData::ApprovalRequired`]
+//! for a command is Codex asking approval to run it. Other output is
+//! ignored. A turn beyond the script fails with a model error.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use codex_protocol::protocol::{AskForApproval, Event, EventMsg, Op, SandboxPolicy, Submission};
+use futures::future::BoxFuture;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use crate::backend::{ConversationBackend, LlmBackend};
+use crate::error::{OutputError, Result};
+use crate::messages::OutputData;
+use crate::protocol;
+use crate::tool_server::{SERVER_NAME, ToolServer};
+
+/// Backend replaying scripted turns.
+///
+/// Clones share the script and the recorded inputs: hand a clone to the
+/// agent and keep one to assert on.
+#[derive(Debug, Clone, Default)]
+pub struct MockBackend {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    /// Output of the turns not run yet
+    turns: VecDeque<Vec<OutputData>>,
+
+    /// Input text of every turn run
+    inputs: Vec<String>,
+
+    /// Approval and sandbox policy of every turn run
+    policies: Vec<(AskForApproval, SandboxPolicy)>,
+
+    /// Number of conversations started
+    conversations: usize,
+}
+
+impl MockBackend {
+    /// Create a backend with an empty script.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Script the output of the next turn.
+    pub fn turn<I>(self, outputs: I) -> Self
+    where
+        I: IntoIterator<Item = OutputData>,
+    {
+        self.lock().turns.push_back(outputs.into_iter().collect());
+        self
+    }
+
+    /// Script a turn answering with a single message.
+    pub fn respond<S: Into<String>>(self, response: S) -> Self {
+        self.turn([OutputData::Primary {
+            content: response.into(),
+        }])
+    }
+
+    /// Input text of every turn run so far, in order.
+    pub fn inputs(&self) -> Vec<String> {
+        self.lock().inputs.clone()
+    }
+
+    /// Approval and sandbox policy every turn run so far was submitted
+    /// under, in order.
+    pub fn turn_policies(&self) -> Vec<(AskForApproval, SandboxPolicy)> {
+        self.lock().policies.clone()
+    }
+
+    /// Number of conversations started on the backend so far.
+    pub fn conversations(&self) -> usize {
+        self.lock().conversations
+    }
+
+    /// Number of scripted turns not run yet.
+    pub fn remaining_turns(&self) -> usize {
+        self.lock().turns.len()
+    }
+
+    /// Assert that the turns run so far had exactly the given inputs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the inputs differ.
+    pub fn assert_inputs<S: AsRef<str>>(&self, expected: &[S]) {
+        let inputs = self.inputs();
+        let expected: Vec<&str> = expected.iter().map(AsRef::as_ref).collect();
+        assert_eq!(inputs, expected, "MockBackend received unexpected inputs");
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Record a turn's input and policies and take its script.
+    fn next_turn(
+        &self,
+        input: String,
+        policies: (AskForApproval, SandboxPolicy),
+    ) -> Option<Vec<OutputData>> {
+        let mut state = self.lock();
+        state.inputs.push(input);
+        state.policies.push(policies);
+        state.turns.pop_front()
+    }
+}
+
+impl LlmBackend for MockBackend {
+    fn start_conversation(
+        &self,
+        tools: ToolServer,
+    ) -> BoxFuture<'_, Result<Arc<dyn ConversationBackend>>> {
+        self.lock().conversations += 1;
+        let config = tools.config();
+        let policies = (*config.approval_policy(), config.sandbox_policy().clone());
+        let conversation: Arc<dyn ConversationBackend> = Arc::new(MockConversation {
+            backend: self.clone(),
+            tools,
+            policies: Mutex::new(policies),
+            events: Arc::new(EventQueue::default()),
+            running: Mutex::new(None),
+        });
+        Box::pin(async move { Ok(conversation) })
+    }
+}
+
+/// Conversation running each scripted turn as it is submitted.
+struct MockConversation {
+    backend: MockBackend,
+    tools: ToolServer,
+    /// Approval and sandbox policy of the following turns
+    policies: Mutex<(AskForApproval, SandboxPolicy)>,
+    events: Arc<EventQueue>,
+    /// Task producing the events of the last turn
+    running: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl MockConversation {
+    fn handle(&self, id: String, op: Op) {
+        let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut policies = self.policies.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(turn_policies) = protocol::turn_policies(&op) {
+            *policies = turn_policies;
+        }
+        if let Some(input) = protocol::input_text(&op) {
+            let outputs = self
+                .backend
+                .next_turn(input, policies.clone())
+                .unwrap_or_else(unscripted_turn);
+            *running = Some(tokio::spawn(run_turn(
+                self.tools.clone(),
+                self.events.clone(),
+                id,
+                outputs,
+            )));
+        } else if protocol::is_interrupt(&op) {
+            let turn_running = running.as_ref().is_some_and(|task| !task.is_finished());
+            if !turn_running && self.events.is_empty() {
+                return;
+            }
+            // The rest of the running turn is dropped
+            if let Some(task) = running.take() {
+                task.abort();
+            }
+            self.events.clear();
+            self.events.push(&id, protocol::turn_aborted());
+        }
+    }
+}
+
+impl ConversationBackend for MockConversation {
+    fn submit(&self, op: Op) -> BoxFuture<'_, Result<String>> {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.handle(id.clone(), op);
+        Box::pin(async move { Ok(id) })
+    }
+
+    fn submit_with_id(&self, submission: Submission) -> BoxFuture<'_, Result<()>> {
+        self.handle(submission.id, submission.op);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn next_event(&self) -> BoxFuture<'_, Result<Event>> {
+        Box::pin(self.events.pop())
+    }
+}
+
+/// Events produced by turns and not read yet.
+#[derive(Default)]
+struct EventQueue {
+    events: Mutex<VecDeque<Event>>,
+    notify: Notify,
+}
+
+impl EventQueue {
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Event>> {
+        self.events.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn push(&self, id: &str, msg: Option<EventMsg>) {
+        if let Some(msg) = msg {
+            self.lock().push_back(Event {
+                id: id.to_string(),
+                msg,
+            });
+            self.notify.notify_one();
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn clear(&self) {
+        self.lock().clear();
+    }
+
+    async fn pop(&self) -> Result<Event> {
+        loop {
+            let event = self.lock().pop_front();
+            match event {
+                Some(event) => return Ok(event),
+                None => self.notify.notified().await,
+            }
+        }
+    }
+}
+
+/// Produce the events of a turn with the given output, running the tool
+/// calls in it.
+async fn run_turn(
+    tools: ToolServer,
+    events: Arc<EventQueue>,
+    id: String,
+    outputs: Vec<OutputData>,
+) {
+    let last_agent_message = outputs.iter().rev().find_map(|output| match output {
+        OutputData::Primary { content } => Some(content.clone()),
+        _ => None,
+    });
+
+    events.push(&id, protocol::task_started());
+    for (index, output) in outputs.iter().enumerate() {
+        match output {
+            OutputData::ToolStart {
+                tool_name,
+                arguments,
+            } if tool_name == "exec_command" => {
+                let call_id = format!("mock-call-{}", index);
+                let command: Vec<String> =
+                    serde_json::from_value(arguments["command"].clone()).unwrap_or_default();
+                let cwd = tools.config().working_directory().clone();
+                events.push(&id, protocol::exec_command_begin(&call_id, &command, &cwd));
+                let output = match command.split_first() {
+                    Some((program, args)) => tokio::process::Command::new(program)
+                        .args(args)
+                        .current_dir(&cwd)
+                        .kill_on_drop(true)
+                        .output()
+                        .await
+                        .ok(),
+                    None => None,
+                };
+                let (stdout, stderr, exit_code) = match output {
+                    Some(output) => (
+                        String::from_utf8_lossy(&output.stdout).into_owned(),
+                        String::from_utf8_lossy(&output.stderr).into_owned(),
+                        output.status.code().unwrap_or(-1),
+                    ),
+                    None => (String::new(), "Failed to run the command".to_string(), -1),
+                };
+                events.push(
+                    &id,
+                    protocol::exec_command_end(&call_id, &stdout, &stderr, exit_code),
+                );
+            }
+            OutputData::ToolStart {
+                tool_name,
+                arguments,
+            } => {
+                let call_id = format!("mock-call-{}", index);
+                events.push(
+                    &id,
+                    protocol::mcp_tool_begin(&call_id, SERVER_NAME, tool_name, arguments),
+                );
+                let result = tools.call(tool_name, arguments.clone()).await;
+                events.push(
+                    &id,
+                    protocol::mcp_tool_end(&call_id, SERVER_NAME, tool_name, arguments, result),
+                );
+            }
+            output => events.push(&id, protocol::output_event(output)),
+        }
+    }
+    events.push(&id, protocol::task_complete(last_agent_message));
+}
+
+/// Output of a turn beyond the script.
+fn unscripted_turn() -> Vec<OutputData> {
+    vec![OutputData::error(OutputError::ModelRequestFailed {
+        error: "MockBackend has no scripted turn left".to_string(),
+    })]
+}
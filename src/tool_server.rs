@@ -0,0 +1,460 @@
+//! Host tools served to Codex over MCP.
+//!
+//! Codex only calls the tools it implements itself and those of MCP servers,
+//! so the tools agent-core runs on the host, like custom tools, search, git,
+//! the scratchpad, sub-agents, web search with a search provider, and bash
+//! with resource limits or under a container backend, are served to Codex
+//! as the tools of an MCP server named `agent_core`. Codex launches the
+//! relay set with `AgentConfigBuilder::tool_bridge`, by default the
+//! `agent-core-tool-bridge` binary of this crate found on `PATH` or next to
+//! the running executable, as that server; configurations with host tools
+//! fail to build when the relay cannot be found. The relay connects back to
+//! a loopback listener of the agent and passes the JSON-RPC messages
+//! through, so calls run in the agent's process on its
+//! [`ToolRegistry`](crate::ToolRegistry), with its middleware, concurrency
+//! limits, output limits, and cancellation. Calls of custom tools outliving
+//! their timeout are cancelled and reported as errors of the turn.
+//!
+//! Tools registered or unregistered while the agent runs are announced to
+//! the connected relays with a `notifications/tools/list_changed`
+//! notification, after which `tools/list` answers the new set. No relay is
+//! launched for agents without host tools.
+//!
+//! Hosts shipping a single binary can call [`relay`] from it when it is
+//! started with [`ADDRESS_VAR`] set, and point `tool_bridge` at it.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use codex_core::config_types::McpServerConfig;
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::blocking::CancellationToken;
+use crate::config::AgentConfig;
+use crate::controller::AgentController;
+use crate::error::{AgentError, OutputError, Result};
+use crate::sandbox::SandboxBackend;
+use crate::tools::{
+    CustomToolHandler, ToolConfig, ToolExecutionContext, ToolExecutionResult, ToolRegistry,
+};
+
+/// Name of the MCP server the host tools are served as.
+pub(crate) const SERVER_NAME: &str = "agent_core";
+
+/// Variable giving the relay the address of the agent's listener.
+pub const ADDRESS_VAR: &str = "AGENT_CORE_TOOL_SERVER";
+
+/// Variable giving the relay the token it authenticates with.
+pub const TOKEN_VAR: &str = "AGENT_CORE_TOOL_TOKEN";
+
+/// Relay Codex launches unless another is configured.
+pub(crate) const DEFAULT_BRIDGE: &str = "agent-core-tool-bridge";
+
+/// MCP revision answered to clients not asking for one.
+const MCP_PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// JSON-RPC error code of unknown methods.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// JSON-RPC error code of malformed parameters.
+const INVALID_PARAMS: i64 = -32602;
+
+/// Server of the host tools, shared by the listener and the execution loop.
+#[derive(Debug, Clone)]
+pub(crate) struct ToolServer {
+    tools: ToolRegistry,
+    /// Controller pausing calls in step-through mode and at breakpoints
+    controller: AgentController,
+    /// Configuration and id of the running turn, given to its calls
+    turn: Arc<Mutex<(AgentConfig, u64)>>,
+    /// Errors of the turn's calls that timed out, reported by the turn
+    timeouts: Arc<Mutex<Vec<OutputError>>>,
+}
+
+impl ToolServer {
+    /// Serve the tools of a registry.
+    pub(crate) fn new(
+        tools: ToolRegistry,
+        controller: AgentController,
+        config: AgentConfig,
+    ) -> Self {
+        Self {
+            tools,
+            controller,
+            turn: Arc::new(Mutex::new((config, 0))),
+            timeouts: Arc::default(),
+        }
+    }
+
+    /// Run the following calls as part of the given turn.
+    pub(crate) fn start_turn(&self, turn_id: u64, config: &AgentConfig) {
+        *self.turn.lock().unwrap_or_else(PoisonError::into_inner) = (config.clone(), turn_id);
+        self.take_timeouts();
+    }
+
+    /// Take the errors of the calls that timed out since last taken.
+    pub(crate) fn take_timeouts(&self) -> Vec<OutputError> {
+        std::mem::take(&mut *self.timeouts.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Configuration of the running turn.
+    #[cfg(feature = "testing")]
+    pub(crate) fn config(&self) -> AgentConfig {
+        self.turn
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .0
+            .clone()
+    }
+
+    /// Whether no tool is served.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.served().is_empty()
+    }
+
+    /// Tools served, with their handlers.
+    fn served(&self) -> Vec<(ToolConfig, Arc<dyn CustomToolHandler>)> {
+        self.tools
+            .tools()
+            .into_iter()
+            .filter_map(|tool| {
+                let handler = self.tools.handler(tool.name())?;
+                Some((tool, handler))
+            })
+            .collect()
+    }
+
+    /// MCP listing of the tools served.
+    pub(crate) fn list(&self) -> Value {
+        let tools: Vec<Value> = self
+            .served()
+            .into_iter()
+            .map(|(tool, handler)| {
+                json!({
+                    "name": tool.name(),
+                    "description": handler.description(),
+                    "inputSchema": handler.parameter_schema(),
+                })
+            })
+            .collect();
+        json!({ "tools": tools })
+    }
+
+    /// Run a call of the model, returning its MCP result.
+    pub(crate) async fn call(&self, name: &str, arguments: Value) -> Value {
+        let (config, turn_id) = self
+            .turn
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        debug!("Calling host tool {} in turn {}", name, turn_id);
+        if self.controller.pause_before_tool(name).await && !self.wait_for_resume().await {
+            return call_result(&ToolExecutionResult::error(
+                "Agent stopped before the tool ran",
+            ));
+        }
+        let timeout = self.tools.get(name).and_then(|tool| tool.call_timeout());
+        let result = match tool_context(&config, name, turn_id, timeout) {
+            Ok(context) => self.tools.call(name, arguments, context).await,
+            Err(e) => Err(e),
+        };
+        let result = result.unwrap_or_else(|e| ToolExecutionResult::error(e.to_string()));
+        if result.is_timed_out()
+            && let Some(error) = result.metadata.get("output_error")
+            && let Ok(error) = serde_json::from_value(error.clone())
+        {
+            warn!("Host tool {} timed out in turn {}", name, turn_id);
+            self.timeouts
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(error);
+        }
+        call_result(&result)
+    }
+
+    /// Wait until the paused agent is resumed, returning false if it stops
+    /// instead.
+    async fn wait_for_resume(&self) -> bool {
+        let mut state = self.controller.subscribe();
+        match state
+            .wait_for(|state| !state.is_paused || state.should_stop)
+            .await
+        {
+            Ok(state) => !state.should_stop,
+            Err(_) => false,
+        }
+    }
+
+    /// Answer a JSON-RPC request; notifications and responses get no answer.
+    async fn handle(&self, message: Value) -> Option<Value> {
+        let id = message.get("id")?.clone();
+        let method = message.get("method")?.as_str()?;
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": params
+                    .get("protocolVersion")
+                    .and_then(Value::as_str)
+                    .unwrap_or(MCP_PROTOCOL_VERSION),
+                "capabilities": { "tools": { "listChanged": true } },
+                "serverInfo": { "name": "agent-core", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(self.list()),
+            "tools/call" => match params.get("name").and_then(Value::as_str) {
+                Some(name) => {
+                    let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+                    Ok(self.call(name, arguments).await)
+                }
+                None => Err((INVALID_PARAMS, "Missing tool name".to_string())),
+            },
+            method => Err((METHOD_NOT_FOUND, format!("Unknown method {}", method))),
+        };
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        })
+    }
+
+    /// Serve one relay until it disconnects.
+    async fn serve<R, W>(self, mut reader: R, mut writer: W)
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (response_tx, mut response_rx) = tokio::sync::mpsc::unbounded_channel::<Value>();
+        let mut changes = self.tools.subscribe_changes();
+        changes.mark_unchanged();
+        let notifications = response_tx.clone();
+        let announcing = tokio::spawn(async move {
+            while changes.changed().await.is_ok() {
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/tools/list_changed",
+                });
+                if notifications.send(notification).is_err() {
+                    break;
+                }
+            }
+        });
+        let writing = tokio::spawn(async move {
+            while let Some(response) = response_rx.recv().await {
+                let line = format!("{}\n", response);
+                if let Err(e) = writer.write_all(line.as_bytes()).await {
+                    warn!("Failed to answer the tool relay: {}", e);
+                    break;
+                }
+                let _ = writer.flush().await;
+            }
+        });
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match reader.read_line(&mut line).await {
+                Ok(0) => break,
+                Ok(_) if line.trim().is_empty() => continue,
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("Failed to read from the tool relay: {}", e);
+                    break;
+                }
+            }
+            let message: Value = match serde_json::from_str(line.trim()) {
+                Ok(message) => message,
+                Err(e) => {
+                    warn!("Ignoring malformed message from the tool relay: {}", e);
+                    continue;
+                }
+            };
+            // Requests run concurrently, so a long call does not hold up the rest
+            let server = self.clone();
+            let response_tx = response_tx.clone();
+            tokio::spawn(async move {
+                if let Some(response) = server.handle(message).await {
+                    let _ = response_tx.send(response);
+                }
+            });
+        }
+        announcing.abort();
+        drop(response_tx);
+        let _ = writing.await;
+    }
+
+    /// Listen for the relay on a loopback port.
+    pub(crate) async fn listen(&self) -> Result<ToolListener> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let address = listener.local_addr()?;
+        let token = uuid::Uuid::new_v4().to_string();
+
+        let server = self.clone();
+        let expected = token.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("Tool server stopped accepting relays: {}", e);
+                        break;
+                    }
+                };
+                let server = server.clone();
+                let expected = expected.clone();
+                tokio::spawn(async move {
+                    let (reader, writer) = stream.into_split();
+                    let mut reader = BufReader::new(reader);
+                    // Any local process can connect; only the relay knows the token
+                    let mut token = String::new();
+                    if reader.read_line(&mut token).await.is_err() || token.trim() != expected {
+                        warn!("Rejected a tool relay connection without the token");
+                        return;
+                    }
+                    server.serve(reader, writer).await;
+                });
+            }
+        });
+        Ok(ToolListener {
+            address,
+            token,
+            task,
+        })
+    }
+}
+
+/// Listener serving the host tools to relays; stops accepting when dropped.
+#[derive(Debug)]
+pub(crate) struct ToolListener {
+    address: SocketAddr,
+    token: String,
+    task: JoinHandle<()>,
+}
+
+impl ToolListener {
+    /// Codex configuration of the MCP server relaying to this listener
+    /// through the `bridge` command.
+    pub(crate) fn mcp_server(&self, bridge: &Path) -> McpServerConfig {
+        McpServerConfig {
+            command: bridge.to_string_lossy().into_owned(),
+            args: Vec::new(),
+            env: Some(
+                [
+                    (ADDRESS_VAR.to_string(), self.address.to_string()),
+                    (TOKEN_VAR.to_string(), self.token.clone()),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+        }
+    }
+}
+
+impl Drop for ToolListener {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Whether Codex calls any of the tools through the relay: host tools, or
+/// the bash tool, which always runs on the host under a container backend or
+/// the path globs of a file tool.
+pub(crate) fn needs_bridge(tools: &[ToolConfig], sandbox_backend: &SandboxBackend) -> bool {
+    matches!(sandbox_backend, SandboxBackend::Container(_))
+        || tools.iter().any(|tool| {
+            matches!(tool, ToolConfig::Scratchpad { .. })
+                || tool.handler().is_some()
+                || tool.has_path_globs()
+        })
+}
+
+/// Locate the relay Codex launches: `configured`, or [`DEFAULT_BRIDGE`].
+///
+/// Paths are taken as given; bare names are looked up on `PATH`, then next
+/// to the running executable, where the relay is installed alongside
+/// applications. Returns `None` if no such file exists.
+pub(crate) fn resolve_bridge(configured: Option<&Path>) -> Option<PathBuf> {
+    let bridge = configured.unwrap_or(Path::new(DEFAULT_BRIDGE));
+    if bridge.components().count() > 1 {
+        return bridge.is_file().then(|| bridge.to_path_buf());
+    }
+    let executable_dir = std::env::current_exe()
+        .ok()
+        .and_then(|executable| executable.parent().map(Path::to_path_buf));
+    std::env::var_os("PATH")
+        .iter()
+        .flat_map(std::env::split_paths)
+        .chain(executable_dir)
+        .map(|dir| dir.join(bridge))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Context of a host tool call in the given turn, cancelled after `timeout`.
+fn tool_context(
+    config: &AgentConfig,
+    name: &str,
+    turn_id: u64,
+    timeout: Option<Duration>,
+) -> Result<ToolExecutionContext> {
+    Ok(ToolExecutionContext {
+        working_directory: config.tool_working_directory(name)?,
+        environment: config.tool_environment()?,
+        agent_config: config.clone(),
+        turn_id,
+        timeout,
+        cancellation: CancellationToken::new(),
+    })
+}
+
+/// MCP result of a tool call.
+fn call_result(result: &ToolExecutionResult) -> Value {
+    let mut content = vec![json!({ "type": "text", "text": result.output })];
+    content.extend(
+        result.images.iter().map(
+            |image| json!({ "type": "image", "data": image.data, "mimeType": image.mime_type }),
+        ),
+    );
+    let mut value = json!({ "content": content, "isError": !result.success });
+    if let Some(data) = result.data.as_ref().filter(|data| data.is_object()) {
+        value["structuredContent"] = data.clone();
+    }
+    value
+}
+
+/// Relay MCP messages between this process's standard streams and the agent
+/// listening at [`ADDRESS_VAR`], until either side closes.
+///
+/// This is the body of the `agent-core-tool-bridge` binary, which Codex
+/// launches with [`ADDRESS_VAR`] and [`TOKEN_VAR`] set.
+pub async fn relay() -> Result<()> {
+    let variable = |name: &str| {
+        std::env::var(name).map_err(|_| AgentError::Config {
+            message: format!(
+                "{} is not set; the tool bridge is launched by Codex for an agent",
+                name
+            ),
+        })
+    };
+    let address = variable(ADDRESS_VAR)?;
+    let token = variable(TOKEN_VAR)?;
+
+    let stream = TcpStream::connect(address).await?;
+    let (mut reader, mut writer) = stream.into_split();
+    writer.write_all(format!("{}\n", token).as_bytes()).await?;
+
+    let mut stdin = tokio::io::stdin();
+    let mut stdout = tokio::io::stdout();
+    tokio::select! {
+        result = tokio::io::copy(&mut stdin, &mut writer) => result?,
+        result = tokio::io::copy(&mut reader, &mut stdout) => result?,
+    };
+    Ok(())
+}
@@ -2,11 +2,30 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+use tokio::sync::{Semaphore, SemaphorePermit, watch};
 
-use crate::error::Result;
+use crate::blocking::{CancellationToken, run_tool};
+use crate::config::AgentConfig;
+use crate::error::{AgentError, OutputError, Result};
+use crate::git_tool::{GitPolicy, GitTool};
+use crate::limits::{KillPolicy, ResourceLimits};
+use crate::messages::OutputData;
+use crate::middleware::ToolCall;
+use crate::scratchpad::{Scratchpad, ScratchpadTool};
+use crate::search::SearchTool;
+use crate::shell::ShellTool;
+use crate::sub_agent::SubAgentTool;
+use crate::truncation::truncate;
+use crate::web_search::{self, SearchProvider, WebSearchTool};
+
+/// Exit code of a tool call stopped after exceeding its timeout.
+const TIMEOUT_EXIT_CODE: i32 = 124;
 
 /// Configuration for different types of tools available to the agent.
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// Clones share the same custom tool handler.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ToolConfig {
     /// Shell command execution with configurable network access
@@ -22,9 +41,31 @@ pub enum ToolConfig {
         #[serde(default)]
         working_directory: Option<String>,
 
-        /// Timeout for command execution in seconds
+        /// Timeout for command execution in seconds; a command running
+        /// longer is terminated and fails with a timeout error, while the
+        /// turn goes on
         #[serde(default)]
         timeout: Option<u64>,
+
+        /// CPU, memory, file size, and process caps for executed commands
+        ///
+        /// Codex cannot cap the commands it spawns, so a bash tool with
+        /// limits runs its commands on the host instead, through the agent's
+        /// sandbox backend.
+        #[serde(default)]
+        limits: ResourceLimits,
+
+        /// How timed-out commands and their process group are terminated
+        ///
+        /// Codex only stops the command it spawned, so a bash tool with a
+        /// kill policy runs its commands on the host instead, like one with
+        /// limits.
+        #[serde(default)]
+        kill_policy: Option<KillPolicy>,
+
+        /// Output size kept per command; the middle of longer output is cut
+        #[serde(default)]
+        max_output_bytes: Option<usize>,
     },
 
     /// Web search capability
@@ -33,13 +74,19 @@ pub enum ToolConfig {
         #[serde(default = "default_search_results")]
         max_results: usize,
 
-        /// Search engine to use (if configurable)
+        /// Built-in search engine ("bing", "brave", or "searxng") to search
+        /// with instead of the model's own web search
         #[serde(default)]
         search_engine: Option<String>,
 
-        /// Additional search parameters
+        /// Additional search parameters, such as the engine's `api_key` or
+        /// `base_url`
         #[serde(default)]
         parameters: HashMap<String, serde_json::Value>,
+
+        /// Provider to search with, taking precedence over `search_engine`
+        #[serde(skip)]
+        provider: Option<Arc<dyn SearchProvider>>,
     },
 
     /// File reading capability
@@ -55,6 +102,21 @@ pub enum ToolConfig {
         /// Whether to read binary files
         #[serde(default)]
         allow_binary: bool,
+
+        /// Directory reads are scoped to (relative to agent's working directory)
+        #[serde(default)]
+        working_directory: Option<String>,
+
+        /// Path globs the tool may read (empty means any path)
+        ///
+        /// Checked against the paths named by the commands of the bash tool,
+        /// which then runs on the host, and the files the search tool reads.
+        #[serde(default)]
+        allowed_paths: Vec<String>,
+
+        /// Path globs the tool may never read, taking precedence over allowed ones
+        #[serde(default)]
+        denied_paths: Vec<String>,
     },
 
     /// File writing capability
@@ -74,9 +136,30 @@ pub enum ToolConfig {
         /// Whether to create directories if they don't exist
         #[serde(default = "default_true")]
         create_directories: bool,
+
+        /// Directory writes are scoped to (relative to agent's working directory)
+        #[serde(default)]
+        working_directory: Option<String>,
+
+        /// Path globs patches and commands may change (empty means any path)
+        ///
+        /// Codex is made to ask approval for every patch, so patches to other
+        /// paths are denied before they are written, and the bash tool runs
+        /// on the host, where the paths its commands write are checked.
+        #[serde(default)]
+        allowed_paths: Vec<String>,
+
+        /// Path globs the tool may never access, taking precedence over allowed ones
+        #[serde(default)]
+        denied_paths: Vec<String>,
     },
 
     /// Patch application tool for code modifications
+    ///
+    /// Patches are size-checked, validated, backed up, and previewed when
+    /// Codex asks approval for them: every patch under
+    /// `AskForApproval::UnlessTrusted`, and otherwise those outside the
+    /// sandbox's writable roots.
     ApplyPatch {
         /// Maximum patch size in bytes
         #[serde(default = "default_max_patch_size")]
@@ -89,6 +172,43 @@ pub enum ToolConfig {
         /// Whether to validate patch syntax before applying
         #[serde(default = "default_true")]
         validate_syntax: bool,
+
+        /// Whether to preview patches and wait for the host before applying them
+        #[serde(default)]
+        dry_run: bool,
+    },
+
+    /// Regex search over file contents
+    Search {
+        /// Maximum number of matches returned per call
+        #[serde(default = "default_search_matches")]
+        max_matches: usize,
+
+        /// Globs of files searched (empty means all files)
+        #[serde(default)]
+        include: Vec<String>,
+
+        /// Globs of files never searched
+        #[serde(default)]
+        exclude: Vec<String>,
+    },
+
+    /// Structured git operations on the working directory's repository
+    Git {
+        /// Operations allowed besides reading the repository
+        #[serde(default)]
+        policy: GitPolicy,
+    },
+
+    /// Key/value notes kept across turns in the agent's scratchpad
+    Scratchpad {
+        /// Maximum number of notes
+        #[serde(default = "default_scratchpad_entries")]
+        max_entries: usize,
+
+        /// Maximum size of one note in bytes
+        #[serde(default = "default_scratchpad_value_bytes")]
+        max_value_bytes: usize,
     },
 
     /// Custom tool with user-defined behavior
@@ -104,7 +224,28 @@ pub enum ToolConfig {
 
         /// The actual tool handler
         #[serde(skip)]
-        handler: Option<Box<dyn CustomToolHandler>>,
+        handler: Option<Arc<dyn CustomToolHandler>>,
+
+        /// Seconds a call may run before it is cancelled
+        #[serde(default)]
+        timeout: Option<u64>,
+
+        /// Output size kept per call; the middle of longer output is cut
+        #[serde(default)]
+        max_output_bytes: Option<usize>,
+    },
+
+    /// Nested agent the model can delegate bounded subtasks to
+    SubAgent {
+        /// Tool name identifier (e.g. "research_agent")
+        name: String,
+
+        /// What the sub-agent is for, shown to the model
+        description: String,
+
+        /// Configuration of the nested agent
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        config: Option<Box<AgentConfig>>,
     },
 }
 
@@ -116,6 +257,9 @@ impl ToolConfig {
             environment: HashMap::new(),
             working_directory: None,
             timeout: None,
+            limits: ResourceLimits::default(),
+            kill_policy: None,
+            max_output_bytes: None,
         }
     }
 
@@ -126,6 +270,194 @@ impl ToolConfig {
             environment: HashMap::new(),
             working_directory: None,
             timeout: None,
+            limits: ResourceLimits::default(),
+            kill_policy: None,
+            max_output_bytes: None,
+        }
+    }
+
+    /// Set resource limits on a bash tool, which then runs its commands on
+    /// the host under them. Has no effect on other tools.
+    pub fn resource_limits(mut self, resource_limits: ResourceLimits) -> Self {
+        if let Self::Bash { limits, .. } = &mut self {
+            *limits = resource_limits;
+        }
+        self
+    }
+
+    /// Set how a bash tool terminates timed-out commands and their process
+    /// group, which then runs its commands on the host. Has no effect on
+    /// other tools.
+    pub fn kill_policy(mut self, policy: KillPolicy) -> Self {
+        if let Self::Bash { kill_policy, .. } = &mut self {
+            *kill_policy = Some(policy);
+        }
+        self
+    }
+
+    /// Whether a bash tool runs its commands on the host instead of through
+    /// Codex, to enforce its resource limits or kill policy.
+    pub fn runs_on_host(&self) -> bool {
+        matches!(
+            self,
+            Self::Bash { limits, kill_policy, .. } if !limits.is_empty() || kill_policy.is_some()
+        )
+    }
+
+    /// Stop bash or custom tool calls running longer than `seconds`. Has no
+    /// effect on other tools.
+    ///
+    /// A custom tool call past its timeout is cancelled through
+    /// [`ToolExecutionContext::cancellation`], and the turn reports an
+    /// `OutputError::ToolExecutionFailed`.
+    pub fn timeout(mut self, seconds: u64) -> Self {
+        if let Self::Bash { timeout, .. } | Self::Custom { timeout, .. } = &mut self {
+            *timeout = Some(seconds);
+        }
+        self
+    }
+
+    /// Get the time a call of a custom tool may run, if limited.
+    ///
+    /// Bash commands are stopped by the bash tool itself, under its kill
+    /// policy.
+    pub fn call_timeout(&self) -> Option<std::time::Duration> {
+        match self {
+            Self::Custom {
+                timeout: Some(seconds),
+                ..
+            } => Some(std::time::Duration::from_secs(*seconds)),
+            _ => None,
+        }
+    }
+
+    /// Keep at most `bytes` of each bash or custom tool output, cutting the
+    /// middle of longer output. Has no effect on other tools.
+    pub fn max_output_bytes(mut self, bytes: usize) -> Self {
+        if let Self::Bash {
+            max_output_bytes, ..
+        }
+        | Self::Custom {
+            max_output_bytes, ..
+        } = &mut self
+        {
+            *max_output_bytes = Some(bytes);
+        }
+        self
+    }
+
+    /// Preview the patches of an apply patch tool before applying them. Has
+    /// no effect on other tools.
+    ///
+    /// When Codex asks approval for a patch, the agent emits its diff as
+    /// [`OutputData::PatchPreview`](crate::messages::OutputData::PatchPreview)
+    /// and pauses. Resuming applies the patch, cancelling the turn rejects it.
+    /// Codex asks for every patch under `AskForApproval::UnlessTrusted`, and
+    /// otherwise for patches outside the sandbox's writable roots.
+    pub fn dry_run(mut self) -> Self {
+        if let Self::ApplyPatch { dry_run, .. } = &mut self {
+            *dry_run = true;
+        }
+        self
+    }
+
+    /// Get the output size kept per call, if capped.
+    pub fn output_limit(&self) -> Option<usize> {
+        match self {
+            Self::Bash {
+                max_output_bytes, ..
+            }
+            | Self::Custom {
+                max_output_bytes, ..
+            } => *max_output_bytes,
+            _ => None,
+        }
+    }
+
+    /// Restrict a file read or file write tool to paths matching the given
+    /// globs (e.g. `./workspace/**`). Has no effect on other tools.
+    pub fn allow_paths<I, S>(mut self, globs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        if let Self::FileRead { allowed_paths, .. } | Self::FileWrite { allowed_paths, .. } =
+            &mut self
+        {
+            allowed_paths.extend(globs.into_iter().map(Into::into));
+        }
+        self
+    }
+
+    /// Forbid a file read or file write tool from paths matching the given
+    /// globs (e.g. `~/.ssh/**`), even if allowed. Has no effect on other tools.
+    pub fn deny_paths<I, S>(mut self, globs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        if let Self::FileRead { denied_paths, .. } | Self::FileWrite { denied_paths, .. } =
+            &mut self
+        {
+            denied_paths.extend(globs.into_iter().map(Into::into));
+        }
+        self
+    }
+
+    /// Get the allowed and denied path globs of a file read or file write tool.
+    pub(crate) fn path_globs(&self) -> Option<(&[String], &[String])> {
+        match self {
+            Self::FileRead {
+                allowed_paths,
+                denied_paths,
+                ..
+            }
+            | Self::FileWrite {
+                allowed_paths,
+                denied_paths,
+                ..
+            } => Some((allowed_paths, denied_paths)),
+            _ => None,
+        }
+    }
+
+    /// Whether a file read or file write tool has path globs to enforce.
+    pub(crate) fn has_path_globs(&self) -> bool {
+        self.path_globs()
+            .is_some_and(|(allowed, denied)| !allowed.is_empty() || !denied.is_empty())
+    }
+
+    /// Set the working directory of a bash, file read, or file write tool,
+    /// relative to the agent's working directory. Has no effect on other tools.
+    pub fn working_directory<S: Into<String>>(mut self, directory: S) -> Self {
+        match &mut self {
+            Self::Bash {
+                working_directory, ..
+            }
+            | Self::FileRead {
+                working_directory, ..
+            }
+            | Self::FileWrite {
+                working_directory, ..
+            } => *working_directory = Some(directory.into()),
+            _ => {}
+        }
+        self
+    }
+
+    /// Get the working directory override, if any.
+    pub fn working_directory_override(&self) -> Option<&str> {
+        match self {
+            Self::Bash {
+                working_directory, ..
+            }
+            | Self::FileRead {
+                working_directory, ..
+            }
+            | Self::FileWrite {
+                working_directory, ..
+            } => working_directory.as_deref(),
+            _ => None,
         }
     }
 
@@ -135,6 +467,19 @@ impl ToolConfig {
             max_results: default_search_results(),
             search_engine: None,
             parameters: HashMap::new(),
+            provider: None,
+        }
+    }
+
+    /// Create a web search tool searching through the given provider.
+    ///
+    /// The search runs on the host and replaces the model's own web search.
+    pub fn web_search_with(provider: Arc<dyn SearchProvider>) -> Self {
+        Self::WebSearch {
+            max_results: default_search_results(),
+            search_engine: None,
+            parameters: HashMap::new(),
+            provider: Some(provider),
         }
     }
 
@@ -144,6 +489,9 @@ impl ToolConfig {
             max_file_size: default_max_file_size(),
             allowed_extensions: Vec::new(),
             allow_binary: false,
+            working_directory: None,
+            allowed_paths: Vec::new(),
+            denied_paths: Vec::new(),
         }
     }
 
@@ -154,6 +502,36 @@ impl ToolConfig {
             allowed_extensions: Vec::new(),
             allow_overwrite: true,
             create_directories: true,
+            working_directory: None,
+            allowed_paths: Vec::new(),
+            denied_paths: Vec::new(),
+        }
+    }
+
+    /// Create a content search tool with default settings.
+    pub fn search() -> Self {
+        Self::Search {
+            max_matches: default_search_matches(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    /// Create a git tool that may commit and branch but not push.
+    pub fn git() -> Self {
+        Self::git_with(GitPolicy::default())
+    }
+
+    /// Create a git tool restricted by the given policy.
+    pub fn git_with(policy: GitPolicy) -> Self {
+        Self::Git { policy }
+    }
+
+    /// Create a scratchpad tool with default settings.
+    pub fn scratchpad() -> Self {
+        Self::Scratchpad {
+            max_entries: default_scratchpad_entries(),
+            max_value_bytes: default_scratchpad_value_bytes(),
         }
     }
 
@@ -163,25 +541,141 @@ impl ToolConfig {
             max_patch_size: default_max_patch_size(),
             create_backup: true,
             validate_syntax: true,
+            dry_run: false,
         }
     }
 
     /// Create a custom tool configuration.
-    pub fn custom<S1, S2>(
+    ///
+    /// The handler can be given as a `Box` or an `Arc` to share it with
+    /// other tools.
+    pub fn custom<S1, S2, H>(
         name: S1,
         description: S2,
         parameters: serde_json::Value,
-        handler: Box<dyn CustomToolHandler>,
+        handler: H,
     ) -> Self
     where
         S1: Into<String>,
         S2: Into<String>,
+        H: Into<Arc<dyn CustomToolHandler>>,
     {
         Self::Custom {
             name: name.into(),
             description: description.into(),
             parameters,
-            handler: Some(handler),
+            handler: Some(handler.into()),
+            timeout: None,
+            max_output_bytes: None,
+        }
+    }
+
+    /// Create a custom tool whose parameters are the Rust type `P`.
+    ///
+    /// The parameter schema is generated from `P`, and arguments are
+    /// deserialized into it before `handler` runs; arguments that don't fit
+    /// are reported back to the model as a failed call.
+    #[cfg(feature = "schemars")]
+    pub fn custom_typed<P, S1, S2, F>(name: S1, description: S2, handler: F) -> Self
+    where
+        P: schemars::JsonSchema + serde::de::DeserializeOwned + 'static,
+        S1: Into<String>,
+        S2: Into<String>,
+        F: Fn(P, &ToolExecutionContext) -> Result<ToolExecutionResult> + Send + Sync + 'static,
+    {
+        let description = description.into();
+        let handler: Arc<dyn CustomToolHandler> =
+            Arc::new(TypedToolHandler::new(description.clone(), handler));
+        Self::custom(name, description, handler.parameter_schema(), handler)
+    }
+
+    /// Get the handler of a custom tool, if set, or of a built-in tool run by
+    /// agent-core (search, git, sub-agents, and bash with resource limits or
+    /// a kill policy).
+    pub fn handler(&self) -> Option<Arc<dyn CustomToolHandler>> {
+        match self {
+            Self::Custom { handler, .. } => handler.clone(),
+            Self::Bash { .. } if self.runs_on_host() => Some(Arc::new(self.shell_tool()?)),
+            Self::Search {
+                max_matches,
+                include,
+                exclude,
+            } => Some(Arc::new(SearchTool::new(
+                *max_matches,
+                include.clone(),
+                exclude.clone(),
+            ))),
+            Self::Git { policy } => Some(Arc::new(GitTool::new(policy.clone()))),
+            Self::WebSearch {
+                max_results,
+                search_engine,
+                parameters,
+                provider,
+            } => {
+                let provider = provider
+                    .clone()
+                    .or_else(|| web_search::provider_for(search_engine.as_deref()?, parameters))?;
+                Some(Arc::new(WebSearchTool::new(provider, *max_results)))
+            }
+            Self::SubAgent { .. } => Some(Arc::new(self.sub_agent_handler()?)),
+            _ => None,
+        }
+    }
+
+    /// Get the handler running a bash tool's commands on the host.
+    fn shell_tool(&self) -> Option<ShellTool> {
+        match self {
+            Self::Bash {
+                timeout,
+                limits,
+                kill_policy,
+                ..
+            } => Some(ShellTool::new(
+                *limits,
+                kill_policy.unwrap_or_default(),
+                timeout.map(std::time::Duration::from_secs),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Create a tool delegating subtasks to a nested agent with its own
+    /// configuration, tools, and context.
+    pub fn sub_agent<S1, S2>(name: S1, description: S2, config: AgentConfig) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        Self::SubAgent {
+            name: name.into(),
+            description: description.into(),
+            config: Some(Box::new(config)),
+        }
+    }
+
+    /// Get the handler running a sub-agent tool's nested agent.
+    pub fn sub_agent_handler(&self) -> Option<SubAgentTool> {
+        match self {
+            Self::SubAgent {
+                description,
+                config: Some(config),
+                ..
+            } => Some(SubAgentTool::new(description.clone(), (**config).clone())),
+            _ => None,
+        }
+    }
+
+    /// Create a code intelligence tool backed by language servers.
+    #[cfg(feature = "lsp")]
+    pub fn code_intelligence(servers: Vec<crate::lsp::LspServerConfig>) -> Self {
+        let handler = crate::lsp::LspTool::new(servers);
+        Self::Custom {
+            name: "code_intelligence".to_string(),
+            description: handler.description(),
+            parameters: handler.parameter_schema(),
+            handler: Some(Arc::new(handler)),
+            timeout: None,
+            max_output_bytes: None,
         }
     }
 
@@ -193,7 +687,10 @@ impl ToolConfig {
             ToolConfig::FileRead { .. } => "file_read",
             ToolConfig::FileWrite { .. } => "file_write",
             ToolConfig::ApplyPatch { .. } => "apply_patch",
-            ToolConfig::Custom { name, .. } => name,
+            ToolConfig::Search { .. } => "search",
+            ToolConfig::Git { .. } => "git",
+            ToolConfig::Scratchpad { .. } => "scratchpad",
+            ToolConfig::Custom { name, .. } | ToolConfig::SubAgent { name, .. } => name,
         }
     }
 
@@ -211,7 +708,14 @@ impl ToolConfig {
             ToolConfig::FileRead { .. } => "Read files from the filesystem".to_string(),
             ToolConfig::FileWrite { .. } => "Write files to the filesystem".to_string(),
             ToolConfig::ApplyPatch { .. } => "Apply code patches to files".to_string(),
-            ToolConfig::Custom { description, .. } => description.clone(),
+            ToolConfig::Git { .. } => "Run git operations on the repository".to_string(),
+            ToolConfig::Scratchpad { .. } => "Keep notes across turns".to_string(),
+            ToolConfig::Search { .. } => {
+                "Search file contents with regular expressions".to_string()
+            }
+            ToolConfig::Custom { description, .. } | ToolConfig::SubAgent { description, .. } => {
+                description.clone()
+            }
         }
     }
 }
@@ -232,6 +736,174 @@ pub trait CustomToolHandler: Send + Sync {
     fn description(&self) -> String;
 }
 
+/// Custom tool handler taking parameters of type `P`.
+///
+/// Created by [`ToolConfig::custom_typed`].
+#[cfg(feature = "schemars")]
+pub struct TypedToolHandler<P, F> {
+    description: String,
+    handler: F,
+    _params: std::marker::PhantomData<fn(P)>,
+}
+
+#[cfg(feature = "schemars")]
+impl<P, F> TypedToolHandler<P, F>
+where
+    P: schemars::JsonSchema + serde::de::DeserializeOwned,
+    F: Fn(P, &ToolExecutionContext) -> Result<ToolExecutionResult> + Send + Sync,
+{
+    /// Wrap a handler taking typed parameters.
+    pub fn new<S: Into<String>>(description: S, handler: F) -> Self {
+        Self {
+            description: description.into(),
+            handler,
+            _params: std::marker::PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "schemars")]
+impl<P, F> CustomToolHandler for TypedToolHandler<P, F>
+where
+    P: schemars::JsonSchema + serde::de::DeserializeOwned,
+    F: Fn(P, &ToolExecutionContext) -> Result<ToolExecutionResult> + Send + Sync,
+{
+    fn execute(
+        &self,
+        parameters: serde_json::Value,
+        context: &ToolExecutionContext,
+    ) -> Result<ToolExecutionResult> {
+        match serde_json::from_value(parameters) {
+            Ok(params) => (self.handler)(params, context),
+            Err(e) => Ok(ToolExecutionResult::error(format!(
+                "Invalid arguments: {e}"
+            ))),
+        }
+    }
+
+    fn parameter_schema(&self) -> serde_json::Value {
+        tool_schema(schemars::schema_for!(P).to_value())
+    }
+
+    fn description(&self) -> String {
+        self.description.clone()
+    }
+}
+
+/// Simplify a generated schema to what tool declarations accept: metadata
+/// is dropped, definitions are inlined, and optional values lose their
+/// `null` alternative. Definitions of recursive types are kept for the
+/// references left in them.
+#[cfg(feature = "schemars")]
+fn tool_schema(mut schema: serde_json::Value) -> serde_json::Value {
+    let mut definitions = serde_json::Map::new();
+    if let Some(root) = schema.as_object_mut() {
+        root.remove("$schema");
+        root.remove("title");
+        for key in ["$defs", "definitions"] {
+            if let Some(serde_json::Value::Object(defs)) = root.remove(key) {
+                definitions.extend(defs);
+            }
+        }
+    }
+
+    let mut inliner = SchemaInliner {
+        definitions: &definitions,
+        expanding: Vec::new(),
+        recursive: false,
+    };
+    let mut schema = inliner.inline(schema);
+    if let Some(root) = schema.as_object_mut() {
+        root.entry("type").or_insert_with(|| "object".into());
+        root.entry("properties")
+            .or_insert_with(|| serde_json::json!({}));
+        if inliner.recursive {
+            root.insert("$defs".to_string(), definitions.clone().into());
+        }
+    }
+    schema
+}
+
+#[cfg(feature = "schemars")]
+struct SchemaInliner<'a> {
+    definitions: &'a serde_json::Map<String, serde_json::Value>,
+    /// Definitions being inlined, whose references are left as they are
+    expanding: Vec<String>,
+    /// Whether a reference was left
+    recursive: bool,
+}
+
+#[cfg(feature = "schemars")]
+impl SchemaInliner<'_> {
+    fn inline(&mut self, schema: serde_json::Value) -> serde_json::Value {
+        use serde_json::Value;
+
+        let mut object = match schema {
+            Value::Object(object) => object,
+            Value::Array(items) => {
+                return Value::Array(items.into_iter().map(|item| self.inline(item)).collect());
+            }
+            other => return other,
+        };
+
+        // An optional value is either its schema or null
+        if let Some(Value::Array(variants)) = object.get("anyOf")
+            && variants.len() == 2
+            && let Some(index) = variants
+                .iter()
+                .position(|v| v.get("type").and_then(Value::as_str) == Some("null"))
+            && let Some(Value::Object(variant)) = variants.get(1 - index).cloned()
+        {
+            object.remove("anyOf");
+            for (key, value) in variant {
+                object.entry(key).or_insert(value);
+            }
+        }
+        if let Some(Value::Array(types)) = object.get("type") {
+            let types: Vec<Value> = types
+                .iter()
+                .filter(|t| t.as_str() != Some("null"))
+                .cloned()
+                .collect();
+            if let [single] = &types[..] {
+                object.insert("type".to_string(), single.clone());
+            }
+        }
+
+        let name = object
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|reference| reference.rsplit('/').next())
+            .map(str::to_string);
+        let definitions = self.definitions;
+        let mut expanded = false;
+        if let Some(name) = name
+            && let Some(Value::Object(definition)) = definitions.get(&name)
+        {
+            if self.expanding.contains(&name) {
+                self.recursive = true;
+            } else {
+                // Keywords next to the reference, like a description, win
+                object.remove("$ref");
+                for (key, value) in definition {
+                    object.entry(key.clone()).or_insert_with(|| value.clone());
+                }
+                self.expanding.push(name);
+                expanded = true;
+            }
+        }
+
+        let object = object
+            .into_iter()
+            .map(|(key, value)| (key, self.inline(value)))
+            .collect();
+        if expanded {
+            self.expanding.pop();
+        }
+        Value::Object(object)
+    }
+}
+
 /// Context provided to tools during execution.
 #[derive(Debug)]
 pub struct ToolExecutionContext {
@@ -249,6 +921,10 @@ pub struct ToolExecutionContext {
 
     /// Tool execution timeout
     pub timeout: Option<std::time::Duration>,
+
+    /// Cancelled when the call times out or the turn is interrupted or
+    /// stopped; long-running handlers should check it and return early
+    pub cancellation: CancellationToken,
 }
 
 /// Result of tool execution.
@@ -268,6 +944,34 @@ pub struct ToolExecutionResult {
 
     /// Additional metadata about the execution
     pub metadata: HashMap<String, serde_json::Value>,
+
+    /// Images produced by the tool, such as plots or screenshots
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ToolImage>,
+}
+
+/// Image returned by a tool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolImage {
+    /// Base64 encoded image data
+    pub data: String,
+
+    /// MIME type (e.g., "image/png", "image/svg+xml")
+    pub mime_type: String,
+
+    /// Optional caption shown with the image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+}
+
+impl From<ToolImage> for OutputData {
+    fn from(image: ToolImage) -> Self {
+        OutputData::Image {
+            data: image.data,
+            mime_type: image.mime_type,
+            caption: image.caption,
+        }
+    }
 }
 
 impl ToolExecutionResult {
@@ -279,6 +983,7 @@ impl ToolExecutionResult {
             data: None,
             exit_code: Some(0),
             metadata: HashMap::new(),
+            images: Vec::new(),
         }
     }
 
@@ -290,6 +995,7 @@ impl ToolExecutionResult {
             data: Some(data),
             exit_code: Some(0),
             metadata: HashMap::new(),
+            images: Vec::new(),
         }
     }
 
@@ -301,9 +1007,41 @@ impl ToolExecutionResult {
             data: None,
             exit_code: Some(exit_code),
             metadata: HashMap::new(),
+            images: Vec::new(),
+        }
+    }
+
+    /// Create the result of a call stopped after exceeding its timeout.
+    ///
+    /// The exit code is 124, as reported by `timeout(1)`, and the
+    /// `output_error` metadata holds an `OutputError::ToolExecutionFailed`.
+    pub fn timed_out(tool_name: &str, timeout: std::time::Duration) -> Self {
+        let message = format!("Timed out after {:.1}s", timeout.as_secs_f64());
+        let error = OutputError::ToolExecutionFailed {
+            tool_name: tool_name.to_string(),
+            error: message.clone(),
+        };
+        let mut metadata = HashMap::new();
+        metadata.insert("timed_out".to_string(), serde_json::Value::Bool(true));
+        metadata.insert(
+            "output_error".to_string(),
+            serde_json::to_value(error).unwrap_or_default(),
+        );
+        Self {
+            success: false,
+            output: message,
+            data: None,
+            exit_code: Some(TIMEOUT_EXIT_CODE),
+            metadata,
+            images: Vec::new(),
         }
     }
 
+    /// Whether the call was stopped after exceeding its timeout.
+    pub fn is_timed_out(&self) -> bool {
+        self.metadata.get("timed_out") == Some(&serde_json::Value::Bool(true))
+    }
+
     /// Create an error tool result.
     pub fn error<S: Into<String>>(error_message: S) -> Self {
         Self {
@@ -312,6 +1050,7 @@ impl ToolExecutionResult {
             data: None,
             exit_code: Some(-1),
             metadata: HashMap::new(),
+            images: Vec::new(),
         }
     }
 
@@ -325,6 +1064,41 @@ impl ToolExecutionResult {
         self.metadata.insert(key.into(), json_value);
         Ok(self)
     }
+
+    /// Attach an image, given as base64 encoded data, to the result.
+    pub fn with_image<S1, S2>(mut self, data: S1, mime_type: S2) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        self.images.push(ToolImage {
+            data: data.into(),
+            mime_type: mime_type.into(),
+            caption: None,
+        });
+        self
+    }
+
+    /// Attach an image with a caption, e.g. a chart's title.
+    pub fn with_captioned_image<S1, S2, S3>(mut self, data: S1, mime_type: S2, caption: S3) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+        S3: Into<String>,
+    {
+        self.images.push(ToolImage {
+            data: data.into(),
+            mime_type: mime_type.into(),
+            caption: Some(caption.into()),
+        });
+        self
+    }
+
+    /// `OutputData::Image` messages for the images of the result, for hosts
+    /// displaying them.
+    pub fn image_outputs(&self) -> Vec<OutputData> {
+        self.images.iter().cloned().map(OutputData::from).collect()
+    }
 }
 
 // Default value functions for serde defaults
@@ -336,6 +1110,18 @@ fn default_max_file_size() -> usize {
     10 * 1024 * 1024 // 10 MB
 }
 
+fn default_scratchpad_entries() -> usize {
+    100
+}
+
+fn default_scratchpad_value_bytes() -> usize {
+    4 * 1024
+}
+
+fn default_search_matches() -> usize {
+    200
+}
+
 fn default_max_patch_size() -> usize {
     1024 * 1024 // 1 MB
 }
@@ -344,74 +1130,311 @@ fn default_true() -> bool {
     true
 }
 
-impl Clone for ToolConfig {
-    fn clone(&self) -> Self {
-        match self {
-            Self::Bash {
-                allow_network,
-                environment,
-                working_directory,
-                timeout,
-            } => Self::Bash {
-                allow_network: *allow_network,
-                environment: environment.clone(),
-                working_directory: working_directory.clone(),
-                timeout: *timeout,
-            },
-            Self::WebSearch {
-                max_results,
-                search_engine,
-                parameters,
-            } => Self::WebSearch {
-                max_results: *max_results,
-                search_engine: search_engine.clone(),
-                parameters: parameters.clone(),
-            },
-            Self::FileRead {
-                max_file_size,
-                allowed_extensions,
-                allow_binary,
-            } => Self::FileRead {
-                max_file_size: *max_file_size,
-                allowed_extensions: allowed_extensions.clone(),
-                allow_binary: *allow_binary,
-            },
-            Self::FileWrite {
-                max_file_size,
-                allowed_extensions,
-                allow_overwrite,
-                create_directories,
-            } => Self::FileWrite {
-                max_file_size: *max_file_size,
-                allowed_extensions: allowed_extensions.clone(),
-                allow_overwrite: *allow_overwrite,
-                create_directories: *create_directories,
-            },
-            Self::ApplyPatch {
-                max_patch_size,
-                create_backup,
-                validate_syntax,
-            } => Self::ApplyPatch {
-                max_patch_size: *max_patch_size,
-                create_backup: *create_backup,
-                validate_syntax: *validate_syntax,
-            },
-            Self::Custom {
-                name,
-                description,
-                parameters,
-                ..
-            } => {
-                // Note: handler is not cloned, as trait objects can't be cloned in general
-                Self::Custom {
-                    name: name.clone(),
-                    description: description.clone(),
-                    parameters: parameters.clone(),
-                    handler: None,
-                }
+/// Caps on concurrently running host tool calls.
+///
+/// Calls over a limit wait for a running call to finish, in arrival order.
+/// The limits apply to the tools agent-core runs, like custom tools and
+/// search, which the model may call in parallel; shell commands and patches
+/// are run by Codex, one at a time, unless the bash tool runs its commands on
+/// the host to enforce resource limits.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolConcurrency {
+    /// Maximum calls running at once across all tools
+    #[serde(default)]
+    pub global: Option<usize>,
+
+    /// Maximum calls running at once per tool name
+    #[serde(default)]
+    pub per_tool: HashMap<String, usize>,
+}
+
+impl ToolConcurrency {
+    /// Create a configuration without limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit calls running at once across all tools.
+    pub fn global(mut self, limit: usize) -> Self {
+        self.global = Some(limit.max(1));
+        self
+    }
+
+    /// Limit calls of one tool running at once.
+    pub fn per_tool<S: Into<String>>(mut self, name: S, limit: usize) -> Self {
+        self.per_tool.insert(name.into(), limit.max(1));
+        self
+    }
+}
+
+/// Semaphores enforcing a [`ToolConcurrency`].
+#[derive(Debug, Default)]
+struct ConcurrencyLimits {
+    global: Option<Semaphore>,
+    per_tool: HashMap<String, Semaphore>,
+}
+
+/// Tools available to a running agent, changeable after it started.
+///
+/// Seeded from the configured tools; clones share the same set, so a
+/// registry taken from an [`AgentHandle`](crate::AgentHandle) reflects tools
+/// registered through any other handle.
+#[derive(Debug, Clone)]
+pub struct ToolRegistry {
+    tools: Arc<RwLock<Vec<ToolConfig>>>,
+    /// Bumped whenever a tool is registered or unregistered
+    changes: Arc<watch::Sender<u64>>,
+    limits: Arc<ConcurrencyLimits>,
+    /// Cancelled to abort the calls in flight, then replaced
+    cancellation: Arc<RwLock<CancellationToken>>,
+    /// Notes of the scratchpad tool
+    scratchpad: Scratchpad,
+    /// Whether the bash tool runs every command on the host
+    shell_on_host: bool,
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl ToolRegistry {
+    /// Create a registry holding the given tools.
+    pub fn new(tools: Vec<ToolConfig>) -> Self {
+        Self {
+            tools: Arc::new(RwLock::new(tools)),
+            changes: Arc::new(watch::channel(0).0),
+            limits: Arc::default(),
+            cancellation: Arc::default(),
+            scratchpad: Scratchpad::default(),
+            shell_on_host: false,
+        }
+    }
+
+    /// Run every command of the bash tool on the host through the sandbox
+    /// backend, registering the tool if missing, so no command runs in
+    /// Codex's own shell; the container backend needs this.
+    pub(crate) fn with_shell_on_host(mut self) -> Self {
+        self.shell_on_host = true;
+        if !self.contains("bash") {
+            self.write().push(ToolConfig::bash());
+        }
+        self
+    }
+
+    /// Whether the bash tool runs its commands on the host instead of
+    /// through Codex, to enforce its resource limits, kill policy, or the
+    /// container backend.
+    pub fn runs_shell_on_host(&self) -> bool {
+        self.get("bash").is_some_and(|tool| {
+            (self.shell_on_host && matches!(tool, ToolConfig::Bash { .. })) || tool.runs_on_host()
+        })
+    }
+
+    /// Cap concurrently running calls.
+    pub fn with_concurrency(mut self, concurrency: &ToolConcurrency) -> Self {
+        self.limits = Arc::new(ConcurrencyLimits {
+            global: concurrency.global.map(|limit| Semaphore::new(limit.max(1))),
+            per_tool: concurrency
+                .per_tool
+                .iter()
+                .map(|(name, limit)| (name.clone(), Semaphore::new((*limit).max(1))))
+                .collect(),
+        });
+        self
+    }
+
+    /// Add a tool, failing if one with the same name is registered.
+    pub fn register(&self, tool: ToolConfig) -> Result<()> {
+        let mut tools = self.write();
+        if tools.iter().any(|existing| existing.name() == tool.name()) {
+            return Err(AgentError::Tool {
+                message: format!("Tool {} is already registered", tool.name()),
+            });
+        }
+        tools.push(tool);
+        drop(tools);
+        self.changes.send_modify(|version| *version += 1);
+        Ok(())
+    }
+
+    /// Remove a tool, returning it if it was registered.
+    pub fn unregister(&self, name: &str) -> Option<ToolConfig> {
+        let mut tools = self.write();
+        let index = tools.iter().position(|tool| tool.name() == name)?;
+        let tool = tools.remove(index);
+        drop(tools);
+        self.changes.send_modify(|version| *version += 1);
+        Some(tool)
+    }
+
+    /// Watch for tools being registered or unregistered.
+    pub(crate) fn subscribe_changes(&self) -> watch::Receiver<u64> {
+        self.changes.subscribe()
+    }
+
+    /// Get a registered tool by name.
+    pub fn get(&self, name: &str) -> Option<ToolConfig> {
+        self.read().iter().find(|tool| tool.name() == name).cloned()
+    }
+
+    /// Get the handler of a registered custom tool.
+    ///
+    /// The scratchpad tool's handler works on the registry's scratchpad.
+    pub fn handler(&self, name: &str) -> Option<Arc<dyn CustomToolHandler>> {
+        match self.get(name)? {
+            ToolConfig::Scratchpad {
+                max_entries,
+                max_value_bytes,
+            } => Some(Arc::new(ScratchpadTool::new(
+                self.scratchpad.clone(),
+                max_entries,
+                max_value_bytes,
+            ))),
+            tool @ ToolConfig::Bash { .. } if self.shell_on_host => {
+                Some(Arc::new(tool.shell_tool()?))
+            }
+            tool => tool.handler(),
+        }
+    }
+
+    /// Get the notes kept by the scratchpad tool.
+    pub fn scratchpad(&self) -> &Scratchpad {
+        &self.scratchpad
+    }
+
+    /// Check if a tool is registered.
+    pub fn contains(&self, name: &str) -> bool {
+        self.read().iter().any(|tool| tool.name() == name)
+    }
+
+    /// List the registered tools.
+    pub fn tools(&self) -> Vec<ToolConfig> {
+        self.read().clone()
+    }
+
+    /// Execute a registered custom tool on the blocking thread pool.
+    ///
+    /// The tool middleware of the context's agent configuration runs around
+    /// the call; a call rejected by middleware returns a failed result.
+    /// Calls over the registry's concurrency limits wait for a running call
+    /// to finish. Output over the tool's `max_output_bytes` is cut in the
+    /// middle, with the number of bytes left out in the `truncated_bytes`
+    /// metadata.
+    ///
+    /// A call outliving `context.timeout` returns a
+    /// [timed out](ToolExecutionResult::is_timed_out) result, and one
+    /// cancelled with [`cancel_calls`](Self::cancel_calls) a failed result.
+    /// Either way `context.cancellation` is cancelled, as it is when the
+    /// call's future is dropped before it finishes; blocking handlers
+    /// cannot be preempted, so the handler's thread is only released once it
+    /// notices and returns.
+    pub async fn call(
+        &self,
+        name: &str,
+        arguments: serde_json::Value,
+        context: ToolExecutionContext,
+    ) -> Result<ToolExecutionResult> {
+        let tool = self.get(name);
+        let handler = self.handler(name).ok_or_else(|| AgentError::Tool {
+            message: format!("Tool {name} is not a registered custom tool"),
+        })?;
+        let middleware = context.agent_config.tool_middleware().to_vec();
+
+        let mut call = ToolCall {
+            tool_name: name.to_string(),
+            arguments,
+            turn_id: context.turn_id,
+        };
+        for hook in &middleware {
+            if let Err(e) = hook.before_call(&mut call) {
+                return Ok(ToolExecutionResult::error(format!(
+                    "Tool call rejected: {e}"
+                )));
             }
         }
+
+        // Wait for the tool's own slot first, so queued calls don't hold global ones
+        let _tool_permit = match self.limits.per_tool.get(name) {
+            Some(semaphore) => Some(acquire(semaphore).await?),
+            None => None,
+        };
+        let _global_permit = match &self.limits.global {
+            Some(semaphore) => Some(acquire(semaphore).await?),
+            None => None,
+        };
+
+        let timeout = context.timeout;
+        let token = context.cancellation.clone();
+        let _cancel_on_drop = token.clone().drop_guard();
+        let cancelled = self.cancellation();
+        let execution = run_tool(handler, call.arguments.clone(), context);
+        let execution = async {
+            match timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, execution).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        token.cancel();
+                        Ok(ToolExecutionResult::timed_out(name, timeout))
+                    }
+                },
+                None => execution.await,
+            }
+        };
+        let mut result = tokio::select! {
+            result = execution => result?,
+            _ = cancelled.cancelled() => {
+                token.cancel();
+                ToolExecutionResult::error("Tool execution cancelled")
+            }
+        };
+        for hook in middleware.iter().rev() {
+            hook.after_call(&call, &mut result);
+        }
+        if let Some(max_bytes) = tool.as_ref().and_then(ToolConfig::output_limit)
+            && let Some((output, omitted)) = truncate(&result.output, max_bytes)
+        {
+            result.output = output;
+            result
+                .metadata
+                .insert("truncated_bytes".to_string(), omitted.into());
+        }
+        Ok(result)
     }
+
+    /// Cancel every call in flight; their tokens are cancelled and they
+    /// return a failed result without waiting for the handler. Later calls
+    /// are unaffected.
+    pub fn cancel_calls(&self) {
+        let mut cancellation = self
+            .cancellation
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        std::mem::take(&mut *cancellation).cancel();
+    }
+
+    fn cancellation(&self) -> CancellationToken {
+        self.cancellation
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    // The list is always left consistent, so a poisoned lock is still usable
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Vec<ToolConfig>> {
+        self.tools.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, Vec<ToolConfig>> {
+        self.tools.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+async fn acquire(semaphore: &Semaphore) -> Result<SemaphorePermit<'_>> {
+    semaphore.acquire().await.map_err(|e| AgentError::Tool {
+        message: format!("Tool concurrency limit unavailable: {e}"),
+    })
 }
 
 impl std::fmt::Debug for dyn CustomToolHandler {
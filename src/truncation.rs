@@ -0,0 +1,103 @@
+//! Head and tail truncation of tool output.
+//!
+//! Output over a tool's `max_output_bytes` keeps its first and last halves,
+//! joined by a marker stating how many bytes were left out.
+
+/// Marker inserted where output was left out.
+fn marker(omitted: usize) -> String {
+    format!("\n[... {omitted} bytes truncated ...]\n")
+}
+
+/// Truncate `text` to its head and tail, returning the truncated text and the
+/// number of bytes left out, or `None` if it fits in `max_bytes`.
+pub(crate) fn truncate(text: &str, max_bytes: usize) -> Option<(String, usize)> {
+    if text.len() <= max_bytes {
+        return None;
+    }
+    let head_end = floor_char_boundary(text, max_bytes / 2);
+    let tail_start = ceil_char_boundary(text, text.len() - (max_bytes - max_bytes / 2));
+    let omitted = tail_start - head_end;
+    let truncated = format!(
+        "{}{}{}",
+        &text[..head_end],
+        marker(omitted),
+        &text[tail_start..]
+    );
+    Some((truncated, omitted))
+}
+
+/// Truncation state of one streamed tool output.
+///
+/// The head is passed through as it arrives; the rest is held back, keeping
+/// only the tail, until the stream finishes.
+#[derive(Debug)]
+pub(crate) struct TruncatedStream {
+    max_bytes: usize,
+    /// Head bytes still passed through as they arrive
+    head_left: usize,
+    /// Most recent output past the head
+    tail: String,
+    /// Bytes dropped from the tail
+    omitted: usize,
+}
+
+impl TruncatedStream {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            head_left: max_bytes / 2,
+            tail: String::new(),
+            omitted: 0,
+        }
+    }
+
+    /// Add the next chunk, returning the part to emit now.
+    pub fn push(&mut self, chunk: &str) -> Option<String> {
+        let head_end = if self.tail.is_empty() {
+            floor_char_boundary(chunk, self.head_left.min(chunk.len()))
+        } else {
+            0
+        };
+        self.head_left -= head_end;
+        // A char straddling the head limit ends the head early
+        if head_end < chunk.len() {
+            self.head_left = 0;
+        }
+
+        self.tail.push_str(&chunk[head_end..]);
+        let keep = self.max_bytes - self.max_bytes / 2;
+        if self.tail.len() > keep {
+            let start = ceil_char_boundary(&self.tail, self.tail.len() - keep);
+            self.omitted += start;
+            self.tail.drain(..start);
+        }
+
+        (head_end > 0).then(|| chunk[..head_end].to_string())
+    }
+
+    /// Finish the stream, returning the rest to emit and the number of bytes
+    /// left out, if any.
+    pub fn finish(self) -> (Option<String>, Option<usize>) {
+        if self.omitted == 0 {
+            return ((!self.tail.is_empty()).then_some(self.tail), None);
+        }
+        (
+            Some(format!("{}{}", marker(self.omitted), self.tail)),
+            Some(self.omitted),
+        )
+    }
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
@@ -1,5 +1,7 @@
 //! Utility functions for text processing and output formatting (optional feature).
 
+pub use crate::markdown::{CodeBlock, extract_code_blocks};
+
 /// Text processing utilities for agent outputs.
 pub mod processing {
     /// Clean and normalize agent output text.
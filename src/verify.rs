@@ -0,0 +1,195 @@
+//! Compiler/test feedback loop that verifies agent edits and asks the model
+//! to repair failures.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::blocking::CancellationToken;
+use crate::diagnostics::{self, Diagnostic};
+use crate::error::Result;
+use crate::limits::{KillPolicy, ReapedProcess, ResourceLimits};
+use crate::sandbox::SandboxBackend;
+
+/// Maximum number of output bytes fed back to the model.
+const MAX_FEEDBACK_BYTES: usize = 8 * 1024;
+
+/// Configuration for the verification step run after the agent edits files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyConfig {
+    /// Shell command to run (e.g., "cargo test")
+    pub command: String,
+
+    /// Maximum number of repair attempts before giving up
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+
+    /// Timeout for the verification command in seconds
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+
+    /// Verify after every turn, not only turns that applied patches
+    #[serde(default)]
+    pub always: bool,
+
+    /// CPU, memory, file size, and process caps for the command
+    #[serde(default)]
+    pub limits: ResourceLimits,
+
+    /// How a timed-out command and its process group are terminated
+    #[serde(default)]
+    pub kill_policy: KillPolicy,
+}
+
+impl VerifyConfig {
+    /// Create a verification step running the given shell command.
+    pub fn new<S: Into<String>>(command: S) -> Self {
+        Self {
+            command: command.into(),
+            max_attempts: default_max_attempts(),
+            timeout: default_timeout(),
+            always: false,
+            limits: ResourceLimits::default(),
+            kill_policy: KillPolicy::default(),
+        }
+    }
+
+    /// Set the maximum number of repair attempts.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// Set the command timeout in seconds.
+    pub fn timeout(mut self, seconds: u64) -> Self {
+        self.timeout = seconds;
+        self
+    }
+
+    /// Verify after every turn, not only turns that applied patches.
+    pub fn always(mut self, always: bool) -> Self {
+        self.always = always;
+        self
+    }
+
+    /// Set resource limits on the command.
+    pub fn resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set how a timed-out command is terminated.
+    pub fn kill_policy(mut self, policy: KillPolicy) -> Self {
+        self.kill_policy = policy;
+        self
+    }
+}
+
+/// Result of running the verification command.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationOutcome {
+    /// Whether the command succeeded
+    pub success: bool,
+
+    /// Exit code, if the command exited normally
+    pub exit_code: Option<i32>,
+
+    /// Combined stdout and stderr
+    pub output: String,
+
+    /// Whether the command was killed after exceeding the timeout
+    pub timed_out: bool,
+
+    /// Structured diagnostics parsed from the output
+    pub diagnostics: Vec<Diagnostic>,
+
+    /// Processes terminated while cleaning up after the command
+    pub reaped: Vec<ReapedProcess>,
+}
+
+impl VerificationOutcome {
+    /// Build the prompt asking the model to repair the failure.
+    pub fn repair_prompt(&self, config: &VerifyConfig, attempt: u32) -> String {
+        let status = if self.timed_out {
+            format!("timed out after {}s", config.timeout)
+        } else {
+            match self.exit_code {
+                Some(code) => format!("failed with exit code {}", code),
+                None => "was terminated by a signal".to_string(),
+            }
+        };
+
+        let mut prompt = format!(
+            "The verification command `{}` {} (repair attempt {} of {}). \
+             Fix the problems below, then finish your turn.\n\n",
+            config.command, status, attempt, config.max_attempts,
+        );
+        if !self.diagnostics.is_empty() {
+            prompt.push_str("Diagnostics:\n");
+            prompt.push_str(&diagnostics::format_for_model(&self.diagnostics));
+            prompt.push_str("\n\n");
+        }
+        prompt.push_str(&format!(
+            "Output:\n```\n{}\n```",
+            tail(&self.output, MAX_FEEDBACK_BYTES)
+        ));
+        prompt
+    }
+}
+
+/// Run the verification command in the working directory with the agent's
+/// sandbox backend and tool environment.
+pub(crate) async fn run_verification(
+    config: &VerifyConfig,
+    sandbox: &SandboxBackend,
+    working_directory: &Path,
+    environment: &HashMap<String, String>,
+) -> Result<VerificationOutcome> {
+    let command = sandbox.command(
+        &config.command,
+        working_directory,
+        environment,
+        &config.limits,
+    );
+    let output = command
+        .run(
+            Some(Duration::from_secs(config.timeout)),
+            &config.kill_policy,
+            &CancellationToken::new(),
+        )
+        .await?;
+
+    let mut combined = output.stdout;
+    combined.push_str(&output.stderr);
+    Ok(VerificationOutcome {
+        success: output.status.is_some_and(|status| status.success()),
+        exit_code: output.status.and_then(|status| status.code()),
+        diagnostics: diagnostics::parse(&combined),
+        output: combined,
+        timed_out: output.timed_out,
+        reaped: output.reaped,
+    })
+}
+
+/// Keep the last `max_bytes` of the text, on a character boundary.
+fn tail(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+
+    let mut start = text.len() - max_bytes;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_timeout() -> u64 {
+    600
+}
@@ -0,0 +1,383 @@
+//! Web search results surfaced to hosts.
+//!
+//! Codex runs web searches on the model side and reports only the query, so
+//! the pages the model used are recovered from the links it cites in its
+//! answer. Each answer following a search is scanned for markdown links, and
+//! the cited pages are emitted as `OutputData::WebSearchResults`.
+//!
+//! A web search tool configured with a [`SearchProvider`] runs searches on
+//! the host instead, against the user's engine and API key. Bing, Brave, and
+//! SearxNG providers are built in behind the `search-bing`, `search-brave`,
+//! and `search-searxng` features.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::error::{AgentError, Result};
+use crate::tools::{CustomToolHandler, ToolExecutionContext, ToolExecutionResult};
+
+/// Page returned by a web search.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebSearchResult {
+    /// Title of the page
+    pub title: String,
+
+    /// URL of the page
+    pub url: String,
+
+    /// Text around the page's mention, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+/// Pages cited as markdown links with an http(s) URL, in order and without
+/// duplicates.
+///
+/// The snippet is the line the link appears on.
+pub(crate) fn citations(content: &str) -> Vec<WebSearchResult> {
+    let mut results: Vec<WebSearchResult> = Vec::new();
+
+    for line in content.lines() {
+        let mut rest = line;
+        while let Some(open) = rest.find('[') {
+            let after_open = &rest[open + 1..];
+            let Some(close) = after_open.find("](") else {
+                break;
+            };
+            let title = after_open[..close].rsplit('[').next().unwrap_or_default();
+            let target = &after_open[close + 2..];
+            let Some(end) = target.find(')') else {
+                break;
+            };
+            let url = target[..end].split_whitespace().next().unwrap_or_default();
+            rest = &target[end + 1..];
+
+            let is_web = url.starts_with("https://") || url.starts_with("http://");
+            if !is_web || results.iter().any(|result| result.url == url) {
+                continue;
+            }
+
+            let snippet = line.trim().trim_start_matches(['-', '*', ' ']).trim();
+            results.push(WebSearchResult {
+                title: title.trim().to_string(),
+                url: url.to_string(),
+                snippet: (!snippet.is_empty()).then(|| snippet.to_string()),
+            });
+        }
+    }
+    results
+}
+
+/// Backend answering web searches for the web search tool.
+pub trait SearchProvider: Send + Sync {
+    /// Search the web, returning at most `max_results` pages.
+    fn search<'a>(
+        &'a self,
+        query: &'a str,
+        max_results: usize,
+    ) -> BoxFuture<'a, Result<Vec<WebSearchResult>>>;
+}
+
+impl std::fmt::Debug for dyn SearchProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SearchProvider")
+    }
+}
+
+/// Built-in provider for a `search_engine` name, configured from the tool's
+/// parameters or the environment.
+///
+/// Returns `None` for engines that are unknown or not compiled in, and when
+/// a required API key or URL is missing.
+pub(crate) fn provider_for(
+    search_engine: &str,
+    parameters: &HashMap<String, Value>,
+) -> Option<Arc<dyn SearchProvider>> {
+    #[allow(unused_variables)]
+    let setting = |key: &str, env: &str| {
+        parameters
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .or_else(|| std::env::var(env).ok())
+    };
+
+    match search_engine {
+        #[cfg(feature = "search-bing")]
+        "bing" => Some(Arc::new(BingSearch::new(setting(
+            "api_key",
+            "BING_SEARCH_API_KEY",
+        )?))),
+        #[cfg(feature = "search-brave")]
+        "brave" => Some(Arc::new(BraveSearch::new(setting(
+            "api_key",
+            "BRAVE_SEARCH_API_KEY",
+        )?))),
+        #[cfg(feature = "search-searxng")]
+        "searxng" => Some(Arc::new(SearxngSearch::new(setting(
+            "base_url",
+            "SEARXNG_URL",
+        )?))),
+        _ => None,
+    }
+}
+
+/// Web search tool answering through a [`SearchProvider`].
+#[derive(Debug, Clone)]
+pub struct WebSearchTool {
+    provider: Arc<dyn SearchProvider>,
+    max_results: usize,
+}
+
+/// Parameters of a web search call.
+#[derive(Debug, Deserialize)]
+struct WebSearchParams {
+    query: String,
+    #[serde(default)]
+    max_results: Option<usize>,
+}
+
+impl WebSearchTool {
+    /// Create a web search tool returning at most `max_results` pages per call.
+    pub fn new(provider: Arc<dyn SearchProvider>, max_results: usize) -> Self {
+        Self {
+            provider,
+            max_results,
+        }
+    }
+}
+
+impl CustomToolHandler for WebSearchTool {
+    fn execute(
+        &self,
+        parameters: Value,
+        _context: &ToolExecutionContext,
+    ) -> Result<ToolExecutionResult> {
+        let params: WebSearchParams = serde_json::from_value(parameters)?;
+        let max_results = params
+            .max_results
+            .map_or(self.max_results, |max| max.min(self.max_results));
+        let runtime = tokio::runtime::Handle::try_current().map_err(|_| AgentError::Tool {
+            message: "The web search tool must run inside a Tokio runtime".to_string(),
+        })?;
+
+        let results = match runtime.block_on(self.provider.search(&params.query, max_results)) {
+            Ok(results) => results,
+            Err(e) => return Ok(ToolExecutionResult::error(e.to_string())),
+        };
+        let mut output: String = results
+            .iter()
+            .map(|result| match &result.snippet {
+                Some(snippet) => format!("{}\n{}\n{}\n\n", result.title, result.url, snippet),
+                None => format!("{}\n{}\n\n", result.title, result.url),
+            })
+            .collect();
+        if results.is_empty() {
+            output.push_str("No results found\n");
+        }
+        Ok(ToolExecutionResult::success_with_data(
+            output,
+            json!({ "query": params.query, "results": results }),
+        ))
+    }
+
+    fn parameter_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Search query"
+                },
+                "max_results": {
+                    "type": "integer",
+                    "description": "Maximum number of results to return"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+}
+
+/// Web search through the Bing Web Search API.
+#[cfg(feature = "search-bing")]
+#[derive(Debug, Clone)]
+pub struct BingSearch {
+    api_key: String,
+    endpoint: String,
+}
+
+#[cfg(feature = "search-bing")]
+impl BingSearch {
+    /// Search with the given subscription key.
+    pub fn new<S: Into<String>>(api_key: S) -> Self {
+        Self {
+            api_key: api_key.into(),
+            endpoint: "https://api.bing.microsoft.com/v7.0/search".to_string(),
+        }
+    }
+
+    /// Use another endpoint, e.g. a regional one.
+    pub fn endpoint<S: Into<String>>(mut self, endpoint: S) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+}
+
+#[cfg(feature = "search-bing")]
+impl SearchProvider for BingSearch {
+    fn search<'a>(
+        &'a self,
+        query: &'a str,
+        max_results: usize,
+    ) -> BoxFuture<'a, Result<Vec<WebSearchResult>>> {
+        Box::pin(async move {
+            let count = max_results.to_string();
+            let request = reqwest::Client::new()
+                .get(&self.endpoint)
+                .header("Ocp-Apim-Subscription-Key", &self.api_key)
+                .query(&[("q", query), ("count", count.as_str())]);
+            let response = send(request).await?;
+            Ok(parse_results(
+                &response["webPages"]["value"],
+                "name",
+                "snippet",
+                max_results,
+            ))
+        })
+    }
+}
+
+/// Web search through the Brave Search API.
+#[cfg(feature = "search-brave")]
+#[derive(Debug, Clone)]
+pub struct BraveSearch {
+    api_key: String,
+}
+
+#[cfg(feature = "search-brave")]
+impl BraveSearch {
+    /// Search with the given subscription token.
+    pub fn new<S: Into<String>>(api_key: S) -> Self {
+        Self {
+            api_key: api_key.into(),
+        }
+    }
+}
+
+#[cfg(feature = "search-brave")]
+impl SearchProvider for BraveSearch {
+    fn search<'a>(
+        &'a self,
+        query: &'a str,
+        max_results: usize,
+    ) -> BoxFuture<'a, Result<Vec<WebSearchResult>>> {
+        Box::pin(async move {
+            let count = max_results.min(20).to_string();
+            let request = reqwest::Client::new()
+                .get("https://api.search.brave.com/res/v1/web/search")
+                .header("X-Subscription-Token", &self.api_key)
+                .header("Accept", "application/json")
+                .query(&[("q", query), ("count", count.as_str())]);
+            let response = send(request).await?;
+            Ok(parse_results(
+                &response["web"]["results"],
+                "title",
+                "description",
+                max_results,
+            ))
+        })
+    }
+}
+
+/// Web search through a SearxNG instance with the JSON format enabled.
+#[cfg(feature = "search-searxng")]
+#[derive(Debug, Clone)]
+pub struct SearxngSearch {
+    base_url: String,
+}
+
+#[cfg(feature = "search-searxng")]
+impl SearxngSearch {
+    /// Search on the instance at `base_url`, e.g. `https://searx.example.org`.
+    pub fn new<S: Into<String>>(base_url: S) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "search-searxng")]
+impl SearchProvider for SearxngSearch {
+    fn search<'a>(
+        &'a self,
+        query: &'a str,
+        max_results: usize,
+    ) -> BoxFuture<'a, Result<Vec<WebSearchResult>>> {
+        Box::pin(async move {
+            let request = reqwest::Client::new()
+                .get(format!("{}/search", self.base_url))
+                .query(&[("q", query), ("format", "json")]);
+            let response = send(request).await?;
+            Ok(parse_results(
+                &response["results"],
+                "title",
+                "content",
+                max_results,
+            ))
+        })
+    }
+}
+
+/// Send a search request and parse its JSON response.
+#[cfg(any(
+    feature = "search-bing",
+    feature = "search-brave",
+    feature = "search-searxng"
+))]
+async fn send(request: reqwest::RequestBuilder) -> Result<Value> {
+    let search_error = |e: reqwest::Error| AgentError::Tool {
+        message: format!("Web search request failed: {}", e),
+    };
+    request
+        .header("User-Agent", "agent-core")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(search_error)?
+        .json()
+        .await
+        .map_err(search_error)
+}
+
+/// Read up to `max_results` results from a JSON array of objects with `url`,
+/// title, and snippet fields.
+#[cfg(any(
+    feature = "search-bing",
+    feature = "search-brave",
+    feature = "search-searxng"
+))]
+fn parse_results(
+    items: &Value,
+    title_key: &str,
+    snippet_key: &str,
+    max_results: usize,
+) -> Vec<WebSearchResult> {
+    items
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            Some(WebSearchResult {
+                title: item[title_key].as_str()?.to_string(),
+                url: item["url"].as_str()?.to_string(),
+                snippet: item[snippet_key].as_str().map(str::to_string),
+            })
+        })
+        .take(max_results)
+        .collect()
+}
@@ -0,0 +1,708 @@
+//! Workspace tracking for summarizing the files an agent touched during a turn.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::git;
+
+/// Directories that are never indexed (agent-core state, VCS metadata and
+/// common build outputs).
+const IGNORED_DIRS: &[&str] = &[
+    ".agent-core",
+    ".git",
+    "target",
+    "node_modules",
+    "__pycache__",
+    ".venv",
+];
+
+/// Branch that receives the checkpoints taken before turns.
+const CHECKPOINT_BRANCH: &str = "agent-core/checkpoints";
+
+/// Lightweight snapshot of the files under a workspace root.
+///
+/// Only file size and modification time are recorded, so building an index is
+/// cheap enough to run at the start and end of every turn.
+#[derive(Debug, Clone, Default)]
+pub struct FileIndex {
+    entries: HashMap<PathBuf, FileEntry>,
+}
+
+/// Metadata recorded for a single indexed file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileEntry {
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileIndex {
+    /// Scan the given root directory and build an index of all regular files.
+    ///
+    /// Paths in the index are relative to `root`.
+    pub fn scan<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let root = root.as_ref();
+        let mut entries = HashMap::new();
+        let mut pending = vec![root.to_path_buf()];
+
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir)? {
+                let entry = entry?;
+                let file_type = entry.file_type()?;
+                let path = entry.path();
+
+                if file_type.is_dir() {
+                    let ignored = entry
+                        .file_name()
+                        .to_str()
+                        .is_some_and(|name| IGNORED_DIRS.contains(&name));
+                    if !ignored {
+                        pending.push(path);
+                    }
+                } else if file_type.is_file() {
+                    let metadata = entry.metadata()?;
+                    let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                    entries.insert(
+                        relative,
+                        FileEntry {
+                            size: metadata.len(),
+                            modified: metadata.modified().ok(),
+                        },
+                    );
+                }
+            }
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Number of files in the index.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Compute the changes between this (earlier) index and a later one.
+    pub fn diff(&self, later: &FileIndex) -> WorkspaceDelta {
+        let mut delta = WorkspaceDelta::default();
+
+        for (path, entry) in &later.entries {
+            match self.entries.get(path) {
+                None => delta.created.push(path.clone()),
+                Some(previous) if previous != entry => delta.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+
+        for path in self.entries.keys() {
+            if !later.entries.contains_key(path) {
+                delta.deleted.push(path.clone());
+            }
+        }
+
+        delta.created.sort();
+        delta.modified.sort();
+        delta.deleted.sort();
+        delta
+    }
+}
+
+/// Summary of the files created, modified, and deleted in the workspace.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceDelta {
+    /// Files that did not exist before
+    pub created: Vec<PathBuf>,
+
+    /// Files whose size or modification time changed
+    pub modified: Vec<PathBuf>,
+
+    /// Files that no longer exist
+    pub deleted: Vec<PathBuf>,
+}
+
+impl WorkspaceDelta {
+    /// Check if no files were changed.
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.modified.is_empty() && self.deleted.is_empty()
+    }
+
+    /// Total number of changed files.
+    pub fn len(&self) -> usize {
+        self.created.len() + self.modified.len() + self.deleted.len()
+    }
+}
+
+/// Scan a workspace root on the blocking thread pool.
+pub(crate) async fn scan_workspace(root: PathBuf) -> Result<FileIndex> {
+    tokio::task::spawn_blocking(move || FileIndex::scan(root))
+        .await
+        .map_err(|e| crate::error::AgentError::Execution {
+            message: format!("Workspace scan task failed: {}", e),
+        })?
+}
+
+/// Byte quota enforced on the agent working directory.
+///
+/// Usage is checked after commands and patches write to the workspace and
+/// periodically while a turn runs. Crossing the soft threshold emits a
+/// warning; exceeding the quota interrupts the turn and rejects new input
+/// with `OutputError::ResourceLimitExceeded`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiskQuota {
+    /// Maximum total size of the working directory in bytes
+    pub max_bytes: u64,
+
+    /// Fraction of the quota at which a warning is emitted
+    #[serde(default = "default_warn_ratio")]
+    pub warn_ratio: f64,
+
+    /// Interval between periodic checks during a turn, in seconds
+    #[serde(default = "default_check_interval")]
+    pub check_interval: u64,
+}
+
+impl DiskQuota {
+    /// Create a quota of the given number of bytes.
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            warn_ratio: default_warn_ratio(),
+            check_interval: default_check_interval(),
+        }
+    }
+
+    /// Set the fraction of the quota at which a warning is emitted.
+    pub fn warn_at(mut self, ratio: f64) -> Self {
+        self.warn_ratio = ratio;
+        self
+    }
+
+    /// Set the periodic check interval in seconds.
+    pub fn check_interval(mut self, seconds: u64) -> Self {
+        self.check_interval = seconds;
+        self
+    }
+
+    /// Usage in bytes at which a warning is emitted.
+    pub fn soft_limit(&self) -> u64 {
+        (self.max_bytes as f64 * self.warn_ratio) as u64
+    }
+
+    /// Classify the given usage against the quota.
+    pub(crate) fn status(&self, used_bytes: u64) -> QuotaStatus {
+        if used_bytes > self.max_bytes {
+            QuotaStatus::Exceeded
+        } else if used_bytes >= self.soft_limit() {
+            QuotaStatus::Warning
+        } else {
+            QuotaStatus::Ok
+        }
+    }
+}
+
+/// Disk usage relative to a quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum QuotaStatus {
+    Ok,
+    Warning,
+    Exceeded,
+}
+
+/// Compute the total size of all regular files under a directory.
+///
+/// Unlike [`FileIndex`], nothing is skipped: build outputs and dependencies
+/// count towards the quota.
+pub fn disk_usage<P: AsRef<Path>>(root: P) -> Result<u64> {
+    let mut total = 0;
+    let mut pending = vec![root.as_ref().to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                total += entry.metadata()?.len();
+            }
+        }
+    }
+
+    Ok(total)
+}
+
+/// Measure disk usage on the blocking thread pool.
+pub(crate) async fn measure_disk_usage(root: PathBuf) -> Result<u64> {
+    tokio::task::spawn_blocking(move || disk_usage(root))
+        .await
+        .map_err(|e| crate::error::AgentError::Execution {
+            message: format!("Disk usage task failed: {}", e),
+        })?
+}
+
+/// Record of what the agent did during a completed turn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TurnRecord {
+    /// Turn identifier
+    pub turn_id: u64,
+
+    /// Input message that started the turn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+
+    /// Final message produced by the model for the turn
+    pub summary: Option<String>,
+
+    /// Workspace changes, if workspace summaries are enabled
+    pub delta: Option<WorkspaceDelta>,
+
+    /// Commit created by auto-commit, if any
+    pub commit: Option<String>,
+
+    /// Checkpoint commit holding the workspace as it was before the turn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<String>,
+
+    /// Tokens used by the turn
+    #[serde(default)]
+    pub usage: crate::ops::TokenUsage,
+
+    /// Outputs of the turn, excluding streaming fragments, for later review
+    #[serde(default)]
+    pub events: Vec<crate::messages::OutputMessage>,
+
+    /// When the turn completed
+    pub completed_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl TurnRecord {
+    /// Create a new turn record.
+    pub fn new(turn_id: u64, summary: Option<String>) -> Self {
+        Self {
+            turn_id,
+            input: None,
+            summary,
+            delta: None,
+            commit: None,
+            checkpoint: None,
+            usage: Default::default(),
+            events: Vec::new(),
+            completed_at: chrono::Utc::now(),
+        }
+    }
+}
+
+/// Configuration for committing agent changes to a dedicated git branch.
+///
+/// Turns that changed the workspace are committed with a message the model
+/// writes in a separate request, from its final message for the turn and
+/// the files changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoCommitConfig {
+    /// Branch that receives the agent commits
+    pub branch: String,
+
+    /// Author name recorded on the commits
+    #[serde(default = "default_author_name")]
+    pub author_name: String,
+
+    /// Author email recorded on the commits
+    #[serde(default = "default_author_email")]
+    pub author_email: String,
+}
+
+impl AutoCommitConfig {
+    /// Create an auto-commit configuration targeting the given branch.
+    pub fn new<S: Into<String>>(branch: S) -> Self {
+        Self {
+            branch: branch.into(),
+            author_name: default_author_name(),
+            author_email: default_author_email(),
+        }
+    }
+
+    /// Set the commit author.
+    pub fn author<S1, S2>(mut self, name: S1, email: S2) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        self.author_name = name.into();
+        self.author_email = email.into();
+        self
+    }
+}
+
+impl Default for AutoCommitConfig {
+    fn default() -> Self {
+        Self::new("agent-core/changes")
+    }
+}
+
+/// Commit the workspace state at the end of a turn to the configured branch.
+///
+/// The commit message is the model's description of the turn's changes,
+/// only awaited once there are changes to commit, and references the turn
+/// id. Returns the new commit SHA, or `None` when the workspace is not a git
+/// repository or nothing changed.
+pub(crate) async fn auto_commit_turn<F>(
+    root: &Path,
+    config: &AutoCommitConfig,
+    turn_id: u64,
+    describe: F,
+) -> Result<Option<String>>
+where
+    F: Future<Output = Option<String>>,
+{
+    if !git::is_repository(root).await {
+        return Ok(None);
+    }
+    let Some(snapshot) = git::snapshot_worktree(root, &config.branch).await? else {
+        return Ok(None);
+    };
+
+    let message = commit_message(turn_id, describe.await.as_deref());
+    let author = git::GitAuthor {
+        name: &config.author_name,
+        email: &config.author_email,
+    };
+    git::commit_snapshot(root, snapshot, &message, &author)
+        .await
+        .map(Some)
+}
+
+/// Commit the workspace state before a turn to the checkpoint branch.
+///
+/// Returns the SHA of the checkpoint holding the current state, which is the
+/// previous checkpoint (or HEAD) when nothing changed since, or `None` when
+/// the workspace is not a git repository.
+pub(crate) async fn checkpoint_turn(root: &Path, turn_id: u64) -> Result<Option<String>> {
+    if !git::is_repository(root).await {
+        return Ok(None);
+    }
+
+    let message = format!(
+        "Checkpoint before turn {}\n\nAgent-Turn: {}",
+        turn_id, turn_id
+    );
+    let (name, email) = (default_author_name(), default_author_email());
+    let author = git::GitAuthor {
+        name: &name,
+        email: &email,
+    };
+    match git::commit_worktree_to_branch(root, CHECKPOINT_BRANCH, &message, &author).await? {
+        Some(sha) => Ok(Some(sha)),
+        None => {
+            let branch_ref = format!("refs/heads/{}", CHECKPOINT_BRANCH);
+            match git::resolve(root, &branch_ref).await {
+                Some(sha) => Ok(Some(sha)),
+                None => Ok(git::resolve(root, "HEAD").await),
+            }
+        }
+    }
+}
+
+/// Prompt asking the model for the commit message of a turn's changes,
+/// given its final message for the turn and the files it changed.
+pub(crate) fn commit_message_prompt(
+    summary: Option<&str>,
+    delta: Option<&WorkspaceDelta>,
+) -> String {
+    let mut prompt = "Write the git commit message for the changes an agent just made. \
+                      Reply with the message only: a subject line of at most 72 \
+                      characters in the imperative mood, a blank line, and a short \
+                      body saying what changed and why."
+        .to_string();
+    if let Some(summary) = summary.map(str::trim).filter(|s| !s.is_empty()) {
+        prompt.push_str("\n\nThe agent's summary of its work:\n");
+        prompt.push_str(summary);
+    }
+    if let Some(delta) = delta.filter(|delta| !delta.is_empty()) {
+        prompt.push_str("\n\nFiles changed:");
+        for (change, paths) in [
+            ("created", &delta.created),
+            ("modified", &delta.modified),
+            ("deleted", &delta.deleted),
+        ] {
+            for path in paths {
+                prompt.push_str(&format!("\n- {} {}", change, path.display()));
+            }
+        }
+    }
+    prompt
+}
+
+/// Build a commit message from the model's description of the turn: its
+/// first line is the subject, the rest the body.
+fn commit_message(turn_id: u64, description: Option<&str>) -> String {
+    let description = description.map(str::trim).unwrap_or_default();
+    let mut lines = description.lines();
+    let subject = lines
+        .by_ref()
+        .map(|line| line.trim_start_matches('#').trim())
+        .find(|line| !line.is_empty())
+        .map(|line| {
+            if line.chars().count() > 72 {
+                format!("{}...", line.chars().take(69).collect::<String>())
+            } else {
+                line.to_string()
+            }
+        })
+        .unwrap_or_else(|| "Apply agent changes".to_string());
+    let body = lines.collect::<Vec<_>>().join("\n");
+
+    if body.trim().is_empty() {
+        format!("{}\n\nAgent-Turn: {}", subject, turn_id)
+    } else {
+        format!("{}\n\n{}\n\nAgent-Turn: {}", subject, body.trim(), turn_id)
+    }
+}
+
+fn default_warn_ratio() -> f64 {
+    0.8
+}
+
+fn default_check_interval() -> u64 {
+    30
+}
+
+fn default_author_name() -> String {
+    "agent-core".to_string()
+}
+
+fn default_author_email() -> String {
+    "agent-core@localhost".to_string()
+}
+
+/// A pull/merge request opened on a code forge.
+#[cfg(feature = "pull-request")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequest {
+    /// Pull request number (GitHub) or merge request IID (GitLab)
+    pub number: u64,
+
+    /// Web URL of the pull request
+    pub url: String,
+
+    /// Source branch that was pushed
+    pub head: String,
+
+    /// Target branch
+    pub base: String,
+}
+
+/// Push the agent's auto-commit branch and open a pull request for it.
+///
+/// The forge (GitHub or GitLab) is detected from the host of the `origin`
+/// remote URL, which must name one of them, and authenticated with the
+/// `GITHUB_TOKEN` or `GITLAB_TOKEN` environment variable. The branch is pushed
+/// through the agent's git tool, so its [`GitPolicy`](crate::GitPolicy) must
+/// allow pushing to `origin`. The description is built from the recorded turn
+/// summaries and workspace deltas. Falls back to the first turn summary when
+/// `title_hint` is empty.
+#[cfg(feature = "pull-request")]
+pub async fn open_pull_request(
+    handle: &crate::agent::AgentHandle,
+    title_hint: &str,
+) -> Result<PullRequest> {
+    use crate::error::AgentError;
+    use crate::git_tool::GitTool;
+    use crate::tools::ToolConfig;
+
+    let root = handle.config().working_directory();
+    let auto_commit = handle
+        .config()
+        .auto_commit()
+        .ok_or_else(|| AgentError::Config {
+            message: "Opening a pull request requires auto_commit to be configured".to_string(),
+        })?;
+    let head = auto_commit.branch.clone();
+    let git_tool = handle
+        .config()
+        .tools()
+        .iter()
+        .find_map(|tool| match tool {
+            ToolConfig::Git { policy } => Some(GitTool::new(policy.clone())),
+            _ => None,
+        })
+        .ok_or_else(|| AgentError::Config {
+            message: "Opening a pull request requires the git tool to be configured".to_string(),
+        })?;
+
+    let remote_url = git::run_git(root, ["remote", "get-url", "origin"]).await?;
+    let (host, project) = parse_remote_url(&remote_url).ok_or_else(|| AgentError::Config {
+        message: format!("Unsupported git remote URL: {}", remote_url),
+    })?;
+    let gitlab = host.contains("gitlab");
+    if !gitlab && !host.contains("github") {
+        return Err(AgentError::Config {
+            message: format!(
+                "Unsupported forge host {}: only GitHub and GitLab hosts are recognized",
+                host
+            ),
+        });
+    }
+    let base = git::run_git(
+        root,
+        ["symbolic-ref", "--short", "refs/remotes/origin/HEAD"],
+    )
+    .await
+    .ok()
+    .and_then(|r| r.strip_prefix("origin/").map(str::to_string))
+    .unwrap_or_else(|| "main".to_string());
+
+    git_tool.push(root, "origin", &head).await?;
+
+    let records = handle.turn_records().await;
+    let title = if title_hint.trim().is_empty() {
+        records
+            .iter()
+            .find_map(|r| r.summary.as_deref())
+            .and_then(|s| s.lines().map(str::trim).find(|l| !l.is_empty()))
+            .unwrap_or("Agent changes")
+            .to_string()
+    } else {
+        title_hint.trim().to_string()
+    };
+    let body = pull_request_body(&records);
+
+    let client = reqwest::Client::builder()
+        .user_agent("agent-core")
+        .build()
+        .map_err(forge_error)?;
+
+    if gitlab {
+        let token = std::env::var("GITLAB_TOKEN").map_err(|_| AgentError::Config {
+            message: "Environment variable GITLAB_TOKEN not found".to_string(),
+        })?;
+        let response: serde_json::Value = client
+            .post(format!(
+                "https://{}/api/v4/projects/{}/merge_requests",
+                host,
+                project.replace('/', "%2F")
+            ))
+            .header("PRIVATE-TOKEN", token)
+            .json(&serde_json::json!({
+                "source_branch": head,
+                "target_branch": base,
+                "title": title,
+                "description": body,
+            }))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(forge_error)?
+            .json()
+            .await
+            .map_err(forge_error)?;
+
+        Ok(PullRequest {
+            number: response["iid"].as_u64().unwrap_or_default(),
+            url: response["web_url"].as_str().unwrap_or_default().to_string(),
+            head,
+            base,
+        })
+    } else {
+        let token = std::env::var("GITHUB_TOKEN").map_err(|_| AgentError::Config {
+            message: "Environment variable GITHUB_TOKEN not found".to_string(),
+        })?;
+        let api_base = if host == "github.com" {
+            "https://api.github.com".to_string()
+        } else {
+            format!("https://{}/api/v3", host)
+        };
+        let response: serde_json::Value = client
+            .post(format!("{}/repos/{}/pulls", api_base, project))
+            .bearer_auth(token)
+            .header("Accept", "application/vnd.github+json")
+            .json(&serde_json::json!({
+                "head": head,
+                "base": base,
+                "title": title,
+                "body": body,
+            }))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(forge_error)?
+            .json()
+            .await
+            .map_err(forge_error)?;
+
+        Ok(PullRequest {
+            number: response["number"].as_u64().unwrap_or_default(),
+            url: response["html_url"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            head,
+            base,
+        })
+    }
+}
+
+/// Build a pull request description from the recorded turns.
+#[cfg(feature = "pull-request")]
+fn pull_request_body(records: &[TurnRecord]) -> String {
+    let mut body = String::from("## Agent turns\n");
+
+    for record in records {
+        body.push_str(&format!("\n### Turn {}\n\n", record.turn_id));
+        if let Some(summary) = &record.summary {
+            body.push_str(summary.trim());
+            body.push('\n');
+        }
+        if let Some(delta) = &record.delta
+            && !delta.is_empty()
+        {
+            body.push_str("\n<details><summary>Files changed</summary>\n\n");
+            for (label, paths) in [
+                ("added", &delta.created),
+                ("modified", &delta.modified),
+                ("deleted", &delta.deleted),
+            ] {
+                for path in paths {
+                    body.push_str(&format!("- {} `{}`\n", label, path.display()));
+                }
+            }
+            body.push_str("\n</details>\n");
+        }
+    }
+
+    body
+}
+
+/// Split a git remote URL into host and `owner/repo` project path.
+#[cfg(feature = "pull-request")]
+fn parse_remote_url(url: &str) -> Option<(String, String)> {
+    let url = url.trim().trim_end_matches('/').trim_end_matches(".git");
+    let (host, path) = if let Some(rest) = url.split_once("://").map(|(_, rest)| rest) {
+        let rest = rest.rsplit_once('@').map_or(rest, |(_, rest)| rest);
+        rest.split_once('/')?
+    } else {
+        // scp-like syntax: git@host:owner/repo
+        let rest = url.rsplit_once('@').map_or(url, |(_, rest)| rest);
+        rest.split_once(':')?
+    };
+    let host = host.split(':').next()?.to_string();
+
+    if path.is_empty() {
+        None
+    } else {
+        Some((host, path.to_string()))
+    }
+}
+
+#[cfg(feature = "pull-request")]
+fn forge_error(e: reqwest::Error) -> crate::error::AgentError {
+    crate::error::AgentError::Execution {
+        message: format!("Forge API request failed: {}", e),
+    }
+}